@@ -57,19 +57,37 @@ soroban contract invoke \
     --id $CONTRACT_ID \
     --source $ADMIN_ID_STR \
     --network Futurenet \
-    -- set_fee \
-    --fee_rate 25 \
+    -- propose_fee \
+    --caller $ADMIN_ACC \
+    --maker_fee_rate 25 \
+    --taker_fee_rate 25 \
     --fee_wallet $FEE_ACC
+soroban contract invoke \
+    --id $CONTRACT_ID \
+    --source $ADMIN_ID_STR \
+    --network Futurenet \
+    -- apply_fee
 
 soroban contract invoke \
 	--id $CONTRACT_ID \
+	--source $ADMIN_ID_STR \
 	--network Futurenet \
 	-- allow_token \
+	--caller $ADMIN_ACC \
 	--token $(cat ../.soroban/ngnc-id)
 soroban contract invoke \
 	--id $(cat ../.soroban/contract-id) \
+	--source $ADMIN_ID_STR \
 	--network Futurenet \
 	-- allow_token \
+	--caller $ADMIN_ACC \
 	--token $(cat ../.soroban/usdc-id)
+soroban contract invoke \
+	--id $(cat ../.soroban/contract-id) \
+	--source $ADMIN_ID_STR \
+	--network Futurenet \
+	-- allow_token \
+	--caller $ADMIN_ACC \
+	--token $(cat ../.soroban/native-asset-id)
 
 echo "Contract initialized."
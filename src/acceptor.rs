@@ -0,0 +1,91 @@
+use soroban_sdk::{ Address, Env, Vec };
+
+use crate::storage_types::{ BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, 
+    DataKey, OfferStatus
+};
+
+use crate::offer::{ 
+    offer_load, 
+};
+use crate::error::SwapError;
+
+
+// Acceptors allowed to fill a private offer. `None` if the offer is open to anyone.
+pub fn acceptors_get(e: &Env, offer_id: u32) -> Option<Vec<Address>> {
+    let key = DataKey::OfferAcceptors(offer_id);
+
+    e.storage().persistent().get(&key)
+}
+
+pub fn acceptor_check(e: &Env, offer_id: u32, acceptor: &Address) -> bool {
+    match acceptors_get(e, offer_id) {
+        Some(acceptors) => acceptors.contains(acceptor),
+        None => true,
+    }
+}
+
+// The only address allowed to accept an OTC offer, which it must take in full.
+// It's set when the offer is created and can't be changed.
+pub fn counterparty_get(e: &Env, offer_id: u32) -> Option<Address> {
+    let key = DataKey::OfferCounterparty(offer_id);
+
+    e.storage().persistent().get(&key)
+}
+
+pub fn counterparty_set(e: &Env, offer_id: u32, counterparty: &Address) {
+    let key = DataKey::OfferCounterparty(offer_id);
+    e.storage().persistent().set(&key, counterparty);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
+
+// Makes the offer private if it wasn't already and allows `acceptor` to fill it.
+pub fn acceptor_add(e: &Env, offeror: &Address, offer_id: u32, acceptor: &Address) -> Result<(), SwapError> {
+    acceptor_auth(e, offeror, offer_id)?;
+
+    let mut acceptors = acceptors_get(e, offer_id).unwrap_or(Vec::new(e));
+    if !acceptors.contains(acceptor) {
+        acceptors.push_back(acceptor.clone());
+    }
+    acceptors_write(e, offer_id, &acceptors);
+    Ok(())
+}
+
+// The offer stays private even when its last acceptor is removed.
+pub fn acceptor_remove(e: &Env, offeror: &Address, offer_id: u32, acceptor: &Address) -> Result<(), SwapError> {
+    acceptor_auth(e, offeror, offer_id)?;
+
+    if let Some(mut acceptors) = acceptors_get(e, offer_id) {
+        if let Some(i) = acceptors.first_index_of(acceptor) {
+            acceptors.remove(i);
+            acceptors_write(e, offer_id, &acceptors);
+        }
+    }
+    Ok(())
+}
+
+// Opens the offer to anyone again.
+pub fn acceptors_clear(e: &Env, offeror: &Address, offer_id: u32) -> Result<(), SwapError> {
+    acceptor_auth(e, offeror, offer_id)?;
+
+    e.storage().persistent().remove(&DataKey::OfferAcceptors(offer_id));
+    Ok(())
+}
+
+fn acceptor_auth(e: &Env, offeror: &Address, offer_id: u32) -> Result<(), SwapError> {
+    let offer = offer_load(e, offer_id)?;
+    if offer.offeror != offeror.clone() {
+        return Err(SwapError::InvalidOfferor);
+    }
+    if offer.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+
+    offeror.require_auth();
+    Ok(())
+}
+
+pub fn acceptors_write(e: &Env, offer_id: u32, acceptors: &Vec<Address>) {
+    let key = DataKey::OfferAcceptors(offer_id);
+    e.storage().persistent().set(&key, acceptors);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
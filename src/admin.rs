@@ -1,18 +1,34 @@
 use soroban_sdk::{Address, Env};
 
 use crate::storage_types::DataKey;
+use crate::error::SwapError;
 
 pub fn has_administrator(e: &Env) -> bool {
     let key = DataKey::Admin;
     e.storage().instance().has(&key)
 }
 
-pub fn read_administrator(e: &Env) -> Address {
+pub fn read_administrator(e: &Env) -> Result<Address, SwapError> {
     let key = DataKey::Admin;
-    e.storage().instance().get(&key).unwrap()
+    e.storage().instance().get(&key).ok_or(SwapError::NotInitialized)
 }
 
 pub fn write_administrator(e: &Env, id: &Address) {
     let key = DataKey::Admin;
     e.storage().instance().set(&key, id);
 }
+
+pub fn read_pending_administrator(e: &Env) -> Result<Address, SwapError> {
+    let key = DataKey::PendingAdmin;
+    e.storage().instance().get(&key).ok_or(SwapError::NoPendingAdmin)
+}
+
+pub fn write_pending_administrator(e: &Env, id: &Address) {
+    let key = DataKey::PendingAdmin;
+    e.storage().instance().set(&key, id);
+}
+
+pub fn remove_pending_administrator(e: &Env) {
+    let key = DataKey::PendingAdmin;
+    e.storage().instance().remove(&key);
+}
@@ -1,46 +1,204 @@
-use soroban_sdk::{ log, Address, Env };
+const ALLOW: Symbol = symbol_short!("ALLOW");
 
-use crate::storage_types::{ /* INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT, */ 
-    DataKey
+use soroban_sdk::{ log, Address, Env, symbol_short, Symbol, Vec };
+
+use crate::storage_types::{ FREEZE_ALLOWLIST, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, 
+    ConfigKey, DataKey, PairMinimums, Role
 };
 
-use crate::admin::{ 
-    read_administrator, 
+use crate::decimals::{ token_probe };
+use crate::freeze::{ freeze_check };
+use crate::role::{ 
+    role_check, 
 };
+use crate::error::SwapError;
+
+
+/*
+Offers can only trade allowed tokens. Operators can further restrict trading to registered
+pairs with `pairs_only_set`, enabling a market like USDC/XLM without enabling every
+combination of allowed tokens. A registered pair trades in both directions.
+
+Operators can also set minimums for each direction of a pair, so the book can't be filled
+with dust offers and fills worth less than the fees and rent they cost.
+*/
 
 
-pub fn allow_set(e: &Env, token_addr: &Address) {
+pub fn allow_set(e: &Env, caller: &Address, token_addr: &Address) -> Result<(), SwapError> {
+    role_check(e, Role::Operator, caller)?;
+    freeze_check(e, FREEZE_ALLOWLIST)?;
+    allow_write(e, token_addr)
+}
+
+pub fn allow_reset(e: &Env, caller: &Address, token_addr: &Address) -> Result<(), SwapError> {
+    role_check(e, Role::Operator, caller)?;
+    freeze_check(e, FREEZE_ALLOWLIST)?;
+    allow_remove(e, token_addr);
+    Ok(())
+}
+
+// Allows all of `tokens` under a single authorization of caller.
+pub fn allow_set_many(e: &Env, caller: &Address, tokens: &Vec<Address>) -> Result<(), SwapError> {
+    role_check(e, Role::Operator, caller)?;
+    freeze_check(e, FREEZE_ALLOWLIST)?;
+    for token_addr in tokens.iter() {
+        allow_write(e, &token_addr)?;
+    }
+    Ok(())
+}
+
+pub fn allow_reset_many(e: &Env, caller: &Address, tokens: &Vec<Address>) -> Result<(), SwapError> {
+    role_check(e, Role::Operator, caller)?;
+    freeze_check(e, FREEZE_ALLOWLIST)?;
+    for token_addr in tokens.iter() {
+        allow_remove(e, &token_addr);
+    }
+    Ok(())
+}
+
+pub fn allow_write(e: &Env, token_addr: &Address) -> Result<(), SwapError> {
     let key = DataKey::Allowance(token_addr.clone());
-    let admin = read_administrator(&e);
-    admin.require_auth();
-    if e.storage().instance().has(&key) && e.storage().instance().get::<_, bool>(&key).unwrap() {
-        log!(&e, "current token was already allowed");
-        return;
+    if allow_get(e, token_addr) {
+        log!(e, "current token was already allowed");
+        return Ok(());
     }
+    token_probe(e, token_addr)?;
+
+    e.storage().persistent().set(&key, &true);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
 
-    e.storage().instance().set(&key, &true);
+    let mut tokens = allow_tokens(e);
+    tokens.push_back(token_addr.clone());
+    allow_tokens_write(e, &tokens);
+
+    // emit TokenAllowed event
+    e.events().publish((ALLOW, symbol_short!("TAllow")), 
+        token_addr.clone()
+    );
+    Ok(())
 }
 
-pub fn allow_reset(e: &Env, token_addr: &Address) {
+pub fn allow_remove(e: &Env, token_addr: &Address) {
     let key = DataKey::Allowance(token_addr.clone());
-    let admin = read_administrator(&e);
-    admin.require_auth();
-    if !e.storage().instance().has(&key) || !e.storage().instance().get::<_, bool>(&key).unwrap() {
-        log!(&e, "current token wasn't allowed");
+    if !allow_get(e, token_addr) {
+        log!(e, "current token wasn't allowed");
         return;
     }
 
-    e.storage().instance().set(&key, &false);
-    // e.storage().instance().bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    e.storage().persistent().remove(&key);
+
+    let mut tokens = allow_tokens(e);
+    if let Some(i) = tokens.first_index_of(token_addr) {
+        tokens.remove(i);
+        allow_tokens_write(e, &tokens);
+    }
+
+    // emit TokenDisallowed event
+    e.events().publish((ALLOW, symbol_short!("TDisallow")), 
+        token_addr.clone()
+    );
 }
 
+// Tokens in use are kept alive by bumping their entry on every lookup.
 pub fn allow_get(e: &Env, token: &Address) -> bool {
     let key = DataKey::Allowance(token.clone());
     
-    if e.storage().instance().has(&key) && e.storage().instance().get::<_, bool>(&key).unwrap() {
-        true
+    let allowed = e.storage().persistent().get::<_, bool>(&key).unwrap_or(false);
+    if allowed {
+        e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    }
+    allowed
+}
+
+// List allowed tokens in the order they were allowed
+pub fn allow_list(e: &Env, cursor: u32, limit: u32) -> Vec<Address> {
+    let tokens = allow_tokens(e);
+    if cursor >= tokens.len() {
+        return Vec::new(e);
     }
-    else {
-        false
+    let end = tokens.len().min(cursor.saturating_add(limit));
+    tokens.slice(cursor..end)
+}
+
+fn allow_tokens(e: &Env) -> Vec<Address> {
+    e.storage().persistent().get(&ConfigKey::AllowedTokens).unwrap_or(Vec::new(e))
+}
+
+fn allow_tokens_write(e: &Env, tokens: &Vec<Address>) {
+    let key = ConfigKey::AllowedTokens;
+    e.storage().persistent().set(&key, tokens);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
+
+// Whether offers selling `send_token` for `recv_token` can be made.
+pub fn allow_pair_get(e: &Env, send_token: &Address, recv_token: &Address) -> bool {
+    if !allow_get(e, send_token) || !allow_get(e, recv_token) {
+        return false;
+    }
+    if !pairs_only_get(e) {
+        return true;
+    }
+    pair_registered(e, send_token, recv_token) || pair_registered(e, recv_token, send_token)
+}
+
+pub fn pair_allow(e: &Env, caller: &Address, send_token: &Address, recv_token: &Address) -> Result<(), SwapError> {
+    role_check(e, Role::Operator, caller)?;
+    freeze_check(e, FREEZE_ALLOWLIST)?;
+    if pair_registered(e, recv_token, send_token) {
+        return Ok(());
+    }
+
+    let key = ConfigKey::AllowedPair(send_token.clone(), recv_token.clone());
+    e.storage().persistent().set(&key, &true);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    Ok(())
+}
+
+pub fn pair_disallow(e: &Env, caller: &Address, send_token: &Address, recv_token: &Address) -> Result<(), SwapError> {
+    role_check(e, Role::Operator, caller)?;
+    freeze_check(e, FREEZE_ALLOWLIST)?;
+    e.storage().persistent().remove(&ConfigKey::AllowedPair(send_token.clone(), recv_token.clone()));
+    e.storage().persistent().remove(&ConfigKey::AllowedPair(recv_token.clone(), send_token.clone()));
+    Ok(())
+}
+
+fn pair_registered(e: &Env, send_token: &Address, recv_token: &Address) -> bool {
+    let key = ConfigKey::AllowedPair(send_token.clone(), recv_token.clone());
+
+    let registered = e.storage().persistent().get::<_, bool>(&key).unwrap_or(false);
+    if registered {
+        e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    }
+    registered
+}
+
+pub fn pairs_only_get(e: &Env) -> bool {
+    e.storage().instance().get::<_, bool>(&ConfigKey::PairsOnly).unwrap_or(false)
+}
+
+// Restricts trading to registered pairs, or back to any pair of allowed tokens.
+pub fn pairs_only_set(e: &Env, caller: &Address, pairs_only: bool) -> Result<(), SwapError> {
+    role_check(e, Role::Operator, caller)?;
+    freeze_check(e, FREEZE_ALLOWLIST)?;
+    e.storage().instance().set(&ConfigKey::PairsOnly, &pairs_only);
+    Ok(())
+}
+
+pub fn minimums_get(e: &Env, send_token: &Address, recv_token: &Address) -> Option<PairMinimums> {
+    e.storage().instance().get(&ConfigKey::PairMinimums(send_token.clone(), recv_token.clone()))
+}
+
+pub fn minimums_set(e: &Env, 
+    caller: &Address, 
+    send_token: &Address, 
+    recv_token: &Address, 
+    minimums: &Option<PairMinimums>
+) -> Result<(), SwapError> {
+    let key = ConfigKey::PairMinimums(send_token.clone(), recv_token.clone());
+    role_check(e, Role::Operator, caller)?;
+    match minimums {
+        Some(minimums) => e.storage().instance().set(&key, minimums),
+        None => e.storage().instance().remove(&key),
     }
+    Ok(())
 }
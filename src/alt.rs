@@ -0,0 +1,138 @@
+const OFFER: Symbol = symbol_short!("OFFER");
+
+use soroban_sdk::{ token, Address, Env, symbol_short, Symbol, Vec };
+
+use crate::storage_types::{ BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, 
+    AltRecv, DataKey, OfferStatus
+};
+use crate::allow::{ allow_pair_get };
+use crate::dispute::{ dispute_window_get };
+use crate::fee::{ fee_get, fee_accrue, calculate_fee, volume_add };
+use crate::offer::{ offer_load, offer_load_live, offer_quote, offer_settle };
+use crate::pause::{ pause_check };
+use crate::escrow::{ escrow_in, escrow_out };
+use crate::error::SwapError;
+
+
+/*
+Besides its recv_token an offer may accept other tokens, each at its own price, so one
+escrow can be bought with near-equivalent quote assets:
+
+1. Offeror calls `set` with a token and the amount of it asked for the offer's current
+   send_amount. The price is kept as that ratio, so it survives fills and resizing, but
+   it doesn't follow updates or dutch auctions of the recv_token price.
+2. Acceptors call `accept` to pay in one of those tokens. The fill counts against the
+   offer like a fill of the equivalent recv_token amount and goes to the offeror directly.
+*/
+
+pub fn alt_get(e: &Env, offer_id: u32) -> Vec<AltRecv> {
+    let key = DataKey::OfferAltRecv(offer_id);
+
+    e.storage().persistent().get(&key).unwrap_or(Vec::new(e))
+}
+
+// Sets the price of the offer in `recv_token` to `recv_amount` for its current send_amount,
+// 0 removes the token. Must be authorized by offeror.
+pub fn alt_set(e: &Env, offeror: &Address, offer_id: u32, recv_token: &Address, recv_amount: u64) -> Result<(), SwapError> {
+    let offer = offer_load(e, offer_id)?;
+    if offer.offeror != offeror.clone() {
+        return Err(SwapError::InvalidOfferor);
+    }
+    if offer.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+    if offer.recv_token == recv_token.clone() || offer.send_token == recv_token.clone() {
+        return Err(SwapError::TokenNotAllowed);
+    }
+    if recv_amount > 0 && !allow_pair_get(e, &offer.send_token, recv_token) {
+        return Err(SwapError::TokenNotAllowed);
+    }
+
+    offeror.require_auth();
+
+    let mut alts = alt_get(e, offer_id);
+    if let Some(i) = alts.iter().position(|alt| alt.recv_token == recv_token.clone()) {
+        alts.remove(i as u32);
+    }
+    if recv_amount > 0 {
+        alts.push_back(AltRecv { recv_token: recv_token.clone(), send_amount: offer.send_amount, recv_amount });
+    }
+
+    alt_write(e, offer_id, &alts);
+    Ok(())
+}
+
+pub fn alt_write(e: &Env, offer_id: u32, alts: &Vec<AltRecv>) {
+    let key = DataKey::OfferAltRecv(offer_id);
+    if alts.is_empty() {
+        e.storage().persistent().remove(&key);
+    } else {
+        e.storage().persistent().set(&key, alts);
+        e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    }
+}
+
+// Swaps `amount` of the alternative `recv_token` for send_token at the offer's price in it,
+// which must give at least `min_send_out`. Returns the amount of send_token received.
+// Must be authorized by acceptor.
+pub fn alt_accept(e: &Env, 
+    acceptor: &Address, 
+    offer_id: u32, 
+    recv_token: &Address, 
+    amount: u64, 
+    min_send_out: u64
+) -> Result<u64, SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+
+    let alt = alt_get(e, offer_id).iter().find(|alt| alt.recv_token == recv_token.clone())
+        .ok_or(SwapError::TokenNotAllowed)?;
+    let mut offer = offer_load_live(e, offer_id)?;
+
+    let send_amount = ((amount as u128) * (alt.send_amount as u128) / (alt.recv_amount as u128)) as u64;
+    if send_amount > offer.send_amount {
+        return Err(SwapError::AmountTooLarge);
+    }
+    // The recv_token amount the fill stands for, all of it when the whole escrow is bought.
+    let equiv_amount = if send_amount == offer.send_amount {
+        offer.recv_amount
+    } else {
+        ((send_amount as u128) * (offer.recv_amount as u128) / (offer.send_amount as u128)) as u64
+    };
+    offer_quote(e, offer_id, &offer, equiv_amount, Some(acceptor))?;
+    // Fills in alternative tokens are paid out right away, so they can't be held for disputes.
+    if dispute_window_get(e, offer_id) > 0 {
+        return Err(SwapError::AcceptorNotAllowed);
+    }
+    if send_amount == 0 {
+        return Err(SwapError::AmountTooSmall);
+    }
+    if send_amount < min_send_out {
+        return Err(SwapError::SlippageExceeded);
+    }
+
+    acceptor.require_auth();
+
+    let fee_info = fee_get(e)?;
+    let fee_amount = calculate_fee(e, recv_token, fee_info.taker_fee_rate, amount, Some(acceptor), true);
+    let recv_token_client = token::Client::new(e, recv_token);
+    if recv_token_client.balance(acceptor) < ((amount + fee_amount) as i128) {
+        return Err(SwapError::InsufficientBalance);
+    }
+
+    let fee_received = escrow_in(e, recv_token, acceptor, fee_amount);
+    fee_accrue(e, recv_token, fee_received);
+    recv_token_client.transfer(acceptor, &offer.offeror, &(amount as i128));
+    escrow_out(e, &offer.send_token, acceptor, send_amount);
+
+    offer_settle(e, offer_id, &mut offer, acceptor, equiv_amount, send_amount)?;
+    volume_add(e, acceptor, recv_token, amount);
+
+    // emit OfferAccepted event for the alternative token
+    e.events().publish((OFFER, symbol_short!("OAcceptAl")), 
+        (acceptor.clone(), offer_id, recv_token.clone(), amount)
+    );
+
+    Ok(send_amount)
+}
@@ -0,0 +1,121 @@
+const AMM: Symbol = symbol_short!("AMM");
+
+use soroban_sdk::{ contractclient, vec, Address, Env, symbol_short, Symbol, Vec };
+
+use crate::storage_types::{ ConfigKey, Role };
+use crate::compliance::{ ban_check };
+use crate::offer::{ offer_load_live, offer_quote, offer_take_best };
+use crate::orderbook::{ book_get };
+use crate::pause::{ pause_check };
+use crate::role::{ role_check };
+use crate::error::SwapError;
+
+
+/*
+With an AMM router set, `swap_best` sends a taker's swap to whichever of the order book
+and the AMM pays more for it, so takers don't have to check both venues themselves.
+
+The book is quoted the way `take_best` would fill it. Its taker fee is paid on top of
+`amount`, so the fee's worth in send_token is taken off the book's quote before the two
+are compared. The whole swap goes to one venue. On the AMM the router moves the tokens
+between the acceptor and the pool directly, none pass through this contract, so the
+acceptor authorizes the router's swap as part of `swap_best`.
+
+The router follows the Soroswap interface.
+*/
+
+// The part of the Soroswap router interface used by the contract.
+#[allow(dead_code)]
+#[contractclient(name = "RouterClient")]
+pub trait RouterInterface {
+    fn router_get_amounts_out(env: Env, amount_in: i128, path: Vec<Address>) -> Vec<i128>;
+    fn swap_exact_tokens_for_tokens(env: Env, amount_in: i128, amount_out_min: i128, path: Vec<Address>, to: Address, deadline: u64) -> Vec<i128>;
+}
+
+pub fn router_get(e: &Env) -> Option<Address> {
+    e.storage().instance().get(&ConfigKey::Router)
+}
+
+pub fn router_set(e: &Env, caller: &Address, router: &Option<Address>) -> Result<(), SwapError> {
+    let key = ConfigKey::Router;
+    role_check(e, Role::Admin, caller)?;
+    match router {
+        Some(router) => e.storage().instance().set(&key, router),
+        None => e.storage().instance().remove(&key),
+    }
+    Ok(())
+}
+
+// Swaps `amount` of recv_token for send_token at the order book or the AMM, whichever
+// pays more. Returns the amount of send_token received, which must be at least `min_out`.
+pub fn swap_best(e: &Env, 
+    acceptor: &Address, 
+    send_token: &Address, 
+    recv_token: &Address, 
+    amount: u64, 
+    min_out: u64
+) -> Result<u64, SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+    if amount == 0 {
+        return Err(SwapError::ZeroAmount);
+    }
+
+    let book_out = book_quote(e, acceptor, send_token, recv_token, amount);
+    let router = match router_get(e) {
+        Some(router) => RouterClient::new(e, &router),
+        None => return Ok(offer_take_best(e, acceptor, send_token, recv_token, amount, min_out)?.1),
+    };
+    let path = vec![e, recv_token.clone(), send_token.clone()];
+    let amm_out = router.router_get_amounts_out(&(amount as i128), &path).last().unwrap_or(0);
+    if amm_out <= book_out as i128 {
+        return Ok(offer_take_best(e, acceptor, send_token, recv_token, amount, min_out)?.1);
+    }
+
+    ban_check(e, acceptor)?;
+
+    acceptor.require_auth();
+
+    let amounts = router.swap_exact_tokens_for_tokens(&(amount as i128), &(min_out as i128), &path, acceptor, &e.ledger().timestamp());
+    let amount_out = u64::try_from(amounts.last().unwrap_or(0)).map_err(|_| SwapError::Overflow)?;
+    if amount_out < min_out {
+        return Err(SwapError::SlippageExceeded);
+    }
+
+    // emit AmmSwap event
+    e.events().publish((AMM, symbol_short!("ASwap")), 
+        (acceptor.clone(), recv_token.clone(), send_token.clone(), amount, amount_out)
+    );
+
+    Ok(amount_out)
+}
+
+// send_token acceptor would get from the book for `amount` of recv_token, less the worth
+// of the taker fee.
+fn book_quote(e: &Env, acceptor: &Address, send_token: &Address, recv_token: &Address, amount: u64) -> u64 {
+    let mut remaining = amount;
+    let mut send_amount: u128 = 0;
+    let mut fee_amount: u128 = 0;
+    for offer_id in book_get(e, send_token, recv_token).iter() {
+        if remaining == 0 {
+            break;
+        }
+        let offer = match offer_load_live(e, offer_id) {
+            Ok(offer) => offer,
+            Err(_) => continue,
+        };
+        let fill_amount = remaining.min(offer.recv_amount);
+        if let Ok((fill_send_amount, fill_fee_amount)) = offer_quote(e, offer_id, &offer, fill_amount, Some(acceptor)) {
+            send_amount += fill_send_amount as u128;
+            fee_amount += fill_fee_amount as u128;
+            remaining -= fill_amount;
+        }
+    }
+
+    let spent = (amount - remaining) as u128 + fee_amount;
+    if spent == 0 {
+        return 0;
+    }
+    (send_amount - send_amount * fee_amount / spent) as u64
+}
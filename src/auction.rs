@@ -0,0 +1,199 @@
+use soroban_sdk::{ Address, Env };
+
+use crate::storage_types::{ BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, 
+    Bid, DataKey, OfferDataKey, OfferStatus
+};
+
+use crate::acceptor::{ acceptors_get, counterparty_get };
+use crate::dutch::{ dutch_get };
+use crate::fee::{ fee_get, fee_accrue, calculate_fee };
+use crate::offer::{ offer_load, offer_settle };
+use crate::pause::{ pause_check };
+use crate::stream::{ proceeds_pay };
+use crate::escrow::{ escrow_in_exact, escrow_out };
+use crate::error::SwapError;
+
+
+/*
+English auctions sell the whole send_amount of an offer to the highest bidder.
+
+1. Offeror calls `start` with the ledger at which bidding ends. While the auction
+   runs the offer can't be accepted, changed or closed.
+2. Bidders call `bid`, escrowing their bid of `recv_token` plus the taker fee. Each bid
+   must be at least the offer's min_recv_amount and above the best bid so far. Bidders
+   may raise their own bid, paying only the difference.
+3. Outbid bidders call `refund` to get their escrow back.
+4. Once bidding has ended anyone may call `settle`, which swaps the send_amount and
+   the best bid. Without any bid the offer simply becomes a regular offer again.
+*/
+
+// Ledger at which bidding on the offer ends, if it's being auctioned.
+pub fn auction_get(e: &Env, offer_id: u32) -> Option<u32> {
+    let key = DataKey::AuctionEnd(offer_id);
+
+    e.storage().persistent().get(&key)
+}
+
+pub fn auction_best_bid(e: &Env, offer_id: u32) -> Option<Bid> {
+    let key = DataKey::AuctionBest(offer_id);
+
+    e.storage().persistent().get(&key)
+}
+
+// Amount escrowed by the bidder, including the taker fee.
+pub fn auction_escrow(e: &Env, offer_id: u32, bidder: &Address) -> u64 {
+    let key = DataKey::AuctionEscrow(offer_id, bidder.clone());
+
+    e.storage().persistent().get(&key).unwrap_or(0)
+}
+
+pub fn auction_start(e: &Env, offeror: &Address, offer_id: u32, end_ledger: u32) -> Result<(), SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+
+    let offer = offer_load(e, offer_id)?;
+    if offer.offeror != offeror.clone() {
+        return Err(SwapError::InvalidOfferor);
+    }
+    if offer.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+    if auction_get(e, offer_id).is_some() {
+        return Err(SwapError::OfferInAuction);
+    }
+    if dutch_get(e, offer_id).is_some() || acceptors_get(e, offer_id).is_some() || counterparty_get(e, offer_id).is_some() {
+        return Err(SwapError::InvalidAuction);
+    }
+    if end_ledger <= e.ledger().sequence() || end_ledger >= offer.expire_ledger {
+        return Err(SwapError::InvalidAuction);
+    }
+
+    offeror.require_auth();
+
+    let key = DataKey::AuctionEnd(offer_id);
+    e.storage().persistent().set(&key, &end_ledger);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    Ok(())
+}
+
+pub fn auction_bid(e: &Env, bidder: &Address, offer_id: u32, amount: u64) -> Result<(), SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+
+    let end_ledger = auction_get(e, offer_id).ok_or(SwapError::OfferNotActive)?;
+    if e.ledger().sequence() >= end_ledger {
+        return Err(SwapError::OfferExpired);
+    }
+
+    let offer = offer_load(e, offer_id)?;
+    if amount < offer.min_recv_amount {
+        return Err(SwapError::BidTooLow);
+    }
+    if let Some(best) = auction_best_bid(e, offer_id) {
+        if amount <= best.amount {
+            return Err(SwapError::BidTooLow);
+        }
+    }
+
+    bidder.require_auth();
+
+    let fee_info = fee_get(e)?;
+    let escrow = amount + calculate_fee(e, &offer.recv_token, fee_info.taker_fee_rate, amount, Some(bidder), true);
+    let escrowed = auction_escrow(e, offer_id, bidder);
+    if escrow > escrowed {
+        escrow_in_exact(e, &offer.recv_token, bidder, escrow - escrowed)?;
+    }
+    if escrowed == 0 {
+        bidders_write(e, offer_id, auction_bidders(e, offer_id) + 1);
+    }
+
+    escrow_write(e, offer_id, bidder, escrow.max(escrowed));
+    let key = DataKey::AuctionBest(offer_id);
+    e.storage().persistent().set(&key, &Bid { bidder: bidder.clone(), amount });
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    Ok(())
+}
+
+// Returns the escrow of a bidder who doesn't hold the best bid.
+pub fn auction_refund(e: &Env, bidder: &Address, offer_id: u32) -> Result<u64, SwapError> {
+    if let Some(best) = auction_best_bid(e, offer_id) {
+        if best.bidder == bidder.clone() {
+            return Err(SwapError::BidNotRefundable);
+        }
+    }
+
+    bidder.require_auth();
+
+    let escrow = auction_escrow(e, offer_id, bidder);
+    if escrow == 0 {
+        return Ok(0);
+    }
+
+    let offer = offer_load(e, offer_id)?;
+    e.storage().persistent().remove(&DataKey::AuctionEscrow(offer_id, bidder.clone()));
+    bidders_write(e, offer_id, auction_bidders(e, offer_id) - 1);
+    escrow_out(e, &offer.recv_token, bidder, escrow);
+    Ok(escrow)
+}
+
+// Ends the auction without a winner, so every bidder can get their bid back.
+pub fn auction_cancel(e: &Env, offer_id: u32) {
+    e.storage().persistent().remove(&DataKey::AuctionEnd(offer_id));
+    e.storage().persistent().remove(&DataKey::AuctionBest(offer_id));
+}
+
+// Swaps the offer with the best bid once bidding has ended. Can be called by anyone.
+pub fn auction_settle(e: &Env, offer_id: u32) -> Result<(), SwapError> {
+    let end_ledger = auction_get(e, offer_id).ok_or(SwapError::OfferNotActive)?;
+    if e.ledger().sequence() < end_ledger {
+        return Err(SwapError::OfferNotExpired);
+    }
+
+    e.storage().persistent().remove(&DataKey::AuctionEnd(offer_id));
+    let best = match auction_best_bid(e, offer_id) {
+        Some(best) => best,
+        None => return Ok(()),
+    };
+    e.storage().persistent().remove(&DataKey::AuctionBest(offer_id));
+
+    let mut offer = offer_load(e, offer_id)?;
+    let escrow = auction_escrow(e, offer_id, &best.bidder);
+    e.storage().persistent().remove(&DataKey::AuctionEscrow(offer_id, best.bidder.clone()));
+    bidders_write(e, offer_id, auction_bidders(e, offer_id) - 1);
+
+    let contract = e.current_contract_address();
+    proceeds_pay(e, offer_id, &offer, &contract, best.amount);
+    fee_accrue(e, &offer.recv_token, escrow - best.amount);
+    escrow_out(e, &offer.send_token, &best.bidder, offer.send_amount);
+
+    // The auction clears the whole offer at the winning bid.
+    offer.recv_amount = best.amount;
+    let send_amount = offer.send_amount;
+    offer_settle(e, offer_id, &mut offer, &best.bidder, best.amount, send_amount)?;
+    Ok(())
+}
+
+// Number of bidders whose escrow is still held for the offer.
+pub fn auction_bidders(e: &Env, offer_id: u32) -> u32 {
+    let key = OfferDataKey::Bidders(offer_id);
+
+    e.storage().persistent().get(&key).unwrap_or(0)
+}
+
+fn bidders_write(e: &Env, offer_id: u32, count: u32) {
+    let key = OfferDataKey::Bidders(offer_id);
+    if count == 0 {
+        e.storage().persistent().remove(&key);
+    } else {
+        e.storage().persistent().set(&key, &count);
+        e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    }
+}
+
+fn escrow_write(e: &Env, offer_id: u32, bidder: &Address, escrow: u64) {
+    let key = DataKey::AuctionEscrow(offer_id, bidder.clone());
+    e.storage().persistent().set(&key, &escrow);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
@@ -0,0 +1,194 @@
+const BASKET: Symbol = symbol_short!("BASKET");
+
+use soroban_sdk::{ token, Address, Env, symbol_short, Symbol, Vec };
+
+use crate::storage_types::{ INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, 
+    BasketItem, BasketOffer, DataKey, OfferStatus
+};
+use crate::allow::{ allow_get, allow_pair_get };
+use crate::fee::{ fee_check, fee_get, fee_accrue, calculate_fee, volume_add };
+use crate::stats::{ stats_fill };
+use crate::pause::{ pause_check };
+use crate::escrow::{ escrow_in, escrow_out };
+use crate::error::SwapError;
+
+
+/*
+Basket offers sell several send tokens together for a single recv token:
+
+1. Offeror calls `create` with the (token, amount) pairs of the basket, escrowing
+   every amount plus the maker fee on it.
+2. Acceptors call `accept` to buy the whole basket for recv_amount plus the taker fee.
+   Baskets can't be partially filled.
+3. Offeror may call `close` to get the basket back, and once `expire_ledger` is
+   reached anyone may call `expire` to do the same.
+*/
+
+pub fn basket_create(
+    e: &Env,
+    offeror: &Address,
+    items: &Vec<(Address, u64)>,
+    recv_token: &Address,
+    recv_amount: u64,
+    expire_ledger: u32,
+) -> Result<u32, SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+    if !fee_check(e) {
+        return Err(SwapError::FeeNotSet);
+    }
+    if items.is_empty() || recv_amount == 0 {
+        return Err(SwapError::ZeroAmount);
+    }
+    if !allow_get(e, recv_token) {
+        return Err(SwapError::TokenNotAllowed);
+    }
+    if expire_ledger <= e.ledger().sequence() {
+        return Err(SwapError::InvalidExpiration);
+    }
+
+    offeror.require_auth();
+
+    let fee_info = fee_get(e)?;
+    let mut basket_items: Vec<BasketItem> = Vec::new(e);
+    for (send_token, send_amount) in items.iter() {
+        if send_amount == 0 {
+            return Err(SwapError::ZeroAmount);
+        }
+        if !allow_pair_get(e, &send_token, recv_token) {
+            return Err(SwapError::TokenNotAllowed);
+        }
+
+        let fee_amount = calculate_fee(e, &send_token, fee_info.maker_fee_rate, send_amount, Some(offeror), false);
+        let token_client = token::Client::new(e, &send_token);
+        if token_client.balance(offeror) < ((send_amount + fee_amount) as i128) {
+            return Err(SwapError::InsufficientBalance);
+        }
+        // The maker fee is escrowed in full, so a shortfall comes out of what's sold.
+        let received = escrow_in(e, &send_token, offeror, send_amount + fee_amount);
+        let send_amount = received.saturating_sub(fee_amount);
+        if send_amount == 0 {
+            return Err(SwapError::AmountTooSmall);
+        }
+        basket_items.push_back(BasketItem { send_token, send_amount, fee_amount });
+    }
+
+    let basket_id: u32 = e.storage().instance().get(&DataKey::BasketCount).unwrap_or(0);
+    basket_write(e, basket_id, &BasketOffer {
+        offeror: offeror.clone(),
+        items: basket_items,
+        recv_token: recv_token.clone(),
+        recv_amount,
+        expire_ledger,
+        status: OfferStatus::ACTIVE,
+    });
+    e.storage().instance().set(&DataKey::BasketCount, &(basket_id + 1));
+    e.storage().instance().extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+
+    // emit BasketCreated event
+    e.events().publish((BASKET, symbol_short!("BCreate")), 
+        (basket_id, offeror.clone(), items.clone(), recv_token.clone(), recv_amount, expire_ledger)
+    );
+
+    Ok(basket_id)
+}
+
+pub fn basket_accept(e: &Env, acceptor: &Address, basket_id: u32) -> Result<(), SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+
+    let mut basket = basket_load(e, basket_id)?;
+    if basket.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+    if e.ledger().sequence() >= basket.expire_ledger {
+        return Err(SwapError::OfferExpired);
+    }
+
+    acceptor.require_auth();
+
+    let fee_info = fee_get(e)?;
+    let fee_amount = calculate_fee(e, &basket.recv_token, fee_info.taker_fee_rate, basket.recv_amount, Some(acceptor), true);
+    let recv_token_client = token::Client::new(e, &basket.recv_token);
+    if recv_token_client.balance(acceptor) < ((basket.recv_amount + fee_amount) as i128) {
+        return Err(SwapError::InsufficientBalance);
+    }
+
+    let fee_received = escrow_in(e, &basket.recv_token, acceptor, fee_amount);
+    fee_accrue(e, &basket.recv_token, fee_received);
+    recv_token_client.transfer(acceptor, &basket.offeror, &(basket.recv_amount as i128));
+    for item in basket.items.iter() {
+        escrow_out(e, &item.send_token, acceptor, item.send_amount);
+        fee_accrue(e, &item.send_token, item.fee_amount);
+    }
+
+    basket.status = OfferStatus::COMPLETE;
+    basket_write(e, basket_id, &basket);
+    volume_add(e, acceptor, &basket.recv_token, basket.recv_amount);
+    stats_fill(e);
+
+    // emit BasketAccepted event
+    e.events().publish((BASKET, symbol_short!("BAccept")), 
+        (acceptor.clone(), basket_id)
+    );
+
+    Ok(())
+}
+
+pub fn basket_close(e: &Env, offeror: &Address, basket_id: u32) -> Result<(), SwapError> {
+    let basket = basket_load(e, basket_id)?;
+    if basket.offeror != offeror.clone() {
+        return Err(SwapError::InvalidOfferor);
+    }
+    if basket.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+
+    offeror.require_auth();
+
+    basket_refund(e, basket_id, basket, OfferStatus::CANCEL);
+    Ok(())
+}
+
+// Can be called by anyone once the basket's expire_ledger is reached.
+pub fn basket_expire(e: &Env, basket_id: u32) -> Result<(), SwapError> {
+    let basket = basket_load(e, basket_id)?;
+    if basket.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+    if e.ledger().sequence() < basket.expire_ledger {
+        return Err(SwapError::OfferNotExpired);
+    }
+
+    basket_refund(e, basket_id, basket, OfferStatus::EXPIRED);
+    Ok(())
+}
+
+pub fn basket_load(e: &Env, basket_id: u32) -> Result<BasketOffer, SwapError> {
+    let key = DataKey::Basket(basket_id);
+
+    e.storage().persistent().get(&key).ok_or(SwapError::BasketNotFound)
+}
+
+// Returns the escrowed items along with their maker fees to the offeror.
+fn basket_refund(e: &Env, basket_id: u32, mut basket: BasketOffer, status: OfferStatus) {
+    for item in basket.items.iter() {
+        escrow_out(e, &item.send_token, &basket.offeror, item.send_amount + item.fee_amount);
+    }
+
+    basket.status = status;
+    basket_write(e, basket_id, &basket);
+
+    // emit BasketRefunded event
+    e.events().publish((BASKET, symbol_short!("BRefund")), 
+        (basket.offeror.clone(), basket_id, status)
+    );
+}
+
+fn basket_write(e: &Env, basket_id: u32, basket: &BasketOffer) {
+    let key = DataKey::Basket(basket_id);
+    e.storage().persistent().set(&key, basket);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
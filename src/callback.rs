@@ -0,0 +1,50 @@
+const CALLBACK: Symbol = symbol_short!("CALLBACK");
+
+use soroban_sdk::{ contractclient, Address, Env, symbol_short, Symbol };
+
+use crate::storage_types::{ BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, ConfigKey };
+
+
+/*
+Vaults and other contracts trading through the swap can ask to be told about their fills.
+An acceptor that registers a callback gets `on_fill` called on itself after each of its
+fills has been settled, in the same transaction.
+
+The callback runs once the settlement has released its lock, so the acceptor can trade
+with the swap again from within it. A callback that fails rolls back the fill.
+*/
+
+// The interface an acceptor registering a callback implements.
+#[allow(dead_code)]
+#[contractclient(name = "FillCallbackClient")]
+pub trait FillCallbackInterface {
+    fn on_fill(env: Env, offer_id: u32, amount_in: u64, amount_out: u64);
+}
+
+pub fn callback_get(e: &Env, acceptor: &Address) -> bool {
+    e.storage().persistent().has(&ConfigKey::FillCallback(acceptor.clone()))
+}
+
+pub fn callback_set(e: &Env, acceptor: &Address, enabled: bool) {
+    let key = ConfigKey::FillCallback(acceptor.clone());
+    acceptor.require_auth();
+    if enabled {
+        e.storage().persistent().set(&key, &true);
+        e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    } else {
+        e.storage().persistent().remove(&key);
+    }
+
+    // emit FillCallbackSet event
+    e.events().publish((CALLBACK, symbol_short!("CbSet")), 
+        (acceptor.clone(), enabled)
+    );
+}
+
+// Tells acceptor, if it registered a callback, that it paid `amount_in` of the offer's
+// recv_token for `amount_out` of its send_token.
+pub fn callback_notify(e: &Env, acceptor: &Address, offer_id: u32, amount_in: u64, amount_out: u64) {
+    if callback_get(e, acceptor) {
+        FillCallbackClient::new(e, acceptor).on_fill(&offer_id, &amount_in, &amount_out);
+    }
+}
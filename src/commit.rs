@@ -0,0 +1,155 @@
+use soroban_sdk::{ xdr::ToXdr, Address, BytesN, Env, Vec };
+
+use crate::storage_types::{ BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, REVEAL_DELAY, REVEAL_WINDOW, MAX_COMMIT_LOCK, 
+    Commitment, DataKey, OfferDataKey, OfferInfo, OfferStatus
+};
+use crate::offer::{ offer_fill, offer_load };
+use crate::escrow::{ escrow_in, escrow_out };
+use crate::error::SwapError;
+
+
+/*
+Two-phase acceptance for takers who don't want their fill seen before it executes:
+
+1. Acceptor calls `commit` with an active offer and the sha256 hash of the xdr of
+   `(acceptor, offer_id, amount, salt)`, escrowing min_recv_amount of the recv token
+   as a bond. If the offer isn't locked already, it can't be repriced or decreased
+   for the next REVEAL_WINDOW ledgers. Commits while it is locked share that lock, and
+   an offer is never locked for more than MAX_COMMIT_LOCK ledgers in total.
+2. At least REVEAL_DELAY ledgers later, and within REVEAL_WINDOW ledgers of the commit,
+   the acceptor calls `reveal` with the amount and salt to get the bond back and
+   perform the fill. Bonds of commitments left to lapse go to the offeror.
+*/
+
+pub fn commit_get(e: &Env, offer_id: u32, acceptor: &Address) -> Option<Commitment> {
+    let key = DataKey::Commit(offer_id, acceptor.clone());
+
+    e.storage().persistent().get(&key)
+}
+
+// Ledger until which the offer can't be repriced because of pending commitments.
+pub fn commit_lock_get(e: &Env, offer_id: u32) -> u32 {
+    let key = DataKey::OfferLock(offer_id);
+
+    e.storage().persistent().get(&key).unwrap_or(0)
+}
+
+pub fn commit_locked(e: &Env, offer_id: u32) -> bool {
+    e.ledger().sequence() < commit_lock_get(e, offer_id)
+}
+
+pub fn commit_hash(e: &Env, acceptor: &Address, offer_id: u32, amount: u64, salt: &BytesN<32>) -> BytesN<32> {
+    let payload = (acceptor.clone(), offer_id, amount, salt.clone()).to_xdr(e);
+
+    e.crypto().sha256(&payload)
+}
+
+pub fn commit_accept(e: &Env, acceptor: &Address, offer_id: u32, hash: &BytesN<32>) -> Result<(), SwapError> {
+    let offer = offer_load(e, offer_id)?;
+    if offer.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+
+    acceptor.require_auth();
+
+    let ledger = e.ledger().sequence();
+    if !commit_locked(e, offer_id) {
+        let key = OfferDataKey::CommitLockUsed(offer_id);
+        let used: u32 = e.storage().persistent().get(&key).unwrap_or(0);
+        if used + REVEAL_WINDOW > MAX_COMMIT_LOCK {
+            return Err(SwapError::OfferLocked);
+        }
+        e.storage().persistent().set(&key, &(used + REVEAL_WINDOW));
+        e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+
+        let key = DataKey::OfferLock(offer_id);
+        e.storage().persistent().set(&key, &(ledger + REVEAL_WINDOW));
+        e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    }
+
+    // a new hash keeps the bond of a pending commitment
+    let bond = match commit_get(e, offer_id, acceptor) {
+        Some(commitment) if ledger < commitment.ledger + REVEAL_WINDOW => commitment.bond,
+        lapsed => {
+            if let Some(commitment) = lapsed {
+                escrow_out(e, &offer.recv_token, &offer.offeror, commitment.bond);
+            }
+            let bond = escrow_in(e, &offer.recv_token, acceptor, offer.min_recv_amount.max(1));
+            if bond == 0 {
+                return Err(SwapError::AmountTooSmall);
+            }
+            bond
+        },
+    };
+
+    let key = DataKey::Commit(offer_id, acceptor.clone());
+    e.storage().persistent().set(&key, &Commitment { hash: hash.clone(), ledger, bond });
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    committers_index(e, offer_id, acceptor, true);
+    Ok(())
+}
+
+// Returns the bond and fills the committed amount of the offer, or only returns the bond
+// if the offer can't be filled anymore. Returns the amount of send_token received.
+pub fn commit_reveal(e: &Env, acceptor: &Address, offer_id: u32, amount: u64, salt: &BytesN<32>) -> Result<u64, SwapError> {
+    let commitment = commit_get(e, offer_id, acceptor).ok_or(SwapError::CommitNotFound)?;
+    let ledger = e.ledger().sequence();
+    if ledger < commitment.ledger + REVEAL_DELAY {
+        return Err(SwapError::InvalidReveal);
+    }
+    if ledger >= commitment.ledger + REVEAL_WINDOW {
+        return Err(SwapError::InvalidReveal);
+    }
+    if commit_hash(e, acceptor, offer_id, amount, salt) != commitment.hash {
+        return Err(SwapError::InvalidReveal);
+    }
+
+    acceptor.require_auth();
+
+    e.storage().persistent().remove(&DataKey::Commit(offer_id, acceptor.clone()));
+    committers_index(e, offer_id, acceptor, false);
+    let offer = offer_load(e, offer_id)?;
+    escrow_out(e, &offer.recv_token, acceptor, commitment.bond);
+    if offer.status != OfferStatus::ACTIVE {
+        return Ok(0);
+    }
+    offer_fill(e, acceptor, offer_id, amount, 0, &None)
+}
+
+// Removes the commitments on the offer which were never revealed and its lock. Bonds
+// of lapsed commitments go to the offeror, the others back to their acceptors.
+pub fn commits_remove(e: &Env, offer_id: u32, offer: &OfferInfo) {
+    let ledger = e.ledger().sequence();
+    let key = OfferDataKey::Committers(offer_id);
+    let committers: Vec<Address> = e.storage().persistent().get(&key).unwrap_or(Vec::new(e));
+    for acceptor in committers.iter() {
+        let commit_key = DataKey::Commit(offer_id, acceptor.clone());
+        if let Some(commitment) = e.storage().persistent().get::<_, Commitment>(&commit_key) {
+            let to = if ledger < commitment.ledger + REVEAL_WINDOW { acceptor } else { offer.offeror.clone() };
+            escrow_out(e, &offer.recv_token, &to, commitment.bond);
+        }
+        e.storage().persistent().remove(&commit_key);
+    }
+    e.storage().persistent().remove(&key);
+    e.storage().persistent().remove(&DataKey::OfferLock(offer_id));
+    e.storage().persistent().remove(&OfferDataKey::CommitLockUsed(offer_id));
+}
+
+// Keeps track of the acceptors with a commitment on the offer, so they can be removed with it.
+fn committers_index(e: &Env, offer_id: u32, acceptor: &Address, committed: bool) {
+    let key = OfferDataKey::Committers(offer_id);
+    let mut committers: Vec<Address> = e.storage().persistent().get(&key).unwrap_or(Vec::new(e));
+    match (committers.first_index_of(acceptor), committed) {
+        (None, true) => committers.push_back(acceptor.clone()),
+        (Some(i), false) => {
+            committers.remove(i);
+        },
+        _ => return,
+    }
+    if committers.is_empty() {
+        e.storage().persistent().remove(&key);
+    } else {
+        e.storage().persistent().set(&key, &committers);
+        e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    }
+}
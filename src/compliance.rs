@@ -0,0 +1,84 @@
+const COMPLY: Symbol = symbol_short!("COMPLY");
+
+use soroban_sdk::{ contractclient, Address, Env, symbol_short, Symbol };
+
+use crate::storage_types::{ BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, ConfigKey, Role };
+use crate::role::{ role_check };
+use crate::error::SwapError;
+
+
+/*
+Issuers with rules of their own can plug in a compliance contract. When one is set, the
+offeror is checked for both tokens of an offer when creating it, and the acceptor for
+both tokens of a fill when accepting. The amount is what the account gives or gets of
+the token. A contract that denies, or fails, stops the call.
+
+The admin can also ban addresses outright, which keeps them from creating and accepting
+offers. A banned offeror can still close its offers and get its tokens back.
+*/
+
+// The interface a compliance contract implements.
+#[allow(dead_code)]
+#[contractclient(name = "ComplianceClient")]
+pub trait ComplianceInterface {
+    fn can_trade(env: Env, account: Address, token: Address, amount: i128) -> bool;
+}
+
+pub fn compliance_get(e: &Env) -> Option<Address> {
+    e.storage().instance().get(&ConfigKey::Compliance)
+}
+
+pub fn compliance_set(e: &Env, caller: &Address, compliance: &Option<Address>) -> Result<(), SwapError> {
+    let key = ConfigKey::Compliance;
+    role_check(e, Role::Admin, caller)?;
+    match compliance {
+        Some(compliance) => e.storage().instance().set(&key, compliance),
+        None => e.storage().instance().remove(&key),
+    }
+    Ok(())
+}
+
+// Asks the compliance contract, if any, whether account may trade `amount` of token.
+pub fn compliance_check(e: &Env, account: &Address, token: &Address, amount: u64) -> Result<(), SwapError> {
+    let compliance = match compliance_get(e) {
+        Some(compliance) => compliance,
+        None => return Ok(()),
+    };
+    if !ComplianceClient::new(e, &compliance).can_trade(account, token, &(amount as i128)) {
+        return Err(SwapError::Unauthorized);
+    }
+    Ok(())
+}
+
+pub fn ban_get(e: &Env, account: &Address) -> bool {
+    e.storage().persistent().has(&ConfigKey::Banned(account.clone()))
+}
+
+pub fn ban_set(e: &Env, caller: &Address, account: &Address, banned: bool) -> Result<(), SwapError> {
+    let key = ConfigKey::Banned(account.clone());
+    role_check(e, Role::Admin, caller)?;
+    if banned {
+        e.storage().persistent().set(&key, &true);
+        e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+
+        // emit AddressBanned event
+        e.events().publish((COMPLY, symbol_short!("Ban")), 
+            account.clone()
+        );
+    } else {
+        e.storage().persistent().remove(&key);
+
+        // emit AddressUnbanned event
+        e.events().publish((COMPLY, symbol_short!("Unban")), 
+            account.clone()
+        );
+    }
+    Ok(())
+}
+
+pub fn ban_check(e: &Env, account: &Address) -> Result<(), SwapError> {
+    if ban_get(e, account) {
+        return Err(SwapError::Unauthorized);
+    }
+    Ok(())
+}
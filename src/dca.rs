@@ -0,0 +1,175 @@
+const DCA: Symbol = symbol_short!("DCA");
+
+use soroban_sdk::{ token, Address, Env, symbol_short, Symbol };
+
+use crate::storage_types::{ INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, 
+    DataKey, DcaPlan, OfferStatus
+};
+use crate::allow::{ allow_pair_get };
+use crate::offer::{ offer_fill, offer_load_live, offer_quote };
+use crate::pause::{ pause_check };
+use crate::escrow::{ escrow_in, escrow_out };
+use crate::error::SwapError;
+
+
+/*
+Recurring swaps (dollar-cost averaging) on top of the offers:
+
+1. Owner calls `create` to escrow a `budget` of `send_token` and buy `recv_token`
+   with `amount` of it every `period_ledgers`.
+2. Once a period is due, any keeper may call `execute` with an offer selling
+   `recv_token` for `send_token`. The contract accepts the offer for `amount` out of
+   the budget, paying the taker fee from it too, and forwards the proceeds to the owner.
+   The fill must return at least `min_recv_amount` per period. The keeper earns
+   `bounty` of `send_token` from the budget.
+3. Owner may call `cancel` at any time to withdraw what's left of the budget.
+*/
+
+#[allow(clippy::too_many_arguments)]
+pub fn dca_create(
+    e: &Env,
+    owner: &Address,
+    send_token: &Address,
+    recv_token: &Address,
+    budget: u64,
+    amount: u64,
+    period_ledgers: u32,
+    min_recv_amount: u64,
+    bounty: u64,
+) -> Result<u32, SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+    if !allow_pair_get(e, send_token, recv_token) {
+        return Err(SwapError::TokenNotAllowed);
+    }
+    if budget == 0 || amount == 0 || period_ledgers == 0 {
+        return Err(SwapError::ZeroAmount);
+    }
+    if amount + bounty > budget {
+        return Err(SwapError::AmountTooLarge);
+    }
+
+    owner.require_auth();
+
+    let token_client = token::Client::new(e, send_token);
+    if token_client.balance(owner) < (budget as i128) {
+        return Err(SwapError::InsufficientBalance);
+    }
+    let budget = escrow_in(e, send_token, owner, budget);
+    if amount + bounty > budget {
+        return Err(SwapError::AmountTooLarge);
+    }
+
+    let plan_id: u32 = e.storage().instance().get(&DataKey::DcaCount).unwrap_or(0);
+    dca_write(e, plan_id, &DcaPlan {
+        owner: owner.clone(),
+        send_token: send_token.clone(),
+        recv_token: recv_token.clone(),
+        budget,
+        amount,
+        period_ledgers,
+        min_recv_amount,
+        bounty,
+        next_ledger: e.ledger().sequence(),
+        status: OfferStatus::ACTIVE,
+    });
+    e.storage().instance().set(&DataKey::DcaCount, &(plan_id + 1));
+    e.storage().instance().extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+
+    // emit DcaCreated event
+    e.events().publish((DCA, symbol_short!("DCreate")), 
+        (plan_id, owner.clone(), send_token.clone(), recv_token.clone(), budget, amount, period_ledgers)
+    );
+
+    Ok(plan_id)
+}
+
+// Runs the due period of the plan against the offer. Returns the amount of recv_token bought.
+pub fn dca_execute(e: &Env, keeper: &Address, plan_id: u32, offer_id: u32) -> Result<u64, SwapError> {
+    let mut plan = dca_load(e, plan_id)?;
+    if plan.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+    if e.ledger().sequence() < plan.next_ledger {
+        return Err(SwapError::DcaNotDue);
+    }
+
+    let offer = offer_load_live(e, offer_id)?;
+    if offer.send_token != plan.recv_token || offer.recv_token != plan.send_token {
+        return Err(SwapError::OffersNotCrossed);
+    }
+
+    // The contract accepts the offer itself, paying from the escrowed budget.
+    let contract = e.current_contract_address();
+    let mut amount = plan.amount.min(plan.budget - plan.bounty);
+    let (_, mut fee_amount) = offer_quote(e, offer_id, &offer, amount, Some(&contract))?;
+    if amount + fee_amount + plan.bounty > plan.budget {
+        // The last period spends what's left after the fee.
+        amount = plan.budget - plan.bounty - fee_amount;
+        (_, fee_amount) = offer_quote(e, offer_id, &offer, amount, Some(&contract))?;
+    }
+    let min_send_out = ((plan.min_recv_amount as u128) * (amount as u128) / (plan.amount as u128)) as u64;
+    let recv_amount = offer_fill(e, &contract, offer_id, amount, min_send_out, &None)?;
+
+    escrow_out(e, &plan.recv_token, &plan.owner, recv_amount);
+    escrow_out(e, &plan.send_token, keeper, plan.bounty);
+
+    plan.budget -= amount + fee_amount + plan.bounty;
+    plan.next_ledger = e.ledger().sequence() + plan.period_ledgers;
+    if plan.budget <= plan.bounty {
+        // What can't pay for another period goes back to the owner.
+        if plan.budget > 0 {
+            escrow_out(e, &plan.send_token, &plan.owner, plan.budget);
+            plan.budget = 0;
+        }
+        plan.status = OfferStatus::COMPLETE;
+    }
+    dca_write(e, plan_id, &plan);
+
+    // emit DcaExecuted event
+    e.events().publish((DCA, symbol_short!("DExecute")), 
+        (plan_id, keeper.clone(), offer_id, amount, recv_amount)
+    );
+
+    Ok(recv_amount)
+}
+
+pub fn dca_cancel(e: &Env, owner: &Address, plan_id: u32) -> Result<u64, SwapError> {
+    let mut plan = dca_load(e, plan_id)?;
+    if plan.owner != owner.clone() {
+        return Err(SwapError::Unauthorized);
+    }
+    if plan.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+
+    owner.require_auth();
+
+    let refund = plan.budget;
+    if refund > 0 {
+        escrow_out(e, &plan.send_token, owner, refund);
+    }
+    plan.budget = 0;
+    plan.status = OfferStatus::CANCEL;
+    dca_write(e, plan_id, &plan);
+
+    // emit DcaCanceled event
+    e.events().publish((DCA, symbol_short!("DCancel")), 
+        (owner.clone(), plan_id, refund)
+    );
+
+    Ok(refund)
+}
+
+pub fn dca_load(e: &Env, plan_id: u32) -> Result<DcaPlan, SwapError> {
+    let key = DataKey::Dca(plan_id);
+
+    e.storage().persistent().get(&key).ok_or(SwapError::DcaNotFound)
+}
+
+fn dca_write(e: &Env, plan_id: u32, plan: &DcaPlan) {
+    let key = DataKey::Dca(plan_id);
+    e.storage().persistent().set(&key, plan);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
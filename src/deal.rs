@@ -0,0 +1,227 @@
+const DEAL: Symbol = symbol_short!("DEAL");
+
+use soroban_sdk::{ token, Address, Env, symbol_short, Symbol };
+
+use crate::storage_types::{ INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT,
+    DealInfo, DealKey, OfferStatus
+};
+use crate::allow::{ allow_pair_get };
+use crate::pause::{ pause_check };
+use crate::escrow::{ escrow_in_exact, escrow_out };
+use crate::error::SwapError;
+
+
+/*
+Two-sided escrow between two known parties, for deals where neither side wants to be
+the one escrowing first against an open offer:
+
+1. Party a calls `open` with both legs of the deal and the ledger it lapses at.
+2. Each party calls `deposit` to escrow its leg, in any order.
+3. Once both legs are in, each party calls `confirm`. The second confirmation swaps
+   the legs.
+4. If the deal isn't settled by `expire_ledger`, each party may `reclaim` its deposit.
+*/
+
+#[allow(clippy::too_many_arguments)]
+pub fn deal_open(
+    e: &Env,
+    party_a: &Address,
+    party_b: &Address,
+    token_a: &Address,
+    amount_a: u64,
+    token_b: &Address,
+    amount_b: u64,
+    expire_ledger: u32,
+) -> Result<u32, SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+    if !allow_pair_get(e, token_a, token_b) {
+        return Err(SwapError::TokenNotAllowed);
+    }
+    if amount_a == 0 || amount_b == 0 {
+        return Err(SwapError::ZeroAmount);
+    }
+    if party_a == party_b {
+        return Err(SwapError::InvalidOfferor);
+    }
+    if expire_ledger <= e.ledger().sequence() {
+        return Err(SwapError::InvalidExpiration);
+    }
+
+    party_a.require_auth();
+
+    let deal_id: u32 = e.storage().instance().get(&DealKey::Count).unwrap_or(0);
+    deal_write(e, deal_id, &DealInfo {
+        party_a: party_a.clone(),
+        party_b: party_b.clone(),
+        token_a: token_a.clone(),
+        amount_a,
+        token_b: token_b.clone(),
+        amount_b,
+        deposited_a: false,
+        deposited_b: false,
+        confirmed_a: false,
+        confirmed_b: false,
+        expire_ledger,
+        status: OfferStatus::ACTIVE,
+    });
+    e.storage().instance().set(&DealKey::Count, &(deal_id + 1));
+    e.storage().instance().extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+
+    // emit DealOpened event
+    e.events().publish((DEAL, symbol_short!("DOpen")),
+        (deal_id, party_a.clone(), party_b.clone(), token_a.clone(), amount_a, token_b.clone(), amount_b, expire_ledger)
+    );
+
+    Ok(deal_id)
+}
+
+// Escrows the leg of party. Depositing again does nothing.
+pub fn deal_deposit(e: &Env, party: &Address, deal_id: u32) -> Result<(), SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+    let mut deal = deal_load_open(e, deal_id)?;
+    let is_a = deal_side(&deal, party)?;
+
+    party.require_auth();
+
+    let (token, amount, deposited) = if is_a {
+        (deal.token_a.clone(), deal.amount_a, deal.deposited_a)
+    } else {
+        (deal.token_b.clone(), deal.amount_b, deal.deposited_b)
+    };
+    if deposited {
+        return Ok(());
+    }
+    if token::Client::new(e, &token).balance(party) < (amount as i128) {
+        return Err(SwapError::InsufficientBalance);
+    }
+    escrow_in_exact(e, &token, party, amount)?;
+
+    if is_a {
+        deal.deposited_a = true;
+    } else {
+        deal.deposited_b = true;
+    }
+    deal_write(e, deal_id, &deal);
+
+    // emit DealDeposited event
+    e.events().publish((DEAL, symbol_short!("DDeposit")),
+        (deal_id, party.clone())
+    );
+
+    Ok(())
+}
+
+// Confirms the deal for party once both legs are escrowed. The second confirmation
+// settles the deal.
+pub fn deal_confirm(e: &Env, party: &Address, deal_id: u32) -> Result<(), SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+    let mut deal = deal_load_open(e, deal_id)?;
+    let is_a = deal_side(&deal, party)?;
+    if !deal.deposited_a || !deal.deposited_b {
+        return Err(SwapError::InsufficientBalance);
+    }
+
+    party.require_auth();
+
+    if is_a {
+        deal.confirmed_a = true;
+    } else {
+        deal.confirmed_b = true;
+    }
+
+    // emit DealConfirmed event
+    e.events().publish((DEAL, symbol_short!("DConfirm")),
+        (deal_id, party.clone())
+    );
+
+    if deal.confirmed_a && deal.confirmed_b {
+        deal.status = OfferStatus::COMPLETE;
+        deal_write(e, deal_id, &deal);
+        escrow_out(e, &deal.token_a, &deal.party_b, deal.amount_a);
+        escrow_out(e, &deal.token_b, &deal.party_a, deal.amount_b);
+
+        // emit DealSettled event
+        e.events().publish((DEAL, symbol_short!("DSettle")),
+            deal_id
+        );
+    } else {
+        deal_write(e, deal_id, &deal);
+    }
+
+    Ok(())
+}
+
+// Returns the deposit of party once the deal has lapsed without settling.
+pub fn deal_reclaim(e: &Env, party: &Address, deal_id: u32) -> Result<(), SwapError> {
+    let mut deal = deal_load(e, deal_id)?;
+    if deal.status != OfferStatus::ACTIVE && deal.status != OfferStatus::EXPIRED {
+        return Err(SwapError::OfferNotActive);
+    }
+    if e.ledger().sequence() < deal.expire_ledger {
+        return Err(SwapError::OfferNotExpired);
+    }
+    let is_a = deal_side(&deal, party)?;
+
+    party.require_auth();
+
+    let (token, amount) = if is_a && deal.deposited_a {
+        deal.deposited_a = false;
+        (deal.token_a.clone(), deal.amount_a)
+    } else if !is_a && deal.deposited_b {
+        deal.deposited_b = false;
+        (deal.token_b.clone(), deal.amount_b)
+    } else {
+        return Err(SwapError::InsufficientBalance);
+    };
+    deal.status = OfferStatus::EXPIRED;
+    deal_write(e, deal_id, &deal);
+    escrow_out(e, &token, party, amount);
+
+    // emit DealReclaimed event
+    e.events().publish((DEAL, symbol_short!("DReclaim")),
+        (deal_id, party.clone(), amount)
+    );
+
+    Ok(())
+}
+
+pub fn deal_load(e: &Env, deal_id: u32) -> Result<DealInfo, SwapError> {
+    let key = DealKey::Deal(deal_id);
+
+    e.storage().persistent().get(&key).ok_or(SwapError::OfferNotFound)
+}
+
+// Loads a deal which can still be deposited to and confirmed.
+fn deal_load_open(e: &Env, deal_id: u32) -> Result<DealInfo, SwapError> {
+    let deal = deal_load(e, deal_id)?;
+    if deal.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+    if e.ledger().sequence() >= deal.expire_ledger {
+        return Err(SwapError::OfferExpired);
+    }
+    Ok(deal)
+}
+
+// Whether party is party a of the deal rather than party b.
+fn deal_side(deal: &DealInfo, party: &Address) -> Result<bool, SwapError> {
+    if party.clone() == deal.party_a {
+        Ok(true)
+    } else if party.clone() == deal.party_b {
+        Ok(false)
+    } else {
+        Err(SwapError::Unauthorized)
+    }
+}
+
+fn deal_write(e: &Env, deal_id: u32, deal: &DealInfo) {
+    let key = DealKey::Deal(deal_id);
+    e.storage().persistent().set(&key, deal);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
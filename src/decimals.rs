@@ -0,0 +1,93 @@
+use soroban_sdk::{ token, Address, Env, String, U256 };
+
+use crate::storage_types::{ BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, PRICE_DECIMALS, 
+    ConfigKey, DataKey
+};
+use crate::offer::{ offer_load_live };
+use crate::error::SwapError;
+
+
+// Checks that token answers `name` and `balance` like a SEP-41 token before it's allowed,
+// and caches its name and decimals. Decimals are optional, NFT collections have none, so
+// pricing a token without them fails with NoDecimals.
+pub fn token_probe(e: &Env, token: &Address) -> Result<(), SwapError> {
+    let client = token::Client::new(e, token);
+    let name = match client.try_name() {
+        Ok(Ok(name)) => name,
+        _ => return Err(SwapError::TokenNotAllowed),
+    };
+    if !matches!(client.try_balance(&e.current_contract_address()), Ok(Ok(_))) {
+        return Err(SwapError::TokenNotAllowed);
+    }
+
+    let key = ConfigKey::TokenName(token.clone());
+    e.storage().persistent().set(&key, &name);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    decimals_record(e, token);
+    Ok(())
+}
+
+// Name of the token as recorded when it was allowed.
+pub fn token_name_get(e: &Env, token: &Address) -> Option<String> {
+    e.storage().persistent().get(&ConfigKey::TokenName(token.clone()))
+}
+
+// Records the token's decimals when it has any, so prices can be normalized.
+pub fn decimals_record(e: &Env, token: &Address) {
+    if let Ok(Ok(decimals)) = token::Client::new(e, token).try_decimals() {
+        let key = DataKey::TokenDecimals(token.clone());
+        e.storage().persistent().set(&key, &decimals);
+        e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    }
+}
+
+// Decimals recorded for the token, read from the token itself for tokens allowed before
+// they were recorded.
+pub fn decimals_get(e: &Env, token: &Address) -> Option<u32> {
+    let key = DataKey::TokenDecimals(token.clone());
+    if let Some(decimals) = e.storage().persistent().get(&key) {
+        return Some(decimals);
+    }
+
+    match token::Client::new(e, token).try_decimals() {
+        Ok(Ok(decimals)) => Some(decimals),
+        _ => None,
+    }
+}
+
+// Price of `send_amount` of send_token for `recv_amount` of recv_token, as whole recv
+// tokens per whole send token with PRICE_DECIMALS decimals.
+pub fn price_get(e: &Env, send_token: &Address, recv_token: &Address, send_amount: u64, recv_amount: u64) -> Result<u128, SwapError> {
+    if send_amount == 0 {
+        return Err(SwapError::ZeroAmount);
+    }
+    let send_decimals = decimals_get(e, send_token).ok_or(SwapError::NoDecimals)?;
+    let recv_decimals = decimals_get(e, recv_token).ok_or(SwapError::NoDecimals)?;
+
+    // recv_amount * 10^(PRICE_DECIMALS + send_decimals) / (send_amount * 10^recv_decimals)
+    let ten = U256::from_u32(e, 10);
+    let price = U256::from_u128(e, recv_amount as u128)
+        .mul(&ten.pow(PRICE_DECIMALS + send_decimals))
+        .div(&U256::from_u128(e, send_amount as u128).mul(&ten.pow(recv_decimals)));
+    price.to_u128().ok_or(SwapError::AmountTooLarge)
+}
+
+// The recv_amount asking `price` (see `price_get`) for `send_amount` of send_token.
+pub fn price_recv_amount(e: &Env, send_token: &Address, recv_token: &Address, send_amount: u64, price: u128) -> Result<u64, SwapError> {
+    let send_decimals = decimals_get(e, send_token).ok_or(SwapError::NoDecimals)?;
+    let recv_decimals = decimals_get(e, recv_token).ok_or(SwapError::NoDecimals)?;
+
+    let ten = U256::from_u32(e, 10);
+    let recv_amount = U256::from_u128(e, send_amount as u128)
+        .mul(&U256::from_u128(e, price))
+        .mul(&ten.pow(recv_decimals))
+        .div(&ten.pow(PRICE_DECIMALS + send_decimals));
+    let recv_amount = recv_amount.to_u128().ok_or(SwapError::AmountTooLarge)?;
+    u64::try_from(recv_amount).map_err(|_| SwapError::AmountTooLarge)
+}
+
+pub fn price_offer(e: &Env, offer_id: u32) -> Result<u128, SwapError> {
+    let offer = offer_load_live(e, offer_id)?;
+
+    price_get(e, &offer.send_token, &offer.recv_token, offer.send_amount, offer.recv_amount)
+}
@@ -0,0 +1,157 @@
+const DISPUTE: Symbol = symbol_short!("DISPUTE");
+
+use soroban_sdk::{ Address, Env, symbol_short, Symbol };
+
+use crate::storage_types::{ BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT,
+    DisputeHold, OfferDataKey, OfferInfo, OfferStatus, Role
+};
+use crate::acceptor::{ counterparty_get };
+use crate::offer::{ offer_load };
+use crate::role::{ role_check };
+use crate::stream::{ proceeds_pay };
+use crate::escrow::{ escrow_in, escrow_out };
+use crate::error::SwapError;
+
+
+/*
+An offer with a designated counterparty can be given a dispute window. Its fill then
+isn't paid out right away: both legs stay in the contract for `ledgers` ledgers, during
+which the offeror or the acceptor may raise a dispute.
+
+Without a dispute anyone can release the hold once the window has passed. A disputed
+hold is resolved by an account with the Arbiter role, which either completes the swap
+or gives each party its own leg back. The fees of the fill are kept either way.
+*/
+
+pub fn dispute_window_get(e: &Env, offer_id: u32) -> u32 {
+    e.storage().persistent().get(&OfferDataKey::DisputeWindow(offer_id)).unwrap_or(0)
+}
+
+// Holds fills of the offer for `ledgers` ledgers, 0 pays them out right away again.
+pub fn dispute_window_set(e: &Env, offeror: &Address, offer_id: u32, ledgers: u32) -> Result<(), SwapError> {
+    let offer = offer_load(e, offer_id)?;
+    if offer.offeror != offeror.clone() {
+        return Err(SwapError::InvalidOfferor);
+    }
+    if offer.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+    if counterparty_get(e, offer_id).is_none() {
+        return Err(SwapError::AcceptorNotAllowed);
+    }
+
+    offeror.require_auth();
+
+    let key = OfferDataKey::DisputeWindow(offer_id);
+    if ledgers == 0 {
+        e.storage().persistent().remove(&key);
+    } else {
+        e.storage().persistent().set(&key, &ledgers);
+        e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    }
+    Ok(())
+}
+
+pub fn dispute_get(e: &Env, offer_id: u32) -> Option<DisputeHold> {
+    e.storage().persistent().get(&OfferDataKey::Hold(offer_id))
+}
+
+// Takes `amount` of recv_token from acceptor into the contract, where it's held along
+// with the `send_amount` the fill bought until the dispute window has passed.
+pub fn dispute_hold(e: &Env, offer_id: u32, offer: &OfferInfo, acceptor: &Address, amount: u64, send_amount: u64) {
+    let amount = escrow_in(e, &offer.recv_token, acceptor, amount);
+    hold_write(e, offer_id, &DisputeHold {
+        acceptor: acceptor.clone(),
+        amount,
+        send_amount,
+        release_ledger: e.ledger().sequence() + dispute_window_get(e, offer_id),
+        disputed: false,
+    });
+    e.storage().persistent().remove(&OfferDataKey::DisputeWindow(offer_id));
+}
+
+// Disputes the held fill, by the offeror or the acceptor before the window has passed.
+pub fn dispute_raise(e: &Env, party: &Address, offer_id: u32) -> Result<(), SwapError> {
+    let mut hold = dispute_get(e, offer_id).ok_or(SwapError::OfferNotFound)?;
+    let offer = offer_load(e, offer_id)?;
+    if party.clone() != offer.offeror && party.clone() != hold.acceptor {
+        return Err(SwapError::Unauthorized);
+    }
+    if hold.disputed {
+        return Err(SwapError::OfferLocked);
+    }
+    if e.ledger().sequence() >= hold.release_ledger {
+        return Err(SwapError::OfferExpired);
+    }
+
+    party.require_auth();
+
+    hold.disputed = true;
+    hold_write(e, offer_id, &hold);
+
+    // emit DisputeRaised event
+    e.events().publish((DISPUTE, symbol_short!("DRaise")),
+        (offer_id, party.clone())
+    );
+
+    Ok(())
+}
+
+// Pays out an undisputed fill once its window has passed. Can be called by anyone.
+pub fn dispute_release(e: &Env, offer_id: u32) -> Result<(), SwapError> {
+    let hold = dispute_get(e, offer_id).ok_or(SwapError::OfferNotFound)?;
+    if hold.disputed {
+        return Err(SwapError::OfferLocked);
+    }
+    if e.ledger().sequence() < hold.release_ledger {
+        return Err(SwapError::OfferNotExpired);
+    }
+
+    hold_pay(e, offer_id, &hold, true)?;
+
+    // emit DisputeReleased event
+    e.events().publish((DISPUTE, symbol_short!("DRelease")),
+        offer_id
+    );
+
+    Ok(())
+}
+
+// Resolves a disputed fill by completing the swap, or with `settle` false by returning
+// each party its own leg.
+pub fn dispute_resolve(e: &Env, arbiter: &Address, offer_id: u32, settle: bool) -> Result<(), SwapError> {
+    role_check(e, Role::Arbiter, arbiter)?;
+    let hold = dispute_get(e, offer_id).ok_or(SwapError::OfferNotFound)?;
+    if !hold.disputed {
+        return Err(SwapError::OfferNotActive);
+    }
+
+    hold_pay(e, offer_id, &hold, settle)?;
+
+    // emit DisputeResolved event
+    e.events().publish((DISPUTE, symbol_short!("DResolve")),
+        (offer_id, arbiter.clone(), settle)
+    );
+
+    Ok(())
+}
+
+fn hold_pay(e: &Env, offer_id: u32, hold: &DisputeHold, settle: bool) -> Result<(), SwapError> {
+    let offer = offer_load(e, offer_id)?;
+    e.storage().persistent().remove(&OfferDataKey::Hold(offer_id));
+
+    if settle {
+        proceeds_pay(e, offer_id, &offer, &e.current_contract_address(), hold.amount);
+        escrow_out(e, &offer.send_token, &hold.acceptor, hold.send_amount);
+    } else {
+        escrow_out(e, &offer.recv_token, &hold.acceptor, hold.amount);
+        escrow_out(e, &offer.send_token, &offer.offeror, hold.send_amount);
+    }
+    Ok(())
+}
+
+fn hold_write(e: &Env, offer_id: u32, hold: &DisputeHold) {
+    let key = OfferDataKey::Hold(offer_id);
+    e.storage().persistent().set(&key, hold);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
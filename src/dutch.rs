@@ -0,0 +1,91 @@
+use soroban_sdk::{ Address, Env };
+
+use crate::storage_types::{ BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, 
+    DataKey, DutchAuction, OfferInfo, OfferStatus
+};
+
+use crate::auction::{ auction_get };
+use crate::commit::{ commit_locked };
+use crate::oracle::{ peg_get };
+use crate::offer::{ 
+    offer_load, 
+};
+use crate::error::SwapError;
+
+
+pub fn dutch_get(e: &Env, offer_id: u32) -> Option<DutchAuction> {
+    let key = DataKey::OfferAuction(offer_id);
+
+    e.storage().persistent().get(&key)
+}
+
+// Starts lowering the price of the offer from its current recv_amount down to
+// `floor_recv_amount` at `end_ledger`. With `step_ledgers` above 1 the price drops
+// once every `step_ledgers` ledgers instead of every ledger.
+pub fn dutch_set(e: &Env, 
+    offeror: &Address, 
+    offer_id: u32, 
+    floor_recv_amount: u64, 
+    end_ledger: u32, 
+    step_ledgers: u32
+) -> Result<(), SwapError> {
+    let offer = offer_load(e, offer_id)?;
+    if offer.offeror != offeror.clone() {
+        return Err(SwapError::InvalidOfferor);
+    }
+    if offer.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+    if auction_get(e, offer_id).is_some() {
+        return Err(SwapError::OfferInAuction);
+    }
+    if peg_get(e, offer_id).is_some() {
+        return Err(SwapError::DynamicPrice);
+    }
+    if commit_locked(e, offer_id) {
+        return Err(SwapError::OfferLocked);
+    }
+    if floor_recv_amount == 0 || floor_recv_amount > offer.recv_amount || end_ledger <= e.ledger().sequence() {
+        return Err(SwapError::InvalidAuction);
+    }
+
+    offeror.require_auth();
+
+    let key = DataKey::OfferAuction(offer_id);
+    e.storage().persistent().set(&key, &DutchAuction {
+        send_amount: offer.send_amount,
+        start_recv_amount: offer.recv_amount,
+        floor_recv_amount,
+        start_ledger: e.ledger().sequence(),
+        end_ledger,
+        step_ledgers,
+    });
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    Ok(())
+}
+
+pub fn dutch_remove(e: &Env, offer_id: u32) {
+    e.storage().persistent().remove(&DataKey::OfferAuction(offer_id));
+}
+
+// Sets recv_amount of an auctioned offer to the current price of its remaining send_amount.
+pub fn dutch_apply(e: &Env, offer_id: u32, offer: &mut OfferInfo) {
+    let auction = match dutch_get(e, offer_id) {
+        Some(auction) => auction,
+        None => return,
+    };
+
+    let ledger = e.ledger().sequence().min(auction.end_ledger);
+    let mut elapsed = (ledger - auction.start_ledger) as u128;
+    if auction.step_ledgers > 1 {
+        elapsed = elapsed / (auction.step_ledgers as u128) * (auction.step_ledgers as u128);
+    }
+    let duration = (auction.end_ledger - auction.start_ledger) as u128;
+    let drop = (auction.start_recv_amount - auction.floor_recv_amount) as u128 * elapsed / duration;
+    let recv_amount = auction.start_recv_amount as u128 - drop;
+
+    offer.recv_amount = ((recv_amount * (offer.send_amount as u128) / (auction.send_amount as u128)) as u64).max(1);
+    if offer.recv_amount < offer.min_recv_amount {
+        offer.min_recv_amount = offer.recv_amount;
+    }
+}
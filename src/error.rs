@@ -0,0 +1,62 @@
+use soroban_sdk::contracterror;
+
+
+// contracterror allows at most 50 variants. Codes are never reused once published, so
+// retired codes stay unassigned: 20 (now Unauthorized), 24 (now FeeRateTooHigh),
+// 32 (now OfferNotExpired), 33 (now OfferExpired), 37 (now OfferLocked), 39, 40 and 44
+// (now InvalidReveal).
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum SwapError {
+    AlreadyInitialized = 1,
+    NotInitialized = 2,
+    FeeNotSet = 3,
+    TokenNotAllowed = 4,
+    ZeroAmount = 5,
+    InvalidMinRecvAmount = 6,
+    InvalidExpiration = 7,
+    InsufficientBalance = 8,
+    OfferNotFound = 9,
+    OfferNotActive = 10,
+    OfferExpired = 11,
+    OfferNotExpired = 12,
+    InvalidOfferor = 13,
+    AmountTooLarge = 14,
+    AmountTooSmall = 15,
+    NoPendingAdmin = 16,
+    Paused = 17,
+    AlreadyMigrated = 18,
+    Unauthorized = 19,
+    NoPendingFee = 21,
+    FeeTimelocked = 22,
+    InvalidFeeTiers = 23,
+    FeeRateTooHigh = 25,
+    AcceptorNotAllowed = 26,
+    SlippageExceeded = 27,
+    OffersNotCrossed = 28,
+    InvalidAuction = 29,
+    OfferInAuction = 30,
+    BidTooLow = 31,
+    BidNotRefundable = 34,
+    RequestNotFound = 35,
+    QuoteNotFound = 36,
+    CommitNotFound = 38,
+    InvalidReveal = 41,
+    OfferLocked = 42,
+    HtlcNotFound = 43,
+    DcaNotFound = 45,
+    DcaNotDue = 46,
+    StreamNotFound = 47,
+    InvalidRoute = 48,
+    InvalidOps = 49,
+    BasketNotFound = 50,
+    OracleUnavailable = 51,
+    Reentrancy = 52,
+    Overflow = 53,
+    TooManyOffers = 54,
+    PriceOutOfBand = 55,
+    MemoTooLong = 56,
+    DynamicPrice = 57,
+    NoDecimals = 58,
+}
@@ -0,0 +1,126 @@
+const ESCROW: Symbol = symbol_short!("ESCROW");
+
+use soroban_sdk::{ panic_with_error, token, Address, Env, symbol_short, Symbol };
+
+use crate::storage_types::{ BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, 
+    EscrowKey, Role
+};
+
+use crate::role::{ role_check };
+use crate::error::SwapError;
+
+
+/*
+Every token the contract holds on someone's behalf (offer escrows, bids, quotes, locks,
+budgets, unvested proceeds and unclaimed fees) comes in through `escrow_in` and leaves
+through `escrow_out`, which keep a per-token total of what the contract owes.
+Whatever the contract holds above that total was sent to it directly and can be
+rescued by the admin.
+
+Transfers from or to the contract itself, as when it fills offers on behalf of a DCA
+plan, move nothing and leave the total unchanged.
+
+Tokens taking a fee on transfers deliver less than was sent, so `escrow_in` counts what
+the contract's balance actually grew by. Offers only sell, fills only book, and stakes,
+votes, locks and budgets only count what arrived, while amounts paid out as they were
+promised must arrive in full through `escrow_in_exact`. Tokens whose balances change without transfers, like rebasing ones, can still
+leave the contract holding less than it owes. Releasing more than the total fails the call
+rather than letting the total wrap.
+*/
+
+pub fn escrow_get(e: &Env, token: &Address) -> u128 {
+    let key = EscrowKey::Total(token.clone());
+
+    e.storage().persistent().get(&key).unwrap_or(0)
+}
+
+fn escrow_write(e: &Env, token: &Address, total: u128) {
+    let key = EscrowKey::Total(token.clone());
+    if total == 0 {
+        e.storage().persistent().remove(&key);
+        return;
+    }
+    e.storage().persistent().set(&key, &total);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
+
+// Marks a settlement as running until `escrow_unlock`. Fails if one already is, so a token
+// contract can't re-enter a settlement halfway through its transfers.
+pub fn escrow_lock(e: &Env) -> Result<(), SwapError> {
+    let key = EscrowKey::Locked;
+    if e.storage().instance().get::<_, bool>(&key).unwrap_or(false) {
+        return Err(SwapError::Reentrancy);
+    }
+    e.storage().instance().set(&key, &true);
+    Ok(())
+}
+
+pub fn escrow_unlock(e: &Env) {
+    e.storage().instance().remove(&EscrowKey::Locked);
+}
+
+// Moves `amount` of token from `from` into the contract's custody. Returns the amount that
+// actually arrived, which is less for tokens taking a fee on transfers, and only counts
+// that toward the total.
+pub fn escrow_in(e: &Env, token: &Address, from: &Address, amount: u64) -> u64 {
+    let contract = e.current_contract_address();
+    if amount == 0 || from.clone() == contract {
+        return amount;
+    }
+    let client = token::Client::new(e, token);
+    let balance = client.balance(&contract);
+    client.transfer(from, &contract, &(amount as i128));
+    let received = (client.balance(&contract) - balance).clamp(0, amount as i128) as u64;
+    escrow_write(e, token, escrow_get(e, token) + received as u128);
+    received
+}
+
+// Like `escrow_in`, for amounts owed to someone else in full, such as a deposit paid out
+// as is. Fails with InsufficientBalance if less than `amount` arrived.
+pub fn escrow_in_exact(e: &Env, token: &Address, from: &Address, amount: u64) -> Result<(), SwapError> {
+    if escrow_in(e, token, from, amount) < amount {
+        return Err(SwapError::InsufficientBalance);
+    }
+    Ok(())
+}
+
+// Releases `amount` of token held by the contract to `to`. Fails with InsufficientBalance
+// if the contract doesn't owe that much of token.
+pub fn escrow_out(e: &Env, token: &Address, to: &Address, amount: u64) {
+    let contract = e.current_contract_address();
+    if amount == 0 || to.clone() == contract {
+        return;
+    }
+    let total = match escrow_get(e, token).checked_sub(amount as u128) {
+        Some(total) => total,
+        None => panic_with_error!(e, SwapError::InsufficientBalance),
+    };
+    escrow_write(e, token, total);
+    token::Client::new(e, token).transfer(&contract, to, &(amount as i128));
+}
+
+// The total the contract owes in token and its actual balance, which must never be lower.
+pub fn escrow_solvency(e: &Env, token: &Address) -> (u128, u128) {
+    let balance = token::Client::new(e, token).balance(&e.current_contract_address());
+
+    (escrow_get(e, token), balance.max(0) as u128)
+}
+
+// Sends `amount` of token the contract holds beyond what it owes to `to`.
+pub fn escrow_rescue(e: &Env, caller: &Address, token: &Address, amount: u64, to: &Address) -> Result<(), SwapError> {
+    role_check(e, Role::Admin, caller)?;
+
+    let balance = token::Client::new(e, token).balance(&e.current_contract_address());
+    let excess = (balance.max(0) as u128).saturating_sub(escrow_get(e, token));
+    if amount as u128 > excess {
+        return Err(SwapError::InsufficientBalance);
+    }
+
+    token::Client::new(e, token).transfer(&e.current_contract_address(), to, &(amount as i128));
+
+    // emit Rescued event
+    e.events().publish((ESCROW, symbol_short!("Rescue")), 
+        (token.clone(), amount, to.clone())
+    );
+    Ok(())
+}
@@ -1,41 +1,416 @@
-use soroban_sdk::{ Env };
-use crate::storage_types::{ FEE_DECIMALS, /* INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT, */ 
-    DataKey, FeeInfo
+const FEE: Symbol = symbol_short!("FEE");
+
+use soroban_sdk::{ token, Address, Env, symbol_short, Symbol, Vec };
+use crate::storage_types::{ FREEZE_FEES, FEE_DECIMALS, MAX_FEE_RATE, MAX_FEE_TOKEN_RATE, PRICE_DECIMALS, /* INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT, */ 
+    BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, 
+    ConfigKey, DataKey, FeeChange, FeeDiscount, FeeInfo, FeeTier, FeeToken, PendingFee, PendingFeeChange, Role
 };
+use crate::allow::{ allow_get };
+use crate::oracle::{ oracle_convert };
 
-use crate::admin::{ 
-    read_administrator, 
+use crate::freeze::{ freeze_check };
+use crate::role::{ 
+    role_check, 
 };
+use crate::stake::{ stake_accrue, stake_get, stake_token_get };
+use crate::stats::{ stats_fee };
+use crate::escrow::{ escrow_out };
+use crate::error::SwapError;
 
 pub fn fee_check(e: &Env) -> bool {
     let key = DataKey::FEE;
 
-    if e.storage().instance().has(&key) {
-        true
-    }
-    else {
-        false
-    }
+    e.storage().instance().has(&key)
 }
 
-pub fn fee_get(e: &Env) -> FeeInfo {
+pub fn fee_get(e: &Env) -> Result<FeeInfo, SwapError> {
     let key = DataKey::FEE;
 
-    if !e.storage().instance().has(&key) {
-        panic!("FeeInfo wasn't initialized");
+    e.storage().instance().get(&key).ok_or(SwapError::FeeNotSet)
+}
+
+fn fee_rate_check(fee_rate: u32) -> Result<(), SwapError> {
+    if fee_rate > MAX_FEE_RATE {
+        return Err(SwapError::FeeRateTooHigh);
     }
-    
-    e.storage().instance().get(&key).unwrap()
+    Ok(())
 }
 
-pub fn fee_set(e: &Env, fee_info: &FeeInfo) {
-    let key = DataKey::FEE;
-    let admin = read_administrator(&e);
-    admin.require_auth();
-    e.storage().instance().set(&key, fee_info);
+// Schedules `fee_info` to become effective after the fee delay has passed.
+pub fn fee_propose(e: &Env, caller: &Address, fee_info: &FeeInfo) -> Result<(), SwapError> {
+    let key = DataKey::PendingFee;
+    role_check(e, Role::FeeManager, caller)?;
+    freeze_check(e, FREEZE_FEES)?;
+    fee_rate_check(fee_info.maker_fee_rate)?;
+    fee_rate_check(fee_info.taker_fee_rate)?;
+    let pending = PendingFee {
+        fee_info: fee_info.clone(),
+        apply_ledger: e.ledger().sequence() + fee_delay_get(e),
+    };
+    e.storage().instance().set(&key, &pending);
+    Ok(())
+}
+
+pub fn fee_pending_get(e: &Env) -> Result<PendingFee, SwapError> {
+    let key = DataKey::PendingFee;
+
+    e.storage().instance().get(&key).ok_or(SwapError::NoPendingFee)
+}
+
+// Makes the pending fee effective once its delay has passed. Can be called by anyone.
+pub fn fee_apply(e: &Env) -> Result<(), SwapError> {
+    freeze_check(e, FREEZE_FEES)?;
+    let pending = fee_pending_get(e)?;
+    if e.ledger().sequence() < pending.apply_ledger {
+        return Err(SwapError::FeeTimelocked);
+    }
+
+    e.storage().instance().set(&DataKey::FEE, &pending.fee_info);
+    e.storage().instance().remove(&DataKey::PendingFee);
     // e.storage().instance().bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+
+    // emit FeeSet event
+    e.events().publish((FEE, symbol_short!("FeeSet")), 
+        pending.fee_info
+    );
+    Ok(())
+}
+
+// Changes the fee rates right away, for changes that went through a timelock of their own.
+pub fn fee_rates_write(e: &Env, maker_fee_rate: u32, taker_fee_rate: u32) -> Result<(), SwapError> {
+    freeze_check(e, FREEZE_FEES)?;
+    fee_rate_check(maker_fee_rate)?;
+    fee_rate_check(taker_fee_rate)?;
+    let mut fee_info = fee_get(e)?;
+    fee_info.maker_fee_rate = maker_fee_rate;
+    fee_info.taker_fee_rate = taker_fee_rate;
+    e.storage().instance().set(&DataKey::FEE, &fee_info);
+
+    // emit FeeSet event
+    e.events().publish((FEE, symbol_short!("FeeSet")), 
+        fee_info
+    );
+    Ok(())
+}
+
+pub fn fee_delay_get(e: &Env) -> u32 {
+    let key = DataKey::FeeDelay;
+
+    e.storage().instance().get(&key).unwrap_or(0)
+}
+
+// Raises the fee delay right away. Lowering it is queued behind the current delay like any
+// other fee change, so it can't be used to skip that delay.
+pub fn fee_delay_set(e: &Env, caller: &Address, delay: u32) -> Result<(), SwapError> {
+    let key = DataKey::FeeDelay;
+    role_check(e, Role::Admin, caller)?;
+    freeze_check(e, FREEZE_FEES)?;
+    if delay >= fee_delay_get(e) {
+        e.storage().instance().set(&key, &delay);
+        return Ok(());
+    }
+    fee_change_queue(e, FeeChange::Delay(delay))
+}
+
+pub fn fee_changes_get(e: &Env) -> Vec<PendingFeeChange> {
+    e.storage().instance().get(&ConfigKey::PendingFeeChanges).unwrap_or(Vec::new(e))
 }
 
-pub fn calculate_fee(_e: &Env, fee_info: &FeeInfo, amount: u64) -> u64 {
-    amount * (fee_info.fee_rate as u64) / (u64::pow(10, FEE_DECIMALS))
+// Queues `change` to become effective after the fee delay has passed, right away without
+// a delay.
+fn fee_change_queue(e: &Env, change: FeeChange) -> Result<(), SwapError> {
+    let delay = fee_delay_get(e);
+    if delay == 0 {
+        return fee_change_write(e, &change);
+    }
+
+    let mut changes = fee_changes_get(e);
+    changes.push_back(PendingFeeChange {
+        change,
+        apply_ledger: e.ledger().sequence() + delay,
+    });
+    e.storage().instance().set(&ConfigKey::PendingFeeChanges, &changes);
+    Ok(())
+}
+
+// Makes the queued changes whose delay has passed effective, in the order they were queued.
+// Can be called by anyone. Returns the number of changes applied.
+pub fn fee_changes_apply(e: &Env) -> Result<u32, SwapError> {
+    freeze_check(e, FREEZE_FEES)?;
+    let changes = fee_changes_get(e);
+    if changes.is_empty() {
+        return Err(SwapError::NoPendingFee);
+    }
+
+    let mut pending: Vec<PendingFeeChange> = Vec::new(e);
+    let mut count: u32 = 0;
+    for pending_change in changes.iter() {
+        if e.ledger().sequence() < pending_change.apply_ledger {
+            pending.push_back(pending_change);
+            continue;
+        }
+        fee_change_write(e, &pending_change.change)?;
+        count += 1;
+    }
+    if count == 0 {
+        return Err(SwapError::FeeTimelocked);
+    }
+
+    if pending.is_empty() {
+        e.storage().instance().remove(&ConfigKey::PendingFeeChanges);
+    } else {
+        e.storage().instance().set(&ConfigKey::PendingFeeChanges, &pending);
+    }
+    Ok(count)
+}
+
+fn fee_change_write(e: &Env, change: &FeeChange) -> Result<(), SwapError> {
+    let storage = e.storage().instance();
+    match change {
+        FeeChange::Delay(delay) => storage.set(&DataKey::FeeDelay, delay),
+        FeeChange::TokenFee(token, fee_rate) => storage.set(&DataKey::TokenFee(token.clone()), fee_rate),
+        FeeChange::ClearTokenFee(token) => storage.remove(&DataKey::TokenFee(token.clone())),
+        FeeChange::Tiers(tiers) => storage.set(&DataKey::FeeTiers, tiers),
+        FeeChange::Token(fee_token) => storage.set(&ConfigKey::FeeToken, fee_token),
+        FeeChange::ClearToken => storage.remove(&ConfigKey::FeeToken),
+        FeeChange::TokenRate(token, rate) => storage.set(&ConfigKey::FeeTokenRate(token.clone()), rate),
+        FeeChange::ClearTokenRate(token) => storage.remove(&ConfigKey::FeeTokenRate(token.clone())),
+        FeeChange::Discounts(token, discounts) => {
+            storage.set(&ConfigKey::DiscountToken, token);
+            storage.set(&ConfigKey::FeeDiscounts, discounts);
+        },
+        FeeChange::ClearDiscounts => {
+            storage.remove(&ConfigKey::DiscountToken);
+            storage.remove(&ConfigKey::FeeDiscounts);
+        },
+    }
+
+    // emit FeeChanged event
+    e.events().publish((FEE, symbol_short!("FeeChange")), 
+        change.clone()
+    );
+    Ok(())
+}
+
+pub fn token_fee_get(e: &Env, token: &Address) -> Option<u32> {
+    let key = DataKey::TokenFee(token.clone());
+
+    e.storage().instance().get(&key)
+}
+
+pub fn token_fee_set(e: &Env, caller: &Address, token: &Address, fee_rate: Option<u32>) -> Result<(), SwapError> {
+    role_check(e, Role::FeeManager, caller)?;
+    freeze_check(e, FREEZE_FEES)?;
+    match fee_rate {
+        Some(fee_rate) => {
+            fee_rate_check(fee_rate)?;
+            fee_change_queue(e, FeeChange::TokenFee(token.clone(), fee_rate))
+        },
+        None => fee_change_queue(e, FeeChange::ClearTokenFee(token.clone())),
+    }
+}
+
+pub fn fee_tiers_get(e: &Env) -> Vec<FeeTier> {
+    let key = DataKey::FeeTiers;
+
+    e.storage().instance().get(&key).unwrap_or(Vec::new(e))
+}
+
+// `tiers` must be sorted by strictly increasing `min_volume`.
+pub fn fee_tiers_set(e: &Env, caller: &Address, tiers: &Vec<FeeTier>) -> Result<(), SwapError> {
+    role_check(e, Role::FeeManager, caller)?;
+    freeze_check(e, FREEZE_FEES)?;
+    for tier in tiers.iter() {
+        fee_rate_check(tier.fee_rate)?;
+    }
+    for i in 1..tiers.len() {
+        if tiers.get_unchecked(i).min_volume <= tiers.get_unchecked(i - 1).min_volume {
+            return Err(SwapError::InvalidFeeTiers);
+        }
+    }
+    fee_change_queue(e, FeeChange::Tiers(tiers.clone()))
+}
+
+// Settled volume of an acceptor in `token`, counted in its raw units. Volume is kept per
+// token so trading a cheap token doesn't earn fee tiers on the others.
+pub fn volume_get(e: &Env, account: &Address, token: &Address) -> u64 {
+    let key = DataKey::Volume(account.clone(), token.clone());
+
+    e.storage().persistent().get(&key).unwrap_or(0)
+}
+
+pub fn volume_add(e: &Env, account: &Address, token: &Address, amount: u64) {
+    let key = DataKey::Volume(account.clone(), token.clone());
+    let volume = volume_get(e, account, token).saturating_add(amount);
+    e.storage().persistent().set(&key, &volume);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
+
+pub fn fee_token_get(e: &Env) -> Option<FeeToken> {
+    e.storage().instance().get(&ConfigKey::FeeToken)
+}
+
+// Charges fees in `fee_token` instead of the traded tokens, None goes back to the latter.
+pub fn fee_token_set(e: &Env, caller: &Address, fee_token: &Option<FeeToken>) -> Result<(), SwapError> {
+    role_check(e, Role::FeeManager, caller)?;
+    freeze_check(e, FREEZE_FEES)?;
+    match fee_token {
+        Some(fee_token) => {
+            if !allow_get(e, &fee_token.token) {
+                return Err(SwapError::TokenNotAllowed);
+            }
+            fee_change_queue(e, FeeChange::Token(fee_token.clone()))
+        },
+        None => fee_change_queue(e, FeeChange::ClearToken),
+    }
+}
+
+pub fn fee_token_rate_get(e: &Env, token: &Address) -> Option<u128> {
+    e.storage().instance().get(&ConfigKey::FeeTokenRate(token.clone()))
+}
+
+// Fixes the fee token units a unit of token is worth, with PRICE_DECIMALS decimals, instead
+// of the oracle price. None removes the rate. Queued behind the fee delay like the fee token.
+pub fn fee_token_rate_set(e: &Env, caller: &Address, token: &Address, rate: &Option<u128>) -> Result<(), SwapError> {
+    role_check(e, Role::FeeManager, caller)?;
+    freeze_check(e, FREEZE_FEES)?;
+    match rate {
+        Some(rate) => {
+            if *rate == 0 {
+                return Err(SwapError::ZeroAmount);
+            }
+            if *rate > MAX_FEE_TOKEN_RATE {
+                return Err(SwapError::FeeRateTooHigh);
+            }
+            fee_change_queue(e, FeeChange::TokenRate(token.clone(), *rate))
+        },
+        None => fee_change_queue(e, FeeChange::ClearTokenRate(token.clone())),
+    }
+}
+
+// The token a fee of `amount` of token is paid in, and the amount of it.
+pub fn fee_in(e: &Env, token: &Address, amount: u64) -> Result<(Address, u64), SwapError> {
+    let fee_token = match fee_token_get(e) {
+        Some(fee_token) if fee_token.token != token.clone() => fee_token,
+        _ => return Ok((token.clone(), amount)),
+    };
+    if amount == 0 {
+        return Ok((fee_token.token, 0));
+    }
+
+    let converted = match fee_token_rate_get(e, token) {
+        Some(rate) => {
+            let value = (amount as u128).checked_mul(rate).ok_or(SwapError::Overflow)? / 10u128.pow(PRICE_DECIMALS);
+            u64::try_from(value).map_err(|_| SwapError::Overflow)?
+        },
+        None => oracle_convert(e, token, &fee_token.token, amount, fee_token.max_age)?,
+    };
+    Ok((fee_token.token, converted))
+}
+
+// Fees collected in `token` and not claimed yet.
+pub fn fee_accrued_get(e: &Env, token: &Address) -> u64 {
+    let key = DataKey::AccruedFees(token.clone());
+
+    e.storage().persistent().get(&key).unwrap_or(0)
+}
+
+pub fn fee_accrue(e: &Env, token: &Address, amount: u64) {
+    if amount == 0 {
+        return;
+    }
+    stats_fee(e, token, amount);
+    let amount = amount - stake_accrue(e, token, amount);
+    let key = DataKey::AccruedFees(token.clone());
+    let accrued = fee_accrued_get(e, token) + amount;
+    e.storage().persistent().set(&key, &accrued);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
+
+// Sends all fees accrued in `token` to the fee wallet, which must authorize the claim.
+pub fn fee_claim(e: &Env, token: &Address) -> Result<u64, SwapError> {
+    let fee_info = fee_get(e)?;
+    fee_info.fee_wallet.require_auth();
+
+    let amount = fee_accrued_get(e, token);
+    if amount == 0 {
+        return Ok(0);
+    }
+
+    e.storage().persistent().remove(&DataKey::AccruedFees(token.clone()));
+    escrow_out(e, token, &fee_info.fee_wallet, amount);
+    Ok(amount)
+}
+
+pub fn discount_token_get(e: &Env) -> Option<Address> {
+    e.storage().instance().get(&ConfigKey::DiscountToken)
+}
+
+pub fn fee_discounts_get(e: &Env) -> Vec<FeeDiscount> {
+    e.storage().instance().get(&ConfigKey::FeeDiscounts).unwrap_or(Vec::new(e))
+}
+
+// Discounts fees of accounts holding `token`. `discounts` must be sorted by strictly
+// increasing `min_balance`, a None token removes the schedule.
+pub fn fee_discounts_set(e: &Env, caller: &Address, token: &Option<Address>, discounts: &Vec<FeeDiscount>) -> Result<(), SwapError> {
+    role_check(e, Role::FeeManager, caller)?;
+    freeze_check(e, FREEZE_FEES)?;
+    let token = match token {
+        Some(token) => token,
+        None => return fee_change_queue(e, FeeChange::ClearDiscounts),
+    };
+    for discount in discounts.iter() {
+        if discount.discount > u32::pow(10, FEE_DECIMALS) {
+            return Err(SwapError::FeeRateTooHigh);
+        }
+    }
+    for i in 1..discounts.len() {
+        if discounts.get_unchecked(i).min_balance <= discounts.get_unchecked(i - 1).min_balance {
+            return Err(SwapError::InvalidFeeTiers);
+        }
+    }
+    fee_change_queue(e, FeeChange::Discounts(token.clone(), discounts.clone()))
+}
+
+// Share of its fees waived for `account`, in FEE_DECIMALS, by its balance of the discount
+// token. Tokens it has staked count toward the balance.
+fn fee_discount(e: &Env, account: &Address) -> u32 {
+    let token = match discount_token_get(e) {
+        Some(token) => token,
+        None => return 0,
+    };
+    let mut balance = token::Client::new(e, &token).balance(account);
+    if stake_token_get(e) == Some(token) {
+        balance += stake_get(e, account) as i128;
+    }
+    let mut discount = 0;
+    for tier in fee_discounts_get(e).iter() {
+        if balance >= tier.min_balance && tier.discount > discount {
+            discount = tier.discount;
+        }
+    }
+    discount
+}
+
+// Calculates the fee `payer` is charged on `amount` of `token`. A fee rate set for the
+// token takes precedence over the global `fee_rate`, and a taker pays the rate of the
+// highest fee tier reached by their volume in the token if it's lower. Holders of the discount token
+// then get their discount off the fee.
+pub fn calculate_fee(e: &Env, token: &Address, fee_rate: u32, amount: u64, payer: Option<&Address>, taker: bool) -> u64 {
+    let mut fee_rate = token_fee_get(e, token).unwrap_or(fee_rate);
+    if let (Some(acceptor), true) = (payer, taker) {
+        let volume = volume_get(e, acceptor, token);
+        for tier in fee_tiers_get(e).iter() {
+            if volume >= tier.min_volume && tier.fee_rate < fee_rate {
+                fee_rate = tier.fee_rate;
+            }
+        }
+    }
+    // Fee rates are capped below 100%, so the fee always fits in a u64.
+    let fee = (amount as u128) * (fee_rate as u128) / (u128::pow(10, FEE_DECIMALS));
+    let discount = match payer {
+        Some(payer) if fee > 0 => fee_discount(e, payer),
+        _ => 0,
+    };
+    (fee - fee * (discount as u128) / u128::pow(10, FEE_DECIMALS)) as u64
 }
@@ -0,0 +1,49 @@
+use soroban_sdk::{ Address, Env, Vec };
+
+use crate::storage_types::{ BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, 
+    DataKey, FillInfo
+};
+
+
+pub fn fill_count(e: &Env, offer_id: u32) -> u32 {
+    let key = DataKey::FillCount(offer_id);
+    e.storage().persistent().get(&key).unwrap_or(0)
+}
+
+pub fn fill_record(e: &Env, offer_id: u32, acceptor: &Address, amount: u64, send_amount: u64) {
+    let count = fill_count(e, offer_id);
+
+    let key = DataKey::Fill(offer_id, count);
+    e.storage().persistent().set(&key, &FillInfo {
+        acceptor: acceptor.clone(),
+        amount,
+        send_amount,
+        ledger: e.ledger().sequence(),
+    });
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+
+    let count_key = DataKey::FillCount(offer_id);
+    e.storage().persistent().set(&count_key, &(count + 1));
+    e.storage().persistent().extend_ttl(&count_key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
+
+// Removes the fill history of the offer.
+pub fn fills_remove(e: &Env, offer_id: u32) {
+    for i in 0..fill_count(e, offer_id) {
+        e.storage().persistent().remove(&DataKey::Fill(offer_id, i));
+    }
+    e.storage().persistent().remove(&DataKey::FillCount(offer_id));
+}
+
+pub fn fill_list(e: &Env, offer_id: u32, cursor: u32, limit: u32) -> Vec<FillInfo> {
+    let mut fills: Vec<FillInfo> = Vec::new(e);
+    let end = fill_count(e, offer_id).min(cursor.saturating_add(limit));
+
+    for i in cursor..end {
+        if let Some(fill) = e.storage().persistent().get(&DataKey::Fill(offer_id, i)) {
+            fills.push_back(fill);
+        }
+    }
+
+    fills
+}
@@ -0,0 +1,40 @@
+use soroban_sdk::{ Address, Env, symbol_short };
+
+use crate::storage_types::{ FREEZE_ALL, ConfigKey, Role };
+use crate::role::{ role_check };
+use crate::error::SwapError;
+
+
+/*
+The admin can freeze parts of the configuration for good, for integrators who need a
+guarantee that the economics never change under their users. FREEZE_FEES locks every fee
+setting, FREEZE_ALLOWLIST the allowed tokens and pairs. Nothing unfreezes a part again.
+*/
+
+// Parts of the configuration frozen so far, as FREEZE_* flags.
+pub fn frozen_get(e: &Env) -> u32 {
+    e.storage().instance().get(&ConfigKey::Frozen).unwrap_or(0)
+}
+
+// Freezes the `parts` of the configuration, on top of those already frozen.
+pub fn config_finalize(e: &Env, caller: &Address, parts: u32) -> Result<(), SwapError> {
+    role_check(e, Role::Admin, caller)?;
+    if parts & !FREEZE_ALL != 0 {
+        return Err(SwapError::InvalidOps);
+    }
+    let frozen = frozen_get(e) | parts;
+    e.storage().instance().set(&ConfigKey::Frozen, &frozen);
+
+    // emit ConfigFinalized event
+    e.events().publish((symbol_short!("CONFIG"), symbol_short!("Finalize")), 
+        frozen
+    );
+    Ok(())
+}
+
+pub fn freeze_check(e: &Env, part: u32) -> Result<(), SwapError> {
+    if frozen_get(e) & part != 0 {
+        return Err(SwapError::Unauthorized);
+    }
+    Ok(())
+}
@@ -0,0 +1,178 @@
+const GOV: Symbol = symbol_short!("GOV");
+
+use soroban_sdk::{ Address, Env, symbol_short, Symbol };
+
+use crate::storage_types::{ FREEZE_ALLOWLIST, INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT,
+    GovAction, GovConfig, GovKey, OfferStatus, Proposal, Role
+};
+use crate::allow::{ allow_write, allow_remove };
+use crate::fee::{ fee_rates_write };
+use crate::freeze::{ freeze_check };
+use crate::role::{ role_check };
+use crate::pause::{ pause_check };
+use crate::escrow::{ escrow_in, escrow_out };
+use crate::error::SwapError;
+
+
+/*
+Holders of the governance token can change fee rates and the token allowlist without
+the admin:
+
+1. Anyone proposes an action with `propose`.
+2. Until the voting period ends, holders vote for or against it by locking governance
+   tokens in the vote. Locked tokens are the voting weight, so the same tokens can't
+   vote twice.
+3. A proposal with more votes for than against, and at least `quorum` votes for, can be
+   executed by anyone once the delay after the vote has passed.
+4. Voters withdraw their locked tokens once voting has ended.
+*/
+
+pub fn gov_config_get(e: &Env) -> Option<GovConfig> {
+    e.storage().instance().get(&GovKey::Config)
+}
+
+// Enables governance with `config`, None stops new proposals.
+pub fn gov_config_set(e: &Env, caller: &Address, config: &Option<GovConfig>) -> Result<(), SwapError> {
+    role_check(e, Role::Admin, caller)?;
+    match config {
+        Some(config) => e.storage().instance().set(&GovKey::Config, config),
+        None => e.storage().instance().remove(&GovKey::Config),
+    }
+    Ok(())
+}
+
+pub fn gov_propose(e: &Env, proposer: &Address, action: &GovAction) -> Result<u32, SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+    let config = gov_config_get(e).ok_or(SwapError::Unauthorized)?;
+
+    proposer.require_auth();
+
+    let end_ledger = e.ledger().sequence() + config.voting_ledgers;
+    let proposal_id: u32 = e.storage().instance().get(&GovKey::Count).unwrap_or(0);
+    proposal_write(e, proposal_id, &Proposal {
+        proposer: proposer.clone(),
+        action: action.clone(),
+        token: config.token,
+        quorum: config.quorum,
+        votes_for: 0,
+        votes_against: 0,
+        end_ledger,
+        execute_ledger: end_ledger + config.delay_ledgers,
+        status: OfferStatus::ACTIVE,
+    });
+    e.storage().instance().set(&GovKey::Count, &(proposal_id + 1));
+    e.storage().instance().extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+
+    // emit ProposalCreated event
+    e.events().publish((GOV, symbol_short!("GPropose")),
+        (proposal_id, proposer.clone(), action.clone())
+    );
+
+    Ok(proposal_id)
+}
+
+// Votes for or against the proposal by locking `amount` of the governance token. Each
+// account votes once per proposal.
+pub fn gov_vote(e: &Env, voter: &Address, proposal_id: u32, support: bool, amount: u64) -> Result<(), SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+    let mut proposal = proposal_load(e, proposal_id)?;
+    if e.ledger().sequence() >= proposal.end_ledger {
+        return Err(SwapError::OfferExpired);
+    }
+    if amount == 0 {
+        return Err(SwapError::ZeroAmount);
+    }
+    let key = GovKey::Vote(proposal_id, voter.clone());
+    if e.storage().persistent().has(&key) {
+        return Err(SwapError::OfferLocked);
+    }
+
+    voter.require_auth();
+
+    let amount = escrow_in(e, &proposal.token, voter, amount);
+    if support {
+        proposal.votes_for = proposal.votes_for.checked_add(amount).ok_or(SwapError::Overflow)?;
+    } else {
+        proposal.votes_against = proposal.votes_against.checked_add(amount).ok_or(SwapError::Overflow)?;
+    }
+    proposal_write(e, proposal_id, &proposal);
+    e.storage().persistent().set(&key, &amount);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+
+    // emit VoteCast event
+    e.events().publish((GOV, symbol_short!("GVote")),
+        (proposal_id, voter.clone(), support, amount)
+    );
+
+    Ok(())
+}
+
+// Returns the tokens voter locked in its vote, once voting has ended.
+pub fn gov_withdraw(e: &Env, voter: &Address, proposal_id: u32) -> Result<u64, SwapError> {
+    let proposal = proposal_load(e, proposal_id)?;
+    if e.ledger().sequence() < proposal.end_ledger {
+        return Err(SwapError::OfferNotExpired);
+    }
+
+    voter.require_auth();
+
+    let key = GovKey::Vote(proposal_id, voter.clone());
+    let amount: u64 = e.storage().persistent().get(&key).unwrap_or(0);
+    if amount == 0 {
+        return Ok(0);
+    }
+    e.storage().persistent().remove(&key);
+    escrow_out(e, &proposal.token, voter, amount);
+    Ok(amount)
+}
+
+// Carries out a passed proposal once its delay has passed. Can be called by anyone.
+pub fn gov_execute(e: &Env, proposal_id: u32) -> Result<(), SwapError> {
+    let mut proposal = proposal_load(e, proposal_id)?;
+    if proposal.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+    if e.ledger().sequence() < proposal.execute_ledger {
+        return Err(SwapError::FeeTimelocked);
+    }
+    if proposal.votes_for <= proposal.votes_against || proposal.votes_for < proposal.quorum {
+        return Err(SwapError::Unauthorized);
+    }
+
+    match &proposal.action {
+        GovAction::FeeRates(maker_fee_rate, taker_fee_rate) => fee_rates_write(e, *maker_fee_rate, *taker_fee_rate)?,
+        GovAction::Allow(token) => {
+            freeze_check(e, FREEZE_ALLOWLIST)?;
+            allow_write(e, token)?
+        },
+        GovAction::Disallow(token) => {
+            freeze_check(e, FREEZE_ALLOWLIST)?;
+            allow_remove(e, token)
+        },
+    }
+    proposal.status = OfferStatus::COMPLETE;
+    proposal_write(e, proposal_id, &proposal);
+
+    // emit ProposalExecuted event
+    e.events().publish((GOV, symbol_short!("GExecute")),
+        proposal_id
+    );
+
+    Ok(())
+}
+
+pub fn proposal_load(e: &Env, proposal_id: u32) -> Result<Proposal, SwapError> {
+    let key = GovKey::Proposal(proposal_id);
+
+    e.storage().persistent().get(&key).ok_or(SwapError::OfferNotFound)
+}
+
+fn proposal_write(e: &Env, proposal_id: u32, proposal: &Proposal) {
+    let key = GovKey::Proposal(proposal_id);
+    e.storage().persistent().set(&key, proposal);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
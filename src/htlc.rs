@@ -0,0 +1,135 @@
+const HTLC: Symbol = symbol_short!("HTLC");
+
+use soroban_sdk::{ Address, Bytes, BytesN, Env, symbol_short, Symbol };
+
+use crate::storage_types::{ INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, 
+    DataKey, HtlcInfo, OfferStatus
+};
+use crate::allow::{ allow_get };
+use crate::pause::{ pause_check };
+use crate::compliance::{ ban_check, compliance_check };
+use crate::escrow::{ escrow_in, escrow_out };
+use crate::error::SwapError;
+
+
+/*
+Hashed-timelock contracts anchoring the Stellar leg of a cross-chain atomic swap:
+
+1. Offeror calls `lock` to escrow `amount` of `send_token` for the acceptor behind
+   the sha256 `hashlock` of a secret, until `timelock`.
+2. Before `timelock`, anyone holding the preimage may call `claim` to release the
+   escrow to the acceptor. The preimage is published in the event so it can be used
+   to claim the other leg.
+3. From `timelock` on, anyone may call `refund` to return the escrow to the offeror.
+*/
+
+pub fn htlc_lock(
+    e: &Env,
+    offeror: &Address,
+    acceptor: &Address,
+    send_token: &Address,
+    amount: u64,
+    hashlock: &BytesN<32>,
+    timelock: u32,
+) -> Result<u32, SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+    if !allow_get(e, send_token) {
+        return Err(SwapError::TokenNotAllowed);
+    }
+    if amount == 0 {
+        return Err(SwapError::ZeroAmount);
+    }
+    if timelock <= e.ledger().sequence() {
+        return Err(SwapError::InvalidExpiration);
+    }
+    ban_check(e, offeror)?;
+    ban_check(e, acceptor)?;
+    compliance_check(e, offeror, send_token, amount)?;
+    compliance_check(e, acceptor, send_token, amount)?;
+
+    offeror.require_auth();
+
+    let amount = escrow_in(e, send_token, offeror, amount);
+    if amount == 0 {
+        return Err(SwapError::AmountTooSmall);
+    }
+
+    let htlc_id: u32 = e.storage().instance().get(&DataKey::HtlcCount).unwrap_or(0);
+    htlc_write(e, htlc_id, &HtlcInfo {
+        offeror: offeror.clone(),
+        acceptor: acceptor.clone(),
+        send_token: send_token.clone(),
+        amount,
+        hashlock: hashlock.clone(),
+        timelock,
+        status: OfferStatus::ACTIVE,
+    });
+    e.storage().instance().set(&DataKey::HtlcCount, &(htlc_id + 1));
+    e.storage().instance().extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+
+    // emit HtlcLocked event
+    e.events().publish((HTLC, symbol_short!("HLock")), 
+        (htlc_id, offeror.clone(), acceptor.clone(), send_token.clone(), amount, hashlock.clone(), timelock)
+    );
+
+    Ok(htlc_id)
+}
+
+pub fn htlc_claim(e: &Env, htlc_id: u32, preimage: &Bytes) -> Result<(), SwapError> {
+    let mut htlc = htlc_load(e, htlc_id)?;
+    if htlc.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+    if e.ledger().sequence() >= htlc.timelock {
+        return Err(SwapError::OfferExpired);
+    }
+    if e.crypto().sha256(preimage) != htlc.hashlock {
+        return Err(SwapError::InvalidReveal);
+    }
+
+    escrow_out(e, &htlc.send_token, &htlc.acceptor, htlc.amount);
+    htlc.status = OfferStatus::COMPLETE;
+    htlc_write(e, htlc_id, &htlc);
+
+    // emit HtlcClaimed event
+    e.events().publish((HTLC, symbol_short!("HClaim")), 
+        (htlc_id, htlc.acceptor.clone(), preimage.clone())
+    );
+
+    Ok(())
+}
+
+pub fn htlc_refund(e: &Env, htlc_id: u32) -> Result<(), SwapError> {
+    let mut htlc = htlc_load(e, htlc_id)?;
+    if htlc.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+    if e.ledger().sequence() < htlc.timelock {
+        return Err(SwapError::OfferNotExpired);
+    }
+
+    escrow_out(e, &htlc.send_token, &htlc.offeror, htlc.amount);
+    htlc.status = OfferStatus::EXPIRED;
+    htlc_write(e, htlc_id, &htlc);
+
+    // emit HtlcRefunded event
+    e.events().publish((HTLC, symbol_short!("HRefund")), 
+        (htlc_id, htlc.offeror.clone())
+    );
+
+    Ok(())
+}
+
+pub fn htlc_load(e: &Env, htlc_id: u32) -> Result<HtlcInfo, SwapError> {
+    let key = DataKey::Htlc(htlc_id);
+
+    e.storage().persistent().get(&key).ok_or(SwapError::HtlcNotFound)
+}
+
+fn htlc_write(e: &Env, htlc_id: u32, htlc: &HtlcInfo) {
+    let key = DataKey::Htlc(htlc_id);
+    e.storage().persistent().set(&key, htlc);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
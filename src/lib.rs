@@ -4,70 +4,623 @@
 #![no_std]
 
 mod admin;
+mod error;
+mod escrow;
+mod dca;
+mod deal;
+mod dispute;
+mod decimals;
+mod dutch;
 mod storage_types;
 mod fee;
 mod allow;
+mod alt;
+mod amm;
+mod acceptor;
+mod auction;
+mod basket;
+mod callback;
+mod commit;
+mod compliance;
+mod fill;
+mod freeze;
+mod gov;
+mod manager;
+mod nft;
+mod htlc;
 mod offer;
+mod oracle;
+mod order;
+mod orderbook;
+mod pause;
+mod referral;
+mod reward;
+mod rfq;
+mod role;
+mod stake;
+mod stats;
+mod stream;
+mod throttle;
+mod ttl;
+pub mod testutils;
+mod upgrade;
 
 
 use soroban_sdk::{
-    contract, contractimpl, Address, Env, /* BytesN */
+    contract, contractimpl, Address, Bytes, BytesN, Env, String, Symbol, Vec,
+};
+use crate::storage_types::{ INSTANCE_BUMP_AMOUNT, INSTANCE_LIFETIME_THRESHOLD, SCHEMA_VERSION };
+use crate::auction::{ auction_get, auction_best_bid, auction_escrow, auction_start, auction_bid, auction_refund, auction_settle };
+use crate::basket::{ basket_create, basket_accept, basket_close, basket_expire, basket_load };
+use crate::callback::{ callback_get, callback_set };
+use crate::commit::{ commit_get, commit_lock_get, commit_accept, commit_reveal };
+use crate::compliance::{ compliance_get, compliance_set, ban_get, ban_set };
+use crate::dca::{ dca_create, dca_execute, dca_cancel, dca_load };
+use crate::deal::{ deal_open, deal_deposit, deal_confirm, deal_reclaim, deal_load };
+use crate::dispute::{ dispute_window_get, dispute_window_set, dispute_get, dispute_raise, dispute_release, dispute_resolve };
+use crate::decimals::{ decimals_get, token_name_get, price_get, price_recv_amount, price_offer };
+use crate::dutch::{ dutch_get, dutch_set };
+use crate::fee::{ fee_propose, fee_apply, fee_get, fee_pending_get, fee_delay_get, fee_delay_set, fee_changes_get, fee_changes_apply, token_fee_get, token_fee_set, 
+    fee_tiers_get, fee_tiers_set, volume_get, fee_accrued_get, fee_claim, fee_token_get, fee_token_set, fee_token_rate_get, fee_token_rate_set, 
+    discount_token_get, fee_discounts_get, fee_discounts_set 
+};
+use crate::acceptor::{ acceptors_get, acceptor_add, acceptor_remove, acceptors_clear, counterparty_get };
+use crate::amm::{ router_get, router_set, swap_best };
+use crate::alt::{ alt_get, alt_set, alt_accept };
+use crate::allow::{ allow_set, allow_reset, allow_set_many, allow_reset_many, allow_get, allow_list, allow_pair_get, pair_allow, pair_disallow, pairs_only_get, pairs_only_set, minimums_get, minimums_set };
+use crate::offer::{ offer_count, offer_create, offer_create_many, offer_accept, offer_accept_exact_out, offer_accept_many, offer_accept_route, offer_match, offer_take_best, offer_market_buy, offer_swap_exact_out, offer_quote, offer_update, offer_increase, offer_decrease, offer_split, offer_transfer, offer_close, offer_pause, offer_resume, offer_admin_close, offer_expire, offer_reopen, offer_extend_ttl, offer_prune, offer_purge_before, offer_load_live, offer_list, offer_list_by_offeror, offer_list_by_pair, offer_balances, offer_dust, offer_memo, offer_open_count, offer_limit_get, offer_limit_set, rounding_get, rounding_set };
+use crate::freeze::{ frozen_get, config_finalize };
+use crate::gov::{ gov_config_get, gov_config_set, gov_propose, gov_vote, gov_withdraw, gov_execute, proposal_load };
+use crate::htlc::{ htlc_lock, htlc_claim, htlc_refund, htlc_load };
+use crate::escrow::{ escrow_rescue, escrow_solvency };
+use crate::fill::{ fill_list };
+use crate::stats::{ stats_global_get, stats_pair_get };
+use crate::manager::{ manager_get, manager_set };
+use crate::nft::{ nft_create, nft_accept, nft_close, nft_expire, nft_load };
+use crate::oracle::{ oracle_get, oracle_set, band_get, band_set, peg_get, peg_set };
+use crate::ttl::{ ttl_get, ttl_set, offer_restore };
+use crate::order::{ order_fill, nonce_used, nonce_min_get, nonce_cancel, nonce_cancel_before };
+use crate::orderbook::{ book_best, book_best_price, book_levels };
+use crate::pause::{ pause_set, pause_check };
+use crate::referral::{ referral_share_get, referral_share_set, referral_accrued_get, referral_claim };
+use crate::reward::{ reward_token_get, reward_token_set, reward_rate_get, reward_rate_set, reward_budget_get, reward_fund, reward_accrued_get, reward_claim };
+use crate::rfq::{ rfq_request, rfq_cancel, rfq_quote, rfq_withdraw, rfq_accept, request_load, quote_load };
+use crate::role::{ role_has, role_grant, role_revoke };
+use crate::stake::{ stake_token_get, stake_token_set, stake_share_get, stake_share_set, stake_total_get, stake_get, stake, unstake, stake_rewards_get, stake_claim };
+use crate::stream::{ vesting_get, vesting_set, stream_get, stream_withdraw };
+use crate::throttle::{ rate_limit_get, rate_limit_set };
+use crate::upgrade::{ upgrade_wasm, migrate_data, read_version, write_version, offer_schema, offers_migrate };
+use crate::admin::{ read_administrator, write_administrator, has_administrator, 
+    read_pending_administrator, write_pending_administrator, remove_pending_administrator 
 };
-use crate::storage_types::{ FeeInfo, INSTANCE_BUMP_AMOUNT, INSTANCE_LIFETIME_THRESHOLD};
-use crate::fee::{ fee_set, fee_get };
-use crate::allow::{ allow_set, allow_reset };
-use crate::offer::{ error, offer_count, offer_create, offer_accept, offer_update, offer_close, offer_load, offer_balances };
-use crate::admin::{ read_administrator, write_administrator, has_administrator };
+
+pub use crate::error::SwapError;
+pub use crate::storage_types::{ EVENT_VERSION, FREEZE_ALLOWLIST, FREEZE_FEES, MANAGE_CLOSE, MANAGE_UPDATE, PRICE_DECIMALS };
+pub use crate::storage_types::{ AltRecv, BasketItem, BasketOffer, Bid, BookLevel, Config, DcaPlan, DealInfo, DisputeHold, DutchAuction, FeeChange, FeeDiscount, FeeInfo, FeeTier, FeeToken, FillInfo, GlobalStats, GovAction, GovConfig, HtlcInfo, NftOffer, OfferAccepted, OfferClosed, OfferCompleted, OfferCreated, OfferInfo, OfferMode, OfferSpec, OfferStatus, OfferUpdated, OraclePeg, PairMinimums, PairStats, PriceBand, Commitment, PendingFeeChange, Proposal, RateLimit, Quote, QuoteRequest, Role, RoundingPolicy, SignedOrder, Stream, TtlConfig };
 
 #[contract]
 pub struct TokenSwap;
 
 #[contractimpl]
 impl TokenSwap {
-    pub fn initialize(e: Env, admin: Address) {
+    pub fn initialize(e: Env, admin: Address) -> Result<(), SwapError> {
         if has_administrator(&e) {
-            panic!("already initialized");
+            return Err(SwapError::AlreadyInitialized);
         }
         write_administrator(&e, &admin);
+        write_version(&e, SCHEMA_VERSION);
+        Ok(())
+    }
+
+    pub fn upgrade(e: Env, new_wasm_hash: BytesN<32>) -> Result<(), SwapError> {
+        upgrade_wasm(&e, &new_wasm_hash)
+    }
+
+    pub fn migrate(e: Env) -> Result<(), SwapError> {
+        migrate_data(&e)
+    }
+
+    // Rewrites offers stored in an older schema in the current one. Requires the Admin role.
+    pub fn migrate_offers(e: Env, caller: Address, offer_ids: Vec<u32>) -> Result<u32, SwapError> {
+        offers_migrate(&e, &caller, &offer_ids)
+    }
+
+    pub fn get_offer_schema(e: Env, offer_id: u32) -> Option<u32> {
+        offer_schema(&e, offer_id)
+    }
+
+    pub fn version(e: Env) -> u32 {
+        read_version(&e)
     }
 
-    pub fn set_admin(e: Env, new_admin: Address) {
-        let admin = read_administrator(&e);
+    // Proposes a new admin. It only takes effect once `new_admin` calls `accept_admin`.
+    pub fn propose_admin(e: Env, new_admin: Address) -> Result<(), SwapError> {
+        let admin = read_administrator(&e)?;
         admin.require_auth();
 
+        e.storage()
+            .instance()
+            .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+
+        write_pending_administrator(&e, &new_admin);
+        Ok(())
+    }
+
+    pub fn accept_admin(e: Env) -> Result<(), SwapError> {
+        let new_admin = read_pending_administrator(&e)?;
+        new_admin.require_auth();
+
         e.storage()
             .instance()
             .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
 
         write_administrator(&e, &new_admin);
+        remove_pending_administrator(&e);
+        Ok(())
+    }
+
+    pub fn get_admin(e: Env) -> Result<Address, SwapError> {
+        read_administrator(&e)
+    }
+
+    pub fn grant_role(e: Env, role: Role, account: Address) -> Result<(), SwapError> {
+        role_grant(&e, role, &account)
+    }
+
+    pub fn revoke_role(e: Env, role: Role, account: Address) -> Result<(), SwapError> {
+        role_revoke(&e, role, &account)
+    }
+
+    pub fn has_role(e: Env, role: Role, account: Address) -> bool {
+        role_has(&e, role, &account)
+    }
+
+    // Fee changes take effect in two steps: `propose_fee` schedules the new fee
+    // and `apply_fee` activates it once the fee delay (in ledgers) has passed.
+    pub fn propose_fee(e: Env, 
+        caller: Address, 
+        maker_fee_rate: u32, 
+        taker_fee_rate: u32, 
+        fee_wallet: Address
+    ) -> Result<(), SwapError> {
+        let fee_info: FeeInfo = FeeInfo {maker_fee_rate, taker_fee_rate, fee_wallet};
+        fee_propose(&e, &caller, &fee_info)
+    }
+
+    pub fn apply_fee(e: Env) -> Result<(), SwapError> {
+        fee_apply(&e)
+    }
+
+    pub fn get_pending_fee(e: Env) -> Result<(u32, u32, Address, u32), SwapError> {
+        let pending = fee_pending_get(&e)?;
+        Ok((pending.fee_info.maker_fee_rate, pending.fee_info.taker_fee_rate, pending.fee_info.fee_wallet, pending.apply_ledger))
+    }
+
+    pub fn set_fee_delay(e: Env, caller: Address, delay: u32) -> Result<(), SwapError> {
+        fee_delay_set(&e, &caller, delay)
+    }
+
+    pub fn get_fee_delay(e: Env) -> u32 {
+        fee_delay_get(&e)
+    }
+
+    // Changes of the other fee settings, and lowering the fee delay, are queued behind the
+    // fee delay as well. `apply_fee_changes` makes those whose delay has passed effective.
+    pub fn apply_fee_changes(e: Env) -> Result<u32, SwapError> {
+        fee_changes_apply(&e)
+    }
+
+    pub fn get_pending_fee_changes(e: Env) -> Vec<PendingFeeChange> {
+        fee_changes_get(&e)
+    }
+
+    // The fee rates charged now, before any token rate or fee tier applies.
+    pub fn get_fee(e: Env) -> Result<FeeInfo, SwapError> {
+        fee_get(&e)
+    }
+
+    // Admin, fee settings, pause state and schema version in a single call.
+    pub fn get_config(e: Env) -> Result<Config, SwapError> {
+        Ok(Config {
+            admin: read_administrator(&e)?,
+            fee: fee_get(&e)?,
+            fee_delay: fee_delay_get(&e),
+            paused: pause_check(&e),
+            schema_version: read_version(&e),
+        })
+    }
+
+    // Freezes the `parts` (FREEZE_* flags) of the configuration for good.
+    pub fn finalize_config(e: Env, caller: Address, parts: u32) -> Result<(), SwapError> {
+        config_finalize(&e, &caller, parts)
+    }
+
+    pub fn get_frozen_config(e: Env) -> u32 {
+        frozen_get(&e)
+    }
+
+    // Overrides both maker and taker fee rates for fees charged in `token`.
+    pub fn set_token_fee(e: Env, caller: Address, token: Address, fee_rate: u32) -> Result<(), SwapError> {
+        token_fee_set(&e, &caller, &token, Some(fee_rate))
+    }
+
+    pub fn clear_token_fee(e: Env, caller: Address, token: Address) -> Result<(), SwapError> {
+        token_fee_set(&e, &caller, &token, None)
+    }
+
+    pub fn get_token_fee(e: Env, token: Address) -> Option<u32> {
+        token_fee_get(&e, &token)
+    }
+
+    // Charges maker and taker fees in a dedicated token, None charges them in the traded tokens.
+    pub fn set_fee_token(e: Env, caller: Address, fee_token: Option<FeeToken>) -> Result<(), SwapError> {
+        fee_token_set(&e, &caller, &fee_token)
+    }
+
+    pub fn get_fee_token(e: Env) -> Option<FeeToken> {
+        fee_token_get(&e)
+    }
+
+    // Fee token units a unit of token is worth, with PRICE_DECIMALS decimals, used instead of
+    // the oracle price once the fee delay has passed. None removes the rate.
+    pub fn set_fee_token_rate(e: Env, caller: Address, token: Address, rate: Option<u128>) -> Result<(), SwapError> {
+        fee_token_rate_set(&e, &caller, &token, &rate)
+    }
+
+    pub fn get_fee_token_rate(e: Env, token: Address) -> Option<u128> {
+        fee_token_rate_get(&e, &token)
+    }
+
+    // Discounts fees by the payer's balance of `token`, None removes the schedule.
+    pub fn set_fee_discounts(e: Env, caller: Address, token: Option<Address>, discounts: Vec<FeeDiscount>) -> Result<(), SwapError> {
+        fee_discounts_set(&e, &caller, &token, &discounts)
+    }
+
+    pub fn get_discount_token(e: Env) -> Option<Address> {
+        discount_token_get(&e)
+    }
+
+    pub fn get_fee_discounts(e: Env) -> Vec<FeeDiscount> {
+        fee_discounts_get(&e)
+    }
+
+    // Discounted taker fee rates by acceptor volume, sorted by increasing `min_volume`.
+    pub fn set_fee_tiers(e: Env, caller: Address, tiers: Vec<FeeTier>) -> Result<(), SwapError> {
+        fee_tiers_set(&e, &caller, &tiers)
+    }
+
+    pub fn get_fee_tiers(e: Env) -> Vec<FeeTier> {
+        fee_tiers_get(&e)
+    }
+
+    // Volume settled by `account` in `token`, which fee tiers are checked against.
+    pub fn get_volume(e: Env, account: Address, token: Address) -> u64 {
+        volume_get(&e, &account, &token)
+    }
+
+    pub fn get_accrued_fees(e: Env, token: Address) -> u64 {
+        fee_accrued_get(&e, &token)
+    }
+
+    // Returns the total held in escrow for `token` and the contract's balance of it. A balance
+    // below the escrowed total means the contract is insolvent in that token.
+    pub fn check_solvency(e: Env, token: Address) -> (u128, u128) {
+        escrow_solvency(&e, &token)
+    }
+
+    // Sends `amount` of tokens sent straight to the contract to `to`. Only what the contract
+    // holds beyond the escrows, bids, locks and fees it owes can be rescued.
+    pub fn rescue(e: Env, caller: Address, token: Address, amount: u64, to: Address) -> Result<(), SwapError> {
+        escrow_rescue(&e, &caller, &token, amount, &to)
+    }
+
+    // Transfers the fees accrued in `token` to the fee wallet and returns the amount.
+    pub fn claim_fees(e: Env, token: Address) -> Result<u64, SwapError> {
+        fee_claim(&e, &token)
+    }
+
+    // Share of the taker fee, in basis points, credited to the referrer passed to `accept_offer`.
+    pub fn set_referral_share(e: Env, caller: Address, share: u32) -> Result<(), SwapError> {
+        referral_share_set(&e, &caller, share)
+    }
+
+    pub fn get_referral_share(e: Env) -> u32 {
+        referral_share_get(&e)
+    }
+
+    pub fn get_referral_fees(e: Env, referrer: Address, token: Address) -> u64 {
+        referral_accrued_get(&e, &referrer, &token)
+    }
+
+    pub fn claim_referral_fees(e: Env, referrer: Address, token: Address) -> u64 {
+        referral_claim(&e, &referrer, &token)
+    }
+
+    // Token makers are paid liquidity mining rewards in. Can only be changed while no budget
+    // is left and all rewards are claimed.
+    pub fn set_reward_token(e: Env, caller: Address, token: Option<Address>) -> Result<(), SwapError> {
+        reward_token_set(&e, &caller, &token)
+    }
+
+    pub fn get_reward_token(e: Env) -> Option<Address> {
+        reward_token_get(&e)
+    }
+
+    // Reward token units a maker earns per unit of `token` received in a fill, with
+    // PRICE_DECIMALS decimals. None removes the rate.
+    pub fn set_reward_rate(e: Env, caller: Address, token: Address, rate: Option<u128>) -> Result<(), SwapError> {
+        reward_rate_set(&e, &caller, &token, &rate)
+    }
+
+    pub fn get_reward_rate(e: Env, token: Address) -> Option<u128> {
+        reward_rate_get(&e, &token)
+    }
+
+    pub fn fund_rewards(e: Env, funder: Address, amount: u64) -> Result<(), SwapError> {
+        reward_fund(&e, &funder, amount)
+    }
+
+    pub fn get_reward_budget(e: Env) -> u64 {
+        reward_budget_get(&e)
+    }
+
+    pub fn get_rewards(e: Env, maker: Address) -> u64 {
+        reward_accrued_get(&e, &maker)
+    }
+
+    pub fn claim_rewards(e: Env, maker: Address) -> u64 {
+        reward_claim(&e, &maker)
+    }
+
+    // Token accounts can stake for fee discounts and rewards. Can only be changed while nothing is staked.
+    pub fn set_stake_token(e: Env, caller: Address, token: Option<Address>) -> Result<(), SwapError> {
+        stake_token_set(&e, &caller, &token)
+    }
+
+    pub fn get_stake_token(e: Env) -> Option<Address> {
+        stake_token_get(&e)
+    }
+
+    // Share of every fee (in basis points) paid to stakers.
+    pub fn set_stake_share(e: Env, caller: Address, share: u32) -> Result<(), SwapError> {
+        stake_share_set(&e, &caller, share)
+    }
+
+    pub fn get_stake_share(e: Env) -> u32 {
+        stake_share_get(&e)
+    }
+
+    pub fn stake(e: Env, staker: Address, amount: u64) -> Result<(), SwapError> {
+        stake(&e, &staker, amount)
+    }
+
+    pub fn unstake(e: Env, staker: Address, amount: u64) -> Result<(), SwapError> {
+        unstake(&e, &staker, amount)
+    }
+
+    pub fn get_stake(e: Env, staker: Address) -> u64 {
+        stake_get(&e, &staker)
+    }
+
+    pub fn get_total_stake(e: Env) -> u64 {
+        stake_total_get(&e)
+    }
+
+    pub fn get_stake_rewards(e: Env, staker: Address, token: Address) -> u64 {
+        stake_rewards_get(&e, &staker, &token)
+    }
+
+    pub fn claim_stake_rewards(e: Env, staker: Address, token: Address) -> u64 {
+        stake_claim(&e, &staker, &token)
     }
 
-    pub fn set_fee(e: Env, fee_rate: u32, fee_wallet: Address) {
-        let fee_info: FeeInfo = FeeInfo {fee_rate, fee_wallet};
-        fee_set(&e, &fee_info);
+    pub fn get_maker_fee_rate(e: Env) -> Result<u32, SwapError> {
+        Ok(fee_get(&e)?.maker_fee_rate)
     }
 
-    pub fn get_fee(e: Env) -> (u32, Address) {
-        let fee_info: FeeInfo = fee_get(&e);
-        (fee_info.fee_rate, fee_info.fee_wallet)
+    pub fn get_taker_fee_rate(e: Env) -> Result<u32, SwapError> {
+        Ok(fee_get(&e)?.taker_fee_rate)
     }
 
-    pub fn allow_token(e: Env, token: Address) {
-        allow_set(&e, &token);
+    pub fn allow_token(e: Env, caller: Address, token: Address) -> Result<(), SwapError> {
+        allow_set(&e, &caller, &token)
     }
 
-    pub fn disallow_token(e: Env, token: Address) {
-        allow_reset(&e, &token);
+    pub fn disallow_token(e: Env, caller: Address, token: Address) -> Result<(), SwapError> {
+        allow_reset(&e, &caller, &token)
     }
 
-    pub fn get_error(e: Env) -> u32 {
-        error(&e)
+    pub fn allow_tokens(e: Env, caller: Address, tokens: Vec<Address>) -> Result<(), SwapError> {
+        allow_set_many(&e, &caller, &tokens)
+    }
+
+    pub fn disallow_tokens(e: Env, caller: Address, tokens: Vec<Address>) -> Result<(), SwapError> {
+        allow_reset_many(&e, &caller, &tokens)
+    }
+
+    pub fn is_token_allowed(e: Env, token: Address) -> bool {
+        allow_get(&e, &token)
+    }
+
+    // Registers the market of two allowed tokens, tradable in both directions.
+    pub fn allow_pair(e: Env, caller: Address, send_token: Address, recv_token: Address) -> Result<(), SwapError> {
+        pair_allow(&e, &caller, &send_token, &recv_token)
+    }
+
+    pub fn disallow_pair(e: Env, caller: Address, send_token: Address, recv_token: Address) -> Result<(), SwapError> {
+        pair_disallow(&e, &caller, &send_token, &recv_token)
+    }
+
+    // Whether offers selling send_token for recv_token can be made.
+    pub fn is_pair_allowed(e: Env, send_token: Address, recv_token: Address) -> bool {
+        allow_pair_get(&e, &send_token, &recv_token)
+    }
+
+    // Restricts trading to pairs registered with `allow_pair` instead of any two allowed tokens.
+    pub fn set_pairs_only(e: Env, caller: Address, pairs_only: bool) -> Result<(), SwapError> {
+        pairs_only_set(&e, &caller, pairs_only)
+    }
+
+    pub fn is_pairs_only(e: Env) -> bool {
+        pairs_only_get(&e)
+    }
+
+    // Sets the smallest offer and fill of the pair, None removes them.
+    pub fn set_pair_minimums(e: Env, 
+        caller: Address, 
+        send_token: Address, 
+        recv_token: Address, 
+        minimums: Option<PairMinimums>
+    ) -> Result<(), SwapError> {
+        minimums_set(&e, &caller, &send_token, &recv_token, &minimums)
+    }
+
+    pub fn get_pair_minimums(e: Env, send_token: Address, recv_token: Address) -> Option<PairMinimums> {
+        minimums_get(&e, &send_token, &recv_token)
+    }
+
+    // List allowed tokens in the order they were allowed
+    pub fn list_allowed_tokens(e: Env, 
+        cursor: u32, 
+        limit: u32
+    ) -> Vec<Address> {
+        allow_list(&e, cursor, limit)
+    }
+
+    // Decimals of the token as recorded when it was allowed.
+    pub fn get_token_decimals(e: Env, token: Address) -> Option<u32> {
+        decimals_get(&e, &token)
+    }
+
+    // Name of the token as recorded when it was allowed.
+    pub fn get_token_name(e: Env, token: Address) -> Option<String> {
+        token_name_get(&e, &token)
+    }
+
+    // Price of `send_amount` of send_token for `recv_amount` of recv_token, in whole recv tokens
+    // per whole send token with PRICE_DECIMALS decimals.
+    pub fn get_price(e: Env, 
+        send_token: Address, 
+        recv_token: Address, 
+        send_amount: u64, 
+        recv_amount: u64
+    ) -> Result<u128, SwapError> {
+        price_get(&e, &send_token, &recv_token, send_amount, recv_amount)
+    }
+
+    // The recv_amount that asks `price` (as returned by get_price) for `send_amount` of send_token.
+    pub fn get_recv_amount_for_price(e: Env, 
+        send_token: Address, 
+        recv_token: Address, 
+        send_amount: u64, 
+        price: u128
+    ) -> Result<u64, SwapError> {
+        price_recv_amount(&e, &send_token, &recv_token, send_amount, price)
+    }
+
+    pub fn get_offer_price(e: Env, 
+        offer_id: u32
+    ) -> Result<u128, SwapError> {
+        price_offer(&e, offer_id)
+    }
+
+    // Sets the Reflector compatible price oracle used by price bands, None removes it.
+    pub fn set_oracle(e: Env, caller: Address, oracle: Option<Address>) -> Result<(), SwapError> {
+        oracle_set(&e, &caller, &oracle)
+    }
+
+    pub fn get_oracle(e: Env) -> Option<Address> {
+        oracle_get(&e)
+    }
+
+    // Restricts offers of the pair to prices within the band around the oracle price,
+    // None removes the band.
+    pub fn set_price_band(e: Env, 
+        caller: Address, 
+        send_token: Address, 
+        recv_token: Address, 
+        band: Option<PriceBand>
+    ) -> Result<(), SwapError> {
+        band_set(&e, &caller, &send_token, &recv_token, &band)
+    }
+
+    pub fn get_price_band(e: Env, send_token: Address, recv_token: Address) -> Option<PriceBand> {
+        band_get(&e, &send_token, &recv_token)
+    }
+
+    // Stops creating, accepting and updating offers. Offerors can still close their offers.
+    pub fn pause(e: Env, caller: Address) -> Result<(), SwapError> {
+        pause_set(&e, &caller, true)
+    }
+
+    pub fn unpause(e: Env, caller: Address) -> Result<(), SwapError> {
+        pause_set(&e, &caller, false)
+    }
+
+    pub fn is_paused(e: Env) -> bool {
+        pause_check(&e)
     }
 
     pub fn count_offers(e: Env) -> u32 {
         offer_count(&e)
     }
 
+    // Number of active offers of offeror.
+    pub fn count_open_offers(e: Env, offeror: Address) -> u32 {
+        offer_open_count(&e, &offeror)
+    }
+
+    // Caps the active offers any single offeror can have, None removes the cap.
+    pub fn set_max_open_offers(e: Env, caller: Address, limit: Option<u32>) -> Result<(), SwapError> {
+        offer_limit_set(&e, &caller, &limit)
+    }
+
+    pub fn get_max_open_offers(e: Env) -> Option<u32> {
+        offer_limit_get(&e)
+    }
+
+    // Contract asked `can_trade(account, token, amount)` before creates and accepts, None removes it.
+    pub fn set_compliance(e: Env, caller: Address, compliance: Option<Address>) -> Result<(), SwapError> {
+        compliance_set(&e, &caller, &compliance)
+    }
+
+    pub fn get_compliance(e: Env) -> Option<Address> {
+        compliance_get(&e)
+    }
+
+    // Keeps account from creating and accepting offers.
+    pub fn ban_address(e: Env, caller: Address, account: Address) -> Result<(), SwapError> {
+        ban_set(&e, &caller, &account, true)
+    }
+
+    pub fn unban_address(e: Env, caller: Address, account: Address) -> Result<(), SwapError> {
+        ban_set(&e, &caller, &account, false)
+    }
+
+    pub fn is_banned(e: Env, account: Address) -> bool {
+        ban_get(&e, &account)
+    }
+
+    // Has `on_fill(offer_id, amount_in, amount_out)` called on acceptor after each of its fills.
+    pub fn set_fill_callback(e: Env, acceptor: Address, enabled: bool) {
+        callback_set(&e, &acceptor, enabled)
+    }
+
+    pub fn has_fill_callback(e: Env, acceptor: Address) -> bool {
+        callback_get(&e, &acceptor)
+    }
+
+    // Limits the offers an account can create and the fills it can take per window of
+    // ledgers, None lifts the limits.
+    pub fn set_rate_limit(e: Env, caller: Address, limit: Option<RateLimit>) -> Result<(), SwapError> {
+        rate_limit_set(&e, &caller, &limit)
+    }
+
+    pub fn get_rate_limit(e: Env) -> Option<RateLimit> {
+        rate_limit_get(&e)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn create_offer(e: Env, 
         offeror: Address, 
         send_token: Address, 
@@ -75,52 +628,981 @@ impl TokenSwap {
         timestamp: u32, 
         send_amount: u64, 
         recv_amount: u64, 
-        min_recv_amount: u64
-    ) -> u32 {
-        let ret: u32 = offer_create(&e, &offeror, &send_token, &recv_token, timestamp, send_amount, recv_amount, min_recv_amount);
+        min_recv_amount: u64,
+        expire_ledger: u32,
+        counterparty: Option<Address>,
+        mode: OfferMode
+    ) -> Result<u32, SwapError> {
+        offer_create(&e, &offeror, &send_token, &recv_token, timestamp, send_amount, recv_amount, min_recv_amount, expire_ledger, &counterparty, mode, &Bytes::new(&e))
+    }
+
+    // Like create_offer with the terms of `spec`, attaching `memo` (at most 64 bytes) such
+    // as a deal reference.
+    pub fn create_offer_with_memo(e: Env, 
+        offeror: Address, 
+        spec: OfferSpec, 
+        counterparty: Option<Address>,
+        memo: Bytes
+    ) -> Result<u32, SwapError> {
+        offer_create(&e, &offeror, &spec.send_token, &spec.recv_token, spec.timestamp, spec.send_amount, spec.recv_amount, 
+            spec.min_recv_amount, spec.expire_ledger, &counterparty, spec.mode, &memo)
+    }
 
-        ret
+    // Creates an offer for each of `specs` atomically and returns their ids.
+    pub fn create_offers(e: Env, 
+        offeror: Address, 
+        specs: Vec<OfferSpec>
+    ) -> Result<Vec<u32>, SwapError> {
+        offer_create_many(&e, &offeror, &specs)
     }
 
     pub fn accept_offer(e: Env, 
         acceptor: Address, 
         offer_id: u32, 
-        amount: u64
-    ) -> u32 {
-        let ret: u32 = offer_accept(&e, &acceptor, offer_id, amount);
-
-        ret
+        amount: u64,
+        min_send_out: u64,
+        referrer: Option<Address>
+    ) -> Result<(), SwapError> {
+        offer_accept(&e, &acceptor, offer_id, amount, min_send_out, &referrer)?;
+        Ok(())
     }
 
-    pub fn update_offer(e: Env, 
-        offeror: Address, 
+    // Buys exactly `send_amount` of the offer's send_token for at most `max_pay` of its
+    // recv_token, taker fee included. Returns the amounts paid and received.
+    pub fn accept_exact_out(e: Env, 
+        acceptor: Address, 
         offer_id: u32, 
-        recv_amount: u64, 
-        min_recv_amount: u64
-    ) -> u32 {
-        let ret: u32 = offer_update(&e, &offeror, offer_id, recv_amount, min_recv_amount);
+        send_amount: u64,
+        max_pay: u64
+    ) -> Result<(u64, u64), SwapError> {
+        offer_accept_exact_out(&e, &acceptor, offer_id, send_amount, max_pay)
+    }
 
-        ret
+    // Accepts `amount` of each offer in `fills` atomically. Returns the total recv_token paid
+    // (without fees) and send_token received, so it's meant for offers of a single pair.
+    pub fn accept_offers(e: Env, 
+        acceptor: Address, 
+        fills: Vec<(u32, u64)>,
+        min_send_out: u64,
+        referrer: Option<Address>
+    ) -> Result<(u64, u64), SwapError> {
+        offer_accept_many(&e, &acceptor, &fills, min_send_out, &referrer)
     }
 
-    pub fn close_offer(e: Env, 
-        offeror: Address,
-        offer_id: u32
-    ) -> u32 {
-        let ret: u32 = offer_close(&e, &offeror, offer_id);
+    // Fills up to `amount` of recv_token from the best priced offers selling `send_token`.
+    pub fn take_best(e: Env, 
+        acceptor: Address, 
+        send_token: Address, 
+        recv_token: Address, 
+        amount: u64, 
+        min_send_out: u64
+    ) -> Result<(u64, u64), SwapError> {
+        offer_take_best(&e, &acceptor, &send_token, &recv_token, amount, min_send_out)
+    }
 
-        ret
+    // Buys `send_token` with up to `spend_amount` of recv_token, taker fees included, from
+    // the resting offers of the pair, best price first. Returns the amounts spent and received.
+    pub fn market_buy(e: Env, 
+        taker: Address, 
+        send_token: Address, 
+        recv_token: Address, 
+        spend_amount: u64, 
+        min_received: u64
+    ) -> Result<(u64, u64), SwapError> {
+        offer_market_buy(&e, &taker, &send_token, &recv_token, spend_amount, min_received)
     }
 
-    pub fn load_offer(e: Env, 
-        offer_id: u32
-    ) -> (Address, Address, Address, u64, u64, u64, u32) {
-        let offer_info = offer_load(&e, offer_id);
-        (offer_info.offeror, 
-            offer_info.send_token, offer_info.recv_token, 
-            offer_info.send_amount, offer_info.recv_amount, offer_info.min_recv_amount, 
-            offer_info.status as u32
-        )
+    // AMM router `swap_best` compares the order book with, None sends every swap to the book.
+    pub fn set_router(e: Env, caller: Address, router: Option<Address>) -> Result<(), SwapError> {
+        router_set(&e, &caller, &router)
+    }
+
+    pub fn get_router(e: Env) -> Option<Address> {
+        router_get(&e)
+    }
+
+    // Swaps `amount` of recv_token for `send_token` at the order book or the AMM router,
+    // whichever pays more.
+    pub fn swap_best(e: Env, 
+        acceptor: Address, 
+        send_token: Address, 
+        recv_token: Address, 
+        amount: u64, 
+        min_out: u64
+    ) -> Result<u64, SwapError> {
+        swap_best(&e, &acceptor, &send_token, &recv_token, amount, min_out)
+    }
+
+    // Router style swap of up to `amount_in` of token_in, fees included, for at least
+    // `min_out` of token_out from the order book. Returns the amount of token_out received.
+    pub fn swap_exact_in(e: Env, 
+        from: Address, 
+        token_in: Address, 
+        token_out: Address, 
+        amount_in: u64, 
+        min_out: u64
+    ) -> Result<u64, SwapError> {
+        Ok(offer_market_buy(&e, &from, &token_out, &token_in, amount_in, min_out)?.1)
+    }
+
+    // Router style swap of at most `max_in` of token_in, fees included, for `amount_out` of
+    // token_out from the order book. Returns the amount of token_in spent.
+    pub fn swap_exact_out(e: Env, 
+        from: Address, 
+        token_in: Address, 
+        token_out: Address, 
+        amount_out: u64, 
+        max_in: u64
+    ) -> Result<u64, SwapError> {
+        offer_swap_exact_out(&e, &from, &token_out, &token_in, amount_out, max_in)
+    }
+
+    // Returns the best priced active offer selling `send_token` for `recv_token`.
+    pub fn best_offer(e: Env, 
+        send_token: Address, 
+        recv_token: Address
+    ) -> Option<u32> {
+        book_best(&e, &send_token, &recv_token)
+    }
+
+    // Top of the book: the best priced offer selling `send_token` for `recv_token` that can
+    // be filled now, its price with PRICE_DECIMALS decimals and the send_token it has left.
+    pub fn best_price(e: Env, 
+        send_token: Address, 
+        recv_token: Address
+    ) -> Result<(u32, u128, u64), SwapError> {
+        book_best_price(&e, &send_token, &recv_token)
+    }
+
+    // Returns the price levels of the pair's order book, best first.
+    pub fn book_levels(e: Env, 
+        send_token: Address, 
+        recv_token: Address, 
+        cursor: u32, 
+        limit: u32
+    ) -> Vec<BookLevel> {
+        book_levels(&e, &send_token, &recv_token, cursor, limit)
+    }
+
+    // Lets the offer be bought with `recv_token` too, at `recv_amount` for its current
+    // send_amount. 0 removes the token.
+    pub fn set_recv_alternative(e: Env, 
+        offeror: Address, 
+        offer_id: u32, 
+        recv_token: Address, 
+        recv_amount: u64
+    ) -> Result<(), SwapError> {
+        alt_set(&e, &offeror, offer_id, &recv_token, recv_amount)
+    }
+
+    pub fn get_recv_alternatives(e: Env, 
+        offer_id: u32
+    ) -> Vec<AltRecv> {
+        alt_get(&e, offer_id)
+    }
+
+    // Like accept_offer, paying `amount` of one of the offer's alternative recv tokens.
+    pub fn accept_offer_with(e: Env, 
+        acceptor: Address, 
+        offer_id: u32, 
+        recv_token: Address, 
+        amount: u64, 
+        min_send_out: u64
+    ) -> Result<u64, SwapError> {
+        alt_accept(&e, &acceptor, offer_id, &recv_token, amount, min_send_out)
+    }
+
+    // Fills an order the maker signed off-chain, see the `order` module.
+    pub fn fill_signed_order(e: Env, taker: Address, order: SignedOrder) -> Result<(), SwapError> {
+        order_fill(&e, &taker, &order)
+    }
+
+    // Whether the signed order of maker with `nonce` was filled or cancelled.
+    pub fn is_nonce_used(e: Env, maker: Address, nonce: u64) -> bool {
+        nonce_used(&e, &maker, nonce)
+    }
+
+    pub fn get_min_nonce(e: Env, maker: Address) -> u64 {
+        nonce_min_get(&e, &maker)
+    }
+
+    // Cancels the signed order of maker with `nonce`.
+    pub fn cancel_nonce(e: Env, maker: Address, nonce: u64) {
+        nonce_cancel(&e, &maker, nonce)
+    }
+
+    // Cancels every signed order of maker with a nonce below `nonce`.
+    pub fn cancel_all_before(e: Env, maker: Address, nonce: u64) {
+        nonce_cancel_before(&e, &maker, nonce)
+    }
+
+    // Swaps `amount_in` through the chain of offers in `path`, fees included. Returns the
+    // amount of the last offer's send_token received, which must be at least `min_out`.
+    pub fn accept_route(e: Env, 
+        acceptor: Address, 
+        path: Vec<u32>, 
+        amount_in: u64, 
+        min_out: u64
+    ) -> Result<u64, SwapError> {
+        offer_accept_route(&e, &acceptor, &path, amount_in, min_out)
+    }
+
+    // Settles `amount` of offer_a's recv_token against the opposing offer_b. Can be called by anyone.
+    pub fn match_offers(e: Env, 
+        offer_a: u32, 
+        offer_b: u32, 
+        amount: u64
+    ) -> Result<(u64, u64), SwapError> {
+        offer_match(&e, offer_a, offer_b, amount)
+    }
+
+    // Returns the send_token amount and the taker fee of accepting `amount` of the offer,
+    // without executing it.
+    pub fn quote_accept(e: Env, 
+        offer_id: u32, 
+        amount: u64, 
+        acceptor: Option<Address>
+    ) -> Result<(u64, u64), SwapError> {
+        let offer = offer_load_live(&e, offer_id)?;
+        offer_quote(&e, offer_id, &offer, amount, acceptor.as_ref())
+    }
+
+    // Reprices the offer. `caller` is the offeror or one of their managers.
+    pub fn update_offer(e: Env, 
+        caller: Address, 
+        offer_id: u32, 
+        recv_amount: u64, 
+        min_recv_amount: u64
+    ) -> Result<(), SwapError> {
+        offer_update(&e, &caller, offer_id, recv_amount, min_recv_amount)
+    }
+
+    // Lets manager perform `allowed_ops` (MANAGE_* flags) on the offer, or on all offers of
+    // offeror when `offer_id` is None. 0 revokes the grant.
+    pub fn set_manager(e: Env, 
+        offeror: Address, 
+        offer_id: Option<u32>, 
+        manager: Address, 
+        allowed_ops: u32
+    ) -> Result<(), SwapError> {
+        manager_set(&e, &offeror, offer_id, &manager, allowed_ops)
+    }
+
+    pub fn get_manager(e: Env, 
+        offeror: Address, 
+        offer_id: Option<u32>, 
+        manager: Address
+    ) -> u32 {
+        manager_get(&e, &offeror, offer_id, &manager)
+    }
+
+    // Adds `send_amount` (plus the maker fee on it) to the offer at its current price.
+    pub fn increase_offer(e: Env, 
+        offeror: Address, 
+        offer_id: u32, 
+        send_amount: u64
+    ) -> Result<(), SwapError> {
+        offer_increase(&e, &offeror, offer_id, send_amount)
+    }
+
+    // Withdraws `send_amount` (and the maker fee paid for it) from the offer at its current price.
+    pub fn decrease_offer(e: Env, 
+        offeror: Address, 
+        offer_id: u32, 
+        send_amount: u64
+    ) -> Result<(), SwapError> {
+        offer_decrease(&e, &offeror, offer_id, send_amount)
+    }
+
+    // Carves child offers with the given send amounts out of the offer at the same price.
+    pub fn split_offer(e: Env, 
+        offeror: Address, 
+        offer_id: u32, 
+        parts: Vec<u64>
+    ) -> Result<Vec<u32>, SwapError> {
+        offer_split(&e, &offeror, offer_id, &parts)
+    }
+
+    // Hands the offer, its escrow and its proceeds over to new_offeror.
+    pub fn transfer_offer(e: Env, 
+        offeror: Address, 
+        offer_id: u32, 
+        new_offeror: Address
+    ) -> Result<(), SwapError> {
+        offer_transfer(&e, &offeror, offer_id, &new_offeror)
+    }
+
+    // Restricts the offer to a whitelist of acceptors, adding `acceptor` to it.
+    pub fn add_acceptor(e: Env, 
+        offeror: Address, 
+        offer_id: u32, 
+        acceptor: Address
+    ) -> Result<(), SwapError> {
+        acceptor_add(&e, &offeror, offer_id, &acceptor)
+    }
+
+    pub fn remove_acceptor(e: Env, 
+        offeror: Address, 
+        offer_id: u32, 
+        acceptor: Address
+    ) -> Result<(), SwapError> {
+        acceptor_remove(&e, &offeror, offer_id, &acceptor)
+    }
+
+    // Drops the whitelist so that anyone can accept the offer again.
+    pub fn clear_acceptors(e: Env, 
+        offeror: Address, 
+        offer_id: u32
+    ) -> Result<(), SwapError> {
+        acceptors_clear(&e, &offeror, offer_id)
+    }
+
+    pub fn get_acceptors(e: Env, 
+        offer_id: u32
+    ) -> Option<Vec<Address>> {
+        acceptors_get(&e, offer_id)
+    }
+
+    pub fn get_counterparty(e: Env, 
+        offer_id: u32
+    ) -> Option<Address> {
+        counterparty_get(&e, offer_id)
+    }
+
+    // Turns the offer into a dutch auction whose price drops from the current recv_amount
+    // to `floor_recv_amount` at `end_ledger`, every `step_ledgers` ledgers.
+    pub fn set_dutch_auction(e: Env, 
+        offeror: Address, 
+        offer_id: u32, 
+        floor_recv_amount: u64, 
+        end_ledger: u32, 
+        step_ledgers: u32
+    ) -> Result<(), SwapError> {
+        dutch_set(&e, &offeror, offer_id, floor_recv_amount, end_ledger, step_ledgers)
+    }
+
+    pub fn get_dutch_auction(e: Env, 
+        offer_id: u32
+    ) -> Option<DutchAuction> {
+        dutch_get(&e, offer_id)
+    }
+
+    // Pegs the price of the offer to the oracle price plus a spread, so its recv_amount
+    // follows the market until the offer is updated.
+    pub fn set_oracle_peg(e: Env, 
+        offeror: Address, 
+        offer_id: u32, 
+        peg: OraclePeg
+    ) -> Result<(), SwapError> {
+        peg_set(&e, &offeror, offer_id, &peg)
+    }
+
+    pub fn get_oracle_peg(e: Env, 
+        offer_id: u32
+    ) -> Option<OraclePeg> {
+        peg_get(&e, offer_id)
+    }
+
+    // Escrows the NFT `token_id` of the `nft` contract to be sold for `recv_amount` of recv_token.
+    pub fn create_nft_offer(e: Env, 
+        offeror: Address, 
+        nft: Address, 
+        token_id: u32, 
+        recv_token: Address, 
+        recv_amount: u64, 
+        expire_ledger: u32
+    ) -> Result<u32, SwapError> {
+        nft_create(&e, &offeror, &nft, token_id, &recv_token, recv_amount, expire_ledger)
+    }
+
+    pub fn accept_nft_offer(e: Env, 
+        acceptor: Address, 
+        offer_id: u32
+    ) -> Result<(), SwapError> {
+        nft_accept(&e, &acceptor, offer_id)
+    }
+
+    pub fn close_nft_offer(e: Env, 
+        offeror: Address, 
+        offer_id: u32
+    ) -> Result<(), SwapError> {
+        nft_close(&e, &offeror, offer_id)
+    }
+
+    pub fn expire_nft_offer(e: Env, 
+        offer_id: u32
+    ) -> Result<(), SwapError> {
+        nft_expire(&e, offer_id)
+    }
+
+    pub fn get_nft_offer(e: Env, 
+        offer_id: u32
+    ) -> Result<NftOffer, SwapError> {
+        nft_load(&e, offer_id)
+    }
+
+    // Escrows a basket of (send_token, send_amount) items to be sold together for
+    // `recv_amount` of recv_token.
+    pub fn create_basket(e: Env, 
+        offeror: Address, 
+        items: Vec<(Address, u64)>, 
+        recv_token: Address, 
+        recv_amount: u64, 
+        expire_ledger: u32
+    ) -> Result<u32, SwapError> {
+        basket_create(&e, &offeror, &items, &recv_token, recv_amount, expire_ledger)
+    }
+
+    // Buys the whole basket for its recv_amount plus the taker fee.
+    pub fn accept_basket(e: Env, 
+        acceptor: Address, 
+        basket_id: u32
+    ) -> Result<(), SwapError> {
+        basket_accept(&e, &acceptor, basket_id)
+    }
+
+    pub fn close_basket(e: Env, 
+        offeror: Address, 
+        basket_id: u32
+    ) -> Result<(), SwapError> {
+        basket_close(&e, &offeror, basket_id)
+    }
+
+    pub fn expire_basket(e: Env, 
+        basket_id: u32
+    ) -> Result<(), SwapError> {
+        basket_expire(&e, basket_id)
+    }
+
+    pub fn get_basket(e: Env, 
+        basket_id: u32
+    ) -> Result<BasketOffer, SwapError> {
+        basket_load(&e, basket_id)
+    }
+
+    // Vests the offer's future proceeds linearly over `duration_ledgers`, 0 pays them out
+    // on each fill.
+    pub fn set_vesting(e: Env, 
+        offeror: Address, 
+        offer_id: u32, 
+        duration_ledgers: u32
+    ) -> Result<(), SwapError> {
+        vesting_set(&e, &offeror, offer_id, duration_ledgers)
+    }
+
+    pub fn get_vesting(e: Env, 
+        offer_id: u32
+    ) -> u32 {
+        vesting_get(&e, offer_id)
+    }
+
+    pub fn get_stream(e: Env, 
+        offer_id: u32
+    ) -> Option<Stream> {
+        stream_get(&e, offer_id)
+    }
+
+    // Sends the vested proceeds of the offer to its offeror.
+    pub fn withdraw_vested(e: Env, 
+        offeror: Address, 
+        offer_id: u32
+    ) -> Result<u64, SwapError> {
+        stream_withdraw(&e, &offeror, offer_id)
+    }
+
+    // Escrows `budget` of send_token to buy recv_token with `amount` every `period_ledgers`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_dca(e: Env, 
+        owner: Address, 
+        send_token: Address, 
+        recv_token: Address, 
+        budget: u64, 
+        amount: u64, 
+        period_ledgers: u32, 
+        min_recv_amount: u64, 
+        bounty: u64
+    ) -> Result<u32, SwapError> {
+        dca_create(&e, &owner, &send_token, &recv_token, budget, amount, period_ledgers, min_recv_amount, bounty)
+    }
+
+    // Fills the due period of a plan from the offer, paying the bounty to keeper.
+    pub fn execute_dca(e: Env, 
+        keeper: Address, 
+        plan_id: u32, 
+        offer_id: u32
+    ) -> Result<u64, SwapError> {
+        dca_execute(&e, &keeper, plan_id, offer_id)
+    }
+
+    pub fn cancel_dca(e: Env, 
+        owner: Address, 
+        plan_id: u32
+    ) -> Result<u64, SwapError> {
+        dca_cancel(&e, &owner, plan_id)
+    }
+
+    pub fn get_dca(e: Env, 
+        plan_id: u32
+    ) -> Result<DcaPlan, SwapError> {
+        dca_load(&e, plan_id)
+    }
+
+    // Locks `amount` of send_token for acceptor behind `hashlock` until `timelock`.
+    pub fn lock_htlc(e: Env, 
+        offeror: Address, 
+        acceptor: Address, 
+        send_token: Address, 
+        amount: u64, 
+        hashlock: BytesN<32>, 
+        timelock: u32
+    ) -> Result<u32, SwapError> {
+        htlc_lock(&e, &offeror, &acceptor, &send_token, amount, &hashlock, timelock)
+    }
+
+    pub fn claim_htlc(e: Env, 
+        htlc_id: u32, 
+        preimage: Bytes
+    ) -> Result<(), SwapError> {
+        htlc_claim(&e, htlc_id, &preimage)
+    }
+
+    pub fn refund_htlc(e: Env, 
+        htlc_id: u32
+    ) -> Result<(), SwapError> {
+        htlc_refund(&e, htlc_id)
+    }
+
+    pub fn get_htlc(e: Env, 
+        htlc_id: u32
+    ) -> Result<HtlcInfo, SwapError> {
+        htlc_load(&e, htlc_id)
+    }
+
+    // Holds the fill of an offer with a designated counterparty in the contract for `ledgers`
+    // ledgers, during which either party can dispute it. 0 removes the window.
+    pub fn set_dispute_window(e: Env, 
+        offeror: Address, 
+        offer_id: u32, 
+        ledgers: u32
+    ) -> Result<(), SwapError> {
+        dispute_window_set(&e, &offeror, offer_id, ledgers)
+    }
+
+    pub fn get_dispute_window(e: Env, offer_id: u32) -> u32 {
+        dispute_window_get(&e, offer_id)
+    }
+
+    pub fn get_dispute_hold(e: Env, offer_id: u32) -> Option<DisputeHold> {
+        dispute_get(&e, offer_id)
+    }
+
+    pub fn raise_dispute(e: Env, 
+        party: Address, 
+        offer_id: u32
+    ) -> Result<(), SwapError> {
+        dispute_raise(&e, &party, offer_id)
+    }
+
+    // Pays out an undisputed held fill once its window has passed.
+    pub fn release_hold(e: Env, 
+        offer_id: u32
+    ) -> Result<(), SwapError> {
+        dispute_release(&e, offer_id)
+    }
+
+    // Completes the disputed swap, or with `settle` false returns each party its own leg.
+    pub fn resolve_dispute(e: Env, 
+        arbiter: Address, 
+        offer_id: u32, 
+        settle: bool
+    ) -> Result<(), SwapError> {
+        dispute_resolve(&e, &arbiter, offer_id, settle)
+    }
+
+    // Lets holders of `config.token` govern fee rates and the allowlist, None stops new proposals.
+    pub fn set_gov_config(e: Env, caller: Address, config: Option<GovConfig>) -> Result<(), SwapError> {
+        gov_config_set(&e, &caller, &config)
+    }
+
+    pub fn get_gov_config(e: Env) -> Option<GovConfig> {
+        gov_config_get(&e)
+    }
+
+    pub fn propose(e: Env, proposer: Address, action: GovAction) -> Result<u32, SwapError> {
+        gov_propose(&e, &proposer, &action)
+    }
+
+    // Votes on the proposal with `amount` governance tokens, which stay locked until voting ends.
+    pub fn vote(e: Env, voter: Address, proposal_id: u32, support: bool, amount: u64) -> Result<(), SwapError> {
+        gov_vote(&e, &voter, proposal_id, support, amount)
+    }
+
+    pub fn withdraw_vote(e: Env, voter: Address, proposal_id: u32) -> Result<u64, SwapError> {
+        gov_withdraw(&e, &voter, proposal_id)
+    }
+
+    pub fn execute_proposal(e: Env, proposal_id: u32) -> Result<(), SwapError> {
+        gov_execute(&e, proposal_id)
+    }
+
+    pub fn get_proposal(e: Env, proposal_id: u32) -> Result<Proposal, SwapError> {
+        proposal_load(&e, proposal_id)
+    }
+
+    // Opens a two-sided escrow of `amount_a` of token_a from party_a for `amount_b` of
+    // token_b from party_b, which lapses at `expire_ledger`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn open_deal(e: Env, 
+        party_a: Address, 
+        party_b: Address, 
+        token_a: Address, 
+        amount_a: u64, 
+        token_b: Address, 
+        amount_b: u64, 
+        expire_ledger: u32
+    ) -> Result<u32, SwapError> {
+        deal_open(&e, &party_a, &party_b, &token_a, amount_a, &token_b, amount_b, expire_ledger)
+    }
+
+    pub fn deposit_deal(e: Env, 
+        party: Address, 
+        deal_id: u32
+    ) -> Result<(), SwapError> {
+        deal_deposit(&e, &party, deal_id)
+    }
+
+    // Settles the deal once both parties have deposited and confirmed.
+    pub fn confirm_deal(e: Env, 
+        party: Address, 
+        deal_id: u32
+    ) -> Result<(), SwapError> {
+        deal_confirm(&e, &party, deal_id)
+    }
+
+    pub fn reclaim_deal(e: Env, 
+        party: Address, 
+        deal_id: u32
+    ) -> Result<(), SwapError> {
+        deal_reclaim(&e, &party, deal_id)
+    }
+
+    pub fn get_deal(e: Env, 
+        deal_id: u32
+    ) -> Result<DealInfo, SwapError> {
+        deal_load(&e, deal_id)
+    }
+
+    // Commits to accepting the active offer for the amount hidden in `hash`, escrowing its
+    // min_recv_amount as a bond. Locks its price until the commitment lapses, unless it
+    // is locked already or has been locked for MAX_COMMIT_LOCK ledgers.
+    pub fn commit_accept(e: Env, 
+        acceptor: Address, 
+        offer_id: u32, 
+        hash: BytesN<32>
+    ) -> Result<(), SwapError> {
+        commit_accept(&e, &acceptor, offer_id, &hash)
+    }
+
+    // Performs a committed acceptance and returns the bond. Returns the amount of
+    // send_token received.
+    pub fn reveal_accept(e: Env, 
+        acceptor: Address, 
+        offer_id: u32, 
+        amount: u64, 
+        salt: BytesN<32>
+    ) -> Result<u64, SwapError> {
+        commit_reveal(&e, &acceptor, offer_id, amount, &salt)
+    }
+
+    pub fn get_commitment(e: Env, 
+        offer_id: u32, 
+        acceptor: Address
+    ) -> Option<Commitment> {
+        commit_get(&e, offer_id, &acceptor)
+    }
+
+    pub fn get_offer_lock(e: Env, 
+        offer_id: u32
+    ) -> u32 {
+        commit_lock_get(&e, offer_id)
+    }
+
+    // Asks makers to quote a price for `send_amount` of send_token.
+    pub fn request_quote(e: Env, 
+        taker: Address, 
+        send_token: Address, 
+        recv_token: Address, 
+        send_amount: u64, 
+        expire_ledger: u32
+    ) -> Result<u32, SwapError> {
+        rfq_request(&e, &taker, &send_token, &recv_token, send_amount, expire_ledger)
+    }
+
+    pub fn cancel_request(e: Env, 
+        taker: Address, 
+        request_id: u32
+    ) -> Result<(), SwapError> {
+        rfq_cancel(&e, &taker, request_id)
+    }
+
+    // Escrows a firm quote of `recv_amount` recv_token valid until `expire_ledger`.
+    pub fn submit_quote(e: Env, 
+        maker: Address, 
+        request_id: u32, 
+        recv_amount: u64, 
+        expire_ledger: u32
+    ) -> Result<u32, SwapError> {
+        rfq_quote(&e, &maker, request_id, recv_amount, expire_ledger)
+    }
+
+    pub fn withdraw_quote(e: Env, 
+        maker: Address, 
+        quote_id: u32
+    ) -> Result<u64, SwapError> {
+        rfq_withdraw(&e, &maker, quote_id)
+    }
+
+    pub fn accept_quote(e: Env, 
+        taker: Address, 
+        quote_id: u32
+    ) -> Result<(), SwapError> {
+        rfq_accept(&e, &taker, quote_id)
+    }
+
+    pub fn get_request(e: Env, 
+        request_id: u32
+    ) -> Result<QuoteRequest, SwapError> {
+        request_load(&e, request_id)
+    }
+
+    pub fn get_quote(e: Env, 
+        quote_id: u32
+    ) -> Result<Quote, SwapError> {
+        quote_load(&e, quote_id)
+    }
+
+    // Auctions the whole offer to the highest bid placed before `end_ledger`.
+    pub fn start_auction(e: Env, 
+        offeror: Address, 
+        offer_id: u32, 
+        end_ledger: u32
+    ) -> Result<(), SwapError> {
+        auction_start(&e, &offeror, offer_id, end_ledger)
+    }
+
+    // Escrows a bid of `amount` recv_token plus the taker fee.
+    pub fn place_bid(e: Env, 
+        bidder: Address, 
+        offer_id: u32, 
+        amount: u64
+    ) -> Result<(), SwapError> {
+        auction_bid(&e, &bidder, offer_id, amount)
+    }
+
+    // Returns the escrow of an outbid bidder.
+    pub fn refund_bid(e: Env, 
+        bidder: Address, 
+        offer_id: u32
+    ) -> Result<u64, SwapError> {
+        auction_refund(&e, &bidder, offer_id)
+    }
+
+    pub fn settle_auction(e: Env, 
+        offer_id: u32
+    ) -> Result<(), SwapError> {
+        auction_settle(&e, offer_id)
+    }
+
+    pub fn get_auction(e: Env, 
+        offer_id: u32
+    ) -> Option<u32> {
+        auction_get(&e, offer_id)
+    }
+
+    pub fn get_best_bid(e: Env, 
+        offer_id: u32
+    ) -> Option<Bid> {
+        auction_best_bid(&e, offer_id)
+    }
+
+    pub fn get_bid_escrow(e: Env, 
+        offer_id: u32, 
+        bidder: Address
+    ) -> u64 {
+        auction_escrow(&e, offer_id, &bidder)
+    }
+
+    // Emergency close by the admin, refunding the rest of the offer to the offeror.
+    pub fn admin_close_offer(e: Env, 
+        caller: Address,
+        offer_id: u32,
+        reason: Symbol
+    ) -> Result<(), SwapError> {
+        offer_admin_close(&e, &caller, offer_id, &reason)
+    }
+
+    // Refunds the rest of the offer to the offeror. `caller` is the offeror or one of their managers.
+    pub fn close_offer(e: Env, 
+        caller: Address,
+        offer_id: u32
+    ) -> Result<(), SwapError> {
+        offer_close(&e, &caller, offer_id)
+    }
+
+    // Stops fills of the offer without refunding its escrow, until it's resumed.
+    pub fn pause_offer(e: Env, 
+        offeror: Address, 
+        offer_id: u32
+    ) -> Result<(), SwapError> {
+        offer_pause(&e, &offeror, offer_id)
+    }
+
+    pub fn resume_offer(e: Env, 
+        offeror: Address, 
+        offer_id: u32
+    ) -> Result<(), SwapError> {
+        offer_resume(&e, &offeror, offer_id)
+    }
+
+    pub fn expire_offer(e: Env, 
+        offer_id: u32
+    ) -> Result<(), SwapError> {
+        offer_expire(&e, offer_id)
+    }
+
+    // Funds a closed, expired or completed offer again with `send_amount` at its last price.
+    pub fn reopen_offer(e: Env, 
+        offeror: Address, 
+        offer_id: u32, 
+        send_amount: u64, 
+        expire_ledger: u32
+    ) -> Result<(), SwapError> {
+        offer_reopen(&e, &offeror, offer_id, send_amount, expire_ledger)
+    }
+
+    // Keeps the offer's storage entry alive for at least `ledgers` more ledgers.
+    pub fn extend_offer_ttl(e: Env, 
+        offer_id: u32, 
+        ledgers: u32
+    ) -> Result<(), SwapError> {
+        offer_extend_ttl(&e, offer_id, ledgers)
+    }
+
+    // Extends the offer and its index entries again after a RestoreFootprint brought them
+    // back from the archive.
+    pub fn restore_offer(e: Env, offer_id: u32) -> Result<(), SwapError> {
+        offer_restore(&e, offer_id)
+    }
+
+    // How far the TTL of offer entries is extended whenever they're used, None restores
+    // the default. Requires the Operator role.
+    pub fn set_ttl_config(e: Env, caller: Address, config: Option<TtlConfig>) -> Result<(), SwapError> {
+        ttl_set(&e, &caller, &config)
+    }
+
+    pub fn get_ttl_config(e: Env) -> TtlConfig {
+        ttl_get(&e)
+    }
+
+    // Deletes inactive offers from storage. Requires the Operator role.
+    pub fn prune_offers(e: Env, 
+        caller: Address, 
+        offer_ids: Vec<u32>
+    ) -> Result<u32, SwapError> {
+        offer_prune(&e, &caller, &offer_ids)
+    }
+
+    // Deletes inactive offers with ids in [cursor, cursor + limit) that expired before `ledger`.
+    pub fn purge_before(e: Env, 
+        caller: Address, 
+        ledger: u32, 
+        cursor: u32, 
+        limit: u32
+    ) -> Result<u32, SwapError> {
+        offer_purge_before(&e, &caller, ledger, cursor, limit)
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn load_offer(e: Env, 
+        offer_id: u32
+    ) -> Result<(Address, Address, Address, u64, u64, u64, u32), SwapError> {
+        let offer_info = offer_load_live(&e, offer_id)?;
+        Ok((offer_info.offeror, 
+            offer_info.send_token, offer_info.recv_token, 
+            offer_info.send_amount, offer_info.recv_amount, offer_info.min_recv_amount, 
+            offer_info.status as u32
+        ))
+    }
+
+    pub fn get_offer(e: Env, 
+        offer_id: u32
+    ) -> Result<OfferInfo, SwapError> {
+        offer_load_live(&e, offer_id)
+    }
+
+    pub fn list_offers(e: Env, 
+        cursor: u32, 
+        limit: u32
+    ) -> Vec<u32> {
+        offer_list(&e, cursor, limit)
+    }
+
+    pub fn offers_of(e: Env, 
+        offeror: Address, 
+        cursor: u32, 
+        limit: u32
+    ) -> Vec<u32> {
+        offer_list_by_offeror(&e, &offeror, cursor, limit)
+    }
+
+    pub fn offers_for_pair(e: Env, 
+        send_token: Address, 
+        recv_token: Address, 
+        cursor: u32, 
+        limit: u32
+    ) -> Vec<u32> {
+        offer_list_by_pair(&e, &send_token, &recv_token, cursor, limit)
+    }
+
+    // Units of send_token set aside for the offeror from rounding on partial fills.
+    pub fn get_offer_dust(e: Env, offer_id: u32) -> u64 {
+        offer_dust(&e, offer_id)
+    }
+
+    // Sends the rounding dust of partial fills to the offeror, the fees or leaves it in
+    // the offer. Offeror by default.
+    pub fn set_rounding_policy(e: Env, caller: Address, policy: RoundingPolicy) -> Result<(), SwapError> {
+        rounding_set(&e, &caller, policy)
+    }
+
+    pub fn get_rounding_policy(e: Env) -> RoundingPolicy {
+        rounding_get(&e)
+    }
+
+    // Memo attached to the offer at creation, empty if none.
+    pub fn get_offer_memo(e: Env, offer_id: u32) -> Bytes {
+        offer_memo(&e, offer_id)
+    }
+
+    pub fn get_fills(e: Env, 
+        offer_id: u32, 
+        cursor: u32, 
+        limit: u32
+    ) -> Vec<FillInfo> {
+        fill_list(&e, offer_id, cursor, limit)
+    }
+
+    // Volume settled and number of fills on the pair, where send_token is the token sold by makers.
+    pub fn get_pair_stats(e: Env, 
+        send_token: Address, 
+        recv_token: Address
+    ) -> PairStats {
+        stats_pair_get(&e, &send_token, &recv_token)
+    }
+
+    // Number of fills and fees collected per token over the whole protocol.
+    pub fn get_global_stats(e: Env) -> GlobalStats {
+        stats_global_get(&e)
     }
 
     pub fn check_balances(e: Env, 
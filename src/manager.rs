@@ -0,0 +1,89 @@
+use soroban_sdk::{ Address, Env, Vec };
+
+use crate::storage_types::{ BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, MANAGE_ALL, 
+    DataKey, OfferDataKey
+};
+use crate::offer::{ offer_load };
+use crate::error::SwapError;
+
+
+// Operations `manager` may perform on the offer, or on all offers of offeror without one.
+pub fn manager_get(e: &Env, offeror: &Address, offer_id: Option<u32>, manager: &Address) -> u32 {
+    let key = manager_key(offeror, offer_id, manager);
+
+    e.storage().persistent().get(&key).unwrap_or(0)
+}
+
+// Grants `allowed_ops` (MANAGE_* flags) to manager, 0 revokes the grant. Must be
+// authorized by offeror.
+pub fn manager_set(e: &Env, offeror: &Address, offer_id: Option<u32>, manager: &Address, allowed_ops: u32) -> Result<(), SwapError> {
+    if allowed_ops & !MANAGE_ALL != 0 {
+        return Err(SwapError::InvalidOps);
+    }
+    if let Some(offer_id) = offer_id {
+        if offer_load(e, offer_id)?.offeror != offeror.clone() {
+            return Err(SwapError::InvalidOfferor);
+        }
+    }
+
+    offeror.require_auth();
+
+    let key = manager_key(offeror, offer_id, manager);
+    if allowed_ops == 0 {
+        e.storage().persistent().remove(&key);
+    } else {
+        e.storage().persistent().set(&key, &allowed_ops);
+        e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    }
+    if let Some(offer_id) = offer_id {
+        managers_index(e, offer_id, offeror, manager, allowed_ops != 0);
+    }
+    Ok(())
+}
+
+// Removes every grant made for the offer alone.
+pub fn managers_remove(e: &Env, offer_id: u32) {
+    let key = OfferDataKey::Managers(offer_id);
+    let grants: Vec<(Address, Address)> = e.storage().persistent().get(&key).unwrap_or(Vec::new(e));
+    for (offeror, manager) in grants.iter() {
+        e.storage().persistent().remove(&DataKey::OfferManager(offer_id, offeror, manager));
+    }
+    e.storage().persistent().remove(&key);
+}
+
+// Keeps track of the grants made for the offer, so they can be removed with it.
+fn managers_index(e: &Env, offer_id: u32, offeror: &Address, manager: &Address, granted: bool) {
+    let key = OfferDataKey::Managers(offer_id);
+    let mut grants: Vec<(Address, Address)> = e.storage().persistent().get(&key).unwrap_or(Vec::new(e));
+    let grant = (offeror.clone(), manager.clone());
+    match (grants.first_index_of(&grant), granted) {
+        (None, true) => grants.push_back(grant),
+        (Some(i), false) => {
+            grants.remove(i);
+        },
+        _ => return,
+    }
+    if grants.is_empty() {
+        e.storage().persistent().remove(&key);
+    } else {
+        e.storage().persistent().set(&key, &grants);
+        e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    }
+}
+
+// Whether caller may perform `op` on the offer of offeror, either as the offeror
+// or as a manager granted it for this offer or all of them.
+pub fn manager_check(e: &Env, offeror: &Address, offer_id: u32, caller: &Address, op: u32) -> bool {
+    if caller == offeror {
+        return true;
+    }
+
+    (manager_get(e, offeror, Some(offer_id), caller) | manager_get(e, offeror, None, caller)) & op != 0
+}
+
+fn manager_key(offeror: &Address, offer_id: Option<u32>, manager: &Address) -> DataKey {
+    match offer_id {
+        Some(offer_id) => DataKey::OfferManager(offer_id, offeror.clone(), manager.clone()),
+        None => DataKey::Manager(offeror.clone(), manager.clone()),
+    }
+}
@@ -0,0 +1,182 @@
+const NFT: Symbol = symbol_short!("NFT");
+
+use soroban_sdk::{ contractclient, token, Address, Env, symbol_short, Symbol };
+
+use crate::storage_types::{ INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, 
+    DataKey, NftOffer, OfferStatus
+};
+use crate::allow::{ allow_pair_get };
+use crate::fee::{ fee_check, fee_get, fee_accrue, calculate_fee, volume_add };
+use crate::stats::{ stats_record };
+use crate::pause::{ pause_check };
+use crate::escrow::{ escrow_in };
+use crate::error::SwapError;
+
+
+/*
+NFT offers sell a single non-fungible token for `recv_amount` of a fungible `recv_token`:
+
+1. Offeror calls `create` to escrow the NFT `token_id` of the `nft` contract, which must be
+   allowed like any token.
+2. Acceptors call `accept` to buy it for recv_amount plus the taker fee. As the NFT can't
+   carry a fee, the maker fee is taken from the offeror's proceeds.
+3. Offeror may call `close` to get the NFT back, and once `expire_ledger` is reached
+   anyone may call `expire` to do the same.
+*/
+
+// The part of the NFT interface used by the contract.
+#[allow(dead_code)]
+#[contractclient(name = "NftClient")]
+pub trait NftInterface {
+    fn owner_of(env: Env, token_id: u32) -> Address;
+    fn transfer(env: Env, from: Address, to: Address, token_id: u32);
+}
+
+pub fn nft_create(
+    e: &Env,
+    offeror: &Address,
+    nft: &Address,
+    token_id: u32,
+    recv_token: &Address,
+    recv_amount: u64,
+    expire_ledger: u32,
+) -> Result<u32, SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+    if !fee_check(e) {
+        return Err(SwapError::FeeNotSet);
+    }
+    if !allow_pair_get(e, nft, recv_token) {
+        return Err(SwapError::TokenNotAllowed);
+    }
+    if recv_amount == 0 {
+        return Err(SwapError::ZeroAmount);
+    }
+    if expire_ledger <= e.ledger().sequence() {
+        return Err(SwapError::InvalidExpiration);
+    }
+
+    offeror.require_auth();
+
+    let nft_client = NftClient::new(e, nft);
+    if nft_client.owner_of(&token_id) != offeror.clone() {
+        return Err(SwapError::InsufficientBalance);
+    }
+    nft_client.transfer(offeror, &e.current_contract_address(), &token_id);
+
+    let offer_id: u32 = e.storage().instance().get(&DataKey::NftCount).unwrap_or(0);
+    nft_write(e, offer_id, &NftOffer {
+        offeror: offeror.clone(),
+        nft: nft.clone(),
+        token_id,
+        recv_token: recv_token.clone(),
+        recv_amount,
+        expire_ledger,
+        status: OfferStatus::ACTIVE,
+    });
+    e.storage().instance().set(&DataKey::NftCount, &(offer_id + 1));
+    e.storage().instance().extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+
+    // emit NftOfferCreated event
+    e.events().publish((NFT, symbol_short!("NCreate")), 
+        (offer_id, offeror.clone(), nft.clone(), token_id, recv_token.clone(), recv_amount, expire_ledger)
+    );
+
+    Ok(offer_id)
+}
+
+pub fn nft_accept(e: &Env, acceptor: &Address, offer_id: u32) -> Result<(), SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+
+    let mut offer = nft_load(e, offer_id)?;
+    if offer.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+    if e.ledger().sequence() >= offer.expire_ledger {
+        return Err(SwapError::OfferExpired);
+    }
+
+    acceptor.require_auth();
+
+    let fee_info = fee_get(e)?;
+    let taker_fee = calculate_fee(e, &offer.recv_token, fee_info.taker_fee_rate, offer.recv_amount, Some(acceptor), true);
+    let maker_fee = calculate_fee(e, &offer.recv_token, fee_info.maker_fee_rate, offer.recv_amount, Some(&offer.offeror), false);
+    let recv_token_client = token::Client::new(e, &offer.recv_token);
+    if recv_token_client.balance(acceptor) < ((offer.recv_amount + taker_fee) as i128) {
+        return Err(SwapError::InsufficientBalance);
+    }
+
+    let contract = e.current_contract_address();
+    let fee_received = escrow_in(e, &offer.recv_token, acceptor, taker_fee + maker_fee);
+    fee_accrue(e, &offer.recv_token, fee_received);
+    recv_token_client.transfer(acceptor, &offer.offeror, &((offer.recv_amount - maker_fee) as i128));
+    NftClient::new(e, &offer.nft).transfer(&contract, acceptor, &offer.token_id);
+
+    offer.status = OfferStatus::COMPLETE;
+    nft_write(e, offer_id, &offer);
+    volume_add(e, acceptor, &offer.recv_token, offer.recv_amount);
+    stats_record(e, &offer.nft, &offer.recv_token, 1, offer.recv_amount);
+
+    // emit NftOfferAccepted event
+    e.events().publish((NFT, symbol_short!("NAccept")), 
+        (acceptor.clone(), offer_id)
+    );
+
+    Ok(())
+}
+
+pub fn nft_close(e: &Env, offeror: &Address, offer_id: u32) -> Result<(), SwapError> {
+    let offer = nft_load(e, offer_id)?;
+    if offer.offeror != offeror.clone() {
+        return Err(SwapError::InvalidOfferor);
+    }
+    if offer.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+
+    offeror.require_auth();
+
+    nft_refund(e, offer_id, offer, OfferStatus::CANCEL);
+    Ok(())
+}
+
+// Can be called by anyone once the offer's expire_ledger is reached.
+pub fn nft_expire(e: &Env, offer_id: u32) -> Result<(), SwapError> {
+    let offer = nft_load(e, offer_id)?;
+    if offer.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+    if e.ledger().sequence() < offer.expire_ledger {
+        return Err(SwapError::OfferNotExpired);
+    }
+
+    nft_refund(e, offer_id, offer, OfferStatus::EXPIRED);
+    Ok(())
+}
+
+pub fn nft_load(e: &Env, offer_id: u32) -> Result<NftOffer, SwapError> {
+    let key = DataKey::Nft(offer_id);
+
+    e.storage().persistent().get(&key).ok_or(SwapError::OfferNotFound)
+}
+
+fn nft_refund(e: &Env, offer_id: u32, mut offer: NftOffer, status: OfferStatus) {
+    NftClient::new(e, &offer.nft).transfer(&e.current_contract_address(), &offer.offeror, &offer.token_id);
+
+    offer.status = status;
+    nft_write(e, offer_id, &offer);
+
+    // emit NftOfferRefunded event
+    e.events().publish((NFT, symbol_short!("NRefund")), 
+        (offer.offeror.clone(), offer_id, status)
+    );
+}
+
+fn nft_write(e: &Env, offer_id: u32, offer: &NftOffer) {
+    let key = DataKey::Nft(offer_id);
+    e.storage().persistent().set(&key, offer);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
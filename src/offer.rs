@@ -1,14 +1,38 @@
 const OFFER: Symbol = symbol_short!("OFFER");
 
 use soroban_sdk::{
-    log, token, unwrap::UnwrapOptimized, Address, Env, symbol_short, /* BytesN, */ Symbol, 
+    log, token, Address, Bytes, Env, symbol_short, /* BytesN, */ Symbol, Val, Vec, 
     /* xdr::{ToXdr} */
 };
-use crate::storage_types::{ INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT, BALANCE_BUMP_AMOUNT, 
-    OfferStatus, OfferInfo, DataKey
+use crate::storage_types::{ INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, 
+    MANAGE_CLOSE, MANAGE_UPDATE, OfferMode, OfferSpec, OfferStatus, OfferInfo, RoundingPolicy, ConfigKey, DataKey, OfferDataKey, Role, DUST_SCALE, EVENT_VERSION, MAX_MEMO_LEN, 
+    OfferAccepted, OfferClosed, OfferCompleted, OfferCreated, OfferUpdated, StoredOffer
 };
-use crate::fee::{ fee_check, fee_get, calculate_fee };
-use crate::allow::{ allow_get };
+use crate::dutch::{ dutch_apply, dutch_get, dutch_remove };
+use crate::callback::{ callback_notify };
+use crate::compliance::{ ban_check, compliance_check };
+use crate::fee::{ fee_check, fee_get, fee_accrue, fee_in, fee_token_get, calculate_fee, volume_add };
+use crate::acceptor::{ acceptor_check, acceptors_get, acceptors_write, counterparty_get, counterparty_set };
+use crate::alt::{ alt_get, alt_write };
+use crate::allow::{ allow_pair_get, minimums_get };
+use crate::auction::{ auction_bidders, auction_cancel, auction_get };
+use crate::commit::{ commit_locked, commits_remove };
+use crate::dispute::{ dispute_get, dispute_hold, dispute_window_get };
+use crate::fill::{ fill_record, fills_remove };
+use crate::reward::{ reward_accrue };
+use crate::throttle::{ throttle_create, throttle_accept };
+use crate::stats::{ stats_record };
+use crate::oracle::{ band_check, peg_apply, peg_get, peg_remove };
+use crate::orderbook::{ book_get, book_insert, book_remove };
+use crate::pause::{ pause_check };
+use crate::referral::{ referral_accrue };
+use crate::manager::{ manager_check, managers_remove };
+use crate::role::{ role_check };
+use crate::ttl::{ ttl_bump };
+use crate::upgrade::{ offer_upcast };
+use crate::stream::{ proceeds_pay, stream_get, stream_transfer, vesting_get, vesting_write };
+use crate::escrow::{ escrow_in, escrow_lock, escrow_out, escrow_unlock };
+use crate::error::SwapError;
 
 
 /*
@@ -21,30 +45,71 @@ How this contract should be used:
    immediately perform the swap and send the respective amounts of `recv_token`
    and `send_token` to the offeror and acceptor respectively.
 4. Offeror may call `close` to claim any remaining `send_token` balance.
+5. Once `expire_ledger` is reached the offer can't be accepted anymore and
+   anyone may call `expire` to return the remaining `send_token` to the offeror.
+
+Tokens only move through `transfer` authorized by their owner, so no allowance
+is needed and the native asset can be traded through its Stellar Asset Contract.
+Balances aren't checked up front either: a short balance fails the transfer, and
+with it the whole call.
+
+The maker fee is escrowed along with `send_amount` and earned pro-rata as the
+offer gets filled. The part paid for unfilled size goes back on close/expire.
+When a fee token is set, fees are paid in it instead: the maker fee is earned on
+creation and the taker fee on every fill.
 */
 
-pub fn error(
+pub fn offer_count(
     e: &Env
 ) -> u32 {
-    if !e.storage().instance().has(&DataKey::ErrorCode) {
-        return 1000;
+    let offer_count: u32 = e.storage().instance().get(&DataKey::OfferCount).unwrap_or(0);
+    offer_count
+}
+
+// Number of active offers of offeror, as kept by the offeror's index.
+pub fn offer_open_count(e: &Env, offeror: &Address) -> u32 {
+    let ids: Vec<u32> = e.storage().persistent().get(&DataKey::OfferorOffers(offeror.clone())).unwrap_or(Vec::new(e));
+    ids.len()
+}
+
+pub fn offer_limit_get(e: &Env) -> Option<u32> {
+    e.storage().instance().get(&ConfigKey::MaxOpenOffers)
+}
+
+// Caps the active offers of any single offeror, None removes the cap.
+pub fn offer_limit_set(e: &Env, caller: &Address, limit: &Option<u32>) -> Result<(), SwapError> {
+    role_check(e, Role::Admin, caller)?;
+    match limit {
+        Some(limit) => e.storage().instance().set(&ConfigKey::MaxOpenOffers, limit),
+        None => e.storage().instance().remove(&ConfigKey::MaxOpenOffers),
     }
+    Ok(())
+}
 
-    let err_code: u32 = e.storage().instance().get(&DataKey::ErrorCode).unwrap_or(0);
-    err_code
+pub fn rounding_get(e: &Env) -> RoundingPolicy {
+    e.storage().instance().get(&ConfigKey::Rounding).unwrap_or(RoundingPolicy::Offeror)
+}
 
-    // 1001
+// Sets where the rounding dust of later fills goes.
+pub fn rounding_set(e: &Env, caller: &Address, policy: RoundingPolicy) -> Result<(), SwapError> {
+    role_check(e, Role::Admin, caller)?;
+    e.storage().instance().set(&ConfigKey::Rounding, &policy);
+    Ok(())
 }
 
-pub fn offer_count(
-    e: &Env
-) -> u32 {
-    let offer_count: u32 = e.storage().instance().get(&DataKey::OfferCount).unwrap_or(0);
-    offer_count
+// Checks that offeror can have `count` more active offers.
+fn offer_limit_check(e: &Env, offeror: &Address, count: u32) -> Result<(), SwapError> {
+    if let Some(limit) = offer_limit_get(e) {
+        if offer_open_count(e, offeror).saturating_add(count) > limit {
+            return Err(SwapError::TooManyOffers);
+        }
+    }
+    Ok(())
 }
 
 // Creates the offer for offeror for the given token pair and initial amounts.
 // See comment above the `Offer` struct for information on swap.
+#[allow(clippy::too_many_arguments)]
 pub fn offer_create(
     e: &Env,
     offeror: &Address,
@@ -54,261 +119,1368 @@ pub fn offer_create(
     send_amount: u64,
     recv_amount: u64,
     min_recv_amount: u64,
-) -> u32 {
+    expire_ledger: u32,
+    counterparty: &Option<Address>,
+    mode: OfferMode,
+    memo: &Bytes,
+) -> Result<u32, SwapError> {
+    // Authorize the `create` call by offeror to verify their identity.
+    offeror.require_auth();
+
+    offer_open(e, offeror, send_token, recv_token, timestamp, send_amount, recv_amount, min_recv_amount, expire_ledger, counterparty, mode, memo)
+}
+
+// Creates an offer for each spec with a single authorization by offeror.
+// Either all offers are created or none is.
+pub fn offer_create_many(
+    e: &Env,
+    offeror: &Address,
+    specs: &Vec<OfferSpec>,
+) -> Result<Vec<u32>, SwapError> {
+    offeror.require_auth();
+
+    let mut offer_ids: Vec<u32> = Vec::new(e);
+    for spec in specs.iter() {
+        offer_ids.push_back(offer_open(e, offeror, &spec.send_token, &spec.recv_token, spec.timestamp, 
+            spec.send_amount, spec.recv_amount, spec.min_recv_amount, spec.expire_ledger, &None, spec.mode, &Bytes::new(e))?);
+    }
+    Ok(offer_ids)
+}
+
+// Reference the offeror attached to the offer at creation, empty if none.
+pub fn offer_memo(e: &Env, offer_id: u32) -> Bytes {
+    e.storage().persistent().get(&OfferDataKey::Memo(offer_id)).unwrap_or(Bytes::new(e))
+}
+
+// Escrows the send_token of an offer authorized by offeror and registers it.
+#[allow(clippy::too_many_arguments)]
+fn offer_open(
+    e: &Env,
+    offeror: &Address,
+    send_token: &Address,
+    recv_token: &Address,
+    timestamp: u32,
+    send_amount: u64,
+    recv_amount: u64,
+    min_recv_amount: u64,
+    expire_ledger: u32,
+    counterparty: &Option<Address>,
+    mode: OfferMode,
+    memo: &Bytes,
+) -> Result<u32, SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
     if !fee_check(e) {
-        // panic!("fee wasn't set");
-        return 101;
+        return Err(SwapError::FeeNotSet);
     }
-    if !allow_get(e, &send_token.clone()) || !allow_get(e, &recv_token.clone()) {
-        // panic!("both tokens aren't allowed");
-        return 102;
+    if !allow_pair_get(e, send_token, recv_token) {
+        return Err(SwapError::TokenNotAllowed);
     }
 
-    let offer_count: u32 = e.storage().instance().get(&DataKey::OfferCount).unwrap_or(0);
-    let offer_id: u32 = offer_count;
-    log!(e, "offer_id = {}", offer_id);
-
     if send_amount == 0 || recv_amount == 0 {
-        // panic!("zero amount is not allowed");
-        return 104;
+        return Err(SwapError::ZeroAmount);
     }
     if min_recv_amount > recv_amount {
-        // panic!("min_recv_amount can't be greater than recv_amount");
-        return 105;
+        return Err(SwapError::InvalidMinRecvAmount);
     }
-    
-    // Authorize the `create` call by offeror to verify their identity.
-    offeror.require_auth();
+    if expire_ledger <= e.ledger().sequence() {
+        return Err(SwapError::InvalidExpiration);
+    }
+    if memo.len() > MAX_MEMO_LEN {
+        return Err(SwapError::MemoTooLong);
+    }
+    if let Some(minimums) = minimums_get(e, send_token, recv_token) {
+        if send_amount < minimums.min_send_amount {
+            return Err(SwapError::AmountTooSmall);
+        }
+    }
+    offer_limit_check(e, offeror, 1)?;
+    throttle_create(e, offeror)?;
+    ban_check(e, offeror)?;
+    compliance_check(e, offeror, send_token, send_amount)?;
+    compliance_check(e, offeror, recv_token, recv_amount)?;
 
-    let fee_info = fee_get(e);
-    let fee_amount: u64 = calculate_fee(e, &fee_info.clone(), send_amount);
-    let transfer_amount = send_amount + fee_amount;
-    
-    let contract = e.current_contract_address();
-    let send_token_client = token::Client::new(e, &send_token.clone());
+    band_check(e, send_token, recv_token, send_amount, recv_amount, false)?;
+
+    let fee_info = fee_get(e)?;
+    let fee_amount: u64 = calculate_fee(e, send_token, fee_info.maker_fee_rate, send_amount, Some(offeror), false);
+    let fee_amount = maker_fee_take(e, offeror, send_token, fee_amount)?;
 
-    if send_token_client.balance(&offeror) < (transfer_amount as i128) {
-        // panic!("insufficient balance");
-        return 106;
+    // The maker fee is held with the offer and only earned as the offer gets filled.
+    let (send_amount, recv_amount) = offer_escrow_in(e, offeror, send_token, send_amount, recv_amount, fee_amount)?;
+    let min_recv_amount = min_recv_amount.min(recv_amount);
+
+    let offer = OfferInfo {
+        offeror: offeror.clone(),
+        send_token: send_token.clone(),
+        recv_token: recv_token.clone(),
+        send_amount,
+        recv_amount,
+        min_recv_amount,
+        fee_amount,
+        expire_ledger,
+        mode,
+        status: OfferStatus::ACTIVE,
+    };
+    let offer_id = offer_insert(e, &offer);
+    if let Some(counterparty) = counterparty {
+        counterparty_set(e, offer_id, counterparty);
     }
-    if send_token_client.allowance(&offeror, &contract) < (transfer_amount as i128) {
-        // panic!(e, "insufficient creator's allowance");
-        send_token_client.approve(&offeror, &contract, &(transfer_amount as i128), &(e.ledger().sequence() + BALANCE_BUMP_AMOUNT));
-        // return 107;
+    if !memo.is_empty() {
+        let key = OfferDataKey::Memo(offer_id);
+        e.storage().persistent().set(&key, memo);
+        e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
     }
 
-    send_token_client.transfer(&offeror, &contract, &(send_amount as i128));
-    send_token_client.transfer(&offeror, &fee_info.fee_wallet, &(fee_amount as i128));
-
-    offer_write(
-        e,
-        offer_id,
-        &OfferInfo {
+    // emit OfferCreated event
+    e.events().publish((OFFER, symbol_short!("OCreate"), EVENT_VERSION), 
+        OfferCreated {
+            offer_id,
             offeror: offeror.clone(),
             send_token: send_token.clone(),
             recv_token: recv_token.clone(),
             send_amount,
             recv_amount,
             min_recv_amount,
-            status: OfferStatus::ACTIVE,
-        },
+            fee_amount,
+            expire_ledger,
+            timestamp,
+            counterparty: match counterparty {
+                Some(counterparty) => Vec::from_array(e, [counterparty.clone()]),
+                None => Vec::new(e),
+            },
+            memo: memo.clone(),
+        }
     );
-    let new_offer_count: u32 = offer_count + 1;
-    e.storage().instance().set(&DataKey::OfferCount, &new_offer_count);
-    e.storage().instance().extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
 
-    // emit OfferCreated event
-    e.events().publish((OFFER, symbol_short!("OCreate")), 
-        (offer_id, offeror.clone(), send_token.clone(), recv_token.clone(), send_amount, recv_amount, min_recv_amount, timestamp)
-    );
-
-    offer_id
+    Ok(offer_id)
 }
 
-// Swaps `amount` of recv_token from acceptor for `send_token` amount calculated by the amount.
+// Swaps `amount` of recv_token from acceptor for `send_token` amount calculated by the amount,
+// which must be at least `min_send_out`. Returns the amount of send_token received.
 // acceptor needs to authorize the `swap` call and internal `transfer` call to the contract address.
 pub fn offer_accept(e: &Env, 
     acceptor: &Address, 
     offer_id: u32,
-    amount: u64
-) -> u32 {
-    if !e.storage().instance().has(&DataKey::RegOffers(offer_id)) {
-        // panic!("can't find offer");
-        return 110;
-    }
+    amount: u64,
+    min_send_out: u64,
+    referrer: &Option<Address>
+) -> Result<u64, SwapError> {
+    // acceptor needs to authorize the trade.
+    acceptor.require_auth();
 
-    let mut offer = offer_load(e, offer_id);
+    offer_fill(e, acceptor, offer_id, amount, min_send_out, referrer)
+}
 
-    if !fee_check(e) {
-        // panic!("fee isn't set");
-        return 111;
-    }
-    if offer.status != OfferStatus::ACTIVE {
-        // panic!("offer not available");
-        return 112;
+// Accept exact out
+// Buys `send_amount` of the offer's send_token, paying the recv_token its price asks for
+// that amount rounded up, so the acceptor gets at least send_amount. The payment, taker fee
+// included unless it's paid in the fee token, must be at most `max_pay`. Returns the amounts paid and received.
+// Must be authorized by acceptor.
+pub fn offer_accept_exact_out(e: &Env, 
+    acceptor: &Address, 
+    offer_id: u32,
+    send_amount: u64,
+    max_pay: u64
+) -> Result<(u64, u64), SwapError> {
+    if send_amount == 0 {
+        return Err(SwapError::ZeroAmount);
     }
-    if offer.recv_amount < amount {
-        panic!("amount is greater than max_recv_amount");
-        // return 113;
+    let offer = offer_load_live(e, offer_id)?;
+    let amount = mul_div_ceil(send_amount, offer.recv_amount, offer.send_amount)?;
+    let (_, fee_amount) = offer_quote(e, offer_id, &offer, amount, Some(acceptor))?;
+    let pay_amount = amount.checked_add(fee_in_token(e, &offer.recv_token, fee_amount)).ok_or(SwapError::Overflow)?;
+    if pay_amount > max_pay {
+        return Err(SwapError::SlippageExceeded);
     }
-    if amount < offer.min_recv_amount {
-        panic!("amount must be more than min_recv_amount");
-        // return 114;
-    }
-    
-    // acceptor needs to authorize the trade.
+
     acceptor.require_auth();
 
-    // Load the offer and prepare the token clients to do the trade.
-    let send_token_client = token::Client::new(e, &offer.send_token);
-    let recv_token_client = token::Client::new(e, &offer.recv_token);
+    let send_amount = offer_fill(e, acceptor, offer_id, amount, send_amount, &None)?;
+    Ok((pay_amount, send_amount))
+}
 
-    let fee_info = fee_get(e);
-    let fee_amount: u64 = calculate_fee(e, &fee_info.clone(), amount);
-    let contract = e.current_contract_address();
-    
-    if recv_token_client.balance(&acceptor) < (amount + fee_amount) as i128 {
-        // panic!("insufficient balance");
-        return 115;
+// Accepts several offers at once. Either all fills succeed or none does.
+// Returns the total amounts of recv_token paid (without fees) and send_token received,
+// the latter must be at least `min_send_out`.
+pub fn offer_accept_many(e: &Env, 
+    acceptor: &Address, 
+    fills: &Vec<(u32, u64)>,
+    min_send_out: u64,
+    referrer: &Option<Address>
+) -> Result<(u64, u64), SwapError> {
+    // A single authorization covers the whole batch.
+    acceptor.require_auth();
+
+    let mut total_amount: u64 = 0;
+    let mut total_send_amount: u64 = 0;
+    for (offer_id, amount) in fills.iter() {
+        let send_amount = offer_fill(e, acceptor, offer_id, amount, 0, referrer)?;
+        total_send_amount = total_send_amount.checked_add(send_amount).ok_or(SwapError::Overflow)?;
+        total_amount = total_amount.checked_add(amount).ok_or(SwapError::Overflow)?;
     }
-    if recv_token_client.allowance(&acceptor, &contract.clone()) < (amount + fee_amount) as i128 {
-        // panic!("insufficient allowance");
-        recv_token_client.approve(&acceptor, &contract, &((amount + fee_amount) as i128), &(e.ledger().sequence() + BALANCE_BUMP_AMOUNT));
-        // return 116;
+
+    if total_send_amount < min_send_out {
+        return Err(SwapError::SlippageExceeded);
     }
+    Ok((total_amount, total_send_amount))
+}
 
-    // Compute the amount of send_token that acceptor can receive.
-    let prop_send_amount = amount.checked_mul(offer.send_amount).unwrap_optimized() / offer.recv_amount;
+// Performs a fill authorized by acceptor.
+pub fn offer_fill(e: &Env, 
+    acceptor: &Address, 
+    offer_id: u32,
+    amount: u64,
+    min_send_out: u64,
+    referrer: &Option<Address>
+) -> Result<u64, SwapError> {
+    escrow_lock(e)?;
+    let send_amount = offer_fill_locked(e, acceptor, offer_id, amount, min_send_out, referrer);
+    escrow_unlock(e);
+    let send_amount = send_amount?;
+    callback_notify(e, acceptor, offer_id, amount, send_amount);
+    Ok(send_amount)
+}
+
+fn offer_fill_locked(e: &Env, 
+    acceptor: &Address, 
+    offer_id: u32,
+    amount: u64,
+    min_send_out: u64,
+    referrer: &Option<Address>
+) -> Result<u64, SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+
+    throttle_accept(e, acceptor)?;
+
+    let mut offer = offer_load_live(e, offer_id)?;
+    let fill = fill_book(e, offer_id, &mut offer, acceptor, acceptor, amount, min_send_out)?;
 
     // Perform the trade in 3 `transfer` steps.
     // Note, that we don't need to verify any balances - the contract would
     // just trap and roll back in case if any of the transfers fails for
     // any reason, including insufficient balance.
+    fill_pay(e, &offer, acceptor, acceptor, &fill, referrer)?;
 
-    // Transfer the `recv_token` from acceptor to this contract.
-    // This `transfer` call should be authorized by acceptor.
-    // This could as well be a direct transfer to the offeror, but sending to
-    // the contract address allows building more transparent signature
-    // payload where the acceptor doesn't need to worry about sending token to
-    // some 'unknown' third party.
-    recv_token_client.transfer(&acceptor, &fee_info.fee_wallet, &(fee_amount as i128));
-    // Transfer the `recv_token` to the offeror immediately.
-    recv_token_client.transfer(&acceptor, &offer.offeror, &(amount as i128));
-    // Transfer the `send_token` from contract to acceptor.
-    send_token_client.transfer(&contract, &acceptor, &(prop_send_amount as i128));
-
-    // Update Offer
-    offer.send_amount -= prop_send_amount;
-    offer.recv_amount -= amount;
+    Ok(fill.send_amount)
+}
 
-    if offer.recv_amount == 0 {
-        offer.status = OfferStatus::COMPLETE;
-        // emit OfferCompleted event
-        e.events().publish((OFFER, symbol_short!("OComplete")), 
-            offer_id
-        );
-    }
-    else if offer.recv_amount < offer.min_recv_amount {
-        offer.min_recv_amount = offer.recv_amount;
+// A fill booked by `fill_book` and waiting for `fill_pay`.
+struct Fill {
+    offer_id: u32,
+    amount: u64,
+    send_amount: u64,
+    fee_token: Address,
+    fee_amount: u64,
+}
+
+// Validates a fill of `amount` of the offer by acceptor, paid by `payer`, and books it,
+// before any token is moved, so a token contract called during the transfers only ever
+// sees the offer as it is after the fill. The payer is the acceptor, or the contract when
+// the fill is paid out of an escrow, in which case the fee is paid in the traded token.
+fn fill_book(e: &Env, 
+    offer_id: u32, 
+    offer: &mut OfferInfo, 
+    acceptor: &Address, 
+    payer: &Address, 
+    amount: u64, 
+    min_send_out: u64
+) -> Result<Fill, SwapError> {
+    let (prop_send_amount, fee_amount) = offer_quote(e, offer_id, offer, amount, Some(acceptor))?;
+    let (fee_token, fee_amount) = if payer.clone() == e.current_contract_address() {
+        (offer.recv_token.clone(), fee_amount)
+    } else {
+        fee_in(e, &offer.recv_token, fee_amount)?
+    };
+    band_check(e, &offer.send_token, &offer.recv_token, offer.send_amount, offer.recv_amount, true)?;
+
+    // The offeror may have repriced the offer after the acceptor signed.
+    if prop_send_amount < min_send_out {
+        return Err(SwapError::SlippageExceeded);
     }
+    ban_check(e, acceptor)?;
+    compliance_check(e, acceptor, &offer.recv_token, amount)?;
+    compliance_check(e, acceptor, &offer.send_token, prop_send_amount)?;
 
-    offer_write(e, offer_id, &offer);
+    offer_settle(e, offer_id, offer, acceptor, amount, prop_send_amount)?;
+    volume_add(e, acceptor, &offer.recv_token, amount);
+
+    Ok(Fill {
+        offer_id,
+        amount,
+        send_amount: prop_send_amount,
+        fee_token,
+        fee_amount,
+    })
+}
+
+// Moves the tokens of a fill booked with `fill_book`: the taker fee and `amount` from payer,
+// and the send_token bought to payer.
+fn fill_pay(e: &Env, 
+    offer: &OfferInfo, 
+    acceptor: &Address, 
+    payer: &Address, 
+    fill: &Fill, 
+    referrer: &Option<Address>
+) -> Result<(), SwapError> {
+    let offer_id = fill.offer_id;
+
+    // Transfer the taker fee in `recv_token`, or the fee token, from payer to this contract.
+    // This `transfer` call should be authorized by payer. Only the part that arrives
+    // is earned.
+    let fee_received = escrow_in(e, &fill.fee_token, payer, fill.fee_amount);
+    let referral_amount = match referrer {
+        Some(referrer) => referral_accrue(e, referrer, &fill.fee_token, fee_received),
+        None => 0,
+    };
+    fee_accrue(e, &fill.fee_token, fee_received.checked_sub(referral_amount).ok_or(SwapError::Overflow)?);
+    if dispute_window_get(e, offer_id) > 0 {
+        // Both legs stay in the contract until the dispute window has passed.
+        dispute_hold(e, offer_id, offer, payer, fill.amount, fill.send_amount);
+    } else {
+        // Transfer the `recv_token` to the offeror immediately, or into its vesting stream.
+        proceeds_pay(e, offer_id, offer, payer, fill.amount);
+        // Transfer the `send_token` from contract to payer.
+        escrow_out(e, &offer.send_token, payer, fill.send_amount);
+    }
 
     // emit OfferAccepted event
-    e.events().publish((OFFER, symbol_short!("OAccept")), 
-        (acceptor.clone(), offer_id, amount)
+    e.events().publish((OFFER, symbol_short!("OAccept"), EVENT_VERSION), 
+        OfferAccepted {
+            offer_id,
+            acceptor: acceptor.clone(),
+            amount: fill.amount,
+            send_amount: fill.send_amount,
+            fee_amount: fill.fee_amount,
+            send_remaining: offer.send_amount,
+            recv_remaining: offer.recv_amount,
+        }
+    );
+
+    Ok(())
+}
+
+// The part of a taker fee of `fee_amount` of `token` that is paid in `token` itself. A fee
+// paid in the fee token is charged on top and doesn't come out of a budget in `token`.
+fn fee_in_token(e: &Env, token: &Address, fee_amount: u64) -> u64 {
+    match fee_token_get(e) {
+        Some(fee_token) if fee_token.token != token.clone() => 0,
+        _ => fee_amount,
+    }
+}
+
+// Take best offers
+// Buys up to `amount` of recv_token worth of send_token from the order book of the pair,
+// best price first. Offers the acceptor can't fill are skipped. Returns the amounts of
+// recv_token paid (without fees) and send_token received, the latter must be at least
+// `min_send_out`. Must be authorized by acceptor.
+pub fn offer_take_best(e: &Env, 
+    acceptor: &Address, 
+    send_token: &Address, 
+    recv_token: &Address, 
+    amount: u64, 
+    min_send_out: u64
+) -> Result<(u64, u64), SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+
+    acceptor.require_auth();
+
+    let mut remaining = amount;
+    let mut total_send_amount: u64 = 0;
+    for offer_id in book_get(e, send_token, recv_token).iter() {
+        if remaining == 0 {
+            break;
+        }
+        let offer = match offer_load_live(e, offer_id) {
+            Ok(offer) => offer,
+            Err(_) => continue,
+        };
+        let fill_amount = remaining.min(offer.recv_amount);
+        if offer_quote(e, offer_id, &offer, fill_amount, Some(acceptor)).is_err() {
+            continue;
+        }
+        let send_amount = offer_fill(e, acceptor, offer_id, fill_amount, 0, &None)?;
+        total_send_amount = total_send_amount.checked_add(send_amount).ok_or(SwapError::Overflow)?;
+        remaining -= fill_amount;
+    }
+
+    if total_send_amount < min_send_out {
+        return Err(SwapError::SlippageExceeded);
+    }
+    Ok((amount - remaining, total_send_amount))
+}
+
+// Market buy
+// Spends at most `amount_in` of recv_token, taker fees paid in recv_token included, on the
+// order book of the pair, best price first, sweeping as many price levels as it takes. Offers the acceptor
+// can't fill are skipped. Returns the amounts of recv_token spent and send_token received,
+// the latter must be at least `min_out`. Must be authorized by acceptor.
+pub fn offer_market_buy(e: &Env, 
+    acceptor: &Address, 
+    send_token: &Address, 
+    recv_token: &Address, 
+    amount_in: u64, 
+    min_out: u64
+) -> Result<(u64, u64), SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+
+    acceptor.require_auth();
+
+    let mut remaining = amount_in;
+    let mut amount_out: u64 = 0;
+    for offer_id in book_get(e, send_token, recv_token).iter() {
+        let offer = match offer_load_live(e, offer_id) {
+            Ok(offer) => offer,
+            Err(_) => continue,
+        };
+        let mut fill_amount = remaining.min(offer.recv_amount);
+        let fee_amount = match offer_quote(e, offer_id, &offer, fill_amount, Some(acceptor)) {
+            Ok((_, fee_amount)) => fee_in_token(e, recv_token, fee_amount),
+            Err(_) => continue,
+        };
+        if fill_amount.checked_add(fee_amount).ok_or(SwapError::Overflow)? > remaining {
+            fill_amount = remaining.saturating_sub(fee_amount);
+        }
+        let fee_amount = match offer_quote(e, offer_id, &offer, fill_amount, Some(acceptor)) {
+            Ok((_, fee_amount)) if fill_amount > 0 => fee_in_token(e, recv_token, fee_amount),
+            _ => continue,
+        };
+        let send_amount = offer_fill(e, acceptor, offer_id, fill_amount, 0, &None)?;
+        amount_out = amount_out.checked_add(send_amount).ok_or(SwapError::Overflow)?;
+        remaining -= fill_amount.checked_add(fee_amount).ok_or(SwapError::Overflow)?;
+        if remaining == 0 {
+            break;
+        }
+    }
+
+    if amount_out < min_out {
+        return Err(SwapError::SlippageExceeded);
+    }
+    Ok((amount_in - remaining, amount_out))
+}
+
+// Swap exact out
+// Buys at least `amount_out` of send_token from the order book of the pair, best price
+// first, paying each offer what its price asks for the part bought, rounded up. Offers the
+// acceptor can't fill are skipped. Returns the amount of recv_token spent, taker fees paid
+// in recv_token included, which must be at most `max_in`. Must be authorized by acceptor.
+pub fn offer_swap_exact_out(e: &Env, 
+    acceptor: &Address, 
+    send_token: &Address, 
+    recv_token: &Address, 
+    amount_out: u64, 
+    max_in: u64
+) -> Result<u64, SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+
+    acceptor.require_auth();
+
+    let mut remaining = amount_out;
+    let mut amount_in: u64 = 0;
+    for offer_id in book_get(e, send_token, recv_token).iter() {
+        let offer = match offer_load_live(e, offer_id) {
+            Ok(offer) => offer,
+            Err(_) => continue,
+        };
+        let want = remaining.min(offer.send_amount);
+        let fill_amount = mul_div_ceil(want, offer.recv_amount, offer.send_amount)?.min(offer.recv_amount);
+        let fee_amount = match offer_quote(e, offer_id, &offer, fill_amount, Some(acceptor)) {
+            Ok((_, fee_amount)) => fee_amount,
+            Err(_) => continue,
+        };
+        let pay_amount = fill_amount.checked_add(fee_in_token(e, recv_token, fee_amount)).ok_or(SwapError::Overflow)?;
+        let send_amount = offer_fill(e, acceptor, offer_id, fill_amount, 0, &None)?;
+        amount_in = amount_in.checked_add(pay_amount).ok_or(SwapError::Overflow)?;
+        remaining = remaining.saturating_sub(send_amount);
+        if remaining == 0 {
+            break;
+        }
+    }
+
+    if remaining > 0 || amount_in > max_in {
+        return Err(SwapError::SlippageExceeded);
+    }
+    Ok(amount_in)
+}
+
+// Accept route
+// Fills the offers of `path` one after the other, paying each with what the previous one
+// returned, so `amount_in` of the first offer's recv_token is swapped for the last offer's
+// send_token in one go. The taker fee of every hop comes out of the amount spent on it,
+// unless it's paid in the fee token.
+// Returns the amount of send_token received, which must be at least `min_out`.
+// Must be authorized by acceptor.
+pub fn offer_accept_route(e: &Env, 
+    acceptor: &Address, 
+    path: &Vec<u32>, 
+    amount_in: u64, 
+    min_out: u64
+) -> Result<u64, SwapError> {
+    if path.is_empty() {
+        return Err(SwapError::InvalidRoute);
+    }
+
+    // Each offer must buy what the previous one sells.
+    let mut token: Option<Address> = None;
+    for offer_id in path.iter() {
+        let offer = offer_load(e, offer_id)?;
+        if let Some(token) = token {
+            if offer.recv_token != token {
+                return Err(SwapError::InvalidRoute);
+            }
+        }
+        token = Some(offer.send_token);
+    }
+
+    acceptor.require_auth();
+
+    let mut amount = amount_in;
+    for offer_id in path.iter() {
+        let offer = offer_load_live(e, offer_id)?;
+        let mut fill_amount = amount;
+        let (_, fee_amount) = offer_quote(e, offer_id, &offer, fill_amount, Some(acceptor))?;
+        let fee_amount = fee_in_token(e, &offer.recv_token, fee_amount);
+        if fill_amount.checked_add(fee_amount).ok_or(SwapError::Overflow)? > amount {
+            fill_amount = amount.checked_sub(fee_amount).ok_or(SwapError::AmountTooSmall)?;
+        }
+        amount = offer_fill(e, acceptor, offer_id, fill_amount, 0, &None)?;
+    }
+
+    if amount < min_out {
+        return Err(SwapError::SlippageExceeded);
+    }
+    Ok(amount)
+}
+
+// Match offers
+// Settles `amount` of offer_a's recv_token against offer_b, which must sell that token for
+// offer_a's send_token at a price at least as good. Each offer is filled like by its own
+// acceptor, the other offer's offeror, who pays the taker fee in the traded token out of
+// its offer's escrow: offer_b sells what pays for `amount` and the fee of that fill, and
+// offer_a pays for it, and its fee, out of what it sold. Any of that left over, the
+// price improvement, goes to offer_a's offeror. Can be called by anyone.
+// Returns the amounts of offer_a's and offer_b's send_token exchanged.
+pub fn offer_match(e: &Env, 
+    offer_a_id: u32, 
+    offer_b_id: u32, 
+    amount: u64
+) -> Result<(u64, u64), SwapError> {
+    escrow_lock(e)?;
+    let result = offer_match_locked(e, offer_a_id, offer_b_id, amount);
+    escrow_unlock(e);
+    result
+}
+
+fn offer_match_locked(e: &Env, 
+    offer_a_id: u32, 
+    offer_b_id: u32, 
+    amount: u64
+) -> Result<(u64, u64), SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+
+    let mut offer_a = offer_load_live(e, offer_a_id)?;
+    let mut offer_b = offer_load_live(e, offer_b_id)?;
+
+    if offer_a.send_token != offer_b.recv_token || offer_a.recv_token != offer_b.send_token {
+        return Err(SwapError::OffersNotCrossed);
+    }
+    // Private offers, and so those with a dispute window, are only filled by their
+    // designated acceptors.
+    for offer_id in [offer_a_id, offer_b_id] {
+        if acceptors_get(e, offer_id).is_some() || counterparty_get(e, offer_id).is_some() {
+            return Err(SwapError::AcceptorNotAllowed);
+        }
+    }
+
+    let contract = e.current_contract_address();
+    let offeror_a = offer_a.offeror.clone();
+    let offeror_b = offer_b.offeror.clone();
+
+    // offer_b must sell enough to pay for `amount` of offer_a with its fee.
+    let (a_send_amount, a_fee_amount) = offer_quote(e, offer_a_id, &offer_a, amount, Some(&offeror_b))?;
+    let b_amount = mul_div_ceil(amount.checked_add(a_fee_amount).ok_or(SwapError::Overflow)?, offer_b.recv_amount, offer_b.send_amount)?;
+    if b_amount > a_send_amount {
+        return Err(SwapError::OffersNotCrossed);
+    }
+
+    // Book both fills before any token is moved.
+    let fill_a = fill_book(e, offer_a_id, &mut offer_a, &offeror_b, &contract, amount, 0)?;
+    let fill_b = fill_book(e, offer_b_id, &mut offer_b, &offeror_a, &contract, b_amount, 0)?;
+    let a_left = fill_a.send_amount
+        .checked_sub(fill_b.amount.checked_add(fill_b.fee_amount).ok_or(SwapError::Overflow)?)
+        .ok_or(SwapError::OffersNotCrossed)?;
+    let b_left = fill_b.send_amount
+        .checked_sub(fill_a.amount.checked_add(fill_a.fee_amount).ok_or(SwapError::Overflow)?)
+        .ok_or(SwapError::OffersNotCrossed)?;
+
+    fill_pay(e, &offer_a, &offeror_b, &contract, &fill_a, &None)?;
+    fill_pay(e, &offer_b, &offeror_a, &contract, &fill_b, &None)?;
+    escrow_out(e, &offer_a.send_token, &offeror_a, a_left);
+    proceeds_pay(e, offer_a_id, &offer_a, &contract, b_left);
+
+    // emit OffersMatched event
+    e.events().publish((OFFER, symbol_short!("OMatch"), EVENT_VERSION), 
+        (offer_a_id, offer_b_id, fill_a.send_amount, fill_b.send_amount)
     );
 
-    0
+    Ok((fill_a.send_amount, fill_b.send_amount))
+}
+
+// Quote fill
+// Validates a fill of `amount` like `offer_accept` and returns the send_token amount the
+// acceptor would receive and the taker fee charged on top of `amount`. Without an acceptor
+// the acceptor specific checks and fee tier discounts are skipped.
+pub fn offer_quote(e: &Env, 
+    offer_id: u32, 
+    offer: &OfferInfo, 
+    amount: u64, 
+    acceptor: Option<&Address>
+) -> Result<(u64, u64), SwapError> {
+    if !fee_check(e) {
+        return Err(SwapError::FeeNotSet);
+    }
+    if offer.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+    if e.ledger().sequence() >= offer.expire_ledger {
+        return Err(SwapError::OfferExpired);
+    }
+    if auction_get(e, offer_id).is_some() {
+        return Err(SwapError::OfferInAuction);
+    }
+    if offer.recv_amount < amount {
+        return Err(SwapError::AmountTooLarge);
+    }
+    if amount < offer.min_recv_amount {
+        return Err(SwapError::AmountTooSmall);
+    }
+    if offer.mode == OfferMode::ALL_OR_NOTHING && amount != offer.recv_amount {
+        return Err(SwapError::AmountTooSmall);
+    }
+    if let Some(minimums) = minimums_get(e, &offer.send_token, &offer.recv_token) {
+        if amount < minimums.min_fill_amount && amount != offer.recv_amount {
+            return Err(SwapError::AmountTooSmall);
+        }
+    }
+    if let Some(acceptor) = acceptor {
+        if !acceptor_check(e, offer_id, acceptor) {
+            return Err(SwapError::AcceptorNotAllowed);
+        }
+        if let Some(counterparty) = counterparty_get(e, offer_id) {
+            if counterparty != acceptor.clone() {
+                return Err(SwapError::AcceptorNotAllowed);
+            }
+            if amount != offer.recv_amount {
+                return Err(SwapError::AmountTooSmall);
+            }
+        }
+    }
+
+    let fee_info = fee_get(e)?;
+    let fee_amount: u64 = calculate_fee(e, &offer.recv_token, fee_info.taker_fee_rate, amount, acceptor, true);
+
+    // Compute the amount of send_token that acceptor can receive, rounded down so the
+    // contract never pays out more than the offer's price. See `dust_accrue`.
+    let prop_send_amount = mul_div(amount, offer.send_amount, offer.recv_amount)?;
+
+    Ok((prop_send_amount, fee_amount))
 }
 
 // Updates offer
-// Must be authorized by offeror.
+// Must be authorized by the offeror or a manager allowed to update it.
 pub fn offer_update(e: &Env, 
-    offeror: &Address, 
+    caller: &Address, 
     offer_id: u32, 
     recv_amount: u64, 
     min_recv_amount: u64
-) -> u32 {
+) -> Result<(), SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
     if recv_amount == 0 {
-        // panic!("zero amount is not allowed");
-        return 121;
+        return Err(SwapError::ZeroAmount);
     }
     if min_recv_amount > recv_amount {
-        // panic!("min_recv_amount can't be greater than recv_amount");
-        return 122;
+        return Err(SwapError::InvalidMinRecvAmount);
     }
 
-    if !e.storage().instance().has(&DataKey::RegOffers(offer_id)) {
-        // panic!("can't find offer");
-        // return 123;
-        return e.ledger().sequence();
+    let mut offer = offer_load(e, offer_id)?;
+    if auction_get(e, offer_id).is_some() {
+        return Err(SwapError::OfferInAuction);
+    }
+    if commit_locked(e, offer_id) {
+        return Err(SwapError::OfferLocked);
     }
 
-    let mut offer = offer_load(e, offer_id);
+    if !manager_check(e, &offer.offeror, offer_id, caller, MANAGE_UPDATE) {
+        return Err(SwapError::InvalidOfferor);
+    }
+    if offer.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+
+    band_check(e, &offer.send_token, &offer.recv_token, offer.send_amount, recv_amount, false)?;
+
+    caller.require_auth();
+
+    offer.recv_amount = recv_amount;
+    offer.min_recv_amount = min_recv_amount;
+    offer_write(e, offer_id, &offer);
+    // A manually set price ends a running dutch auction or oracle peg.
+    dutch_remove(e, offer_id);
+    peg_remove(e, offer_id);
+    // The price has changed, so the offer moves in the order book.
+    book_remove(e, offer_id, &offer);
+    book_insert(e, offer_id, &offer);
+
+    // emit OfferUpdated event
+    e.events().publish((OFFER, symbol_short!("OUpdate"), EVENT_VERSION), 
+        OfferUpdated {
+            offer_id,
+            offeror: offer.offeror.clone(),
+            send_remaining: offer.send_amount,
+            recv_remaining: offer.recv_amount,
+            min_recv_amount: offer.min_recv_amount,
+            fee_remaining: offer.fee_amount,
+        }
+    );
+
+    Ok(())
+}
+
+// Top up offer
+// Escrows `add_send_amount` more send_token along with its maker fee and raises recv_amount
+// so that the price stays the same. Must be authorized by offeror.
+pub fn offer_increase(e: &Env, 
+    offeror: &Address, 
+    offer_id: u32, 
+    add_send_amount: u64
+) -> Result<(), SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+    if add_send_amount == 0 {
+        return Err(SwapError::ZeroAmount);
+    }
+
+    let mut offer = offer_load_live(e, offer_id)?;
+    if auction_get(e, offer_id).is_some() {
+        return Err(SwapError::OfferInAuction);
+    }
 
     if offer.offeror != offeror.clone() {
-        // panic!("invalid offeror");
-        return 124;
+        return Err(SwapError::InvalidOfferor);
     }
     if offer.status != OfferStatus::ACTIVE {
-        // panic!("offer not available");
-        return 125;
+        return Err(SwapError::OfferNotActive);
+    }
+    if e.ledger().sequence() >= offer.expire_ledger {
+        return Err(SwapError::OfferExpired);
     }
 
     offeror.clone().require_auth();
 
-    offer.recv_amount = recv_amount;
-    offer.min_recv_amount = min_recv_amount;
+    let fee_info = fee_get(e)?;
+    let fee_amount: u64 = calculate_fee(e, &offer.send_token, fee_info.maker_fee_rate, add_send_amount, Some(offeror), false);
+    let fee_amount = maker_fee_take(e, offeror, &offer.send_token, fee_amount)?;
+
+    let add_recv_amount = mul_div(add_send_amount, offer.recv_amount, offer.send_amount)?;
+    let (add_send_amount, add_recv_amount) = offer_escrow_in(e, offeror, &offer.send_token, add_send_amount, add_recv_amount, fee_amount)?;
+
+    offer.send_amount = offer.send_amount.checked_add(add_send_amount).ok_or(SwapError::Overflow)?;
+    offer.recv_amount = offer.recv_amount.checked_add(add_recv_amount).ok_or(SwapError::Overflow)?;
+    offer.fee_amount = offer.fee_amount.checked_add(fee_amount).ok_or(SwapError::Overflow)?;
     offer_write(e, offer_id, &offer);
 
     // emit OfferUpdated event
-    e.events().publish((OFFER, symbol_short!("OUpdate")), 
-        (offeror.clone(), offer_id, recv_amount, min_recv_amount)
+    e.events().publish((OFFER, symbol_short!("OIncrease"), EVENT_VERSION), 
+        OfferUpdated {
+            offer_id,
+            offeror: offer.offeror.clone(),
+            send_remaining: offer.send_amount,
+            recv_remaining: offer.recv_amount,
+            min_recv_amount: offer.min_recv_amount,
+            fee_remaining: offer.fee_amount,
+        }
     );
 
-    0
+    Ok(())
+}
+
+// Partially withdraw offer
+// Returns `sub_send_amount` of the escrowed send_token along with the maker fee paid for it
+// and lowers recv_amount so that the price stays the same. The offer stays active, so
+// the whole send_amount can't be withdrawn this way. Must be authorized by offeror.
+pub fn offer_decrease(e: &Env, 
+    offeror: &Address, 
+    offer_id: u32, 
+    sub_send_amount: u64
+) -> Result<(), SwapError> {
+    if sub_send_amount == 0 {
+        return Err(SwapError::ZeroAmount);
+    }
+
+    let mut offer = offer_load_live(e, offer_id)?;
+    if auction_get(e, offer_id).is_some() {
+        return Err(SwapError::OfferInAuction);
+    }
+    if commit_locked(e, offer_id) {
+        return Err(SwapError::OfferLocked);
+    }
+
+    if offer.offeror != offeror.clone() {
+        return Err(SwapError::InvalidOfferor);
+    }
+    if offer.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+    if sub_send_amount >= offer.send_amount {
+        return Err(SwapError::AmountTooLarge);
+    }
+
+    let sub_recv_amount = mul_div(sub_send_amount, offer.recv_amount, offer.send_amount)?;
+    let sub_fee_amount = mul_div(sub_send_amount, offer.fee_amount, offer.send_amount)?;
+    if sub_recv_amount >= offer.recv_amount {
+        return Err(SwapError::AmountTooLarge);
+    }
+
+    offeror.clone().require_auth();
+
+    escrow_out(e, &offer.send_token, offeror, sub_send_amount.checked_add(sub_fee_amount).ok_or(SwapError::Overflow)?);
+
+    offer.send_amount = offer.send_amount.checked_sub(sub_send_amount).ok_or(SwapError::Overflow)?;
+    offer.recv_amount = offer.recv_amount.checked_sub(sub_recv_amount).ok_or(SwapError::Overflow)?;
+    offer.fee_amount = offer.fee_amount.checked_sub(sub_fee_amount).ok_or(SwapError::Overflow)?;
+    if offer.recv_amount < offer.min_recv_amount {
+        offer.min_recv_amount = offer.recv_amount;
+    }
+    offer_write(e, offer_id, &offer);
+
+    // emit OfferUpdated event
+    e.events().publish((OFFER, symbol_short!("ODecrease"), EVENT_VERSION), 
+        OfferUpdated {
+            offer_id,
+            offeror: offer.offeror.clone(),
+            send_remaining: offer.send_amount,
+            recv_remaining: offer.recv_amount,
+            min_recv_amount: offer.min_recv_amount,
+            fee_remaining: offer.fee_amount,
+        }
+    );
+
+    Ok(())
+}
+
+// Split offer
+// Carves child offers out of the offer, one per entry of `parts` giving the child's
+// send_amount. Children get the same price, expiry, mode, acceptor whitelist, alternative
+// recv tokens and vesting along with their share of the escrowed maker fee, the rest stays
+// with the offer. Offers with a counterparty can't be split. Returns the ids of the
+// children. Must be authorized by offeror.
+pub fn offer_split(e: &Env, 
+    offeror: &Address, 
+    offer_id: u32, 
+    parts: &Vec<u64>
+) -> Result<Vec<u32>, SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+
+    let mut offer = offer_load(e, offer_id)?;
+    if auction_get(e, offer_id).is_some() {
+        return Err(SwapError::OfferInAuction);
+    }
+    if dutch_get(e, offer_id).is_some() || peg_get(e, offer_id).is_some() {
+        return Err(SwapError::DynamicPrice);
+    }
+    if commit_locked(e, offer_id) {
+        return Err(SwapError::OfferLocked);
+    }
+    // A counterparty takes its offer whole, and with it any dispute window.
+    if counterparty_get(e, offer_id).is_some() {
+        return Err(SwapError::AcceptorNotAllowed);
+    }
+
+    if offer.offeror != offeror.clone() {
+        return Err(SwapError::InvalidOfferor);
+    }
+    if offer.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+    if e.ledger().sequence() >= offer.expire_ledger {
+        return Err(SwapError::OfferExpired);
+    }
+
+    offeror.clone().require_auth();
+    offer_limit_check(e, offeror, parts.len())?;
+
+    // Children keep who may fill them, in which tokens, and how the proceeds vest.
+    let vesting = vesting_get(e, offer_id);
+    let acceptors = acceptors_get(e, offer_id);
+    let alts = alt_get(e, offer_id);
+    let mut child_ids: Vec<u32> = Vec::new(e);
+    for part in parts.iter() {
+        if part == 0 {
+            return Err(SwapError::ZeroAmount);
+        }
+        // The offer must keep a part of its own.
+        if part >= offer.send_amount {
+            return Err(SwapError::AmountTooLarge);
+        }
+
+        let recv_amount = mul_div(part, offer.recv_amount, offer.send_amount)?;
+        let fee_amount = mul_div(part, offer.fee_amount, offer.send_amount)?;
+        if recv_amount == 0 {
+            return Err(SwapError::AmountTooSmall);
+        }
+        if recv_amount >= offer.recv_amount {
+            return Err(SwapError::AmountTooLarge);
+        }
+
+        let child_id = offer_insert(e, &OfferInfo {
+            send_amount: part,
+            recv_amount,
+            min_recv_amount: offer.min_recv_amount.min(recv_amount),
+            fee_amount,
+            ..offer.clone()
+        });
+        vesting_write(e, child_id, vesting);
+        if let Some(acceptors) = &acceptors {
+            acceptors_write(e, child_id, acceptors);
+        }
+        alt_write(e, child_id, &alts);
+        child_ids.push_back(child_id);
+
+        offer.send_amount = offer.send_amount.checked_sub(part).ok_or(SwapError::Overflow)?;
+        offer.recv_amount = offer.recv_amount.checked_sub(recv_amount).ok_or(SwapError::Overflow)?;
+        offer.fee_amount = offer.fee_amount.checked_sub(fee_amount).ok_or(SwapError::Overflow)?;
+    }
+
+    if offer.recv_amount < offer.min_recv_amount {
+        offer.min_recv_amount = offer.recv_amount;
+    }
+    offer_write(e, offer_id, &offer);
+
+    // emit OfferSplit event
+    e.events().publish((OFFER, symbol_short!("OSplit"), EVENT_VERSION), 
+        (offeror.clone(), offer_id, child_ids.clone())
+    );
+
+    Ok(child_ids)
+}
+
+// Transfer offer
+// Hands the offer over to new_offeror, who then receives its proceeds, including those still
+// vesting, and its escrow on close or expiry. Must be authorized by the current offeror.
+pub fn offer_transfer(e: &Env, 
+    offeror: &Address, 
+    offer_id: u32, 
+    new_offeror: &Address
+) -> Result<(), SwapError> {
+    let mut offer = offer_load(e, offer_id)?;
+
+    if offer.offeror != offeror.clone() {
+        return Err(SwapError::InvalidOfferor);
+    }
+
+    ban_check(e, new_offeror)?;
+
+    offeror.clone().require_auth();
+
+    if offer.status == OfferStatus::ACTIVE || offer.status == OfferStatus::PAUSED {
+        offer_limit_check(e, new_offeror, 1)?;
+        compliance_check(e, new_offeror, &offer.send_token, offer.send_amount)?;
+        compliance_check(e, new_offeror, &offer.recv_token, offer.recv_amount)?;
+        index_remove(e, &DataKey::OfferorOffers(offeror.clone()), offer_id);
+        index_push(e, &DataKey::OfferorOffers(new_offeror.clone()), offer_id);
+    }
+    offer.offeror = new_offeror.clone();
+    offer_write(e, offer_id, &offer);
+    stream_transfer(e, offer_id, new_offeror);
+
+    // emit OfferTransferred event
+    e.events().publish((OFFER, symbol_short!("OTransfer"), EVENT_VERSION), 
+        (offeror.clone(), offer_id, new_offeror.clone())
+    );
+
+    Ok(())
 }
 
 // Cancel offer
-// Must be authorized by offeror.
+// Must be authorized by the offeror or a manager allowed to close it. The escrow
+// always goes back to the offeror.
 pub fn offer_close(e: &Env, 
-    offeror: &Address, 
+    caller: &Address, 
     offer_id: u32
-) -> u32 {
-    if !e.storage().instance().has(&DataKey::RegOffers(offer_id)) {
-        // panic!("can't find offer");
-        return 131;
+) -> Result<(), SwapError> {
+    let mut offer = offer_load(e, offer_id)?;
+    if auction_get(e, offer_id).is_some() {
+        return Err(SwapError::OfferInAuction);
+    }
+
+    if !manager_check(e, &offer.offeror, offer_id, caller, MANAGE_CLOSE) {
+        return Err(SwapError::InvalidOfferor);
+    }
+    if offer.status != OfferStatus::ACTIVE && offer.status != OfferStatus::PAUSED {
+        return Err(SwapError::OfferNotActive);
     }
 
-    let mut offer = offer_load(e, offer_id);
+    caller.require_auth();
+    
+    // Refund the remaining send_amount along with the maker fee paid for it.
+    let refund_amount = offer.send_amount.checked_add(offer.fee_amount).ok_or(SwapError::Overflow)?;
+    escrow_out(e, &offer.send_token, &offer.offeror, refund_amount);
+    let refund_amount = refund_amount.checked_add(dust_sweep(e, offer_id, &offer)).ok_or(SwapError::Overflow)?;
+
+    offer.fee_amount = 0;
+    offer.status = OfferStatus::CANCEL;
+    offer_unindex(e, offer_id, &offer);
+    offer_write(e, offer_id, &offer);
+
+    // emit OfferClosed event
+    e.events().publish((OFFER, symbol_short!("ORevoke"), EVENT_VERSION), 
+        OfferClosed {
+            offer_id,
+            offeror: offer.offeror.clone(),
+            status: offer.status,
+            refund_amount,
+            reason: Vec::new(e),
+        }
+    );
+
+    Ok(())
+}
+
+// Pause offer
+// Stops fills of the offer until it's resumed, keeping its escrow. Must be authorized by
+// the offeror. A paused offer leaves the order book but can still be closed or expired.
+pub fn offer_pause(e: &Env, 
+    offeror: &Address, 
+    offer_id: u32
+) -> Result<(), SwapError> {
+    let mut offer = offer_load(e, offer_id)?;
+    if auction_get(e, offer_id).is_some() {
+        return Err(SwapError::OfferInAuction);
+    }
+    if commit_locked(e, offer_id) {
+        return Err(SwapError::OfferLocked);
+    }
 
     if offer.offeror != offeror.clone() {
-        // panic!("invalid offeror");
-        return 132;
+        return Err(SwapError::InvalidOfferor);
     }
     if offer.status != OfferStatus::ACTIVE {
-        // panic!("offer not available");
-        return 133;
+        return Err(SwapError::OfferNotActive);
     }
 
     offeror.clone().require_auth();
-    
-    token::Client::new(e, &offer.send_token).transfer(
-        &e.current_contract_address(),
-        &offeror,
-        &(offer.send_amount as i128),
+
+    offer.status = OfferStatus::PAUSED;
+    book_remove(e, offer_id, &offer);
+    offer_write(e, offer_id, &offer);
+
+    // emit OfferPaused event
+    e.events().publish((OFFER, symbol_short!("OPause"), EVENT_VERSION), 
+        (offeror.clone(), offer_id)
     );
 
-    offer.status = OfferStatus::CANCEL;
+    Ok(())
+}
+
+// Resume offer
+// Lets a paused offer be filled again. Must be authorized by the offeror.
+pub fn offer_resume(e: &Env, 
+    offeror: &Address, 
+    offer_id: u32
+) -> Result<(), SwapError> {
+    let mut offer = offer_load(e, offer_id)?;
+
+    if offer.offeror != offeror.clone() {
+        return Err(SwapError::InvalidOfferor);
+    }
+    if offer.status != OfferStatus::PAUSED {
+        return Err(SwapError::OfferNotActive);
+    }
+
+    offeror.clone().require_auth();
+
+    offer.status = OfferStatus::ACTIVE;
+    book_insert(e, offer_id, &offer);
     offer_write(e, offer_id, &offer);
 
-    // emit OfferRevoked event
-    e.events().publish((OFFER, symbol_short!("ORevoke")), 
+    // emit OfferResumed event
+    e.events().publish((OFFER, symbol_short!("OResume"), EVENT_VERSION), 
         (offeror.clone(), offer_id)
     );
 
-    0
+    Ok(())
+}
+
+// Force close offer
+// Lets the admin unwind an offer, e.g. when one of its tokens turns out to be malicious or
+// frozen. The escrow goes back to the offeror as on close and a running auction is called
+// off, leaving the bids refundable.
+pub fn offer_admin_close(e: &Env, 
+    caller: &Address, 
+    offer_id: u32, 
+    reason: &Symbol
+) -> Result<(), SwapError> {
+    role_check(e, Role::Admin, caller)?;
+
+    let mut offer = offer_load(e, offer_id)?;
+    if offer.status != OfferStatus::ACTIVE && offer.status != OfferStatus::PAUSED {
+        return Err(SwapError::OfferNotActive);
+    }
+
+    auction_cancel(e, offer_id);
+    let refund_amount = offer.send_amount.checked_add(offer.fee_amount).ok_or(SwapError::Overflow)?;
+    escrow_out(e, &offer.send_token, &offer.offeror, refund_amount);
+    let refund_amount = refund_amount.checked_add(dust_sweep(e, offer_id, &offer)).ok_or(SwapError::Overflow)?;
+
+    offer.fee_amount = 0;
+    offer.status = OfferStatus::CANCELED_BY_ADMIN;
+    offer_unindex(e, offer_id, &offer);
+    offer_write(e, offer_id, &offer);
+
+    // emit OfferClosed event
+    e.events().publish((OFFER, symbol_short!("OAdmClose"), EVENT_VERSION), 
+        OfferClosed {
+            offer_id,
+            offeror: offer.offeror.clone(),
+            status: offer.status,
+            refund_amount,
+            reason: Vec::from_array(e, [reason.clone()]),
+        }
+    );
+
+    Ok(())
+}
+
+// Expire offer
+// Can be called by anyone once the offer's expire_ledger is reached.
+pub fn offer_expire(e: &Env, 
+    offer_id: u32
+) -> Result<(), SwapError> {
+    let mut offer = offer_load(e, offer_id)?;
+    if auction_get(e, offer_id).is_some() {
+        return Err(SwapError::OfferInAuction);
+    }
+
+    if offer.status != OfferStatus::ACTIVE && offer.status != OfferStatus::PAUSED {
+        return Err(SwapError::OfferNotActive);
+    }
+    if e.ledger().sequence() < offer.expire_ledger {
+        return Err(SwapError::OfferNotExpired);
+    }
+
+    // The maker fee for the unfilled part is returned like on close.
+    let refund_amount = offer.send_amount.checked_add(offer.fee_amount).ok_or(SwapError::Overflow)?;
+    escrow_out(e, &offer.send_token, &offer.offeror, refund_amount);
+    let refund_amount = refund_amount.checked_add(dust_sweep(e, offer_id, &offer)).ok_or(SwapError::Overflow)?;
+
+    offer.fee_amount = 0;
+    offer.status = OfferStatus::EXPIRED;
+    offer_unindex(e, offer_id, &offer);
+    offer_write(e, offer_id, &offer);
+
+    // emit OfferClosed event
+    e.events().publish((OFFER, symbol_short!("OExpire"), EVENT_VERSION), 
+        OfferClosed {
+            offer_id,
+            offeror: offer.offeror.clone(),
+            status: offer.status,
+            refund_amount,
+            reason: Vec::new(e),
+        }
+    );
+
+    Ok(())
+}
+
+// Reopen offer
+// Lets the offeror fund a closed, expired or completed offer again with `send_amount` at the
+// price it had when it stopped trading. The offer keeps its id and fill history, and trades
+// at a fixed price even if it was a dutch auction or pegged to the oracle before.
+pub fn offer_reopen(e: &Env, 
+    offeror: &Address, 
+    offer_id: u32, 
+    send_amount: u64, 
+    expire_ledger: u32
+) -> Result<(), SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+    if !fee_check(e) {
+        return Err(SwapError::FeeNotSet);
+    }
+
+    let mut offer = offer_load(e, offer_id)?;
+    if offer.offeror != offeror.clone() {
+        return Err(SwapError::InvalidOfferor);
+    }
+    let (price_send_amount, price_recv_amount) = match offer.status {
+        OfferStatus::CANCEL | OfferStatus::EXPIRED => (offer.send_amount, offer.recv_amount),
+        OfferStatus::COMPLETE => e.storage().persistent().get(&OfferDataKey::Terms(offer_id)).ok_or(SwapError::OfferNotActive)?,
+        _ => return Err(SwapError::OfferNotActive),
+    };
+    // A held fill is paid out from the offer's terms, which must not change until then.
+    if dispute_get(e, offer_id).is_some() {
+        return Err(SwapError::OfferLocked);
+    }
+    if !allow_pair_get(e, &offer.send_token, &offer.recv_token) {
+        return Err(SwapError::TokenNotAllowed);
+    }
+    if send_amount == 0 {
+        return Err(SwapError::ZeroAmount);
+    }
+    let recv_amount = mul_div(send_amount, price_recv_amount, price_send_amount)?;
+    if recv_amount == 0 {
+        return Err(SwapError::AmountTooSmall);
+    }
+    if expire_ledger <= e.ledger().sequence() {
+        return Err(SwapError::InvalidExpiration);
+    }
+    if let Some(minimums) = minimums_get(e, &offer.send_token, &offer.recv_token) {
+        if send_amount < minimums.min_send_amount {
+            return Err(SwapError::AmountTooSmall);
+        }
+    }
+    offer_limit_check(e, offeror, 1)?;
+    throttle_create(e, offeror)?;
+    ban_check(e, offeror)?;
+    compliance_check(e, offeror, &offer.send_token, send_amount)?;
+    compliance_check(e, offeror, &offer.recv_token, recv_amount)?;
+    band_check(e, &offer.send_token, &offer.recv_token, send_amount, recv_amount, false)?;
+
+    offeror.clone().require_auth();
+
+    let fee_info = fee_get(e)?;
+    let fee_amount: u64 = calculate_fee(e, &offer.send_token, fee_info.maker_fee_rate, send_amount, Some(offeror), false);
+    let fee_amount = maker_fee_take(e, offeror, &offer.send_token, fee_amount)?;
+    let (send_amount, recv_amount) = offer_escrow_in(e, offeror, &offer.send_token, send_amount, recv_amount, fee_amount)?;
+
+    offer.send_amount = send_amount;
+    offer.recv_amount = recv_amount;
+    offer.min_recv_amount = offer.min_recv_amount.min(recv_amount);
+    offer.fee_amount = fee_amount;
+    offer.expire_ledger = expire_ledger;
+    offer.status = OfferStatus::ACTIVE;
+    e.storage().persistent().remove(&OfferDataKey::Terms(offer_id));
+    dutch_remove(e, offer_id);
+    peg_remove(e, offer_id);
+    offer_index(e, offer_id, &offer);
+    offer_write(e, offer_id, &offer);
+
+    // emit OfferUpdated event
+    e.events().publish((OFFER, symbol_short!("OReopen"), EVENT_VERSION), 
+        OfferUpdated {
+            offer_id,
+            offeror: offer.offeror.clone(),
+            send_remaining: offer.send_amount,
+            recv_remaining: offer.recv_amount,
+            min_recv_amount: offer.min_recv_amount,
+            fee_remaining: offer.fee_amount,
+        }
+    );
+
+    Ok(())
+}
+
+// Extend offer TTL
+// Can be called by anyone willing to pay the rent. `ledgers` is capped by the network's max TTL.
+pub fn offer_extend_ttl(e: &Env, 
+    offer_id: u32, 
+    ledgers: u32
+) -> Result<(), SwapError> {
+    let key = DataKey::RegOffers(offer_id);
+    if !e.storage().persistent().has(&key) {
+        return Err(SwapError::OfferNotFound);
+    }
+
+    let ledgers = ledgers.min(e.storage().max_ttl() - 1);
+    e.storage().persistent().extend_ttl(&key, ledgers, ledgers);
+    Ok(())
+}
+
+// Prune offers
+// Deletes the given offers once they're no longer active, emitting their final state first.
+// Active or unknown ids are skipped. Returns the number of deleted offers.
+pub fn offer_prune(e: &Env, 
+    caller: &Address, 
+    offer_ids: &Vec<u32>
+) -> Result<u32, SwapError> {
+    role_check(e, Role::Operator, caller)?;
+
+    let mut count: u32 = 0;
+    for offer_id in offer_ids.iter() {
+        if offer_delete(e, offer_id) {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+// Purge offers
+// Like `offer_prune` for the ids in [cursor, cursor + limit) whose expire_ledger is before `ledger`.
+pub fn offer_purge_before(e: &Env, 
+    caller: &Address, 
+    ledger: u32, 
+    cursor: u32, 
+    limit: u32
+) -> Result<u32, SwapError> {
+    role_check(e, Role::Operator, caller)?;
+
+    let mut count: u32 = 0;
+    let end = offer_count(e).min(cursor.saturating_add(limit));
+    for offer_id in cursor..end {
+        let expired = match offer_load(e, offer_id) {
+            Ok(offer) => offer.expire_ledger < ledger,
+            Err(_) => false,
+        };
+        if expired && offer_delete(e, offer_id) {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+// List open offers
+// Returns up to `limit` ids of active offers starting from position `cursor` of the index.
+pub fn offer_list(e: &Env, 
+    cursor: u32, 
+    limit: u32
+) -> Vec<u32> {
+    index_page(e, &DataKey::OpenOffers, cursor, limit)
+}
+
+// List open offers of the offeror
+pub fn offer_list_by_offeror(e: &Env, 
+    offeror: &Address, 
+    cursor: u32, 
+    limit: u32
+) -> Vec<u32> {
+    index_page(e, &DataKey::OfferorOffers(offeror.clone()), cursor, limit)
+}
+
+// List open offers selling `send_token` for `recv_token`, oldest first
+pub fn offer_list_by_pair(e: &Env, 
+    send_token: &Address, 
+    recv_token: &Address, 
+    cursor: u32, 
+    limit: u32
+) -> Vec<u32> {
+    index_page(e, &DataKey::PairOffers(send_token.clone(), recv_token.clone()), cursor, limit)
 }
 
 // Check balances
@@ -323,11 +1495,284 @@ pub fn offer_balances(e: &Env,
     (send_token_client.balance(account) as u64, recv_token_client.balance(account) as u64)
 }
 
-pub fn offer_load(e: &Env, key: u32) -> OfferInfo {
-    e.storage().instance().get(&DataKey::RegOffers(key)).unwrap()
+// Offers live in persistent storage, one entry per offer, so their number isn't
+// bounded by the size of the contract instance.
+pub fn offer_load(e: &Env, key: u32) -> Result<OfferInfo, SwapError> {
+    let key = DataKey::RegOffers(key);
+    let entry: Val = e.storage().persistent().get(&key).ok_or(SwapError::OfferNotFound)?;
+    let (offer, _) = offer_upcast(e, &entry).ok_or(SwapError::OfferNotFound)?;
+    ttl_bump(e, &key);
+    Ok(offer)
+}
+
+// Loads the offer with its recv_amount at the current price, see `dutch_apply` and `peg_apply`.
+pub fn offer_load_live(e: &Env, key: u32) -> Result<OfferInfo, SwapError> {
+    let mut offer = offer_load(e, key)?;
+    if offer.status == OfferStatus::ACTIVE {
+        dutch_apply(e, key, &mut offer);
+        peg_apply(e, key, &mut offer)?;
+    }
+    Ok(offer)
+}
+
+// Stores the offer in the current schema.
+pub fn offer_write(e: &Env, key: u32, offer: &OfferInfo) {
+    let key = DataKey::RegOffers(key);
+    e.storage().persistent().set(&key, &StoredOffer::V2(offer.clone()));
+    ttl_bump(e, &key);
+}
+
+// Books a fill of `amount` recv_token for `send_amount` of the offer's escrow, releasing
+// the maker fee paid for it.
+pub fn offer_settle(e: &Env, offer_id: u32, offer: &mut OfferInfo, acceptor: &Address, amount: u64, send_amount: u64) -> Result<(), SwapError> {
+    // The maker fee for the filled part, all of what's left once the offer completes.
+    let fee_amount = if amount == offer.recv_amount {
+        offer.fee_amount
+    } else {
+        mul_div(send_amount, offer.fee_amount, offer.send_amount)?
+    };
+    fee_accrue(e, &offer.send_token, fee_amount);
+
+    let (prev_send_amount, prev_recv_amount) = (offer.send_amount, offer.recv_amount);
+    offer.send_amount = offer.send_amount.checked_sub(send_amount).ok_or(SwapError::Overflow)?;
+    offer.fee_amount = offer.fee_amount.checked_sub(fee_amount).ok_or(SwapError::Overflow)?;
+    offer.recv_amount = offer.recv_amount.checked_sub(amount).ok_or(SwapError::Overflow)?;
+
+    if offer.recv_amount > 0 {
+        dust_accrue(e, offer_id, offer, amount, send_amount, prev_send_amount, prev_recv_amount);
+    }
+
+    if offer.recv_amount == 0 {
+        offer.status = OfferStatus::COMPLETE;
+        dust_sweep(e, offer_id, offer);
+        // Keep the price of the last fill, which `offer_reopen` reuses.
+        let key = OfferDataKey::Terms(offer_id);
+        e.storage().persistent().set(&key, &(prev_send_amount, prev_recv_amount));
+        e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+        offer_unindex(e, offer_id, offer);
+        // emit OfferCompleted event
+        e.events().publish((OFFER, symbol_short!("OComplete"), EVENT_VERSION), 
+            OfferCompleted {
+                offer_id,
+                offeror: offer.offeror.clone(),
+                fee_amount,
+            }
+        );
+    }
+    else if offer.recv_amount < offer.min_recv_amount {
+        offer.min_recv_amount = offer.recv_amount;
+    }
+
+    offer_write(e, offer_id, offer);
+    fill_record(e, offer_id, acceptor, amount, send_amount);
+    stats_record(e, &offer.send_token, &offer.recv_token, send_amount, amount);
+    // Offerors filling their own offers trade with themselves and earn nothing for it.
+    if acceptor.clone() != offer.offeror {
+        reward_accrue(e, &offer.offeror, &offer.recv_token, amount);
+    }
+    Ok(())
+}
+
+// Takes the maker fee of `fee_amount` of token from offeror. Paid in the fee token it's earned
+// right away, otherwise it's escrowed with the offer. Returns the part to escrow with the offer.
+fn maker_fee_take(e: &Env, offeror: &Address, token: &Address, fee_amount: u64) -> Result<u64, SwapError> {
+    let (fee_token, amount) = fee_in(e, token, fee_amount)?;
+    if fee_token == token.clone() {
+        return Ok(fee_amount);
+    }
+    let received = escrow_in(e, &fee_token, offeror, amount);
+    fee_accrue(e, &fee_token, received);
+    Ok(0)
 }
 
-fn offer_write(e: &Env, key: u32, offer: &OfferInfo) {
-    e.storage().instance().set(&DataKey::RegOffers(key), offer);
-    // e.storage().instance().bump(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+// Escrows `send_amount` of token and the maker fee of `fee_amount` from offeror for an offer
+// asking `recv_amount`. Of a token taking a fee on transfers, the offer only sells what
+// arrived, at the price asked. Returns the send_amount and recv_amount of what arrived.
+fn offer_escrow_in(e: &Env, 
+    offeror: &Address, 
+    token: &Address, 
+    send_amount: u64, 
+    recv_amount: u64, 
+    fee_amount: u64
+) -> Result<(u64, u64), SwapError> {
+    let transfer_amount = send_amount.checked_add(fee_amount).ok_or(SwapError::Overflow)?;
+    let received = escrow_in(e, token, offeror, transfer_amount);
+    if received == transfer_amount {
+        return Ok((send_amount, recv_amount));
+    }
+
+    // The maker fee is escrowed in full, so the shortfall comes out of what's sold.
+    let send_received = received.saturating_sub(fee_amount);
+    if send_received == 0 {
+        return Err(SwapError::AmountTooSmall);
+    }
+    Ok((send_received, mul_div_ceil(recv_amount, send_received, send_amount)?))
+}
+
+// Rounding dust of the offer: whole units of send_token set aside for the offeror and the
+// fraction of a unit, in 1/DUST_SCALE, still held in send_amount.
+fn dust_get(e: &Env, offer_id: u32) -> (u64, u64) {
+    e.storage().persistent().get(&OfferDataKey::Dust(offer_id)).unwrap_or((0, 0))
+}
+
+// Units of send_token set aside for the offeror from rounding.
+pub fn offer_dust(e: &Env, offer_id: u32) -> u64 {
+    dust_get(e, offer_id).0
+}
+
+// Fills pay the acceptor the pro-rata send_amount rounded down, so the offer keeps the
+// fraction of a unit it would have owed. Unless the rounding policy leaves them in the
+// offer, those fractions are summed up and every whole unit is moved out of the offer,
+// which would otherwise drift to a better price for later acceptors, and set aside for
+// the offeror or earned as fees.
+fn dust_accrue(e: &Env, 
+    offer_id: u32, 
+    offer: &mut OfferInfo, 
+    amount: u64, 
+    send_amount: u64, 
+    prev_send_amount: u64, 
+    prev_recv_amount: u64
+) {
+    let policy = rounding_get(e);
+    let owed = (amount as u128) * (prev_send_amount as u128);
+    let paid = (send_amount as u128) * (prev_recv_amount as u128);
+    if owed <= paid || policy == RoundingPolicy::Offer {
+        return;
+    }
+
+    let (mut units, mut fraction) = dust_get(e, offer_id);
+    let over = owed - paid;
+    let mut whole = over / (prev_recv_amount as u128);
+    fraction += ((over % (prev_recv_amount as u128)) * (DUST_SCALE as u128) / (prev_recv_amount as u128)) as u64;
+    if fraction >= DUST_SCALE {
+        whole += 1;
+        fraction -= DUST_SCALE;
+    }
+    // The offer keeps at least one unit to sell for its remaining recv_amount.
+    let whole = (whole as u64).min(offer.send_amount - 1);
+    offer.send_amount -= whole;
+    if policy == RoundingPolicy::Fees {
+        fee_accrue(e, &offer.send_token, whole);
+    } else {
+        units += whole;
+    }
+
+    let key = OfferDataKey::Dust(offer_id);
+    e.storage().persistent().set(&key, &(units, fraction));
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
+
+// Pays the dust set aside to the offeror once the offer is no longer active, returning it.
+fn dust_sweep(e: &Env, offer_id: u32, offer: &OfferInfo) -> u64 {
+    let (units, _) = dust_get(e, offer_id);
+    e.storage().persistent().remove(&OfferDataKey::Dust(offer_id));
+    escrow_out(e, &offer.send_token, &offer.offeror, units);
+    units
+}
+
+// Computes `a * b / c` without intermediate overflow, failing if the result doesn't fit.
+pub fn mul_div(a: u64, b: u64, c: u64) -> Result<u64, SwapError> {
+    let result = (a as u128) * (b as u128) / (c as u128);
+    u64::try_from(result).map_err(|_| SwapError::Overflow)
+}
+
+// a * b / c rounded up.
+fn mul_div_ceil(a: u64, b: u64, c: u64) -> Result<u64, SwapError> {
+    let result = ((a as u128) * (b as u128)).div_ceil(c as u128);
+    u64::try_from(result).map_err(|_| SwapError::Overflow)
+}
+
+// Removes an inactive offer and everything stored for it, settling commitment bonds.
+// Offers still holding funds for someone, a disputed fill, unvested proceeds or bids
+// to refund, are kept. Returns
+// false if there was nothing to delete.
+fn offer_delete(e: &Env, offer_id: u32) -> bool {
+    let offer = match offer_load(e, offer_id) {
+        Ok(offer) => offer,
+        Err(_) => return false,
+    };
+    if offer.status == OfferStatus::ACTIVE || offer.status == OfferStatus::PAUSED {
+        return false;
+    }
+    if dispute_get(e, offer_id).is_some() || stream_get(e, offer_id).is_some() || auction_bidders(e, offer_id) > 0 {
+        return false;
+    }
+
+    // emit OfferPruned event with the final snapshot of the offer
+    e.events().publish((OFFER, symbol_short!("OPrune"), EVENT_VERSION), 
+        (offer_id, offer.clone())
+    );
+    e.storage().persistent().remove(&DataKey::RegOffers(offer_id));
+    e.storage().persistent().remove(&DataKey::OfferAcceptors(offer_id));
+    e.storage().persistent().remove(&DataKey::OfferCounterparty(offer_id));
+    e.storage().persistent().remove(&DataKey::OfferVesting(offer_id));
+    e.storage().persistent().remove(&DataKey::OfferAltRecv(offer_id));
+    e.storage().persistent().remove(&OfferDataKey::Dust(offer_id));
+    e.storage().persistent().remove(&OfferDataKey::Terms(offer_id));
+    e.storage().persistent().remove(&OfferDataKey::DisputeWindow(offer_id));
+    e.storage().persistent().remove(&OfferDataKey::Memo(offer_id));
+    auction_cancel(e, offer_id);
+    commits_remove(e, offer_id, &offer);
+    managers_remove(e, offer_id);
+    fills_remove(e, offer_id);
+    dutch_remove(e, offer_id);
+    peg_remove(e, offer_id);
+    true
+}
+
+// Registers a new active offer under the next offer id and returns the id.
+fn offer_insert(e: &Env, offer: &OfferInfo) -> u32 {
+    let offer_id: u32 = e.storage().instance().get(&DataKey::OfferCount).unwrap_or(0);
+    log!(e, "offer_id = {}", offer_id);
+
+    offer_write(e, offer_id, offer);
+    offer_index(e, offer_id, offer);
+    e.storage().instance().set(&DataKey::OfferCount, &(offer_id + 1));
+    e.storage().instance().extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    offer_id
+}
+
+// Adds an active offer to the lookup indexes.
+fn offer_index(e: &Env, offer_id: u32, offer: &OfferInfo) {
+    index_push(e, &DataKey::OpenOffers, offer_id);
+    index_push(e, &DataKey::OfferorOffers(offer.offeror.clone()), offer_id);
+    index_push(e, &DataKey::PairOffers(offer.send_token.clone(), offer.recv_token.clone()), offer_id);
+    book_insert(e, offer_id, offer);
+}
+
+// Removes an offer which is no longer active from the lookup indexes.
+fn offer_unindex(e: &Env, offer_id: u32, offer: &OfferInfo) {
+    index_remove(e, &DataKey::OpenOffers, offer_id);
+    index_remove(e, &DataKey::OfferorOffers(offer.offeror.clone()), offer_id);
+    index_remove(e, &DataKey::PairOffers(offer.send_token.clone(), offer.recv_token.clone()), offer_id);
+    book_remove(e, offer_id, offer);
+}
+
+fn index_push(e: &Env, key: &DataKey, offer_id: u32) {
+    let mut ids: Vec<u32> = e.storage().persistent().get(key).unwrap_or(Vec::new(e));
+    ids.push_back(offer_id);
+    e.storage().persistent().set(key, &ids);
+    ttl_bump(e, key);
+}
+
+fn index_remove(e: &Env, key: &DataKey, offer_id: u32) {
+    let mut ids: Vec<u32> = e.storage().persistent().get(key).unwrap_or(Vec::new(e));
+    if let Some(i) = ids.first_index_of(offer_id) {
+        ids.remove(i);
+        if ids.is_empty() {
+            e.storage().persistent().remove(key);
+        } else {
+            e.storage().persistent().set(key, &ids);
+            ttl_bump(e, key);
+        }
+    }
+}
+
+fn index_page(e: &Env, key: &DataKey, cursor: u32, limit: u32) -> Vec<u32> {
+    let ids: Vec<u32> = e.storage().persistent().get(key).unwrap_or(Vec::new(e));
+    if cursor >= ids.len() {
+        return Vec::new(e);
+    }
+    let end = ids.len().min(cursor.saturating_add(limit));
+    ids.slice(cursor..end)
 }
@@ -0,0 +1,193 @@
+use soroban_sdk::{ contractclient, contracttype, Address, Env, Symbol, U256 };
+
+use crate::storage_types::{ BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, 
+    ConfigKey, OfferDataKey, OfferInfo, OfferStatus, OraclePeg, PriceBand, Role
+};
+use crate::auction::{ auction_get };
+use crate::commit::{ commit_locked };
+use crate::decimals::{ decimals_get };
+use crate::dutch::{ dutch_get };
+use crate::offer::{ offer_load };
+use crate::role::{ role_check };
+use crate::error::SwapError;
+
+
+/*
+Offers on a pair with a price band must ask a price within `max_deviation` (in 1/10000)
+of the oracle's price. The band is checked when offers are created or updated, and also
+on every fill when `check_accept` is set, as dutch auctions and stale offers drift.
+The oracle follows the Reflector interface, pricing every asset in a common base.
+
+A pegged offer has no fixed price: whenever it's loaded its recv_amount is set to the
+oracle value of its send_amount plus the maker's spread, so the maker doesn't have
+to keep updating the offer as the market moves.
+*/
+
+#[contracttype]
+pub enum Asset {
+    Stellar(Address),
+    Other(Symbol),
+}
+
+#[contracttype]
+pub struct PriceData {
+    pub price: i128,
+    pub timestamp: u64,
+}
+
+// The part of the Reflector oracle interface used by the contract.
+#[allow(dead_code)]
+#[contractclient(name = "OracleClient")]
+pub trait OracleInterface {
+    fn lastprice(env: Env, asset: Asset) -> Option<PriceData>;
+}
+
+pub fn oracle_get(e: &Env) -> Option<Address> {
+    let key = ConfigKey::Oracle;
+
+    e.storage().instance().get(&key)
+}
+
+pub fn oracle_set(e: &Env, caller: &Address, oracle: &Option<Address>) -> Result<(), SwapError> {
+    let key = ConfigKey::Oracle;
+    role_check(e, Role::Operator, caller)?;
+    match oracle {
+        Some(oracle) => e.storage().instance().set(&key, oracle),
+        None => e.storage().instance().remove(&key),
+    }
+    Ok(())
+}
+
+pub fn band_get(e: &Env, send_token: &Address, recv_token: &Address) -> Option<PriceBand> {
+    let key = ConfigKey::PriceBand(send_token.clone(), recv_token.clone());
+
+    e.storage().instance().get(&key)
+}
+
+pub fn band_set(e: &Env, caller: &Address, send_token: &Address, recv_token: &Address, band: &Option<PriceBand>) -> Result<(), SwapError> {
+    let key = ConfigKey::PriceBand(send_token.clone(), recv_token.clone());
+    role_check(e, Role::Operator, caller)?;
+    match band {
+        Some(band) => e.storage().instance().set(&key, band),
+        None => e.storage().instance().remove(&key),
+    }
+    Ok(())
+}
+
+// Checks the price of `send_amount` for `recv_amount` against the band of the pair, if any.
+// With `on_accept` only bands that re-check fills apply.
+pub fn band_check(e: &Env, 
+    send_token: &Address, 
+    recv_token: &Address, 
+    send_amount: u64, 
+    recv_amount: u64, 
+    on_accept: bool
+) -> Result<(), SwapError> {
+    let band = match band_get(e, send_token, recv_token) {
+        Some(band) if band.check_accept || !on_accept => band,
+        _ => return Ok(()),
+    };
+
+    let fair = oracle_value(e, send_token, recv_token, send_amount, band.max_age)?;
+    let asked = U256::from_u128(e, recv_amount as u128);
+    let deviation = if asked > fair { asked.sub(&fair) } else { fair.sub(&asked) };
+    if deviation.mul(&U256::from_u32(e, 10000)) > fair.mul(&U256::from_u32(e, band.max_deviation)) {
+        return Err(SwapError::PriceOutOfBand);
+    }
+    Ok(())
+}
+
+// The value of `send_amount` of send_token in recv_token at the oracle prices:
+// send_amount * send_price * 10^recv_decimals / (recv_price * 10^send_decimals)
+fn oracle_value(e: &Env, send_token: &Address, recv_token: &Address, send_amount: u64, max_age: u64) -> Result<U256, SwapError> {
+    let oracle = OracleClient::new(e, &oracle_get(e).ok_or(SwapError::OracleUnavailable)?);
+    let send_price = oracle_price(e, &oracle, send_token, max_age)?;
+    let recv_price = oracle_price(e, &oracle, recv_token, max_age)?;
+    let send_decimals = decimals_get(e, send_token).ok_or(SwapError::NoDecimals)?;
+    let recv_decimals = decimals_get(e, recv_token).ok_or(SwapError::NoDecimals)?;
+
+    let ten = U256::from_u32(e, 10);
+    Ok(U256::from_u128(e, send_amount as u128)
+        .mul(&U256::from_u128(e, send_price))
+        .mul(&ten.pow(recv_decimals))
+        .div(&U256::from_u128(e, recv_price).mul(&ten.pow(send_decimals))))
+}
+
+// Value of `send_amount` of send_token in recv_token at oracle prices no older than `max_age` seconds.
+pub fn oracle_convert(e: &Env, send_token: &Address, recv_token: &Address, send_amount: u64, max_age: u64) -> Result<u64, SwapError> {
+    let value = oracle_value(e, send_token, recv_token, send_amount, max_age)?;
+    value.to_u128().and_then(|value| u64::try_from(value).ok()).ok_or(SwapError::Overflow)
+}
+
+fn oracle_price(e: &Env, oracle: &OracleClient, token: &Address, max_age: u64) -> Result<u128, SwapError> {
+    let data = oracle.lastprice(&Asset::Stellar(token.clone())).ok_or(SwapError::OracleUnavailable)?;
+    if data.price <= 0 || e.ledger().timestamp().saturating_sub(data.timestamp) > max_age {
+        return Err(SwapError::OracleUnavailable);
+    }
+    Ok(data.price as u128)
+}
+
+pub fn peg_get(e: &Env, offer_id: u32) -> Option<OraclePeg> {
+    let key = OfferDataKey::Peg(offer_id);
+
+    e.storage().persistent().get(&key)
+}
+
+// Pegs the price of the offer to the oracle price plus `spread`.
+// Must be authorized by offeror.
+pub fn peg_set(e: &Env, offeror: &Address, offer_id: u32, peg: &OraclePeg) -> Result<(), SwapError> {
+    let offer = offer_load(e, offer_id)?;
+    if offer.offeror != offeror.clone() {
+        return Err(SwapError::InvalidOfferor);
+    }
+    if offer.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+    if auction_get(e, offer_id).is_some() {
+        return Err(SwapError::OfferInAuction);
+    }
+    if dutch_get(e, offer_id).is_some() {
+        return Err(SwapError::DynamicPrice);
+    }
+    if commit_locked(e, offer_id) {
+        return Err(SwapError::OfferLocked);
+    }
+    if peg.spread <= -10000 {
+        return Err(SwapError::PriceOutOfBand);
+    }
+    // The oracle must be able to price the pair right away.
+    oracle_value(e, &offer.send_token, &offer.recv_token, offer.send_amount, peg.max_age)?;
+
+    offeror.require_auth();
+
+    let key = OfferDataKey::Peg(offer_id);
+    e.storage().persistent().set(&key, peg);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    Ok(())
+}
+
+pub fn peg_remove(e: &Env, offer_id: u32) {
+    e.storage().persistent().remove(&OfferDataKey::Peg(offer_id));
+}
+
+// Sets recv_amount of a pegged offer to the current oracle value of its remaining send_amount.
+pub fn peg_apply(e: &Env, offer_id: u32, offer: &mut OfferInfo) -> Result<(), SwapError> {
+    let peg = match peg_get(e, offer_id) {
+        Some(peg) => peg,
+        None => return Ok(()),
+    };
+
+    let fair = oracle_value(e, &offer.send_token, &offer.recv_token, offer.send_amount, peg.max_age)?;
+    let recv_amount = fair
+        .mul(&U256::from_u32(e, (10000 + peg.spread) as u32))
+        .div(&U256::from_u32(e, 10000))
+        .to_u128()
+        .filter(|amount| *amount <= u64::MAX as u128)
+        .ok_or(SwapError::AmountTooLarge)?;
+
+    offer.recv_amount = (recv_amount as u64).max(1);
+    if offer.recv_amount < offer.min_recv_amount {
+        offer.min_recv_amount = offer.recv_amount;
+    }
+    Ok(())
+}
@@ -0,0 +1,139 @@
+const ORDER: Symbol = symbol_short!("ORDER");
+
+use soroban_sdk::{ token, Address, Env, IntoVal, symbol_short, Symbol };
+
+use crate::storage_types::{ BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT,
+    OrderKey, SignedOrder
+};
+use crate::allow::{ allow_pair_get };
+use crate::compliance::{ ban_check, compliance_check };
+use crate::fee::{ fee_get, fee_accrue, fee_in, calculate_fee, volume_add };
+use crate::pause::{ pause_check };
+use crate::stats::{ stats_record };
+use crate::throttle::{ throttle_accept };
+use crate::escrow::{ escrow_in, escrow_in_exact, escrow_out };
+use crate::error::SwapError;
+
+
+/*
+Signed orders let makers quote without escrowing anything up front:
+
+1. The maker signs an authorization for `fill_signed_order` with just the order as its
+   arguments, plus the transfer of `send_amount` of send_token to this contract.
+2. Any taker submits the order with that signature. The contract checks the maker's
+   authorization, pulls the send_token from the maker and swaps it for the taker's
+   recv_token in one go.
+
+The maker's authorization doesn't name the taker, so the send_token passes through the
+contract on its way to the taker. An order is filled whole, at most once, and its maker
+fee comes out of the recv_token the maker gets. With a fee token configured each side pays
+its own fee in it instead, so the maker also authorizes the transfer of that fee.
+
+Each nonce of a maker is good for one order. Filling the order uses the nonce up, and so
+does cancelling it with `cancel_nonce`. `cancel_all_before` invalidates every order with
+a lower nonce at once, without the maker having to submit them one by one.
+*/
+
+pub fn order_fill(e: &Env, taker: &Address, order: &SignedOrder) -> Result<(), SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+    if !allow_pair_get(e, &order.send_token, &order.recv_token) {
+        return Err(SwapError::TokenNotAllowed);
+    }
+    if order.send_amount == 0 || order.recv_amount == 0 {
+        return Err(SwapError::ZeroAmount);
+    }
+    if e.ledger().sequence() >= order.expire_ledger {
+        return Err(SwapError::OfferExpired);
+    }
+    if taker.clone() == order.maker {
+        return Err(SwapError::AcceptorNotAllowed);
+    }
+    if nonce_used(e, &order.maker, order.nonce) {
+        return Err(SwapError::OfferNotActive);
+    }
+    ban_check(e, &order.maker)?;
+    ban_check(e, taker)?;
+    throttle_accept(e, taker)?;
+    compliance_check(e, &order.maker, &order.send_token, order.send_amount)?;
+    compliance_check(e, taker, &order.recv_token, order.recv_amount)?;
+
+    order.maker.require_auth_for_args((order.clone(),).into_val(e));
+    taker.require_auth();
+
+    nonce_use(e, &order.maker, order.nonce);
+
+    let fee_info = fee_get(e)?;
+    let taker_fee = calculate_fee(e, &order.recv_token, fee_info.taker_fee_rate, order.recv_amount, Some(taker), true);
+    let maker_fee = calculate_fee(e, &order.recv_token, fee_info.maker_fee_rate, order.recv_amount, Some(&order.maker), false);
+    let (fee_token, taker_fee) = fee_in(e, &order.recv_token, taker_fee)?;
+    let (_, maker_fee) = fee_in(e, &order.recv_token, maker_fee)?;
+    let fee_amount = taker_fee.checked_add(maker_fee).ok_or(SwapError::Overflow)?;
+
+    escrow_in_exact(e, &order.send_token, &order.maker, order.send_amount)?;
+    let (fee_received, maker_amount) = if fee_token == order.recv_token {
+        let maker_amount = order.recv_amount.checked_sub(maker_fee).ok_or(SwapError::Overflow)?;
+        (escrow_in(e, &fee_token, taker, fee_amount), maker_amount)
+    } else {
+        let received = escrow_in(e, &fee_token, taker, taker_fee) + escrow_in(e, &fee_token, &order.maker, maker_fee);
+        (received, order.recv_amount)
+    };
+    fee_accrue(e, &fee_token, fee_received);
+    token::Client::new(e, &order.recv_token).transfer(taker, &order.maker, &(maker_amount as i128));
+    escrow_out(e, &order.send_token, taker, order.send_amount);
+
+    volume_add(e, taker, &order.recv_token, order.recv_amount);
+    stats_record(e, &order.send_token, &order.recv_token, order.send_amount, order.recv_amount);
+
+    // emit SignedOrderFilled event
+    e.events().publish((ORDER, symbol_short!("SFill")),
+        (order.maker.clone(), order.nonce, taker.clone(), fee_amount)
+    );
+
+    Ok(())
+}
+
+// Whether the order of maker with `nonce` can no longer be filled, as it was filled or cancelled.
+pub fn nonce_used(e: &Env, maker: &Address, nonce: u64) -> bool {
+    nonce < nonce_min_get(e, maker) || e.storage().persistent().has(&OrderKey::Used(maker.clone(), nonce))
+}
+
+// Lowest nonce of maker still valid.
+pub fn nonce_min_get(e: &Env, maker: &Address) -> u64 {
+    e.storage().persistent().get(&OrderKey::MinNonce(maker.clone())).unwrap_or(0)
+}
+
+pub fn nonce_cancel(e: &Env, maker: &Address, nonce: u64) {
+    maker.require_auth();
+
+    nonce_use(e, maker, nonce);
+
+    // emit NonceCancelled event
+    e.events().publish((ORDER, symbol_short!("SCancel")),
+        (maker.clone(), nonce)
+    );
+}
+
+// Cancels every order of maker with a nonce below `nonce`. Lowering the bound again does nothing.
+pub fn nonce_cancel_before(e: &Env, maker: &Address, nonce: u64) {
+    maker.require_auth();
+
+    if nonce <= nonce_min_get(e, maker) {
+        return;
+    }
+    let key = OrderKey::MinNonce(maker.clone());
+    e.storage().persistent().set(&key, &nonce);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+
+    // emit NoncesCancelled event
+    e.events().publish((ORDER, symbol_short!("SCancelTo")),
+        (maker.clone(), nonce)
+    );
+}
+
+fn nonce_use(e: &Env, maker: &Address, nonce: u64) {
+    let key = OrderKey::Used(maker.clone(), nonce);
+    e.storage().persistent().set(&key, &true);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
@@ -0,0 +1,128 @@
+use soroban_sdk::{ Address, Env, Vec };
+
+use crate::storage_types::{ BookLevel, DataKey, OfferInfo };
+use crate::ttl::{ ttl_bump };
+
+use crate::decimals::{ price_get };
+use crate::offer::{ 
+    offer_load, offer_load_live, offer_quote, 
+};
+use crate::error::SwapError;
+
+
+// Active offers of a pair sorted by price, the cheapest recv_token per send_token first.
+// Offers at the same price keep their insertion order (price-time priority).
+pub fn book_get(e: &Env, send_token: &Address, recv_token: &Address) -> Vec<u32> {
+    let key = DataKey::Book(send_token.clone(), recv_token.clone());
+    match e.storage().persistent().get(&key) {
+        Some(ids) => {
+            ttl_bump(e, &key);
+            ids
+        },
+        None => Vec::new(e),
+    }
+}
+
+pub fn book_best(e: &Env, send_token: &Address, recv_token: &Address) -> Option<u32> {
+    book_get(e, send_token, recv_token).first()
+}
+
+// The first offer of the book that can be filled now, with its price (as in `price_get`)
+// and the send_token it has left.
+pub fn book_best_price(e: &Env, send_token: &Address, recv_token: &Address) -> Result<(u32, u128, u64), SwapError> {
+    for offer_id in book_get(e, send_token, recv_token).iter() {
+        // An offer that can't be priced right now, like one pegged to a stale oracle
+        // price, is passed over.
+        let offer = match offer_load_live(e, offer_id) {
+            Ok(offer) => offer,
+            Err(_) => continue,
+        };
+        if offer_quote(e, offer_id, &offer, offer.recv_amount, None).is_err() {
+            continue;
+        }
+        let price = price_get(e, send_token, recv_token, offer.send_amount, offer.recv_amount)?;
+        return Ok((offer_id, price, offer.send_amount));
+    }
+    Err(SwapError::OfferNotFound)
+}
+
+pub fn book_insert(e: &Env, offer_id: u32, offer: &OfferInfo) {
+    let mut ids = book_get(e, &offer.send_token, &offer.recv_token);
+
+    // Insert after the last offer with a better or equal price.
+    let mut i = ids.len();
+    while i > 0 {
+        let other = offer_load(e, ids.get_unchecked(i - 1)).unwrap();
+        if price_cmp(&other, offer) != core::cmp::Ordering::Greater {
+            break;
+        }
+        i -= 1;
+    }
+    ids.insert(i, offer_id);
+    book_write(e, &offer.send_token, &offer.recv_token, &ids);
+}
+
+pub fn book_remove(e: &Env, offer_id: u32, offer: &OfferInfo) {
+    let mut ids = book_get(e, &offer.send_token, &offer.recv_token);
+    if let Some(i) = ids.first_index_of(offer_id) {
+        ids.remove(i);
+        book_write(e, &offer.send_token, &offer.recv_token, &ids);
+    }
+}
+
+// Aggregates the offers of the book by price. Returns up to `limit` levels starting from
+// the `cursor`-th best one.
+pub fn book_levels(e: &Env, send_token: &Address, recv_token: &Address, cursor: u32, limit: u32) -> Vec<BookLevel> {
+    let mut levels: Vec<BookLevel> = Vec::new(e);
+    let mut level_index: u32 = 0;
+    let mut last: Option<OfferInfo> = None;
+
+    for offer_id in book_get(e, send_token, recv_token).iter() {
+        let offer = offer_load(e, offer_id).unwrap();
+        let same_level = match &last {
+            Some(last) => price_cmp(last, &offer) == core::cmp::Ordering::Equal,
+            None => false,
+        };
+        if !same_level && last.is_some() {
+            level_index += 1;
+        }
+        if level_index >= cursor.saturating_add(limit) {
+            break;
+        }
+        if level_index >= cursor {
+            if same_level {
+                let mut level = levels.pop_back_unchecked();
+                level.send_amount += offer.send_amount;
+                level.recv_amount += offer.recv_amount;
+                level.offer_count += 1;
+                levels.push_back(level);
+            } else {
+                levels.push_back(BookLevel {
+                    send_amount: offer.send_amount,
+                    recv_amount: offer.recv_amount,
+                    offer_count: 1,
+                });
+            }
+        }
+        last = Some(offer);
+    }
+
+    levels
+}
+
+// Compares the prices (recv_amount / send_amount) of two offers.
+fn price_cmp(a: &OfferInfo, b: &OfferInfo) -> core::cmp::Ordering {
+    let a_price = (a.recv_amount as u128) * (b.send_amount as u128);
+    let b_price = (b.recv_amount as u128) * (a.send_amount as u128);
+    a_price.cmp(&b_price)
+}
+
+fn book_write(e: &Env, send_token: &Address, recv_token: &Address, ids: &Vec<u32>) {
+    let key = DataKey::Book(send_token.clone(), recv_token.clone());
+    if ids.is_empty() {
+        e.storage().persistent().remove(&key);
+        return;
+    }
+    e.storage().persistent().set(&key, ids);
+    ttl_bump(e, &key);
+}
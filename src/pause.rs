@@ -0,0 +1,22 @@
+use soroban_sdk::{ Address, Env };
+
+use crate::storage_types::{ DataKey, Role };
+
+use crate::role::{ 
+    role_check, 
+};
+use crate::error::SwapError;
+
+
+pub fn pause_check(e: &Env) -> bool {
+    let key = DataKey::Paused;
+
+    e.storage().instance().get::<_, bool>(&key).unwrap_or(false)
+}
+
+pub fn pause_set(e: &Env, caller: &Address, paused: bool) -> Result<(), SwapError> {
+    let key = DataKey::Paused;
+    role_check(e, Role::Pauser, caller)?;
+    e.storage().instance().set(&key, &paused);
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use soroban_sdk::{ Address, Env };
+
+use crate::storage_types::{ FREEZE_FEES, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, 
+    DataKey, Role
+};
+
+use crate::freeze::{ freeze_check };
+use crate::role::{ 
+    role_check, 
+};
+use crate::escrow::{ escrow_out };
+use crate::error::SwapError;
+
+
+// Share of the taker fee (in basis points) credited to the referrer of a fill.
+pub fn referral_share_get(e: &Env) -> u32 {
+    let key = DataKey::ReferralShare;
+
+    e.storage().instance().get(&key).unwrap_or(0)
+}
+
+pub fn referral_share_set(e: &Env, caller: &Address, share: u32) -> Result<(), SwapError> {
+    let key = DataKey::ReferralShare;
+    role_check(e, Role::FeeManager, caller)?;
+    freeze_check(e, FREEZE_FEES)?;
+    if share > 10000 {
+        return Err(SwapError::FeeRateTooHigh);
+    }
+    e.storage().instance().set(&key, &share);
+    Ok(())
+}
+
+pub fn referral_accrued_get(e: &Env, referrer: &Address, token: &Address) -> u64 {
+    let key = DataKey::ReferralFees(referrer.clone(), token.clone());
+
+    e.storage().persistent().get(&key).unwrap_or(0)
+}
+
+// Credits the referrer's part of `fee_amount` and returns it.
+pub fn referral_accrue(e: &Env, referrer: &Address, token: &Address, fee_amount: u64) -> u64 {
+    let amount = fee_amount * (referral_share_get(e) as u64) / 10000;
+    if amount == 0 {
+        return 0;
+    }
+
+    let key = DataKey::ReferralFees(referrer.clone(), token.clone());
+    let accrued = referral_accrued_get(e, referrer, token) + amount;
+    e.storage().persistent().set(&key, &accrued);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    amount
+}
+
+pub fn referral_claim(e: &Env, referrer: &Address, token: &Address) -> u64 {
+    referrer.require_auth();
+
+    let amount = referral_accrued_get(e, referrer, token);
+    if amount == 0 {
+        return 0;
+    }
+
+    e.storage().persistent().remove(&DataKey::ReferralFees(referrer.clone(), token.clone()));
+    escrow_out(e, token, referrer, amount);
+    amount
+}
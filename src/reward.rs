@@ -0,0 +1,117 @@
+const REWARD: Symbol = symbol_short!("REWARD");
+
+use soroban_sdk::{ Address, Env, symbol_short, Symbol };
+
+use crate::storage_types::{ PRICE_DECIMALS, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT,
+    RewardKey, Role
+};
+use crate::role::{ role_check };
+use crate::escrow::{ escrow_in, escrow_out };
+use crate::error::SwapError;
+
+
+/*
+Liquidity mining for makers. The admin funds a budget of the reward token, and every fill
+of an offer earns its offeror rewards at the rate set for the token it received, until
+the budget runs out. Makers claim their rewards whenever they like.
+*/
+
+pub fn reward_token_get(e: &Env) -> Option<Address> {
+    e.storage().instance().get(&RewardKey::Token)
+}
+
+// The reward token can only be changed while no budget is left and all rewards are claimed.
+pub fn reward_token_set(e: &Env, caller: &Address, token: &Option<Address>) -> Result<(), SwapError> {
+    role_check(e, Role::Admin, caller)?;
+    if reward_budget_get(e) > 0 || reward_owed_get(e) > 0 {
+        return Err(SwapError::OfferLocked);
+    }
+    match token {
+        Some(token) => e.storage().instance().set(&RewardKey::Token, token),
+        None => e.storage().instance().remove(&RewardKey::Token),
+    }
+    Ok(())
+}
+
+pub fn reward_rate_get(e: &Env, token: &Address) -> Option<u128> {
+    e.storage().instance().get(&RewardKey::Rate(token.clone()))
+}
+
+// Reward token units earned per unit of `token` a maker receives, with PRICE_DECIMALS
+// decimals. None stops rewarding fills in token.
+pub fn reward_rate_set(e: &Env, caller: &Address, token: &Address, rate: &Option<u128>) -> Result<(), SwapError> {
+    let key = RewardKey::Rate(token.clone());
+    role_check(e, Role::Admin, caller)?;
+    match rate {
+        Some(rate) => e.storage().instance().set(&key, rate),
+        None => e.storage().instance().remove(&key),
+    }
+    Ok(())
+}
+
+pub fn reward_budget_get(e: &Env) -> u64 {
+    e.storage().instance().get(&RewardKey::Budget).unwrap_or(0)
+}
+
+fn reward_owed_get(e: &Env) -> u64 {
+    e.storage().instance().get(&RewardKey::Owed).unwrap_or(0)
+}
+
+// Adds `amount` of the reward token from funder to the budget.
+pub fn reward_fund(e: &Env, funder: &Address, amount: u64) -> Result<(), SwapError> {
+    role_check(e, Role::Admin, funder)?;
+    let token = reward_token_get(e).ok_or(SwapError::TokenNotAllowed)?;
+    if amount == 0 {
+        return Err(SwapError::ZeroAmount);
+    }
+
+    let amount = escrow_in(e, &token, funder, amount);
+    let budget = reward_budget_get(e).checked_add(amount).ok_or(SwapError::Overflow)?;
+    e.storage().instance().set(&RewardKey::Budget, &budget);
+
+    // emit RewardFunded event
+    e.events().publish((REWARD, symbol_short!("RFund")),
+        (funder.clone(), amount)
+    );
+
+    Ok(())
+}
+
+pub fn reward_accrued_get(e: &Env, maker: &Address) -> u64 {
+    e.storage().persistent().get(&RewardKey::Accrued(maker.clone())).unwrap_or(0)
+}
+
+// Credits maker the rewards for receiving `amount` of token in a fill, as far as the
+// budget goes.
+pub fn reward_accrue(e: &Env, maker: &Address, token: &Address, amount: u64) {
+    let rate = match reward_rate_get(e, token) {
+        Some(rate) if reward_token_get(e).is_some() => rate,
+        _ => return,
+    };
+    let budget = reward_budget_get(e);
+    let reward = ((amount as u128) * rate / 10u128.pow(PRICE_DECIMALS)).min(budget as u128) as u64;
+    if reward == 0 {
+        return;
+    }
+
+    e.storage().instance().set(&RewardKey::Budget, &(budget - reward));
+    e.storage().instance().set(&RewardKey::Owed, &(reward_owed_get(e) + reward));
+    let key = RewardKey::Accrued(maker.clone());
+    e.storage().persistent().set(&key, &(reward_accrued_get(e, maker) + reward));
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
+
+pub fn reward_claim(e: &Env, maker: &Address) -> u64 {
+    maker.require_auth();
+
+    let amount = reward_accrued_get(e, maker);
+    let token = match reward_token_get(e) {
+        Some(token) if amount > 0 => token,
+        _ => return 0,
+    };
+
+    e.storage().persistent().remove(&RewardKey::Accrued(maker.clone()));
+    e.storage().instance().set(&RewardKey::Owed, &(reward_owed_get(e) - amount));
+    escrow_out(e, &token, maker, amount);
+    amount
+}
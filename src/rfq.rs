@@ -0,0 +1,254 @@
+const RFQ: Symbol = symbol_short!("RFQ");
+
+use soroban_sdk::{ token, Address, Env, symbol_short, Symbol };
+
+use crate::storage_types::{ INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, 
+    DataKey, OfferStatus, Quote, QuoteRequest
+};
+use crate::allow::{ allow_pair_get };
+use crate::fee::{ fee_check, fee_get, fee_accrue, calculate_fee, volume_add };
+use crate::stats::{ stats_record };
+use crate::pause::{ pause_check };
+use crate::escrow::{ escrow_in, escrow_in_exact, escrow_out };
+use crate::error::SwapError;
+
+
+/*
+Request-for-quote flow, for block trades where the taker names the size:
+
+1. Taker calls `request` with the pair and the send_amount of `send_token` to sell.
+2. Makers call `quote` to answer with the recv_amount of `recv_token` they pay for it,
+   escrowing that amount plus the maker fee. A quote is firm until its `expire_ledger`.
+3. Taker calls `accept` on the quote of their choice before both the request and the
+   quote expire. The taker pays send_amount to the maker plus the taker fee and gets
+   the escrowed recv_amount.
+4. Makers call `withdraw` to take back a quote that wasn't accepted, once it has
+   expired or its request is no longer active.
+*/
+
+pub fn rfq_request(
+    e: &Env,
+    taker: &Address,
+    send_token: &Address,
+    recv_token: &Address,
+    send_amount: u64,
+    expire_ledger: u32,
+) -> Result<u32, SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+    if !fee_check(e) {
+        return Err(SwapError::FeeNotSet);
+    }
+    if !allow_pair_get(e, recv_token, send_token) {
+        return Err(SwapError::TokenNotAllowed);
+    }
+    if send_amount == 0 {
+        return Err(SwapError::ZeroAmount);
+    }
+    if expire_ledger <= e.ledger().sequence() {
+        return Err(SwapError::InvalidExpiration);
+    }
+
+    taker.require_auth();
+
+    let request_id: u32 = e.storage().instance().get(&DataKey::RequestCount).unwrap_or(0);
+    request_write(e, request_id, &QuoteRequest {
+        taker: taker.clone(),
+        send_token: send_token.clone(),
+        recv_token: recv_token.clone(),
+        send_amount,
+        expire_ledger,
+        status: OfferStatus::ACTIVE,
+    });
+    e.storage().instance().set(&DataKey::RequestCount, &(request_id + 1));
+    e.storage().instance().extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+
+    // emit QuoteRequested event
+    e.events().publish((RFQ, symbol_short!("RRequest")), 
+        (request_id, taker.clone(), send_token.clone(), recv_token.clone(), send_amount, expire_ledger)
+    );
+
+    Ok(request_id)
+}
+
+pub fn rfq_cancel(e: &Env, taker: &Address, request_id: u32) -> Result<(), SwapError> {
+    let mut request = request_load(e, request_id)?;
+    if request.taker != taker.clone() {
+        return Err(SwapError::Unauthorized);
+    }
+    if request.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+
+    taker.require_auth();
+
+    request.status = OfferStatus::CANCEL;
+    request_write(e, request_id, &request);
+
+    // emit RequestCanceled event
+    e.events().publish((RFQ, symbol_short!("RCancel")), 
+        (taker.clone(), request_id)
+    );
+
+    Ok(())
+}
+
+pub fn rfq_quote(
+    e: &Env,
+    maker: &Address,
+    request_id: u32,
+    recv_amount: u64,
+    expire_ledger: u32,
+) -> Result<u32, SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+
+    let request = request_load(e, request_id)?;
+    if request.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+    if e.ledger().sequence() >= request.expire_ledger {
+        return Err(SwapError::OfferExpired);
+    }
+    if recv_amount == 0 {
+        return Err(SwapError::ZeroAmount);
+    }
+    if expire_ledger <= e.ledger().sequence() {
+        return Err(SwapError::InvalidExpiration);
+    }
+
+    maker.require_auth();
+
+    let fee_info = fee_get(e)?;
+    let fee_amount = calculate_fee(e, &request.recv_token, fee_info.maker_fee_rate, recv_amount, Some(maker), false);
+    let token_client = token::Client::new(e, &request.recv_token);
+    if token_client.balance(maker) < ((recv_amount + fee_amount) as i128) {
+        return Err(SwapError::InsufficientBalance);
+    }
+    escrow_in_exact(e, &request.recv_token, maker, recv_amount + fee_amount)?;
+
+    let quote_id: u32 = e.storage().instance().get(&DataKey::QuoteCount).unwrap_or(0);
+    quote_write(e, quote_id, &Quote {
+        maker: maker.clone(),
+        request_id,
+        recv_amount,
+        fee_amount,
+        expire_ledger,
+        status: OfferStatus::ACTIVE,
+    });
+    e.storage().instance().set(&DataKey::QuoteCount, &(quote_id + 1));
+    e.storage().instance().extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+
+    // emit QuoteSubmitted event
+    e.events().publish((RFQ, symbol_short!("RQuote")), 
+        (quote_id, maker.clone(), request_id, recv_amount, expire_ledger)
+    );
+
+    Ok(quote_id)
+}
+
+pub fn rfq_withdraw(e: &Env, maker: &Address, quote_id: u32) -> Result<u64, SwapError> {
+    let mut quote = quote_load(e, quote_id)?;
+    if quote.maker != maker.clone() {
+        return Err(SwapError::Unauthorized);
+    }
+    if quote.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+
+    // Quotes are firm while both the quote and its request are live.
+    let request = request_load(e, quote.request_id)?;
+    let now = e.ledger().sequence();
+    if request.status == OfferStatus::ACTIVE && now < request.expire_ledger && now < quote.expire_ledger {
+        return Err(SwapError::OfferLocked);
+    }
+
+    maker.require_auth();
+
+    let refund = quote.recv_amount + quote.fee_amount;
+    escrow_out(e, &request.recv_token, maker, refund);
+    quote.status = OfferStatus::CANCEL;
+    quote_write(e, quote_id, &quote);
+
+    // emit QuoteWithdrawn event
+    e.events().publish((RFQ, symbol_short!("RWithdraw")), 
+        (maker.clone(), quote_id, refund)
+    );
+
+    Ok(refund)
+}
+
+pub fn rfq_accept(e: &Env, taker: &Address, quote_id: u32) -> Result<(), SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+
+    let mut quote = quote_load(e, quote_id)?;
+    let mut request = request_load(e, quote.request_id)?;
+    if request.taker != taker.clone() {
+        return Err(SwapError::Unauthorized);
+    }
+    if quote.status != OfferStatus::ACTIVE || request.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+    let now = e.ledger().sequence();
+    if now >= quote.expire_ledger || now >= request.expire_ledger {
+        return Err(SwapError::OfferExpired);
+    }
+
+    taker.require_auth();
+
+    let fee_info = fee_get(e)?;
+    let taker_fee = calculate_fee(e, &request.send_token, fee_info.taker_fee_rate, request.send_amount, Some(taker), true);
+    let send_token_client = token::Client::new(e, &request.send_token);
+    if send_token_client.balance(taker) < ((request.send_amount + taker_fee) as i128) {
+        return Err(SwapError::InsufficientBalance);
+    }
+
+    let fee_received = escrow_in(e, &request.send_token, taker, taker_fee);
+    fee_accrue(e, &request.send_token, fee_received);
+    send_token_client.transfer(taker, &quote.maker, &(request.send_amount as i128));
+    escrow_out(e, &request.recv_token, taker, quote.recv_amount);
+    fee_accrue(e, &request.recv_token, quote.fee_amount);
+
+    request.status = OfferStatus::COMPLETE;
+    request_write(e, quote.request_id, &request);
+    quote.status = OfferStatus::COMPLETE;
+    quote_write(e, quote_id, &quote);
+    volume_add(e, taker, &request.send_token, request.send_amount);
+    // The maker sold recv_token for the taker's send_token.
+    stats_record(e, &request.recv_token, &request.send_token, quote.recv_amount, request.send_amount);
+
+    // emit QuoteAccepted event
+    e.events().publish((RFQ, symbol_short!("RAccept")), 
+        (taker.clone(), quote.request_id, quote_id, request.send_amount, quote.recv_amount)
+    );
+
+    Ok(())
+}
+
+pub fn request_load(e: &Env, request_id: u32) -> Result<QuoteRequest, SwapError> {
+    let key = DataKey::Request(request_id);
+
+    e.storage().persistent().get(&key).ok_or(SwapError::RequestNotFound)
+}
+
+pub fn quote_load(e: &Env, quote_id: u32) -> Result<Quote, SwapError> {
+    let key = DataKey::Quote(quote_id);
+
+    e.storage().persistent().get(&key).ok_or(SwapError::QuoteNotFound)
+}
+
+fn request_write(e: &Env, request_id: u32, request: &QuoteRequest) {
+    let key = DataKey::Request(request_id);
+    e.storage().persistent().set(&key, request);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
+
+fn quote_write(e: &Env, quote_id: u32, quote: &Quote) {
+    let key = DataKey::Quote(quote_id);
+    e.storage().persistent().set(&key, quote);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
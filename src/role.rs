@@ -0,0 +1,59 @@
+use soroban_sdk::{ Address, Env };
+
+use crate::storage_types::{ DataKey, Role };
+
+use crate::admin::{ 
+    read_administrator, 
+};
+use crate::error::SwapError;
+
+
+// The admin implicitly holds every role.
+pub fn role_has(e: &Env, role: Role, account: &Address) -> bool {
+    if let Ok(admin) = read_administrator(e) {
+        if admin == *account {
+            return true;
+        }
+    }
+    if role == Role::Admin {
+        return false;
+    }
+
+    let key = DataKey::Role(role, account.clone());
+    e.storage().instance().get::<_, bool>(&key).unwrap_or(false)
+}
+
+// Checks that `caller` holds `role` and authorized the call.
+pub fn role_check(e: &Env, role: Role, caller: &Address) -> Result<(), SwapError> {
+    read_administrator(e)?;
+    if !role_has(e, role, caller) {
+        return Err(SwapError::Unauthorized);
+    }
+    caller.require_auth();
+    Ok(())
+}
+
+pub fn role_grant(e: &Env, role: Role, account: &Address) -> Result<(), SwapError> {
+    let admin = read_administrator(e)?;
+    admin.require_auth();
+    if role == Role::Admin {
+        // admin is handed over with propose_admin / accept_admin
+        return Err(SwapError::Unauthorized);
+    }
+
+    let key = DataKey::Role(role, account.clone());
+    e.storage().instance().set(&key, &true);
+    Ok(())
+}
+
+pub fn role_revoke(e: &Env, role: Role, account: &Address) -> Result<(), SwapError> {
+    let admin = read_administrator(e)?;
+    admin.require_auth();
+    if role == Role::Admin {
+        return Err(SwapError::Unauthorized);
+    }
+
+    let key = DataKey::Role(role, account.clone());
+    e.storage().instance().remove(&key);
+    Ok(())
+}
@@ -0,0 +1,202 @@
+const STAKE: Symbol = symbol_short!("STAKE");
+
+// Reward per staked unit is counted in 1/REWARD_SCALE of a unit.
+const REWARD_SCALE: u128 = 1_000_000_000_000_000_000;
+
+use soroban_sdk::{ Address, Env, symbol_short, Symbol, Vec };
+
+use crate::storage_types::{ FREEZE_FEES, INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT,
+    Role, StakeKey
+};
+use crate::freeze::{ freeze_check };
+use crate::role::{ role_check };
+use crate::pause::{ pause_check };
+use crate::escrow::{ escrow_in, escrow_out };
+use crate::error::SwapError;
+
+
+/*
+Accounts can stake the stake token with the contract. A stake counts toward the fee
+discounts as if it were held, when the stake token is the discount token.
+
+Stakers can also be paid a share of every fee collected. Each fee token keeps a running
+reward per staked unit, and a staker's rewards are brought up to date before its stake
+changes or it claims.
+*/
+
+pub fn stake_token_get(e: &Env) -> Option<Address> {
+    e.storage().instance().get(&StakeKey::Token)
+}
+
+// The stake token can only be changed while nothing is staked.
+pub fn stake_token_set(e: &Env, caller: &Address, token: &Option<Address>) -> Result<(), SwapError> {
+    role_check(e, Role::FeeManager, caller)?;
+    freeze_check(e, FREEZE_FEES)?;
+    if stake_total_get(e) > 0 {
+        return Err(SwapError::OfferLocked);
+    }
+    match token {
+        Some(token) => e.storage().instance().set(&StakeKey::Token, token),
+        None => e.storage().instance().remove(&StakeKey::Token),
+    }
+    Ok(())
+}
+
+// Share of every fee (in basis points) paid to stakers.
+pub fn stake_share_get(e: &Env) -> u32 {
+    e.storage().instance().get(&StakeKey::Share).unwrap_or(0)
+}
+
+pub fn stake_share_set(e: &Env, caller: &Address, share: u32) -> Result<(), SwapError> {
+    role_check(e, Role::FeeManager, caller)?;
+    freeze_check(e, FREEZE_FEES)?;
+    if share > 10000 {
+        return Err(SwapError::FeeRateTooHigh);
+    }
+    e.storage().instance().set(&StakeKey::Share, &share);
+    Ok(())
+}
+
+pub fn stake_total_get(e: &Env) -> u64 {
+    e.storage().instance().get(&StakeKey::Total).unwrap_or(0)
+}
+
+pub fn stake_get(e: &Env, staker: &Address) -> u64 {
+    e.storage().persistent().get(&StakeKey::Staked(staker.clone())).unwrap_or(0)
+}
+
+pub fn stake(e: &Env, staker: &Address, amount: u64) -> Result<(), SwapError> {
+    if pause_check(e) {
+        return Err(SwapError::Paused);
+    }
+    let token = stake_token_get(e).ok_or(SwapError::TokenNotAllowed)?;
+    if amount == 0 {
+        return Err(SwapError::ZeroAmount);
+    }
+
+    staker.require_auth();
+
+    stake_settle(e, staker);
+    let amount = escrow_in(e, &token, staker, amount);
+    let staked = stake_get(e, staker).checked_add(amount).ok_or(SwapError::Overflow)?;
+    let total = stake_total_get(e).checked_add(amount).ok_or(SwapError::Overflow)?;
+    stake_write(e, staker, staked, total);
+
+    // emit Staked event
+    e.events().publish((STAKE, symbol_short!("Stake")),
+        (staker.clone(), amount)
+    );
+
+    Ok(())
+}
+
+pub fn unstake(e: &Env, staker: &Address, amount: u64) -> Result<(), SwapError> {
+    let token = stake_token_get(e).ok_or(SwapError::TokenNotAllowed)?;
+    if amount == 0 {
+        return Err(SwapError::ZeroAmount);
+    }
+    let staked = stake_get(e, staker);
+    if amount > staked {
+        return Err(SwapError::InsufficientBalance);
+    }
+
+    staker.require_auth();
+
+    stake_settle(e, staker);
+    stake_write(e, staker, staked - amount, stake_total_get(e) - amount);
+    escrow_out(e, &token, staker, amount);
+
+    // emit Unstaked event
+    e.events().publish((STAKE, symbol_short!("Unstake")),
+        (staker.clone(), amount)
+    );
+
+    Ok(())
+}
+
+// Rewards of staker in `token` not claimed yet.
+pub fn stake_rewards_get(e: &Env, staker: &Address, token: &Address) -> u64 {
+    rewards_get(e, staker, token) + reward_pending(e, staker, token)
+}
+
+pub fn stake_claim(e: &Env, staker: &Address, token: &Address) -> u64 {
+    staker.require_auth();
+
+    stake_settle(e, staker);
+    let amount = rewards_get(e, staker, token);
+    if amount == 0 {
+        return 0;
+    }
+
+    e.storage().persistent().remove(&StakeKey::Rewards(staker.clone(), token.clone()));
+    escrow_out(e, token, staker, amount);
+    amount
+}
+
+// Credits the stakers' part of `fee_amount` in `token` and returns it. The part is
+// rounded down to what can be spread evenly over the total stake.
+pub fn stake_accrue(e: &Env, token: &Address, fee_amount: u64) -> u64 {
+    let total = stake_total_get(e) as u128;
+    if total == 0 {
+        return 0;
+    }
+    let share = (fee_amount as u128) * (stake_share_get(e) as u128) / 10000;
+    let per_share = share * REWARD_SCALE / total;
+    if per_share == 0 {
+        return 0;
+    }
+
+    let mut tokens = reward_tokens_get(e);
+    if !tokens.contains(token) {
+        tokens.push_back(token.clone());
+        e.storage().instance().set(&StakeKey::RewardTokens, &tokens);
+    }
+    let key = StakeKey::PerShare(token.clone());
+    e.storage().instance().set(&key, &(per_share_get(e, token) + per_share));
+    e.storage().instance().extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+    (per_share * total / REWARD_SCALE) as u64
+}
+
+fn reward_tokens_get(e: &Env) -> Vec<Address> {
+    e.storage().instance().get(&StakeKey::RewardTokens).unwrap_or(Vec::new(e))
+}
+
+fn per_share_get(e: &Env, token: &Address) -> u128 {
+    e.storage().instance().get(&StakeKey::PerShare(token.clone())).unwrap_or(0)
+}
+
+fn rewards_get(e: &Env, staker: &Address, token: &Address) -> u64 {
+    e.storage().persistent().get(&StakeKey::Rewards(staker.clone(), token.clone())).unwrap_or(0)
+}
+
+// Rewards earned by the stake of staker in `token` since it was last settled.
+fn reward_pending(e: &Env, staker: &Address, token: &Address) -> u64 {
+    let paid: u128 = e.storage().persistent().get(&StakeKey::Paid(staker.clone(), token.clone())).unwrap_or(0);
+    ((stake_get(e, staker) as u128) * (per_share_get(e, token) - paid) / REWARD_SCALE) as u64
+}
+
+// Moves the pending rewards of staker into its claimable rewards, in every fee token.
+fn stake_settle(e: &Env, staker: &Address) {
+    for token in reward_tokens_get(e).iter() {
+        let pending = reward_pending(e, staker, &token);
+        if pending > 0 {
+            let key = StakeKey::Rewards(staker.clone(), token.clone());
+            e.storage().persistent().set(&key, &(rewards_get(e, staker, &token) + pending));
+            e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+        }
+        let key = StakeKey::Paid(staker.clone(), token.clone());
+        e.storage().persistent().set(&key, &per_share_get(e, &token));
+        e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    }
+}
+
+fn stake_write(e: &Env, staker: &Address, staked: u64, total: u64) {
+    let key = StakeKey::Staked(staker.clone());
+    if staked == 0 {
+        e.storage().persistent().remove(&key);
+    } else {
+        e.storage().persistent().set(&key, &staked);
+        e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    }
+    e.storage().instance().set(&StakeKey::Total, &total);
+}
@@ -0,0 +1,65 @@
+use soroban_sdk::{ Address, Env, Map };
+
+use crate::storage_types::{ INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, 
+    GlobalStats, PairStats, StatsKey
+};
+
+
+/*
+Running totals for dashboards, so they don't have to replay every event:
+- per pair, the send and recv volume settled and the number of fills, where the pair is
+  (token sold by the maker, token paid by the taker) as for offers. A basket has no single
+  pair, so it only counts toward the global fill count.
+- globally, the number of fills and the fees collected per token, claimed or not.
+*/
+
+pub fn stats_pair_get(e: &Env, send_token: &Address, recv_token: &Address) -> PairStats {
+    let key = StatsKey::Pair(send_token.clone(), recv_token.clone());
+
+    e.storage().persistent().get(&key).unwrap_or(PairStats {
+        send_volume: 0,
+        recv_volume: 0,
+        fill_count: 0,
+    })
+}
+
+pub fn stats_global_get(e: &Env) -> GlobalStats {
+    let key = StatsKey::Global;
+
+    e.storage().instance().get(&key).unwrap_or(GlobalStats {
+        fill_count: 0,
+        fees: Map::new(e),
+    })
+}
+
+fn stats_global_write(e: &Env, stats: &GlobalStats) {
+    e.storage().instance().set(&StatsKey::Global, stats);
+    e.storage().instance().extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+}
+
+// Counts a fill of `send_amount` of send_token for `recv_amount` of recv_token.
+pub fn stats_record(e: &Env, send_token: &Address, recv_token: &Address, send_amount: u64, recv_amount: u64) {
+    let key = StatsKey::Pair(send_token.clone(), recv_token.clone());
+    let mut stats = stats_pair_get(e, send_token, recv_token);
+    stats.send_volume = stats.send_volume.saturating_add(send_amount as u128);
+    stats.recv_volume = stats.recv_volume.saturating_add(recv_amount as u128);
+    stats.fill_count += 1;
+    e.storage().persistent().set(&key, &stats);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+
+    stats_fill(e);
+}
+
+// Counts a fill that belongs to no single pair.
+pub fn stats_fill(e: &Env) {
+    let mut stats = stats_global_get(e);
+    stats.fill_count += 1;
+    stats_global_write(e, &stats);
+}
+
+pub fn stats_fee(e: &Env, token: &Address, amount: u64) {
+    let mut stats = stats_global_get(e);
+    let fees = stats.fees.get(token.clone()).unwrap_or(0);
+    stats.fees.set(token.clone(), fees.saturating_add(amount as u128));
+    stats_global_write(e, &stats);
+}
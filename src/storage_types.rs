@@ -1,28 +1,161 @@
-use soroban_sdk::{ contracttype, Address/* , BytesN */ };
+// The variants of OfferStatus and OfferMode keep their published upper case names, which
+// the code contracttype generates for tests repeats.
+#![allow(non_camel_case_types)]
+
+use soroban_sdk::{ contracttype, Address, Bytes, BytesN, Map, Symbol, Vec };
 
 
 pub(crate) const FEE_DECIMALS: u32 = 4;
+// Upper bound for any fee rate: 10%
+pub(crate) const MAX_FEE_RATE: u32 = 1000;
+pub(crate) const SCHEMA_VERSION: u32 = 2;
+// Version of the offer event payloads, published as the last event topic.
+pub const EVENT_VERSION: u32 = 2;
+// Longest memo an offer can carry, in bytes.
+pub(crate) const MAX_MEMO_LEN: u32 = 64;
+// Decimals of normalized prices.
+pub const PRICE_DECIMALS: u32 = 18;
+// Upper bound for fixed fee token rates: 10^9 fee token units per unit
+pub(crate) const MAX_FEE_TOKEN_RATE: u128 = 10u128.pow(PRICE_DECIMALS + 9);
+// Rounding dust fractions are counted in 1/DUST_SCALE of a unit.
+pub(crate) const DUST_SCALE: u64 = 1_000_000_000_000_000_000;
+// Ledgers a commitment must wait before it can be revealed, and after which it lapses.
+pub(crate) const REVEAL_DELAY: u32 = 2;
+pub(crate) const REVEAL_WINDOW: u32 = 100;
+// Ledgers for which commitments can lock an offer in total.
+pub(crate) const MAX_COMMIT_LOCK: u32 = 3 * REVEAL_WINDOW;
+
+// Operations offerors may delegate to managers.
+pub const MANAGE_UPDATE: u32 = 1;
+pub const MANAGE_CLOSE: u32 = 2;
+pub(crate) const MANAGE_ALL: u32 = MANAGE_UPDATE | MANAGE_CLOSE;
+
+// Parts of the configuration `finalize_config` can freeze.
+pub const FREEZE_FEES: u32 = 1;
+pub const FREEZE_ALLOWLIST: u32 = 2;
+pub(crate) const FREEZE_ALL: u32 = FREEZE_FEES | FREEZE_ALLOWLIST;
 
 pub(crate) const DAY_IN_LEDGERS: u32 = 17280;
 pub(crate) const INSTANCE_BUMP_AMOUNT: u32 = 7 * DAY_IN_LEDGERS; // 7 days
 pub(crate) const INSTANCE_LIFETIME_THRESHOLD: u32 = INSTANCE_BUMP_AMOUNT - DAY_IN_LEDGERS; // 6 days
 pub(crate) const BALANCE_BUMP_AMOUNT: u32 = 30 * DAY_IN_LEDGERS; // 30 days
+pub(crate) const BALANCE_LIFETIME_THRESHOLD: u32 = BALANCE_BUMP_AMOUNT - DAY_IN_LEDGERS; // 29 days
 
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 #[contracttype]
 pub struct FeeInfo {
-    pub fee_rate: u32,
+    // charged to the offeror on the send token at offer creation
+    pub maker_fee_rate: u32,
+    // charged to the acceptor on the recv token at each fill
+    pub taker_fee_rate: u32,
     pub fee_wallet: Address,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+// Contract wide settings, see `get_config`.
+#[derive(Clone)]
+#[contracttype]
+pub struct Config {
+    pub admin: Address,
+    pub fee: FeeInfo,
+    pub fee_delay: u32,
+    pub paused: bool,
+    pub schema_version: u32,
+}
+
+// Fee rate applied to acceptors whose settled volume reached `min_volume`.
+#[derive(Clone)]
+#[contracttype]
+pub struct FeeTier {
+    pub min_volume: u64,
+    pub fee_rate: u32,
+}
+
+// Share of the fee, in FEE_DECIMALS, waived for accounts holding at least `min_balance`
+// of the discount token.
+#[derive(Clone)]
+#[contracttype]
+pub struct FeeDiscount {
+    pub min_balance: i128,
+    pub discount: u32,
+}
+
+// Fee change waiting for its timelock to pass.
+#[derive(Clone)]
+#[contracttype]
+pub struct PendingFee {
+    pub fee_info: FeeInfo,
+    // ledger sequence from which the fee can be applied
+    pub apply_ledger: u32,
+}
+
+// Change of a fee setting other than the fee itself, see `fee_change_queue`. The Clear
+// variants remove the setting.
+#[derive(Clone)]
+#[contracttype]
+pub enum FeeChange {
+    Delay(u32),
+    TokenFee(Address, u32),
+    ClearTokenFee(Address),
+    Tiers(Vec<FeeTier>),
+    Token(FeeToken),
+    ClearToken,
+    TokenRate(Address, u128),
+    ClearTokenRate(Address),
+    Discounts(Address, Vec<FeeDiscount>),
+    ClearDiscounts,
+}
+
+// Fee setting change waiting for the fee delay to pass.
+#[derive(Clone)]
+#[contracttype]
+pub struct PendingFeeChange {
+    pub change: FeeChange,
+    // ledger sequence from which the change can be applied
+    pub apply_ledger: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 #[contracttype]
 pub enum OfferStatus {
     INIT = 0,
     ACTIVE = 1,
     COMPLETE = 2,
-    CANCEL = 3
+    CANCEL = 3,
+    EXPIRED = 4,
+    CANCELED_BY_ADMIN = 5,
+    PAUSED = 6
+}
+
+// Where the rounding dust of partial fills goes. Fills always round the acceptor's
+// send_amount down; see `dust_accrue`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[contracttype]
+pub enum RoundingPolicy {
+    // Swept to the offeror once the offer is no longer active.
+    Offeror = 0,
+    // Earned as fees in send_token.
+    Fees = 1,
+    // Left in the offer, slightly improving its price for later fills.
+    Offer = 2,
+}
+
+// PARTIAL offers can be filled in several parts, ALL_OR_NOTHING ones only at once.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[contracttype]
+pub enum OfferMode {
+    PARTIAL = 0,
+    ALL_OR_NOTHING = 1
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[contracttype]
+pub enum Role {
+    Admin = 0,
+    Operator = 1,
+    Pauser = 2,
+    FeeManager = 3,
+    Arbiter = 4
 }
 
 // Represents an offer managed by the TokenSwap contract.
@@ -42,10 +175,438 @@ pub struct OfferInfo {
     // offeror-defined amount of the recv token
     pub recv_amount: u64,
     pub min_recv_amount: u64,
+    // maker fee paid for the part of send_amount which hasn't been filled yet
+    pub fee_amount: u64,
+
+    // ledger sequence from which the offer can no longer be accepted
+    pub expire_ledger: u32,
 
+    pub mode: OfferMode,
     pub status: OfferStatus
 }
 
+// An offer as stored, tagged with the schema it was written in so older entries can be
+// upcast when loaded. Schema 1 stored the bare OfferInfo. A change to OfferInfo keeps the
+// old struct under a new name and adds a variant for the new one, see `offer_upcast`.
+#[derive(Clone)]
+#[contracttype]
+pub enum StoredOffer {
+    V2(OfferInfo),
+}
+
+// Parameters of an offer posted with `create_offers`, see `create_offer`.
+#[derive(Clone)]
+#[contracttype]
+pub struct OfferSpec {
+    pub send_token: Address,
+    pub recv_token: Address,
+    pub timestamp: u32,
+    pub send_amount: u64,
+    pub recv_amount: u64,
+    pub min_recv_amount: u64,
+    pub expire_ledger: u32,
+    pub mode: OfferMode,
+}
+
+// Payloads of the offer events. Amounts left on the offer are as after the event.
+#[derive(Clone)]
+#[contracttype]
+pub struct OfferCreated {
+    pub offer_id: u32,
+    pub offeror: Address,
+    pub send_token: Address,
+    pub recv_token: Address,
+    pub send_amount: u64,
+    pub recv_amount: u64,
+    pub min_recv_amount: u64,
+    // maker fee escrowed with the offer
+    pub fee_amount: u64,
+    pub expire_ledger: u32,
+    pub timestamp: u32,
+    // the only acceptor allowed, if any; a list as the event types can't hold options
+    pub counterparty: Vec<Address>,
+    // reference attached by the offeror, empty if none
+    pub memo: Bytes,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct OfferAccepted {
+    pub offer_id: u32,
+    pub acceptor: Address,
+    // recv_token paid to the offeror and send_token received by the acceptor
+    pub amount: u64,
+    pub send_amount: u64,
+    // taker fee paid in recv_token, or in the fee token when one is set
+    pub fee_amount: u64,
+    pub send_remaining: u64,
+    pub recv_remaining: u64,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct OfferUpdated {
+    pub offer_id: u32,
+    pub offeror: Address,
+    pub send_remaining: u64,
+    pub recv_remaining: u64,
+    pub min_recv_amount: u64,
+    pub fee_remaining: u64,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct OfferClosed {
+    pub offer_id: u32,
+    pub offeror: Address,
+    pub status: OfferStatus,
+    // send_token returned to the offeror, the unearned maker fee included
+    pub refund_amount: u64,
+    // reason given by the admin on a forced close, empty otherwise
+    pub reason: Vec<Symbol>,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct OfferCompleted {
+    pub offer_id: u32,
+    pub offeror: Address,
+    // maker fee earned by the last fill
+    pub fee_amount: u64,
+}
+
+// Offers of an order book at the same price. The price is `recv_amount / send_amount`.
+#[derive(Clone)]
+#[contracttype]
+pub struct BookLevel {
+    pub send_amount: u64,
+    pub recv_amount: u64,
+    pub offer_count: u32,
+}
+
+// Price schedule of a dutch auction offer. The price of the whole `send_amount` drops
+// from `start_recv_amount` to `floor_recv_amount` between `start_ledger` and `end_ledger`.
+#[derive(Clone)]
+#[contracttype]
+pub struct DutchAuction {
+    pub send_amount: u64,
+    pub start_recv_amount: u64,
+    pub floor_recv_amount: u64,
+    pub start_ledger: u32,
+    pub end_ledger: u32,
+    pub step_ledgers: u32,
+}
+
+// Best bid of an english auction.
+#[derive(Clone)]
+#[contracttype]
+pub struct Bid {
+    pub bidder: Address,
+    // bid in recv token, without the taker fee
+    pub amount: u64,
+}
+
+// A taker's request to sell `send_amount` of `send_token` for `recv_token`.
+#[derive(Clone)]
+#[contracttype]
+pub struct QuoteRequest {
+    pub taker: Address,
+    pub send_token: Address,
+    pub recv_token: Address,
+    pub send_amount: u64,
+    pub expire_ledger: u32,
+    pub status: OfferStatus,
+}
+
+// A maker's escrowed answer to a request.
+#[derive(Clone)]
+#[contracttype]
+pub struct Quote {
+    pub maker: Address,
+    pub request_id: u32,
+    // amount of the recv token paid to the taker
+    pub recv_amount: u64,
+    // escrowed maker fee
+    pub fee_amount: u64,
+    pub expire_ledger: u32,
+    pub status: OfferStatus,
+}
+
+// Hidden acceptance of an offer, see `commit.rs`.
+#[derive(Clone)]
+#[contracttype]
+pub struct Commitment {
+    pub hash: BytesN<32>,
+    pub ledger: u32,
+    // recv_token escrowed by the acceptor until the reveal
+    pub bond: u64,
+}
+
+// Escrow released to `acceptor` with the preimage of `hashlock` before `timelock`.
+#[derive(Clone)]
+#[contracttype]
+pub struct HtlcInfo {
+    pub offeror: Address,
+    pub acceptor: Address,
+    pub send_token: Address,
+    pub amount: u64,
+    pub hashlock: BytesN<32>,
+    pub timelock: u32,
+    pub status: OfferStatus,
+}
+
+// Fill of an offer with a dispute window, held in the contract until `release_ledger`.
+#[derive(Clone)]
+#[contracttype]
+pub struct DisputeHold {
+    pub acceptor: Address,
+    // recv_token paid by the acceptor and send_token bought with it
+    pub amount: u64,
+    pub send_amount: u64,
+    pub release_ledger: u32,
+    pub disputed: bool,
+}
+
+// Swap of `amount_a` of token_a from party_a for `amount_b` of token_b from party_b, settled
+// once both legs are deposited and both parties confirmed, before `expire_ledger`.
+#[derive(Clone)]
+#[contracttype]
+pub struct DealInfo {
+    pub party_a: Address,
+    pub party_b: Address,
+    pub token_a: Address,
+    pub amount_a: u64,
+    pub token_b: Address,
+    pub amount_b: u64,
+    pub deposited_a: bool,
+    pub deposited_b: bool,
+    pub confirmed_a: bool,
+    pub confirmed_b: bool,
+    pub expire_ledger: u32,
+    pub status: OfferStatus,
+}
+
+// Most offers an account may create, and fills it may take, in any `window_ledgers`
+// ledgers. 0 leaves the call unlimited.
+#[derive(Clone)]
+#[contracttype]
+pub struct RateLimit {
+    pub window_ledgers: u32,
+    pub max_creates: u32,
+    pub max_accepts: u32,
+}
+
+// Persistent offer entries have their TTL extended to `extend_to` ledgers whenever they're
+// used with less than `threshold` ledgers left.
+#[derive(Clone)]
+#[contracttype]
+pub struct TtlConfig {
+    pub threshold: u32,
+    pub extend_to: u32,
+}
+
+// Calls an account made in the window starting at `window_start`.
+#[derive(Clone)]
+#[contracttype]
+pub struct RateUsage {
+    pub window_start: u32,
+    pub creates: u32,
+    pub accepts: u32,
+}
+
+// Order signed by `maker` off-chain: `send_amount` of send_token for `recv_amount` of
+// recv_token, fillable once before `expire_ledger`. `nonce` tells the orders of a maker apart.
+#[derive(Clone)]
+#[contracttype]
+pub struct SignedOrder {
+    pub maker: Address,
+    pub send_token: Address,
+    pub recv_token: Address,
+    pub send_amount: u64,
+    pub recv_amount: u64,
+    pub expire_ledger: u32,
+    pub nonce: u64,
+}
+
+// Governance by holders of `token`. Proposals are voted on for `voting_ledgers`, need
+// `quorum` votes for to pass and can be executed `delay_ledgers` after the vote.
+#[derive(Clone)]
+#[contracttype]
+pub struct GovConfig {
+    pub token: Address,
+    pub voting_ledgers: u32,
+    pub delay_ledgers: u32,
+    pub quorum: u64,
+}
+
+// Change a governance proposal makes: the (maker, taker) fee rates, or allowing or
+// disallowing a token.
+#[derive(Clone)]
+#[contracttype]
+pub enum GovAction {
+    FeeRates(u32, u32),
+    Allow(Address),
+    Disallow(Address),
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct Proposal {
+    pub proposer: Address,
+    pub action: GovAction,
+    pub token: Address,
+    pub quorum: u64,
+    pub votes_for: u64,
+    pub votes_against: u64,
+    pub end_ledger: u32,
+    pub execute_ledger: u32,
+    pub status: OfferStatus,
+}
+
+// Recurring purchase of `recv_token` with `amount` of `send_token` every `period_ledgers`.
+#[derive(Clone)]
+#[contracttype]
+pub struct DcaPlan {
+    pub owner: Address,
+    pub send_token: Address,
+    pub recv_token: Address,
+    // escrowed send token left to spend, including fees and bounties
+    pub budget: u64,
+    pub amount: u64,
+    pub period_ledgers: u32,
+    // least recv token to get for a full period
+    pub min_recv_amount: u64,
+    // send token paid to the keeper per execution
+    pub bounty: u64,
+    pub next_ledger: u32,
+    pub status: OfferStatus,
+}
+
+// Proceeds of an offer vesting linearly to its offeror.
+#[derive(Clone)]
+#[contracttype]
+pub struct Stream {
+    pub offeror: Address,
+    pub recv_token: Address,
+    // not vested yet, vests linearly from `last_ledger` to `end_ledger`
+    pub locked: u64,
+    // vested and not withdrawn yet
+    pub vested: u64,
+    pub last_ledger: u32,
+    pub end_ledger: u32,
+}
+
+// One send token of a basket offer and the maker fee escrowed for it.
+#[derive(Clone)]
+#[contracttype]
+pub struct BasketItem {
+    pub send_token: Address,
+    pub send_amount: u64,
+    pub fee_amount: u64,
+}
+
+// Offer of several send tokens bought together for `recv_amount` of `recv_token`.
+#[derive(Clone)]
+#[contracttype]
+pub struct BasketOffer {
+    pub offeror: Address,
+    pub items: Vec<BasketItem>,
+    pub recv_token: Address,
+    pub recv_amount: u64,
+    pub expire_ledger: u32,
+    pub status: OfferStatus,
+}
+
+// Alternative recv token of an offer, priced at `recv_amount` for `send_amount`.
+#[derive(Clone)]
+#[contracttype]
+pub struct AltRecv {
+    pub recv_token: Address,
+    pub send_amount: u64,
+    pub recv_amount: u64,
+}
+
+// Offer of the NFT `token_id` of the `nft` contract for `recv_amount` of `recv_token`.
+#[derive(Clone)]
+#[contracttype]
+pub struct NftOffer {
+    pub offeror: Address,
+    pub nft: Address,
+    pub token_id: u32,
+    pub recv_token: Address,
+    pub recv_amount: u64,
+    pub expire_ledger: u32,
+    pub status: OfferStatus,
+}
+
+// Largest deviation from the oracle price allowed for offers of a pair.
+#[derive(Clone)]
+#[contracttype]
+pub struct PriceBand {
+    // in 1/10000 of the oracle price
+    pub max_deviation: u32,
+    // oldest oracle price accepted, in seconds
+    pub max_age: u64,
+    // whether fills re-check the offer's price too
+    pub check_accept: bool,
+}
+
+// Smallest offers and fills of a pair, `min_send_amount` in send_token and `min_fill_amount`
+// in recv_token. A fill taking all that's left of an offer is always allowed.
+#[derive(Clone)]
+#[contracttype]
+pub struct PairMinimums {
+    pub min_send_amount: u64,
+    pub min_fill_amount: u64,
+}
+
+// Token fees are paid in instead of the traded tokens. Fees are converted at the fixed rate
+// set for the traded token, or else at oracle prices no older than `max_age` seconds.
+#[derive(Clone)]
+#[contracttype]
+pub struct FeeToken {
+    pub token: Address,
+    pub max_age: u64,
+}
+
+// Price of an offer pegged to the oracle, `spread` in 1/10000 above (or below when
+// negative) the oracle price, using prices no older than `max_age` seconds.
+#[derive(Clone)]
+#[contracttype]
+pub struct OraclePeg {
+    pub spread: i32,
+    pub max_age: u64,
+}
+
+// Totals settled on a pair, see stats.rs.
+#[derive(Clone)]
+#[contracttype]
+pub struct PairStats {
+    pub send_volume: u128,
+    pub recv_volume: u128,
+    pub fill_count: u64,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct GlobalStats {
+    pub fill_count: u64,
+    // fees collected per token
+    pub fees: Map<Address, u128>,
+}
+
+// A single accepted fill of an offer.
+#[derive(Clone)]
+#[contracttype]
+pub struct FillInfo {
+    pub acceptor: Address,
+    // amount of the recv token paid by acceptor
+    pub amount: u64,
+    // amount of the send token received by acceptor
+    pub send_amount: u64,
+    pub ledger: u32,
+}
+
 #[derive(Clone)]
 #[contracttype]
 pub struct OfferKey {
@@ -56,13 +617,167 @@ pub struct OfferKey {
 }
 
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone)]
 #[contracttype]
 pub enum DataKey {
     FEE,
+    PendingFee,
+    FeeDelay,
+    TokenFee(Address),
+    FeeTiers,
+    Volume(Address, Address),
+    AccruedFees(Address),
+    ReferralShare,
+    ReferralFees(Address, Address),
     Allowance(Address),
     OfferCount,
     RegOffers(u32),
-    ErrorCode,
+    OfferAcceptors(u32),
+    OfferCounterparty(u32),
+    OfferAuction(u32),
+    AuctionEnd(u32),
+    AuctionBest(u32),
+    AuctionEscrow(u32, Address),
+    RequestCount,
+    Request(u32),
+    QuoteCount,
+    Quote(u32),
+    Commit(u32, Address),
+    OfferLock(u32),
+    HtlcCount,
+    Htlc(u32),
+    DcaCount,
+    Dca(u32),
+    OfferVesting(u32),
+    Stream(u32),
+    Manager(Address, Address),
+    OfferManager(u32, Address, Address),
+    BasketCount,
+    Basket(u32),
+    OfferAltRecv(u32),
+    NftCount,
+    Nft(u32),
+    TokenDecimals(Address),
+    OpenOffers,
+    OfferorOffers(Address),
+    PairOffers(Address, Address),
+    Book(Address, Address),
+    FillCount(u32),
+    Fill(u32, u32),
     Admin,
+    PendingAdmin,
+    Paused,
+    SchemaVersion,
+    Role(Role, Address),
+}
+
+// Keys of the settings operators configure; kept apart from DataKey as a
+// contracttype enum holds at most 50 variants.
+#[derive(Clone)]
+#[contracttype]
+pub enum ConfigKey {
+    Oracle,
+    PriceBand(Address, Address),
+    AllowedTokens,
+    PairsOnly,
+    AllowedPair(Address, Address),
+    PairMinimums(Address, Address),
+    MaxOpenOffers,
+    FeeToken,
+    FeeTokenRate(Address),
+    DiscountToken,
+    FeeDiscounts,
+    Frozen,
+    RateLimit,
+    Compliance,
+    Banned(Address),
+    FillCallback(Address),
+    Router,
+    Ttl,
+    TokenName(Address),
+    PendingFeeChanges,
+    Rounding,
+}
+
+// Keys of optional per-offer settings, see ConfigKey.
+#[derive(Clone)]
+#[contracttype]
+pub enum OfferDataKey {
+    Peg(u32),
+    Dust(u32),
+    Terms(u32),
+    DisputeWindow(u32),
+    Hold(u32),
+    Memo(u32),
+    Managers(u32),
+    Committers(u32),
+    CommitLockUsed(u32),
+    Bidders(u32),
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub enum RewardKey {
+    Token,
+    Rate(Address),
+    Budget,
+    Owed,
+    Accrued(Address),
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub enum StakeKey {
+    Token,
+    Share,
+    Total,
+    Staked(Address),
+    RewardTokens,
+    PerShare(Address),
+    Paid(Address, Address),
+    Rewards(Address, Address),
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub enum StatsKey {
+    Pair(Address, Address),
+    Global,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub enum EscrowKey {
+    Total(Address),
+    Locked,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub enum OrderKey {
+    Used(Address, u64),
+    MinNonce(Address),
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub enum ThrottleKey {
+    Usage(Address),
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub enum GovKey {
+    Config,
+    Count,
+    Proposal(u32),
+    Vote(u32, Address),
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub enum DealKey {
+    Count,
+    Deal(u32),
 }
@@ -0,0 +1,138 @@
+use soroban_sdk::{ token, Address, Env };
+
+use crate::storage_types::{ BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, 
+    DataKey, OfferInfo, OfferStatus, Stream
+};
+use crate::offer::{ offer_load };
+use crate::escrow::{ escrow_in, escrow_out };
+use crate::error::SwapError;
+
+
+/*
+Offerors may have the recv_token proceeds of an offer vest linearly instead of
+receiving them with each fill:
+
+1. Offeror calls `set` with the vesting duration in ledgers, 0 turns vesting off.
+2. Every fill credits the proceeds to the offer's stream held by the contract.
+   Whatever is still locked, including the new proceeds, vests linearly until
+   `duration_ledgers` after the fill.
+3. Offeror calls `withdraw` at any time to claim what has vested so far. The
+   stream outlives the offer, so it can be withdrawn after the offer is pruned.
+*/
+
+pub fn vesting_get(e: &Env, offer_id: u32) -> u32 {
+    let key = DataKey::OfferVesting(offer_id);
+
+    e.storage().persistent().get(&key).unwrap_or(0)
+}
+
+pub fn vesting_set(e: &Env, offeror: &Address, offer_id: u32, duration_ledgers: u32) -> Result<(), SwapError> {
+    let offer = offer_load(e, offer_id)?;
+    if offer.offeror != offeror.clone() {
+        return Err(SwapError::InvalidOfferor);
+    }
+    if offer.status != OfferStatus::ACTIVE {
+        return Err(SwapError::OfferNotActive);
+    }
+
+    offeror.require_auth();
+
+    vesting_write(e, offer_id, duration_ledgers);
+    Ok(())
+}
+
+pub fn vesting_write(e: &Env, offer_id: u32, duration_ledgers: u32) {
+    let key = DataKey::OfferVesting(offer_id);
+    if duration_ledgers == 0 {
+        e.storage().persistent().remove(&key);
+    } else {
+        e.storage().persistent().set(&key, &duration_ledgers);
+        e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+    }
+}
+
+// The stream of the offer with everything vested up to the current ledger.
+pub fn stream_get(e: &Env, offer_id: u32) -> Option<Stream> {
+    let key = DataKey::Stream(offer_id);
+    let mut stream: Stream = e.storage().persistent().get(&key)?;
+
+    let ledger = e.ledger().sequence();
+    if ledger >= stream.end_ledger {
+        stream.vested += stream.locked;
+        stream.locked = 0;
+    } else if ledger > stream.last_ledger {
+        let vested = ((stream.locked as u128) * ((ledger - stream.last_ledger) as u128)
+            / ((stream.end_ledger - stream.last_ledger) as u128)) as u64;
+        stream.vested += vested;
+        stream.locked -= vested;
+    }
+    stream.last_ledger = stream.last_ledger.max(ledger);
+    Some(stream)
+}
+
+// Pays `amount` of the offer's recv_token proceeds from `from`, into the offer's stream
+// if vesting is on.
+pub fn proceeds_pay(e: &Env, offer_id: u32, offer: &OfferInfo, from: &Address, amount: u64) {
+    let contract = e.current_contract_address();
+    let duration_ledgers = vesting_get(e, offer_id);
+    if duration_ledgers == 0 {
+        if from.clone() == contract {
+            escrow_out(e, &offer.recv_token, &offer.offeror, amount);
+        } else {
+            token::Client::new(e, &offer.recv_token).transfer(from, &offer.offeror, &(amount as i128));
+        }
+        return;
+    }
+
+    let amount = escrow_in(e, &offer.recv_token, from, amount);
+
+    let ledger = e.ledger().sequence();
+    let mut stream = stream_get(e, offer_id).unwrap_or(Stream {
+        offeror: offer.offeror.clone(),
+        recv_token: offer.recv_token.clone(),
+        locked: 0,
+        vested: 0,
+        last_ledger: ledger,
+        end_ledger: ledger,
+    });
+    stream.locked += amount;
+    stream.end_ledger = ledger + duration_ledgers;
+    stream_write(e, offer_id, &stream);
+}
+
+// Sends the vested proceeds of the offer to its offeror. Returns the amount sent.
+pub fn stream_withdraw(e: &Env, offeror: &Address, offer_id: u32) -> Result<u64, SwapError> {
+    let mut stream = stream_get(e, offer_id).ok_or(SwapError::StreamNotFound)?;
+    if stream.offeror != offeror.clone() {
+        return Err(SwapError::InvalidOfferor);
+    }
+
+    offeror.require_auth();
+
+    let amount = stream.vested;
+    if amount > 0 {
+        escrow_out(e, &stream.recv_token, offeror, amount);
+    }
+
+    stream.vested = 0;
+    if stream.locked == 0 {
+        e.storage().persistent().remove(&DataKey::Stream(offer_id));
+    } else {
+        stream_write(e, offer_id, &stream);
+    }
+    Ok(amount)
+}
+
+// Moves the proceeds of the offer, vested or not, to its new offeror.
+pub fn stream_transfer(e: &Env, offer_id: u32, new_offeror: &Address) {
+    if let Some(mut stream) = stream_get(e, offer_id) {
+        stream.offeror = new_offeror.clone();
+        stream_write(e, offer_id, &stream);
+    }
+}
+
+fn stream_write(e: &Env, offer_id: u32, stream: &Stream) {
+    let key = DataKey::Stream(offer_id);
+    e.storage().persistent().set(&key, stream);
+    e.storage().persistent().extend_ttl(&key, BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT);
+}
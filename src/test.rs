@@ -1,33 +1,227 @@
 #![cfg(test)]
 extern crate std;
 
-pub(crate) const DEF_FEE_RATE: u32 = 25;   // default fee_rate is 0.25%
+pub(crate) const DEF_MAKER_FEE_RATE: u32 = 25;   // default maker fee_rate is 0.25%
+pub(crate) const DEF_TAKER_FEE_RATE: u32 = 50;   // default taker fee_rate is 0.5%
 pub(crate) const TOKEN_DECIMALS: u32 = 4;
 
 
-use soroban_sdk::{ log, token, BytesN };
-use crate::storage_types::{ BALANCE_BUMP_AMOUNT, FeeInfo };
-use crate::{ TokenSwap, TokenSwapClient };
+use soroban_sdk::{ log, token, xdr::ToXdr, Bytes, BytesN, String, Vec };
+use crate::storage_types::{ DataKey, EscrowKey, OfferDataKey, DAY_IN_LEDGERS };
+use crate::oracle::{ Asset, PriceData };
+use crate::testutils::{ create_funded_offer, register_token as create_token_contract, register_token_swap, set_fees };
+use crate::{ FeeDiscount, FeeInfo, FeeTier, FeeToken, GovAction, GovConfig, OfferAccepted, OfferClosed, OfferCompleted, OfferCreated, OraclePeg, PairMinimums, PriceBand, RateLimit, RoundingPolicy, EVENT_VERSION, FREEZE_ALLOWLIST, FREEZE_FEES, MANAGE_CLOSE, MANAGE_UPDATE, OfferMode, OfferSpec, OfferStatus, Role, SignedOrder, SwapError, TokenSwap, TokenSwapClient, TtlConfig };
 
 
 use soroban_sdk::{
-    symbol_short, Symbol,
-    testutils::{Address as _, AuthorizedFunction, AuthorizedInvocation},
-    Address, Env, IntoVal,
+    contract, contractimpl, symbol_short, vec, Symbol,
+    testutils::{storage::Instance as _, Address as _, AuthorizedFunction, AuthorizedInvocation, Events, Ledger},
+    Address, Env, IntoVal, TryFromVal,
 };
 
 
-fn create_token_contract<'a>(
-    e: &Env,
-    admin: &Address,
-) -> (Address, token::Client<'a>, token::StellarAssetClient<'a>) {
-    let addr = e.register_stellar_asset_contract(admin.clone());
-    (
-        addr.clone(),
-        token::Client::new(e, &addr),
-        token::StellarAssetClient::new(e, &addr),
-    )
+
+// Minimal NFT contract for the NFT offer tests.
+#[contract]
+pub struct MockNft;
+
+#[contractimpl]
+impl MockNft {
+    pub fn mint(e: Env, to: Address, token_id: u32) {
+        e.storage().persistent().set(&token_id, &to);
+    }
+
+    pub fn owner_of(e: Env, token_id: u32) -> Address {
+        e.storage().persistent().get(&token_id).unwrap()
+    }
+
+    pub fn transfer(e: Env, from: Address, to: Address, token_id: u32) {
+        from.require_auth();
+        e.storage().persistent().set(&token_id, &to);
+    }
+
+    pub fn name(e: Env) -> String {
+        String::from_str(&e, "Mock NFT")
+    }
+
+    pub fn balance(_e: Env, _id: Address) -> i128 {
+        0
+    }
+}
+
+// Token with 18 decimals, for the price normalization tests. In a module of its own as its
+// functions share names with the other mocks.
+mod wide_token {
+    use soroban_sdk::{ contract, contractimpl, Address, Env, String };
+
+    #[contract]
+    pub struct MockWideToken;
+
+    #[contractimpl]
+    impl MockWideToken {
+        pub fn decimals(_e: Env) -> u32 {
+            18
+        }
+
+        pub fn name(e: Env) -> String {
+            String::from_str(&e, "Wide")
+        }
+
+        pub fn balance(_e: Env, _id: Address) -> i128 {
+            0
+        }
+    }
+}
+use wide_token::MockWideToken;
+
+#[contract]
+pub struct MockOracle;
+
+#[contractimpl]
+impl MockOracle {
+    pub fn set_price(e: Env, token: Address, price: i128) {
+        e.storage().instance().set(&token, &(price, e.ledger().timestamp()));
+    }
+
+    pub fn lastprice(e: Env, asset: Asset) -> Option<PriceData> {
+        let token = match asset {
+            Asset::Stellar(token) => token,
+            Asset::Other(_) => return None,
+        };
+        let (price, timestamp): (i128, u64) = e.storage().instance().get(&token)?;
+        Some(PriceData { price, timestamp })
+    }
+}
+
+// Compliance contract denying the accounts it was told to.
+#[contract]
+pub struct MockCompliance;
+
+#[contractimpl]
+impl MockCompliance {
+    pub fn deny(e: Env, account: Address) {
+        e.storage().instance().set(&account, &true);
+    }
+
+    pub fn can_trade(e: Env, account: Address, _token: Address, _amount: i128) -> bool {
+        !e.storage().instance().has(&account)
+    }
+}
+
+// Acceptor contract remembering the last fill it was told about.
+#[contract]
+pub struct MockVault;
+
+#[contractimpl]
+impl MockVault {
+    pub fn on_fill(e: Env, offer_id: u32, amount_in: u64, amount_out: u64) {
+        e.storage().instance().set(&symbol_short!("fill"), &(offer_id, amount_in, amount_out));
+    }
+
+    pub fn last_fill(e: Env) -> Option<(u32, u64, u64)> {
+        e.storage().instance().get(&symbol_short!("fill"))
+    }
+}
+
+// AMM router paying a fixed `rate` of the second token of the path per unit of the first.
+#[contract]
+pub struct MockRouter;
+
+#[contractimpl]
+impl MockRouter {
+    pub fn set_rate(e: Env, rate: i128) {
+        e.storage().instance().set(&symbol_short!("rate"), &rate);
+    }
+
+    pub fn router_get_amounts_out(e: Env, amount_in: i128, _path: Vec<Address>) -> Vec<i128> {
+        let rate: i128 = e.storage().instance().get(&symbol_short!("rate")).unwrap();
+        vec![&e, amount_in, amount_in * rate]
+    }
+
+    pub fn swap_exact_tokens_for_tokens(e: Env, amount_in: i128, _amount_out_min: i128, path: Vec<Address>, to: Address, _deadline: u64) -> Vec<i128> {
+        to.require_auth();
+        let amounts = Self::router_get_amounts_out(e.clone(), amount_in, path.clone());
+        token::Client::new(&e, &path.get(0).unwrap()).transfer(&to, &e.current_contract_address(), &amount_in);
+        token::Client::new(&e, &path.get(1).unwrap()).transfer(&e.current_contract_address(), &to, &amounts.get(1).unwrap());
+        amounts
+    }
+}
+
+// Token burning 1% of every transfer, rounded up, on its way to the recipient. In a module
+// of its own for the same reason.
+mod fee_token {
+    use soroban_sdk::{ contract, contractimpl, Address, Env, String };
+
+    #[contract]
+    pub struct MockFeeToken;
+
+    #[contractimpl]
+    impl MockFeeToken {
+        pub fn mint(e: Env, to: Address, amount: i128) {
+            let balance = Self::balance(e.clone(), to.clone());
+            e.storage().persistent().set(&to, &(balance + amount));
+        }
+
+        pub fn balance(e: Env, id: Address) -> i128 {
+            e.storage().persistent().get(&id).unwrap_or(0)
+        }
+
+        pub fn transfer(e: Env, from: Address, to: Address, amount: i128) {
+            from.require_auth();
+            let from_balance = Self::balance(e.clone(), from.clone());
+            assert!(from_balance >= amount);
+            e.storage().persistent().set(&from, &(from_balance - amount));
+            Self::mint(e, to, amount - (amount + 99) / 100);
+        }
+
+        pub fn decimals(_e: Env) -> u32 {
+            7
+        }
+
+        pub fn name(e: Env) -> String {
+            String::from_str(&e, "Fee on transfer")
+        }
+    }
+}
+use fee_token::{ MockFeeToken, MockFeeTokenClient };
+
+// Token without allowances, moving balances by authorized transfers only. In a module of
+// its own for the same reason.
+mod plain_token {
+    use soroban_sdk::{ contract, contractimpl, Address, Env, String };
+
+    #[contract]
+    pub struct MockPlainToken;
+
+    #[contractimpl]
+    impl MockPlainToken {
+        pub fn mint(e: Env, to: Address, amount: i128) {
+            let balance = Self::balance(e.clone(), to.clone());
+            e.storage().persistent().set(&to, &(balance + amount));
+        }
+
+        pub fn balance(e: Env, id: Address) -> i128 {
+            e.storage().persistent().get(&id).unwrap_or(0)
+        }
+
+        pub fn transfer(e: Env, from: Address, to: Address, amount: i128) {
+            from.require_auth();
+            let from_balance = Self::balance(e.clone(), from.clone());
+            assert!(from_balance >= amount);
+            e.storage().persistent().set(&from, &(from_balance - amount));
+            Self::mint(e, to, amount);
+        }
+
+        pub fn decimals(_e: Env) -> u32 {
+            7
+        }
+
+        pub fn name(e: Env) -> String {
+            String::from_str(&e, "native")
+        }
+    }
 }
+use plain_token::{ MockPlainToken, MockPlainTokenClient };
 
 fn create_token_swap_contract<'a>(
     e: &Env,
@@ -44,9 +238,10 @@ fn test() {
     e.mock_all_auths();
 
 
-    let token_admin = Address::random(&e);
-    let offeror = Address::random(&e);
-    let acceptor = Address::random(&e);
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
     const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
     
     
@@ -54,6 +249,7 @@ fn test() {
     let token_swap = create_token_swap_contract(
         &e,
     );
+    token_swap.initialize(&admin);
 
 
     let send_token = create_token_contract(&e, &token_admin);
@@ -71,25 +267,21 @@ fn test() {
     
     
     // init fee
-    let fee_rate = DEF_FEE_RATE;
-    let fee_wallet = Address::random(&e);
+    let fee_wallet = Address::generate(&e);
 
-    token_swap.set_fee(&fee_rate, &fee_wallet);
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &fee_wallet);
+    token_swap.apply_fee();
     
 
     // allow tokens
-    token_swap.allow_token(&send_token_id);
-    token_swap.allow_token(&recv_token_id);
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
 
-    send_token_client.approve(&offeror.clone(), &token_swap.address.clone(), 
-        &((1000 * MUL_VAL) as i128), &(e.ledger().sequence() + BALANCE_BUMP_AMOUNT));
-    recv_token_client.approve(&acceptor.clone(), &token_swap.address.clone(), 
-        &((100 * MUL_VAL) as i128), &(e.ledger().sequence() + BALANCE_BUMP_AMOUNT));
-    
     
     // Initial transaction 1 - create offer
     // 500 send_tokens : 50 recv_tokens (10 min_recv_tokens)
     let timestamp: u32 = e.ledger().timestamp() as u32;
+    let expire_ledger: u32 = e.ledger().sequence() + 1000;
     
     let offer_id: u32 = token_swap.create_offer(
         &offeror,
@@ -98,7 +290,8 @@ fn test() {
         &timestamp,
         &(500 * MUL_VAL),
         &(50 * MUL_VAL),
-        &(10 * MUL_VAL));
+        &(10 * MUL_VAL),
+        &expire_ledger, &None, &OfferMode::PARTIAL);
     
     // Verify that authorization is required for the offeror.
     assert_eq!(
@@ -116,7 +309,10 @@ fn test() {
                         timestamp,
                         500 * MUL_VAL,
                         50 * MUL_VAL,
-                        10 * MUL_VAL
+                        10 * MUL_VAL,
+                        expire_ledger,
+                        None::<Address>,
+                        OfferMode::PARTIAL
                     )
                         .into_val(&e)
                 )),
@@ -128,74 +324,72 @@ fn test() {
                             (
                                 offeror.clone(),
                                 token_swap.address.clone(),
-                                (500 * MUL_VAL) as i128,
+                                (500 * MUL_VAL) as i128 + 12500,
                             )
                                 .into_val(&e)
                         )),
                         sub_invocations: std::vec![]
-                    },
-                    AuthorizedInvocation {
-                        function: AuthorizedFunction::Contract((
-                            send_token_id.clone(),
-                            symbol_short!("transfer"),
-                            (
-                                offeror.clone(),
-                                fee_wallet.clone(),
-                                12500_i128,
-                            )
-                                .into_val(&e)
-                        )),
-                        sub_invocations: std::vec![],
                     }
                 ]
             }
         )]
     );
 
-    // trying to create an offer with same params - still success
-    let _ = token_swap.try_create_offer(
-        &offeror,
-        &send_token_id,
-        &recv_token_id,
-        &timestamp,
-        &(500 * MUL_VAL),
-        &(50 * MUL_VAL),
-        &(10 * MUL_VAL));
+    // the escrow is funded by a plain transfer without any allowance
+    assert_eq!(send_token_client.allowance(&offeror, &token_swap.address), 0);
 
-    // trying to create an offer with different timestamp - fails due to insufficient balance
-    let timestamp2: u32 = timestamp + 127;
-    let _ = token_swap.try_create_offer(
-        &offeror,
-        &send_token_id,
-        &recv_token_id,
-        &timestamp2,
-        &(500 * MUL_VAL),
-        &(50 * MUL_VAL),
-        &(10 * MUL_VAL));
+    let offer = token_swap.get_offer(&offer_id);
+    assert_eq!(offer.offeror, offeror);
+    assert_eq!(offer.send_token, send_token_id);
+    assert_eq!(offer.recv_token, recv_token_id);
+    assert_eq!(offer.send_amount, 500 * MUL_VAL);
+    assert_eq!(offer.recv_amount, 50 * MUL_VAL);
+    assert_eq!(offer.min_recv_amount, 10 * MUL_VAL);
+    assert_eq!(offer.expire_ledger, expire_ledger);
+    assert_eq!(offer.mode, OfferMode::PARTIAL);
+    assert_eq!(offer.status, OfferStatus::ACTIVE);
+
+    // each offer has its own persistent entry instead of growing the instance
+    e.as_contract(&token_swap.address, || {
+        assert!(e.storage().persistent().has(&DataKey::RegOffers(offer_id)));
+        assert!(!e.storage().instance().has(&DataKey::RegOffers(offer_id)));
+        assert!(e.storage().persistent().has(&DataKey::Allowance(send_token_id.clone())));
+    });
+
+    assert_eq!(token_swap.list_offers(&0, &10), vec![&e, offer_id]);
+    assert_eq!(token_swap.list_offers(&1, &10), vec![&e]);
+    assert_eq!(token_swap.offers_of(&offeror, &0, &10), vec![&e, offer_id]);
+    assert_eq!(token_swap.offers_of(&acceptor, &0, &10), vec![&e]);
+    assert_eq!(token_swap.offers_for_pair(&send_token_id, &recv_token_id, &0, &10), vec![&e, offer_id]);
+    assert_eq!(token_swap.offers_for_pair(&recv_token_id, &send_token_id, &0, &10), vec![&e]);
     
     
     // Try accepting 9 recv_token for at least 10 recv_token - that wouldn't
     // succeed because minimum recv amount is 10 recv_token.
-    assert!(token_swap.try_accept_offer(
+    assert_eq!(token_swap.try_accept_offer(
         &acceptor, 
         &offer_id, 
-        &(9 * MUL_VAL)).is_err());
+        &(9 * MUL_VAL), &0, &None), Err(Ok(SwapError::AmountTooSmall)));
     
+    // the quote matches the fill below
+    assert_eq!(token_swap.quote_accept(&offer_id, &(10 * MUL_VAL), &Some(acceptor.clone())), (100 * MUL_VAL, 500));
+    assert_eq!(token_swap.try_quote_accept(&offer_id, &(9 * MUL_VAL), &None), Err(Ok(SwapError::AmountTooSmall)));
+
     // acceptor accepts 10 recv_tokens.
     token_swap.accept_offer(
         &acceptor,
         &offer_id,
-        &(10 * MUL_VAL));
+        &(10 * MUL_VAL), &0, &None);
     
     assert_eq!(send_token_client.balance(&offeror), (500 * MUL_VAL) as i128 - 12500);
-    assert_eq!(send_token_client.balance(&token_swap.address), (400 * MUL_VAL) as i128);
+    assert_eq!(send_token_client.balance(&token_swap.address), (400 * MUL_VAL) as i128 + 12500);
     assert_eq!(send_token_client.balance(&acceptor), (100 * MUL_VAL) as i128);
-    assert_eq!(send_token_client.balance(&fee_wallet), 12500);
+    assert_eq!(send_token_client.balance(&fee_wallet), 0);
     
     assert_eq!(recv_token_client.balance(&offeror), (10 * MUL_VAL) as i128);
-    assert_eq!(recv_token_client.balance(&token_swap.address), 0);
-    assert_eq!(recv_token_client.balance(&acceptor), (90 * MUL_VAL) as i128 - 250);
-    assert_eq!(recv_token_client.balance(&fee_wallet), 250);
+    assert_eq!(recv_token_client.balance(&token_swap.address), 500);
+    assert_eq!(recv_token_client.balance(&acceptor), (90 * MUL_VAL) as i128 - 500);
+    assert_eq!(recv_token_client.balance(&fee_wallet), 0);
     
     
     // update (recv_amount, min_recv_amount) from (40, 10) to (80, 20)
@@ -207,41 +401,5157 @@ fn test() {
     );
 
     
+    // at the new price 40 recv tokens only buy 200 send tokens
+    assert_eq!(token_swap.try_accept_offer(
+        &acceptor, 
+        &offer_id, 
+        &(40 * MUL_VAL), &(201 * MUL_VAL), &None), Err(Ok(SwapError::SlippageExceeded)));
+
     // acceptor accepts 40 recv_tokens.
     token_swap.accept_offer(
         &acceptor, 
         &offer_id, 
-        &(40 * MUL_VAL));
+        &(40 * MUL_VAL), &(200 * MUL_VAL), &None);
     
     assert_eq!(send_token_client.balance(&offeror), (500 * MUL_VAL) as i128 - 12500);
-    assert_eq!(send_token_client.balance(&token_swap.address), (200 * MUL_VAL) as i128);
+    assert_eq!(send_token_client.balance(&token_swap.address), (200 * MUL_VAL) as i128 + 12500);
     assert_eq!(send_token_client.balance(&acceptor), (300 * MUL_VAL) as i128);
-    assert_eq!(send_token_client.balance(&fee_wallet), 12500);
+    assert_eq!(send_token_client.balance(&fee_wallet), 0);
 
     assert_eq!(recv_token_client.balance(&offeror), (50 * MUL_VAL) as i128);
-    assert_eq!(recv_token_client.balance(&token_swap.address), 0);
-    assert_eq!(recv_token_client.balance(&acceptor), (50 * MUL_VAL) as i128 - 1250);
-    assert_eq!(recv_token_client.balance(&fee_wallet), 1250);
+    assert_eq!(recv_token_client.balance(&token_swap.address), 2500);
+    assert_eq!(recv_token_client.balance(&acceptor), (50 * MUL_VAL) as i128 - 2500);
+    assert_eq!(recv_token_client.balance(&fee_wallet), 0);
+
+    assert_eq!(token_swap.get_volume(&acceptor, &recv_token_id), 50 * MUL_VAL);
+    assert_eq!(token_swap.get_volume(&acceptor, &send_token_id), 0);
+    assert_eq!(token_swap.get_volume(&offeror, &recv_token_id), 0);
+
+    let fills = token_swap.get_fills(&offer_id, &0, &10);
+    assert_eq!(fills.len(), 2);
+    assert_eq!(fills.get_unchecked(0).acceptor, acceptor);
+    assert_eq!(fills.get_unchecked(0).amount, 10 * MUL_VAL);
+    assert_eq!(fills.get_unchecked(0).send_amount, 100 * MUL_VAL);
+    assert_eq!(fills.get_unchecked(1).amount, 40 * MUL_VAL);
+    assert_eq!(fills.get_unchecked(1).send_amount, 200 * MUL_VAL);
+    assert_eq!(token_swap.get_fills(&offer_id, &1, &10).len(), 1);
     
     
-    // offeror closes offer
+    // 300 of the 500 send tokens were filled, so 60% of the maker fee is earned
+    assert_eq!(token_swap.get_offer(&offer_id).fee_amount, 5000);
+    assert_eq!(token_swap.get_accrued_fees(&send_token_id), 7500);
+
+    // offeror closes offer and gets back the unearned maker fee
     token_swap.close_offer(
         &offeror,
         &offer_id
     );
 
-    assert_eq!(send_token_client.balance(&offeror), (700 * MUL_VAL) as i128 - 12500);
-    assert_eq!(send_token_client.balance(&token_swap.address), 0);
+    assert_eq!(send_token_client.balance(&offeror), (700 * MUL_VAL) as i128 - 7500);
+    assert_eq!(send_token_client.balance(&token_swap.address), 7500);
     assert_eq!(send_token_client.balance(&acceptor), (300 * MUL_VAL) as i128);
-    assert_eq!(send_token_client.balance(&fee_wallet), 12500);
+    assert_eq!(send_token_client.balance(&fee_wallet), 0);
     
     assert_eq!(recv_token_client.balance(&offeror), (50 * MUL_VAL) as i128);
+    assert_eq!(recv_token_client.balance(&token_swap.address), 2500);
+    assert_eq!(recv_token_client.balance(&acceptor), (50 * MUL_VAL) as i128 - 2500);
+    assert_eq!(recv_token_client.balance(&fee_wallet), 0);
+
+    // fee wallet claims the accrued fees
+    assert_eq!(token_swap.get_offer(&offer_id).fee_amount, 0);
+    assert_eq!(token_swap.get_accrued_fees(&send_token_id), 7500);
+    assert_eq!(token_swap.get_accrued_fees(&recv_token_id), 2500);
+    assert_eq!(token_swap.claim_fees(&send_token_id), 7500);
+    assert_eq!(token_swap.claim_fees(&recv_token_id), 2500);
+    assert_eq!(e.auths()[0].0, fee_wallet);
+    assert_eq!(token_swap.claim_fees(&recv_token_id), 0);
+
+    assert_eq!(send_token_client.balance(&token_swap.address), 0);
+    assert_eq!(send_token_client.balance(&fee_wallet), 7500);
     assert_eq!(recv_token_client.balance(&token_swap.address), 0);
-    assert_eq!(recv_token_client.balance(&acceptor), (50 * MUL_VAL) as i128 - 1250);
-    assert_eq!(recv_token_client.balance(&fee_wallet), 1250);
+    assert_eq!(recv_token_client.balance(&fee_wallet), 2500);
+
+    assert_eq!(token_swap.list_offers(&0, &10), vec![&e]);
+    assert_eq!(token_swap.offers_of(&offeror, &0, &10), vec![&e]);
+    assert_eq!(token_swap.offers_for_pair(&send_token_id, &recv_token_id, &0, &10), vec![&e]);
 
 
     // disallow tokens
-    token_swap.disallow_token(&send_token_id);
-    token_swap.disallow_token(&recv_token_id);
+    assert_eq!(token_swap.list_allowed_tokens(&0, &10), vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+    assert_eq!(token_swap.list_allowed_tokens(&1, &10), vec![&e, recv_token_id.clone()]);
+    token_swap.disallow_token(&admin, &send_token_id);
+    assert!(!token_swap.is_token_allowed(&send_token_id));
+    assert!(token_swap.is_token_allowed(&recv_token_id));
+    assert_eq!(token_swap.list_allowed_tokens(&0, &10), vec![&e, recv_token_id.clone()]);
+    token_swap.disallow_token(&admin, &recv_token_id);
+    assert_eq!(token_swap.list_allowed_tokens(&0, &10), vec![&e]);
+
+    // in batches, skipping tokens already in the requested state
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone(), send_token_id.clone()]);
+    assert_eq!(token_swap.list_allowed_tokens(&0, &10), vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+    assert_eq!(token_swap.try_disallow_tokens(&offeror, &vec![&e, send_token_id.clone()]), Err(Ok(SwapError::Unauthorized)));
+    token_swap.disallow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+    assert_eq!(token_swap.list_allowed_tokens(&0, &10), vec![&e]);
+    e.as_contract(&token_swap.address, || {
+        assert!(!e.storage().persistent().has(&DataKey::Allowance(send_token_id.clone())));
+    });
+}
+
+
+#[test]
+fn test_expire() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    // expire_ledger must be in the future
+    assert_eq!(token_swap.try_create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(500 * MUL_VAL), &(50 * MUL_VAL), &(10 * MUL_VAL),
+        &e.ledger().sequence(), &None, &OfferMode::PARTIAL), Err(Ok(SwapError::InvalidExpiration)));
+
+    let expire_ledger: u32 = e.ledger().sequence() + 100;
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(500 * MUL_VAL), &(50 * MUL_VAL), &(10 * MUL_VAL),
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+
+    // offers can't be accepted while the contract is paused
+    token_swap.pause(&admin);
+    assert!(token_swap.is_paused());
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &None), Err(Ok(SwapError::Paused)));
+    token_swap.unpause(&admin);
+    assert!(!token_swap.is_paused());
+
+    // 20% of the offer is filled, which earns 20% of the maker fee
+    token_swap.accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &None);
+    assert_eq!(token_swap.get_accrued_fees(&send_token_id), 2500);
+
+    // can't be expired before expire_ledger
+    assert_eq!(token_swap.try_expire_offer(&offer_id), Err(Ok(SwapError::OfferNotExpired)));
+
+    e.ledger().with_mut(|li| li.sequence_number = expire_ledger);
+
+    // expired offer can't be accepted anymore
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &None), Err(Ok(SwapError::OfferExpired)));
+
+    // anyone can return the remaining send_token and unearned maker fee to the offeror
+    token_swap.expire_offer(&offer_id);
+    assert_eq!(send_token_client.balance(&offeror), (900 * MUL_VAL) as i128 - 2500);
+    assert_eq!(send_token_client.balance(&token_swap.address), 2500);
+    assert_eq!(token_swap.get_accrued_fees(&send_token_id), 2500);
+    assert_eq!(token_swap.get_offer(&offer_id).fee_amount, 0);
+    assert_eq!(token_swap.get_offer(&offer_id).status, OfferStatus::EXPIRED);
+
+    assert_eq!(token_swap.try_expire_offer(&offer_id), Err(Ok(SwapError::OfferNotActive)));
+
+    // the entry of any stored offer can be kept alive, up to the max TTL
+    token_swap.extend_offer_ttl(&offer_id, &(60 * DAY_IN_LEDGERS));
+    token_swap.extend_offer_ttl(&offer_id, &u32::MAX);
+    assert_eq!(token_swap.try_extend_offer_ttl(&(offer_id + 1), &DAY_IN_LEDGERS), Err(Ok(SwapError::OfferNotFound)));
+}
+
+
+#[test]
+fn test_admin() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let new_admin = Address::generate(&e);
+    let fee_wallet = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+
+    // fee can't be proposed before the admin is initialized
+    assert_eq!(token_swap.try_propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &fee_wallet), Err(Ok(SwapError::NotInitialized)));
+
+    token_swap.initialize(&admin);
+    assert_eq!(token_swap.try_initialize(&new_admin), Err(Ok(SwapError::AlreadyInitialized)));
+
+    // a fresh deployment is already at the current schema version
+    assert_eq!(token_swap.version(), 2);
+    assert_eq!(token_swap.try_migrate(), Err(Ok(SwapError::AlreadyMigrated)));
+
+    // propose_fee must be authorized by the admin
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &fee_wallet);
+    assert_eq!(e.auths()[0].0, admin);
+
+    assert_eq!(token_swap.try_accept_admin(), Err(Ok(SwapError::NoPendingAdmin)));
+
+    // admin stays in charge until the proposal is accepted
+    token_swap.propose_admin(&new_admin);
+    assert_eq!(e.auths()[0].0, admin);
+    assert_eq!(token_swap.get_admin(), admin);
+
+    token_swap.accept_admin();
+    assert_eq!(e.auths()[0].0, new_admin);
+    assert_eq!(token_swap.get_admin(), new_admin);
+    assert_eq!(token_swap.try_accept_admin(), Err(Ok(SwapError::NoPendingAdmin)));
+
+    token_swap.propose_fee(&new_admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &fee_wallet);
+    assert_eq!(e.auths()[0].0, new_admin);
+}
+
+
+#[test]
+fn test_roles() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let manager = Address::generate(&e);
+    let fee_wallet = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    assert!(token_swap.has_role(&Role::FeeManager, &admin));
+    assert!(!token_swap.has_role(&Role::FeeManager, &manager));
+    assert_eq!(token_swap.try_propose_fee(&manager, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &fee_wallet), Err(Ok(SwapError::Unauthorized)));
+
+    // admin is only transferred by propose_admin / accept_admin
+    assert_eq!(token_swap.try_grant_role(&Role::Admin, &manager), Err(Ok(SwapError::Unauthorized)));
+
+    token_swap.grant_role(&Role::FeeManager, &manager);
+    assert!(token_swap.has_role(&Role::FeeManager, &manager));
+    token_swap.propose_fee(&manager, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &fee_wallet);
+    assert_eq!(e.auths()[0].0, manager);
+
+    // a role doesn't grant the other ones
+    assert_eq!(token_swap.try_pause(&manager), Err(Ok(SwapError::Unauthorized)));
+    assert_eq!(token_swap.try_allow_token(&manager, &fee_wallet), Err(Ok(SwapError::Unauthorized)));
+
+    token_swap.revoke_role(&Role::FeeManager, &manager);
+    assert!(!token_swap.has_role(&Role::FeeManager, &manager));
+    assert_eq!(token_swap.try_propose_fee(&manager, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &fee_wallet), Err(Ok(SwapError::Unauthorized)));
+}
+
+
+#[test]
+fn test_fee_timelock() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let fee_wallet = Address::generate(&e);
+    let new_fee_wallet = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    assert_eq!(token_swap.try_apply_fee(), Err(Ok(SwapError::NoPendingFee)));
+
+    // without a delay the first fee can be applied right away
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &fee_wallet);
+    token_swap.apply_fee();
+    assert_eq!(token_swap.get_fee(), FeeInfo { maker_fee_rate: DEF_MAKER_FEE_RATE, taker_fee_rate: DEF_TAKER_FEE_RATE, fee_wallet: fee_wallet.clone() });
+
+    // rates above 10% are rejected
+    assert_eq!(token_swap.try_propose_fee(&admin, &1001, &DEF_TAKER_FEE_RATE, &fee_wallet), Err(Ok(SwapError::FeeRateTooHigh)));
+    assert_eq!(token_swap.try_propose_fee(&admin, &DEF_MAKER_FEE_RATE, &1001, &fee_wallet), Err(Ok(SwapError::FeeRateTooHigh)));
+    assert_eq!(token_swap.try_get_pending_fee(), Err(Ok(SwapError::NoPendingFee)));
+
+    token_swap.set_fee_delay(&admin, &100);
+    assert_eq!(token_swap.get_fee_delay(), 100);
+
+    let apply_ledger = e.ledger().sequence() + 100;
+    token_swap.propose_fee(&admin, &10, &20, &new_fee_wallet);
+    assert_eq!(token_swap.get_pending_fee(), (10, 20, new_fee_wallet.clone(), apply_ledger));
+
+    // the current fee stays effective until the delay has passed
+    assert_eq!(token_swap.try_apply_fee(), Err(Ok(SwapError::FeeTimelocked)));
+    assert_eq!(token_swap.get_fee(), FeeInfo { maker_fee_rate: DEF_MAKER_FEE_RATE, taker_fee_rate: DEF_TAKER_FEE_RATE, fee_wallet: fee_wallet.clone() });
+
+    e.ledger().with_mut(|li| li.sequence_number = apply_ledger);
+    token_swap.apply_fee();
+    assert_eq!(token_swap.get_fee(), FeeInfo { maker_fee_rate: 10, taker_fee_rate: 20, fee_wallet: new_fee_wallet.clone() });
+
+    let config = token_swap.get_config();
+    assert_eq!(config.admin, admin);
+    assert_eq!(config.fee, token_swap.get_fee());
+    assert_eq!(config.fee_delay, token_swap.get_fee_delay());
+    assert!(!config.paused);
+    assert_eq!(config.schema_version, token_swap.version());
+    assert_eq!(token_swap.get_maker_fee_rate(), 10);
+    assert_eq!(token_swap.get_taker_fee_rate(), 20);
+    assert_eq!(token_swap.try_get_pending_fee(), Err(Ok(SwapError::NoPendingFee)));
+
+    // lowering the delay waits out the current one, so a fee can't be rushed through
+    assert_eq!(token_swap.try_apply_fee_changes(), Err(Ok(SwapError::NoPendingFee)));
+    token_swap.set_fee_delay(&admin, &0);
+    assert_eq!(token_swap.get_fee_delay(), 100);
+    assert_eq!(token_swap.get_pending_fee_changes().get_unchecked(0).apply_ledger, apply_ledger + 100);
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &fee_wallet);
+    assert_eq!(token_swap.try_apply_fee(), Err(Ok(SwapError::FeeTimelocked)));
+    assert_eq!(token_swap.try_apply_fee_changes(), Err(Ok(SwapError::FeeTimelocked)));
+
+    e.ledger().with_mut(|li| li.sequence_number = apply_ledger + 100);
+    assert_eq!(token_swap.apply_fee_changes(), 1);
+    assert_eq!(token_swap.get_fee_delay(), 0);
+    assert!(token_swap.get_pending_fee_changes().is_empty());
+
+    // raising it takes effect right away
+    token_swap.set_fee_delay(&admin, &50);
+    assert_eq!(token_swap.get_fee_delay(), 50);
+}
+
+#[test]
+fn test_token_fee() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let fee_wallet = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, _) = create_token_contract(&e, &token_admin);
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &fee_wallet);
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    assert_eq!(token_swap.try_set_token_fee(&admin, &send_token_id, &1001), Err(Ok(SwapError::FeeRateTooHigh)));
+
+    // 0.05% instead of the global 0.25% maker fee
+    token_swap.set_token_fee(&admin, &send_token_id, &5);
+    assert_eq!(token_swap.get_token_fee(&send_token_id), Some(5));
+    assert_eq!(token_swap.get_token_fee(&recv_token_id), None);
+
+    let expire_ledger: u32 = e.ledger().sequence() + 1000;
+    let offer_id = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(10 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+    assert_eq!(token_swap.get_offer(&offer_id).fee_amount, 500);
+
+    // back to the global rate
+    token_swap.clear_token_fee(&admin, &send_token_id);
+    assert_eq!(token_swap.get_token_fee(&send_token_id), None);
+    let offer_id = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(10 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+    assert_eq!(token_swap.get_offer(&offer_id).fee_amount, 2500);
+    assert_eq!(send_token_client.balance(&offeror), (800 * MUL_VAL) as i128 - 500 - 2500);
+
+    // with a fee delay, token rates, fee tiers, the fee token and discounts wait it out
+    token_swap.set_fee_delay(&admin, &10);
+    token_swap.set_token_fee(&admin, &send_token_id, &5);
+    token_swap.set_fee_tiers(&admin, &vec![&e, FeeTier { min_volume: 1, fee_rate: 1 }]);
+    token_swap.set_fee_token(&admin, &Some(FeeToken { token: recv_token_id.clone(), max_age: 300 }));
+    token_swap.set_fee_discounts(&admin, &Some(recv_token_id.clone()), &vec![&e, FeeDiscount { min_balance: 1, discount: 5000 }]);
+    assert_eq!(token_swap.get_pending_fee_changes().len(), 4);
+    assert_eq!(token_swap.get_token_fee(&send_token_id), None);
+    assert!(token_swap.get_fee_tiers().is_empty());
+    assert!(token_swap.get_fee_token().is_none());
+    assert!(token_swap.get_discount_token().is_none());
+    assert_eq!(token_swap.try_apply_fee_changes(), Err(Ok(SwapError::FeeTimelocked)));
+
+    e.ledger().with_mut(|li| li.sequence_number += 10);
+    assert_eq!(token_swap.apply_fee_changes(), 4);
+    assert_eq!(token_swap.get_token_fee(&send_token_id), Some(5));
+    assert_eq!(token_swap.get_fee_tiers().len(), 1);
+    assert_eq!(token_swap.get_fee_token().unwrap().token, recv_token_id);
+    assert_eq!(token_swap.get_discount_token(), Some(recv_token_id.clone()));
+
+    // and so does clearing them
+    token_swap.clear_token_fee(&admin, &send_token_id);
+    token_swap.set_fee_token(&admin, &None);
+    token_swap.set_fee_discounts(&admin, &None, &vec![&e]);
+    assert_eq!(token_swap.get_token_fee(&send_token_id), Some(5));
+    e.ledger().with_mut(|li| li.sequence_number += 10);
+    assert_eq!(token_swap.apply_fee_changes(), 3);
+    assert_eq!(token_swap.get_token_fee(&send_token_id), None);
+    assert!(token_swap.get_fee_token().is_none());
+    assert!(token_swap.get_discount_token().is_none());
+}
+
+
+#[test]
+fn test_fee_tiers() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    let fee_wallet = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &fee_wallet);
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    // thresholds must be increasing
+    assert_eq!(token_swap.try_set_fee_tiers(&admin, &vec![&e, 
+        FeeTier { min_volume: 10 * MUL_VAL, fee_rate: 10 }, 
+        FeeTier { min_volume: 10 * MUL_VAL, fee_rate: 5 }
+    ]), Err(Ok(SwapError::InvalidFeeTiers)));
+    assert_eq!(token_swap.try_set_fee_tiers(&admin, &vec![&e, 
+        FeeTier { min_volume: 10 * MUL_VAL, fee_rate: 1001 }
+    ]), Err(Ok(SwapError::FeeRateTooHigh)));
+
+    // 0.1% taker fee from 10 tokens of volume
+    token_swap.set_fee_tiers(&admin, &vec![&e, 
+        FeeTier { min_volume: 10 * MUL_VAL, fee_rate: 10 }, 
+        FeeTier { min_volume: 1000 * MUL_VAL, fee_rate: 5 }
+    ]);
+    assert_eq!(token_swap.get_fee_tiers().len(), 2);
+
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(500 * MUL_VAL), &(50 * MUL_VAL), &MUL_VAL,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+
+    token_swap.accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &None);
+    assert_eq!(token_swap.get_accrued_fees(&recv_token_id), 500);
+
+    token_swap.accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &None);
+    assert_eq!(token_swap.get_accrued_fees(&recv_token_id), 500 + 100);
+    assert_eq!(token_swap.get_volume(&acceptor, &recv_token_id), 20 * MUL_VAL);
+
+    // volume in recv_token doesn't earn a tier on fees charged in another token
+    let (other_token_id, _, other_token_admin_client) = create_token_contract(&e, &token_admin);
+    other_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    token_swap.allow_token(&admin, &other_token_id);
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &other_token_id, &0,
+        &(500 * MUL_VAL), &(50 * MUL_VAL), &MUL_VAL,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+    token_swap.accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &None);
+    assert_eq!(token_swap.get_accrued_fees(&other_token_id), 500);
+    assert_eq!(token_swap.get_volume(&acceptor, &other_token_id), 10 * MUL_VAL);
+}
+
+
+#[test]
+fn test_referral() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    let referrer = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    assert_eq!(token_swap.try_set_referral_share(&admin, &10001), Err(Ok(SwapError::FeeRateTooHigh)));
+    // 20% of the taker fee goes to the referrer
+    token_swap.set_referral_share(&admin, &2000);
+    assert_eq!(token_swap.get_referral_share(), 2000);
+
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(500 * MUL_VAL), &(50 * MUL_VAL), &MUL_VAL,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+
+    token_swap.accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &Some(referrer.clone()));
+    assert_eq!(token_swap.get_referral_fees(&referrer, &recv_token_id), 100);
+    assert_eq!(token_swap.get_accrued_fees(&recv_token_id), 400);
+
+    // fills without a referrer keep the whole fee
+    token_swap.accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &None);
+    assert_eq!(token_swap.get_referral_fees(&referrer, &recv_token_id), 100);
+    assert_eq!(token_swap.get_accrued_fees(&recv_token_id), 400 + 500);
+
+    assert_eq!(token_swap.claim_referral_fees(&referrer, &recv_token_id), 100);
+    assert_eq!(e.auths()[0].0, referrer);
+    assert_eq!(recv_token_client.balance(&referrer), 100);
+    assert_eq!(token_swap.get_referral_fees(&referrer, &recv_token_id), 0);
+}
+
+
+#[test]
+fn test_prune() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    let manager = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let expire_ledger: u32 = e.ledger().sequence() + 100;
+    let mut offer_ids = vec![&e];
+    for _ in 0..4 {
+        offer_ids.push_back(token_swap.create_offer(
+            &offeror, &send_token_id, &recv_token_id, &0,
+            &(100 * MUL_VAL), &(10 * MUL_VAL), &MUL_VAL,
+            &expire_ledger, &None, &OfferMode::PARTIAL));
+    }
+    let closed = offer_ids.get_unchecked(0);
+    let completed = offer_ids.get_unchecked(1);
+    let active = offer_ids.get_unchecked(2);
+    let expired = offer_ids.get_unchecked(3);
+    token_swap.commit_accept(&acceptor, &closed, &BytesN::from_array(&e, &[1; 32]));
+    token_swap.close_offer(&offeror, &closed);
+    token_swap.set_manager(&offeror, &Some(closed), &manager, &MANAGE_CLOSE);
+    token_swap.accept_offer(&acceptor, &completed, &(10 * MUL_VAL), &0, &None);
+
+    assert_eq!(token_swap.try_prune_offers(&offeror, &offer_ids), Err(Ok(SwapError::Unauthorized)));
+
+    // active offers and unknown ids are skipped
+    assert_eq!(token_swap.prune_offers(&admin, &vec![&e, closed, completed, active, 100]), 2);
+    assert_eq!(token_swap.try_get_offer(&closed).err(), Some(Ok(SwapError::OfferNotFound)));
+    assert_eq!(token_swap.try_get_offer(&completed).err(), Some(Ok(SwapError::OfferNotFound)));
+    assert_eq!(token_swap.get_offer(&active).status, OfferStatus::ACTIVE);
+
+    // nothing stored for the pruned offers survives them
+    e.as_contract(&token_swap.address, || {
+        let storage = e.storage().persistent();
+        assert!(!storage.has(&DataKey::OfferManager(closed, offeror.clone(), manager.clone())));
+        assert!(!storage.has(&OfferDataKey::Managers(closed)));
+        assert!(!storage.has(&DataKey::Commit(closed, acceptor.clone())));
+        assert!(!storage.has(&OfferDataKey::Committers(closed)));
+        assert!(!storage.has(&DataKey::OfferLock(closed)));
+        assert!(!storage.has(&OfferDataKey::CommitLockUsed(closed)));
+        assert!(!storage.has(&DataKey::FillCount(completed)));
+        assert!(!storage.has(&DataKey::Fill(completed, 0)));
+        assert!(!storage.has(&OfferDataKey::Dust(completed)));
+    });
+
+    e.ledger().with_mut(|li| li.sequence_number = expire_ledger);
+    token_swap.expire_offer(&expired);
+
+    assert_eq!(token_swap.purge_before(&admin, &expire_ledger, &0, &10), 0);
+    assert_eq!(token_swap.purge_before(&admin, &(expire_ledger + 1), &0, &3), 0);
+    assert_eq!(token_swap.purge_before(&admin, &(expire_ledger + 1), &3, &10), 1);
+    assert_eq!(token_swap.try_get_offer(&expired).err(), Some(Ok(SwapError::OfferNotFound)));
+    assert_eq!(token_swap.get_offer(&active).status, OfferStatus::ACTIVE);
+
+    // an auctioned offer is kept until every outbid bidder got their escrow back
+    let bidder = Address::generate(&e);
+    recv_token_admin_client.mint(&bidder, &(100_i128 * MUL_VAL as i128));
+    let auctioned = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(10 * MUL_VAL), &MUL_VAL,
+        &(e.ledger().sequence() + 100), &None, &OfferMode::PARTIAL);
+    token_swap.start_auction(&offeror, &auctioned, &(e.ledger().sequence() + 10));
+    token_swap.place_bid(&bidder, &auctioned, &(10 * MUL_VAL));
+    token_swap.place_bid(&acceptor, &auctioned, &(11 * MUL_VAL));
+    e.ledger().with_mut(|li| li.sequence_number += 10);
+    token_swap.settle_auction(&auctioned);
+    assert_eq!(token_swap.prune_offers(&admin, &vec![&e, auctioned]), 0);
+    token_swap.refund_bid(&bidder, &auctioned);
+    assert_eq!(token_swap.prune_offers(&admin, &vec![&e, auctioned]), 1);
+    e.as_contract(&token_swap.address, || {
+        assert!(!e.storage().persistent().has(&OfferDataKey::Bidders(auctioned)));
+        assert!(!e.storage().persistent().has(&DataKey::AuctionEnd(auctioned)));
+    });
+}
+
+
+#[test]
+fn test_resize() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(200 * MUL_VAL), &(20 * MUL_VAL), &MUL_VAL,
+        &(e.ledger().sequence() + 100), &None, &OfferMode::PARTIAL);
+
+    assert_eq!(token_swap.try_increase_offer(&acceptor, &offer_id, &(100 * MUL_VAL)), Err(Ok(SwapError::InvalidOfferor)));
+    assert_eq!(token_swap.try_increase_offer(&offeror, &offer_id, &0), Err(Ok(SwapError::ZeroAmount)));
+
+    // topping up keeps the price of 10 send tokens per recv token
+    token_swap.increase_offer(&offeror, &offer_id, &(100 * MUL_VAL));
+    let offer = token_swap.get_offer(&offer_id);
+    assert_eq!(offer.send_amount, 300 * MUL_VAL);
+    assert_eq!(offer.recv_amount, 30 * MUL_VAL);
+    assert_eq!(offer.fee_amount, 5000 + 2500);
+    assert_eq!(send_token_client.balance(&offeror), (700 * MUL_VAL) as i128 - 7500);
+    assert_eq!(send_token_client.balance(&token_swap.address), (300 * MUL_VAL) as i128 + 7500);
+
+    // the whole send_amount can only be withdrawn by closing the offer
+    assert_eq!(token_swap.try_decrease_offer(&acceptor, &offer_id, &(100 * MUL_VAL)), Err(Ok(SwapError::InvalidOfferor)));
+    assert_eq!(token_swap.try_decrease_offer(&offeror, &offer_id, &(300 * MUL_VAL)), Err(Ok(SwapError::AmountTooLarge)));
+
+    // withdrawing returns the maker fee paid for the withdrawn part
+    token_swap.decrease_offer(&offeror, &offer_id, &(250 * MUL_VAL));
+    let offer = token_swap.get_offer(&offer_id);
+    assert_eq!(offer.send_amount, 50 * MUL_VAL);
+    assert_eq!(offer.recv_amount, 5 * MUL_VAL);
+    assert_eq!(offer.min_recv_amount, MUL_VAL);
+    assert_eq!(offer.fee_amount, 1250);
+    assert_eq!(send_token_client.balance(&offeror), (950 * MUL_VAL) as i128 - 1250);
+    assert_eq!(send_token_client.balance(&token_swap.address), (50 * MUL_VAL) as i128 + 1250);
+
+    // the reduced offer can still be filled
+    token_swap.accept_offer(&acceptor, &offer_id, &(5 * MUL_VAL), &0, &None);
+    assert_eq!(token_swap.get_offer(&offer_id).status, OfferStatus::COMPLETE);
+    assert_eq!(send_token_client.balance(&acceptor), (50 * MUL_VAL) as i128);
+
+    assert_eq!(token_swap.try_increase_offer(&offeror, &offer_id, &(100 * MUL_VAL)), Err(Ok(SwapError::OfferNotActive)));
+    assert_eq!(token_swap.try_decrease_offer(&offeror, &offer_id, &MUL_VAL), Err(Ok(SwapError::OfferNotActive)));
+}
+
+
+#[test]
+fn test_private_offer() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    let stranger = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+    recv_token_admin_client.mint(&stranger, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(500 * MUL_VAL), &(50 * MUL_VAL), &MUL_VAL,
+        &(e.ledger().sequence() + 100), &None, &OfferMode::PARTIAL);
+    assert_eq!(token_swap.get_acceptors(&offer_id), None);
+
+    assert_eq!(token_swap.try_add_acceptor(&stranger, &offer_id, &stranger), Err(Ok(SwapError::InvalidOfferor)));
+    token_swap.add_acceptor(&offeror, &offer_id, &acceptor);
+    token_swap.add_acceptor(&offeror, &offer_id, &acceptor);
+    assert_eq!(token_swap.get_acceptors(&offer_id), Some(vec![&e, acceptor.clone()]));
+
+    assert_eq!(token_swap.try_accept_offer(&stranger, &offer_id, &(10 * MUL_VAL), &0, &None), Err(Ok(SwapError::AcceptorNotAllowed)));
+    token_swap.accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &None);
+
+    // an emptied whitelist keeps the offer private
+    token_swap.remove_acceptor(&offeror, &offer_id, &acceptor);
+    assert_eq!(token_swap.get_acceptors(&offer_id), Some(vec![&e]));
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &None), Err(Ok(SwapError::AcceptorNotAllowed)));
+
+    token_swap.clear_acceptors(&offeror, &offer_id);
+    assert_eq!(token_swap.get_acceptors(&offer_id), None);
+    token_swap.accept_offer(&stranger, &offer_id, &(10 * MUL_VAL), &0, &None);
+
+    // an offer for a designated counterparty must be taken in full by that address
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(10 * MUL_VAL), &MUL_VAL,
+        &(e.ledger().sequence() + 100), &Some(acceptor.clone()), &OfferMode::PARTIAL);
+    assert_eq!(token_swap.get_counterparty(&offer_id), Some(acceptor.clone()));
+
+    assert_eq!(token_swap.try_accept_offer(&stranger, &offer_id, &(10 * MUL_VAL), &0, &None), Err(Ok(SwapError::AcceptorNotAllowed)));
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &(5 * MUL_VAL), &0, &None), Err(Ok(SwapError::AmountTooSmall)));
+    token_swap.accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &None);
+    assert_eq!(token_swap.get_offer(&offer_id).status, OfferStatus::COMPLETE);
+
+    // an all-or-nothing offer can't be partially filled
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(10 * MUL_VAL), &MUL_VAL,
+        &(e.ledger().sequence() + 100), &None, &OfferMode::ALL_OR_NOTHING);
+    assert_eq!(token_swap.get_offer(&offer_id).mode, OfferMode::ALL_OR_NOTHING);
+
+    assert_eq!(token_swap.try_accept_offer(&stranger, &offer_id, &(5 * MUL_VAL), &0, &None), Err(Ok(SwapError::AmountTooSmall)));
+    token_swap.accept_offer(&stranger, &offer_id, &(10 * MUL_VAL), &0, &None);
+    assert_eq!(token_swap.get_offer(&offer_id).status, OfferStatus::COMPLETE);
+}
+
+
+#[test]
+fn test_accept_offers() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    // 10 and 8 send tokens per recv token
+    let expire_ledger: u32 = e.ledger().sequence() + 100;
+    let offer_a: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(200 * MUL_VAL), &(20 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+    let offer_b: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(200 * MUL_VAL), &(25 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+
+    // a failing fill reverts the whole batch
+    assert_eq!(token_swap.try_accept_offers(&acceptor, 
+        &vec![&e, (offer_a, 20 * MUL_VAL), (offer_b, 30 * MUL_VAL)], &0, &None), 
+        Err(Ok(SwapError::AmountTooLarge)));
+    assert_eq!(token_swap.try_accept_offers(&acceptor, 
+        &vec![&e, (offer_a, 20 * MUL_VAL), (offer_b, 5 * MUL_VAL)], &(241 * MUL_VAL), &None), 
+        Err(Ok(SwapError::SlippageExceeded)));
+    assert_eq!(token_swap.get_offer(&offer_a).recv_amount, 20 * MUL_VAL);
+    assert_eq!(recv_token_client.balance(&acceptor), (100 * MUL_VAL) as i128);
+
+    assert_eq!(token_swap.accept_offers(&acceptor, 
+        &vec![&e, (offer_a, 20 * MUL_VAL), (offer_b, 5 * MUL_VAL)], &(240 * MUL_VAL), &None), 
+        (25 * MUL_VAL, 240 * MUL_VAL));
+    assert_eq!(token_swap.get_offer(&offer_a).status, OfferStatus::COMPLETE);
+    assert_eq!(token_swap.get_offer(&offer_b).recv_amount, 20 * MUL_VAL);
+    assert_eq!(send_token_client.balance(&acceptor), (240 * MUL_VAL) as i128);
+    assert_eq!(recv_token_client.balance(&acceptor), (75 * MUL_VAL) as i128 - 1250);
+}
+
+
+#[test]
+fn test_create_offers() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, _) = create_token_contract(&e, &token_admin);
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let spec = |send_amount: u64, recv_amount: u64| OfferSpec {
+        send_token: send_token_id.clone(),
+        recv_token: recv_token_id.clone(),
+        timestamp: 0,
+        send_amount,
+        recv_amount,
+        min_recv_amount: MUL_VAL,
+        expire_ledger: e.ledger().sequence() + 100,
+        mode: OfferMode::PARTIAL,
+    };
+
+    // a single invalid spec fails the whole ladder
+    assert_eq!(token_swap.try_create_offers(&offeror, &vec![&e, spec(100 * MUL_VAL, 10 * MUL_VAL), spec(100 * MUL_VAL, 0)]), 
+        Err(Ok(SwapError::ZeroAmount)));
+    assert_eq!(token_swap.count_offers(), 0);
+
+    // a ladder of 10, 11 and 12 send tokens per recv token
+    let offer_ids = token_swap.create_offers(&offeror, &vec![&e, 
+        spec(100 * MUL_VAL, 10 * MUL_VAL), 
+        spec(110 * MUL_VAL, 10 * MUL_VAL), 
+        spec(120 * MUL_VAL, 10 * MUL_VAL)
+    ]);
+    assert_eq!(offer_ids, vec![&e, 0, 1, 2]);
+    assert_eq!(token_swap.get_offer(&1).send_amount, 110 * MUL_VAL);
+    assert_eq!(token_swap.list_offers(&0, &10), offer_ids);
+    assert_eq!(send_token_client.balance(&offeror), (670 * MUL_VAL) as i128 - 8250);
+}
+
+
+#[test]
+fn test_match_offers() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror_a = Address::generate(&e);
+    let offeror_b = Address::generate(&e);
+    let fee_wallet = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (token_x, token_x_client, token_x_admin_client) = create_token_contract(&e, &token_admin);
+    token_x_admin_client.mint(&offeror_a, &(1000_i128 * MUL_VAL as i128));
+    let (token_y, token_y_client, token_y_admin_client) = create_token_contract(&e, &token_admin);
+    token_y_admin_client.mint(&offeror_b, &(1000_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &fee_wallet);
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &token_x);
+    token_swap.allow_token(&admin, &token_y);
+
+    // a sells x for 10 x per y, b buys x for up to 100 / 12 x per y
+    let expire_ledger: u32 = e.ledger().sequence() + 100;
+    let offer_a: u32 = token_swap.create_offer(
+        &offeror_a, &token_x, &token_y, &0,
+        &(100 * MUL_VAL), &(10 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+    let offer_b: u32 = token_swap.create_offer(
+        &offeror_b, &token_y, &token_x, &0,
+        &(12 * MUL_VAL), &(100 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+    let offer_c: u32 = token_swap.create_offer(
+        &offeror_b, &token_y, &token_x, &0,
+        &(5 * MUL_VAL), &(100 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+
+    assert_eq!(token_swap.try_match_offers(&offer_a, &offer_a, &(5 * MUL_VAL)), Err(Ok(SwapError::OffersNotCrossed)));
+    assert_eq!(token_swap.try_match_offers(&offer_a, &offer_c, &(5 * MUL_VAL)), Err(Ok(SwapError::OffersNotCrossed)));
+
+    // 5 y and its taker fee of 0.025 y cost b 41.875 x, which a pays with a fee of 0.2093 x
+    // out of the 50 x it sells for them, the rest going back to a
+    assert_eq!(token_swap.match_offers(&offer_a, &offer_b, &(5 * MUL_VAL)), (50 * MUL_VAL, 50250));
+    assert_eq!(token_x_client.balance(&offeror_b), 418750);
+    assert_eq!(token_y_client.balance(&offeror_a), (5 * MUL_VAL) as i128);
+    assert_eq!(token_x_client.balance(&offeror_a), (900 * MUL_VAL) as i128 - 2500 + 500000 - 418750 - 2093);
+
+    let a = token_swap.get_offer(&offer_a);
+    assert_eq!((a.send_amount, a.recv_amount, a.fee_amount), (50 * MUL_VAL, 5 * MUL_VAL, 1250));
+    let b = token_swap.get_offer(&offer_b);
+    assert_eq!((b.send_amount, b.recv_amount, b.fee_amount), (12 * MUL_VAL - 50250, 100 * MUL_VAL - 418750, 300 - 125));
+    assert_eq!(token_swap.get_fills(&offer_a, &0, &10).get_unchecked(0).acceptor, offeror_b);
+    assert_eq!(token_swap.get_fills(&offer_b, &0, &10).get_unchecked(0).acceptor, offeror_a);
+
+    // both makers earn their maker fee and both sides pay a taker fee
+    assert_eq!(token_swap.get_accrued_fees(&token_x), 1250 + 2093);
+    assert_eq!(token_swap.get_accrued_fees(&token_y), 125 + 250);
+    token_swap.claim_fees(&token_x);
+    token_swap.claim_fees(&token_y);
+    assert_eq!(token_x_client.balance(&fee_wallet), 1250 + 2093);
+    assert_eq!(token_y_client.balance(&fee_wallet), 125 + 250);
+    for token in [&token_x, &token_y] {
+        let (owed, balance) = token_swap.check_solvency(token);
+        assert_eq!(owed, balance);
+    }
+
+    // a banned offeror's offer can't be matched
+    token_swap.ban_address(&admin, &offeror_b);
+    assert_eq!(token_swap.try_match_offers(&offer_a, &offer_b, &(5 * MUL_VAL)), Err(Ok(SwapError::Unauthorized)));
+    token_swap.unban_address(&admin, &offeror_b);
+
+    // the rest of a
+    token_swap.match_offers(&offer_a, &offer_b, &(5 * MUL_VAL));
+    assert_eq!(token_swap.get_offer(&offer_a).status, OfferStatus::COMPLETE);
+    assert_eq!(token_swap.get_offer(&offer_b).status, OfferStatus::ACTIVE);
+}
+
+
+#[test]
+fn test_orderbook() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    assert_eq!(token_swap.best_offer(&send_token_id, &recv_token_id), None);
+
+    let expire_ledger: u32 = e.ledger().sequence() + 100;
+    let create = |send_amount: u64, recv_amount: u64| token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &send_amount, &recv_amount, &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+    let offer_a = create(100 * MUL_VAL, 10 * MUL_VAL);
+    let offer_b = create(125 * MUL_VAL, 10 * MUL_VAL);
+    let offer_c = create(100 * MUL_VAL, 10 * MUL_VAL);
+    let offer_d = create(80 * MUL_VAL, 10 * MUL_VAL);
+
+    // b is the cheapest, a and c share a level in time order
+    assert_eq!(token_swap.best_offer(&send_token_id, &recv_token_id), Some(offer_b));
+    let levels = token_swap.book_levels(&send_token_id, &recv_token_id, &0, &10);
+    assert_eq!(levels.len(), 3);
+    assert_eq!((levels.get_unchecked(0).send_amount, levels.get_unchecked(0).recv_amount), (125 * MUL_VAL, 10 * MUL_VAL));
+    assert_eq!((levels.get_unchecked(1).send_amount, levels.get_unchecked(1).offer_count), (200 * MUL_VAL, 2));
+    assert_eq!(levels.get_unchecked(2).send_amount, 80 * MUL_VAL);
+    let levels = token_swap.book_levels(&send_token_id, &recv_token_id, &1, &1);
+    assert_eq!(levels.len(), 1);
+    assert_eq!(levels.get_unchecked(0).recv_amount, 20 * MUL_VAL);
+
+    // repricing moves d to the top
+    token_swap.update_offer(&offeror, &offer_d, &(5 * MUL_VAL), &MUL_VAL);
+    assert_eq!(token_swap.best_offer(&send_token_id, &recv_token_id), Some(offer_d));
+
+    assert_eq!(token_swap.try_take_best(&acceptor, &send_token_id, &recv_token_id, &(15 * MUL_VAL), &(206 * MUL_VAL)), 
+        Err(Ok(SwapError::SlippageExceeded)));
+    assert_eq!(token_swap.take_best(&acceptor, &send_token_id, &recv_token_id, &(15 * MUL_VAL), &(205 * MUL_VAL)), 
+        (15 * MUL_VAL, 205 * MUL_VAL));
+    assert_eq!(send_token_client.balance(&acceptor), (205 * MUL_VAL) as i128);
+    assert_eq!(token_swap.get_offer(&offer_d).status, OfferStatus::COMPLETE);
+    assert_eq!(token_swap.get_offer(&offer_b).status, OfferStatus::COMPLETE);
+    assert_eq!(token_swap.best_offer(&send_token_id, &recv_token_id), Some(offer_a));
+
+    // takes what's left when the book runs out
+    assert_eq!(token_swap.take_best(&acceptor, &send_token_id, &recv_token_id, &(50 * MUL_VAL), &0), 
+        (20 * MUL_VAL, 200 * MUL_VAL));
+    assert_eq!(token_swap.get_offer(&offer_c).status, OfferStatus::COMPLETE);
+    assert_eq!(token_swap.best_offer(&send_token_id, &recv_token_id), None);
+}
+
+
+#[test]
+fn test_dutch_auction() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let start_ledger = e.ledger().sequence();
+    let expire_ledger: u32 = start_ledger + 1000;
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(20 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+    let stepped_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(20 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+
+    assert_eq!(token_swap.try_set_dutch_auction(&offeror, &offer_id, &(30 * MUL_VAL), &(start_ledger + 100), &0), 
+        Err(Ok(SwapError::InvalidAuction)));
+    assert_eq!(token_swap.try_set_dutch_auction(&offeror, &offer_id, &(10 * MUL_VAL), &start_ledger, &0), 
+        Err(Ok(SwapError::InvalidAuction)));
+    token_swap.set_dutch_auction(&offeror, &offer_id, &(10 * MUL_VAL), &(start_ledger + 100), &0);
+    token_swap.set_dutch_auction(&offeror, &stepped_id, &(10 * MUL_VAL), &(start_ledger + 100), &40);
+    assert_eq!(token_swap.get_dutch_auction(&offer_id).unwrap().start_recv_amount, 20 * MUL_VAL);
+
+    // halfway through the price has dropped by half, the stepped one only for 40 ledgers
+    e.ledger().with_mut(|li| li.sequence_number = start_ledger + 50);
+    assert_eq!(token_swap.get_offer(&offer_id).recv_amount, 15 * MUL_VAL);
+    assert_eq!(token_swap.get_offer(&stepped_id).recv_amount, 16 * MUL_VAL);
+    assert_eq!(token_swap.quote_accept(&offer_id, &(3 * MUL_VAL), &None).0, 20 * MUL_VAL);
+
+    token_swap.accept_offer(&acceptor, &offer_id, &(3 * MUL_VAL), &(20 * MUL_VAL), &None);
+    assert_eq!(send_token_client.balance(&acceptor), (20 * MUL_VAL) as i128);
+
+    // the rest stops at the floor price
+    e.ledger().with_mut(|li| li.sequence_number = start_ledger + 500);
+    let offer = token_swap.get_offer(&offer_id);
+    assert_eq!((offer.send_amount, offer.recv_amount), (80 * MUL_VAL, 8 * MUL_VAL));
+
+    // repricing by hand ends the auction
+    token_swap.update_offer(&offeror, &stepped_id, &(18 * MUL_VAL), &MUL_VAL);
+    assert!(token_swap.get_dutch_auction(&stepped_id).is_none());
+    assert_eq!(token_swap.get_offer(&stepped_id).recv_amount, 18 * MUL_VAL);
+}
+
+#[test]
+fn test_english_auction() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let bidder1 = Address::generate(&e);
+    let bidder2 = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&bidder1, &(100_i128 * MUL_VAL as i128));
+    recv_token_admin_client.mint(&bidder2, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let start_ledger = e.ledger().sequence();
+    let end_ledger = start_ledger + 100;
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(20 * MUL_VAL), &(10 * MUL_VAL),
+        &(start_ledger + 1000), &None, &OfferMode::PARTIAL);
+
+    assert_eq!(token_swap.try_start_auction(&offeror, &offer_id, &(start_ledger + 1000)), 
+        Err(Ok(SwapError::InvalidAuction)));
+    token_swap.start_auction(&offeror, &offer_id, &end_ledger);
+    assert_eq!(token_swap.get_auction(&offer_id), Some(end_ledger));
+
+    // the offer is locked while bidding runs
+    assert_eq!(token_swap.try_accept_offer(&bidder1, &offer_id, &(20 * MUL_VAL), &0, &None), 
+        Err(Ok(SwapError::OfferInAuction)));
+    assert_eq!(token_swap.try_close_offer(&offeror, &offer_id), Err(Ok(SwapError::OfferInAuction)));
+
+    // bids start at min_recv_amount and must beat the best bid
+    assert_eq!(token_swap.try_place_bid(&bidder1, &offer_id, &(5 * MUL_VAL)), Err(Ok(SwapError::BidTooLow)));
+    token_swap.place_bid(&bidder1, &offer_id, &(10 * MUL_VAL));
+    assert_eq!(token_swap.get_bid_escrow(&offer_id, &bidder1), 10 * MUL_VAL + MUL_VAL / 20);
+    assert_eq!(token_swap.try_place_bid(&bidder2, &offer_id, &(10 * MUL_VAL)), Err(Ok(SwapError::BidTooLow)));
+    token_swap.place_bid(&bidder2, &offer_id, &(12 * MUL_VAL));
+
+    // the best bidder can't back out, the outbid one can
+    assert_eq!(token_swap.try_refund_bid(&bidder2, &offer_id), Err(Ok(SwapError::BidNotRefundable)));
+    assert_eq!(token_swap.refund_bid(&bidder1, &offer_id), 10 * MUL_VAL + MUL_VAL / 20);
+    assert_eq!(recv_token_client.balance(&bidder1), (100 * MUL_VAL) as i128);
+
+    // raising a bid only pays the difference
+    token_swap.place_bid(&bidder1, &offer_id, &(15 * MUL_VAL));
+    assert_eq!(recv_token_client.balance(&bidder1), (85 * MUL_VAL - 15 * MUL_VAL / 200) as i128);
+
+    assert_eq!(token_swap.try_settle_auction(&offer_id), Err(Ok(SwapError::OfferNotExpired)));
+    e.ledger().with_mut(|li| li.sequence_number = end_ledger);
+    assert_eq!(token_swap.try_place_bid(&bidder2, &offer_id, &(16 * MUL_VAL)), Err(Ok(SwapError::OfferExpired)));
+
+    token_swap.settle_auction(&offer_id);
+    assert!(token_swap.get_auction(&offer_id).is_none());
+    assert_eq!(token_swap.get_offer(&offer_id).status, OfferStatus::COMPLETE);
+    assert_eq!(send_token_client.balance(&bidder1), (100 * MUL_VAL) as i128);
+    assert_eq!(recv_token_client.balance(&offeror), (15 * MUL_VAL) as i128);
+
+    // the loser still gets the escrow back after settlement
+    token_swap.refund_bid(&bidder2, &offer_id);
+    assert_eq!(recv_token_client.balance(&bidder2), (100 * MUL_VAL) as i128);
+}
+
+#[test]
+fn test_rfq() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let taker = Address::generate(&e);
+    let maker1 = Address::generate(&e);
+    let maker2 = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&taker, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&maker1, &(1000_i128 * MUL_VAL as i128));
+    recv_token_admin_client.mint(&maker2, &(1000_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let start_ledger = e.ledger().sequence();
+    let request_id = token_swap.request_quote(&taker, &send_token_id, &recv_token_id, &(200 * MUL_VAL), &(start_ledger + 100));
+
+    let quote1 = token_swap.submit_quote(&maker1, &request_id, &(400 * MUL_VAL), &(start_ledger + 50));
+    let quote2 = token_swap.submit_quote(&maker2, &request_id, &(420 * MUL_VAL), &(start_ledger + 10));
+    assert_eq!(token_swap.get_quote(&quote1).fee_amount, MUL_VAL);
+    assert_eq!(recv_token_client.balance(&maker1), (599 * MUL_VAL) as i128);
+
+    // quotes are firm while the request is live
+    assert_eq!(token_swap.try_withdraw_quote(&maker1, &quote1), Err(Ok(SwapError::OfferLocked)));
+    assert_eq!(token_swap.try_accept_quote(&maker1, &quote1), Err(Ok(SwapError::Unauthorized)));
+
+    // the better quote expired before the taker got to it
+    e.ledger().with_mut(|li| li.sequence_number = start_ledger + 10);
+    assert_eq!(token_swap.try_accept_quote(&taker, &quote2), Err(Ok(SwapError::OfferExpired)));
+    token_swap.accept_quote(&taker, &quote1);
+
+    assert_eq!(token_swap.get_request(&request_id).status, OfferStatus::COMPLETE);
+    assert_eq!(send_token_client.balance(&taker), (799 * MUL_VAL) as i128);
+    assert_eq!(send_token_client.balance(&maker1), (200 * MUL_VAL) as i128);
+    assert_eq!(recv_token_client.balance(&taker), (400 * MUL_VAL) as i128);
+    assert_eq!(token_swap.try_accept_quote(&taker, &quote1), Err(Ok(SwapError::OfferNotActive)));
+
+    // the other maker takes the escrow back
+    assert_eq!(token_swap.withdraw_quote(&maker2, &quote2), 420 * MUL_VAL + 105 * MUL_VAL / 100);
+    assert_eq!(recv_token_client.balance(&maker2), (1000 * MUL_VAL) as i128);
+    assert_eq!(token_swap.try_submit_quote(&maker2, &request_id, &MUL_VAL, &(start_ledger + 50)), 
+        Err(Ok(SwapError::OfferNotActive)));
+}
+
+#[test]
+fn test_commit_reveal() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let start_ledger = e.ledger().sequence();
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(20 * MUL_VAL), &MUL_VAL,
+        &(start_ledger + 1000), &None, &OfferMode::PARTIAL);
+
+    let salt = BytesN::from_array(&e, &[7; 32]);
+    let amount = 10 * MUL_VAL;
+    let hash = e.crypto().sha256(&(acceptor.clone(), offer_id, amount, salt.clone()).to_xdr(&e));
+    token_swap.commit_accept(&acceptor, &offer_id, &hash);
+
+    // the offeror can't reprice against the pending acceptance
+    assert_eq!(token_swap.try_update_offer(&offeror, &offer_id, &(40 * MUL_VAL), &MUL_VAL), 
+        Err(Ok(SwapError::OfferLocked)));
+    assert_eq!(token_swap.try_reveal_accept(&acceptor, &offer_id, &amount, &salt), 
+        Err(Ok(SwapError::InvalidReveal)));
+
+    e.ledger().with_mut(|li| li.sequence_number = start_ledger + 2);
+    assert_eq!(token_swap.try_reveal_accept(&acceptor, &offer_id, &(amount + 1), &salt), 
+        Err(Ok(SwapError::InvalidReveal)));
+    assert_eq!(token_swap.reveal_accept(&acceptor, &offer_id, &amount, &salt), 50 * MUL_VAL);
+    assert_eq!(send_token_client.balance(&acceptor), (50 * MUL_VAL) as i128);
+    assert_eq!(token_swap.try_reveal_accept(&acceptor, &offer_id, &amount, &salt), 
+        Err(Ok(SwapError::CommitNotFound)));
+
+    // unrevealed commitments lapse along with the lock
+    token_swap.commit_accept(&acceptor, &offer_id, &hash);
+    e.ledger().with_mut(|li| li.sequence_number = start_ledger + 102);
+    assert_eq!(token_swap.try_reveal_accept(&acceptor, &offer_id, &amount, &salt), 
+        Err(Ok(SwapError::InvalidReveal)));
+    token_swap.update_offer(&offeror, &offer_id, &(20 * MUL_VAL), &MUL_VAL);
+
+    // committing again forfeits the lapsed bond, revealing on a closed offer returns the new one
+    let acceptor_balance = recv_token_client.balance(&acceptor);
+    let offeror_balance = recv_token_client.balance(&offeror);
+    token_swap.commit_accept(&acceptor, &offer_id, &hash);
+    assert_eq!(token_swap.get_commitment(&offer_id, &acceptor).unwrap().bond, MUL_VAL);
+    assert_eq!(recv_token_client.balance(&offeror), offeror_balance + MUL_VAL as i128);
+    token_swap.close_offer(&offeror, &offer_id);
+    e.ledger().with_mut(|li| li.sequence_number = start_ledger + 104);
+    assert_eq!(token_swap.reveal_accept(&acceptor, &offer_id, &amount, &salt), 0);
+    assert_eq!(recv_token_client.balance(&acceptor), acceptor_balance);
+    assert_eq!(token_swap.try_commit_accept(&acceptor, &offer_id, &hash), Err(Ok(SwapError::OfferNotActive)));
+}
+
+#[test]
+fn test_commit_lock_cap() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let griefer = Address::generate(&e);
+    let other = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&griefer, &(100_i128 * MUL_VAL as i128));
+    recv_token_admin_client.mint(&other, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let start_ledger = e.ledger().sequence();
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(20 * MUL_VAL), &MUL_VAL,
+        &(start_ledger + 1000), &None, &OfferMode::PARTIAL);
+
+    // each commitment escrows a bond of min_recv_amount
+    let hash = BytesN::from_array(&e, &[1; 32]);
+    token_swap.commit_accept(&griefer, &offer_id, &hash);
+    assert_eq!(recv_token_client.balance(&griefer), (99 * MUL_VAL) as i128);
+    assert_eq!(token_swap.get_offer_lock(&offer_id), start_ledger + 100);
+
+    // commits while the offer is locked don't extend the lock
+    e.ledger().with_mut(|li| li.sequence_number = start_ledger + 50);
+    token_swap.commit_accept(&other, &offer_id, &hash);
+    token_swap.commit_accept(&griefer, &offer_id, &hash);
+    assert_eq!(token_swap.get_offer_lock(&offer_id), start_ledger + 100);
+    assert_eq!(recv_token_client.balance(&griefer), (99 * MUL_VAL) as i128);
+
+    // re-committing after each lapse locks the offer for at most MAX_COMMIT_LOCK ledgers
+    e.ledger().with_mut(|li| li.sequence_number = start_ledger + 150);
+    token_swap.commit_accept(&griefer, &offer_id, &hash);
+    assert_eq!(token_swap.get_offer_lock(&offer_id), start_ledger + 250);
+    e.ledger().with_mut(|li| li.sequence_number = start_ledger + 250);
+    token_swap.commit_accept(&griefer, &offer_id, &hash);
+    assert_eq!(token_swap.get_offer_lock(&offer_id), start_ledger + 350);
+    e.ledger().with_mut(|li| li.sequence_number = start_ledger + 350);
+    assert_eq!(token_swap.try_commit_accept(&griefer, &offer_id, &hash), Err(Ok(SwapError::OfferLocked)));
+    assert_eq!(token_swap.get_offer_lock(&offer_id), start_ledger + 350);
+    token_swap.update_offer(&offeror, &offer_id, &(40 * MUL_VAL), &MUL_VAL);
+
+    // the lapsed bonds went to the offeror, the rest is settled when the offer is pruned
+    assert_eq!(recv_token_client.balance(&griefer), (97 * MUL_VAL) as i128);
+    assert_eq!(recv_token_client.balance(&offeror), (2 * MUL_VAL) as i128);
+    token_swap.close_offer(&offeror, &offer_id);
+    assert_eq!(token_swap.prune_offers(&admin, &vec![&e, offer_id]), 1);
+    assert_eq!(recv_token_client.balance(&offeror), (4 * MUL_VAL) as i128);
+    assert_eq!(recv_token_client.balance(&other), (99 * MUL_VAL) as i128);
+}
+
+#[test]
+fn test_htlc() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    token_swap.allow_token(&admin, &send_token_id);
+
+    let start_ledger = e.ledger().sequence();
+    let preimage = Bytes::from_array(&e, &[42; 32]);
+    let hashlock = e.crypto().sha256(&preimage);
+    let claimed_id = token_swap.lock_htlc(&offeror, &acceptor, &send_token_id, &(100 * MUL_VAL), &hashlock, &(start_ledger + 100));
+    let refunded_id = token_swap.lock_htlc(&offeror, &acceptor, &send_token_id, &(100 * MUL_VAL), &hashlock, &(start_ledger + 100));
+    assert_eq!(send_token_client.balance(&offeror), (800 * MUL_VAL) as i128);
+
+    assert_eq!(token_swap.try_claim_htlc(&claimed_id, &Bytes::from_array(&e, &[0; 32])), 
+        Err(Ok(SwapError::InvalidReveal)));
+    assert_eq!(token_swap.try_refund_htlc(&refunded_id), Err(Ok(SwapError::OfferNotExpired)));
+    token_swap.claim_htlc(&claimed_id, &preimage);
+    assert_eq!(send_token_client.balance(&acceptor), (100 * MUL_VAL) as i128);
+    assert_eq!(token_swap.get_htlc(&claimed_id).status, OfferStatus::COMPLETE);
+
+    // past the timelock only a refund is possible
+    e.ledger().with_mut(|li| li.sequence_number = start_ledger + 100);
+    assert_eq!(token_swap.try_claim_htlc(&refunded_id, &preimage), Err(Ok(SwapError::OfferExpired)));
+    token_swap.refund_htlc(&refunded_id);
+    assert_eq!(send_token_client.balance(&offeror), (900 * MUL_VAL) as i128);
+    assert_eq!(token_swap.try_refund_htlc(&refunded_id), Err(Ok(SwapError::OfferNotActive)));
+
+    // banned accounts can't lock or be locked for
+    token_swap.ban_address(&admin, &acceptor);
+    assert_eq!(token_swap.try_lock_htlc(&offeror, &acceptor, &send_token_id, &MUL_VAL, &hashlock, &(start_ledger + 200)), 
+        Err(Ok(SwapError::Unauthorized)));
+    assert_eq!(token_swap.try_lock_htlc(&acceptor, &offeror, &send_token_id, &MUL_VAL, &hashlock, &(start_ledger + 200)), 
+        Err(Ok(SwapError::Unauthorized)));
+}
+
+#[test]
+fn test_dca() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let owner = Address::generate(&e);
+    let keeper = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1100_i128 * MUL_VAL as i128));
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&owner, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let start_ledger = e.ledger().sequence();
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(1000 * MUL_VAL), &(200 * MUL_VAL), &MUL_VAL,
+        &(start_ledger + 1000), &None, &OfferMode::PARTIAL);
+
+    // buy the offer's send token with 10 recv token every 10 ledgers
+    let plan_id = token_swap.create_dca(&owner, &recv_token_id, &send_token_id, 
+        &(25 * MUL_VAL), &(10 * MUL_VAL), &10, &(45 * MUL_VAL), &MUL_VAL);
+    assert_eq!(recv_token_client.balance(&owner), (75 * MUL_VAL) as i128);
+
+    assert_eq!(token_swap.execute_dca(&keeper, &plan_id, &offer_id), 50 * MUL_VAL);
+    assert_eq!(send_token_client.balance(&owner), (50 * MUL_VAL) as i128);
+    assert_eq!(recv_token_client.balance(&keeper), MUL_VAL as i128);
+    assert_eq!(token_swap.get_dca(&plan_id).budget, 25 * MUL_VAL - 11 * MUL_VAL - MUL_VAL / 20);
+    assert_eq!(token_swap.try_execute_dca(&keeper, &plan_id, &offer_id), Err(Ok(SwapError::DcaNotDue)));
+
+    // a worse price than min_recv_amount is refused
+    token_swap.update_offer(&offeror, &offer_id, &(400 * MUL_VAL), &MUL_VAL);
+    e.ledger().with_mut(|li| li.sequence_number = start_ledger + 10);
+    assert_eq!(token_swap.try_execute_dca(&keeper, &plan_id, &offer_id), Err(Ok(SwapError::SlippageExceeded)));
+    token_swap.update_offer(&offeror, &offer_id, &(190 * MUL_VAL), &MUL_VAL);
+    token_swap.execute_dca(&keeper, &plan_id, &offer_id);
+
+    // the last period spends the rest of the budget
+    e.ledger().with_mut(|li| li.sequence_number = start_ledger + 20);
+    token_swap.execute_dca(&keeper, &plan_id, &offer_id);
+    let plan = token_swap.get_dca(&plan_id);
+    assert_eq!((plan.budget, plan.status), (0, OfferStatus::COMPLETE));
+    assert_eq!(recv_token_client.balance(&keeper), (3 * MUL_VAL) as i128);
+    assert_eq!(token_swap.try_cancel_dca(&owner, &plan_id), Err(Ok(SwapError::OfferNotActive)));
+
+    // the fills made by the contract itself keep the escrow accounting exact
+    let (escrowed, balance) = token_swap.check_solvency(&send_token_id);
+    assert_eq!(escrowed, balance);
+    assert_eq!(escrowed, (send_token_client.balance(&token_swap.address)) as u128);
+    let (escrowed, balance) = token_swap.check_solvency(&recv_token_id);
+    assert_eq!(escrowed, balance);
+}
+
+#[test]
+fn test_vesting() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let start_ledger = e.ledger().sequence();
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(20 * MUL_VAL), &MUL_VAL,
+        &(start_ledger + 1000), &None, &OfferMode::PARTIAL);
+    token_swap.set_vesting(&offeror, &offer_id, &100);
+
+    token_swap.accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &None);
+    assert_eq!(recv_token_client.balance(&offeror), 0);
+
+    // half way through, new proceeds restart the vesting of what's still locked
+    e.ledger().with_mut(|li| li.sequence_number = start_ledger + 50);
+    assert_eq!(token_swap.get_stream(&offer_id).unwrap().vested, 5 * MUL_VAL);
+    token_swap.accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &None);
+
+    e.ledger().with_mut(|li| li.sequence_number = start_ledger + 100);
+    assert_eq!(token_swap.withdraw_vested(&offeror, &offer_id), 125 * MUL_VAL / 10);
+    assert_eq!(recv_token_client.balance(&offeror), (125 * MUL_VAL / 10) as i128);
+    assert_eq!(token_swap.try_withdraw_vested(&acceptor, &offer_id), Err(Ok(SwapError::InvalidOfferor)));
+
+    e.ledger().with_mut(|li| li.sequence_number = start_ledger + 150);
+    assert_eq!(token_swap.withdraw_vested(&offeror, &offer_id), 75 * MUL_VAL / 10);
+    assert_eq!(recv_token_client.balance(&offeror), (20 * MUL_VAL) as i128);
+    assert!(token_swap.get_stream(&offer_id).is_none());
+}
+
+#[test]
+fn test_accept_route() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (token_a_id, token_a_client, token_a_admin_client) = create_token_contract(&e, &token_admin);
+    let (token_b_id, token_b_client, token_b_admin_client) = create_token_contract(&e, &token_admin);
+    let (token_c_id, token_c_client, token_c_admin_client) = create_token_contract(&e, &token_admin);
+    token_a_admin_client.mint(&acceptor, &(1000_i128 * MUL_VAL as i128));
+    token_b_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    token_c_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &0, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &token_a_id);
+    token_swap.allow_token(&admin, &token_b_id);
+    token_swap.allow_token(&admin, &token_c_id);
+
+    // A -> B at 2 B per A, B -> C at 1 C per 2 B
+    let expire_ledger = e.ledger().sequence() + 1000;
+    let ab_id: u32 = token_swap.create_offer(
+        &offeror, &token_b_id, &token_a_id, &0,
+        &(400 * MUL_VAL), &(200 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+    let bc_id: u32 = token_swap.create_offer(
+        &offeror, &token_c_id, &token_b_id, &0,
+        &(100 * MUL_VAL), &(200 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+
+    assert_eq!(token_swap.try_accept_route(&acceptor, &vec![&e, bc_id, ab_id], &(100 * MUL_VAL), &0), 
+        Err(Ok(SwapError::InvalidRoute)));
+    assert_eq!(token_swap.try_accept_route(&acceptor, &vec![&e, ab_id, bc_id], &(100 * MUL_VAL), &(100 * MUL_VAL)), 
+        Err(Ok(SwapError::SlippageExceeded)));
+
+    // each hop pays its 0.5% taker fee out of its input, the rounding dust stays with the acceptor
+    let out = token_swap.accept_route(&acceptor, &vec![&e, ab_id, bc_id], &(201 * MUL_VAL / 2), &0);
+    assert_eq!(token_a_client.balance(&acceptor), (1000 * MUL_VAL - 1004974) as i128);
+    assert_eq!(token_b_client.balance(&acceptor), 50);
+    assert_eq!(out, 994975);
+    assert_eq!(token_c_client.balance(&acceptor), out as i128);
+
+    // taker fees paid in the fee token leave every hop its whole input
+    let (fee_token_id, fee_token_client, fee_token_admin_client) = create_token_contract(&e, &token_admin);
+    fee_token_admin_client.mint(&acceptor, &10000);
+    token_swap.allow_token(&admin, &fee_token_id);
+    token_swap.set_fee_token(&admin, &Some(FeeToken { token: fee_token_id.clone(), max_age: 300 }));
+    token_swap.set_fee_token_rate(&admin, &token_a_id, &Some(10u128.pow(18)));
+    token_swap.set_fee_token_rate(&admin, &token_b_id, &Some(10u128.pow(18)));
+    let ab_id: u32 = token_swap.create_offer(
+        &offeror, &token_b_id, &token_a_id, &0,
+        &(400 * MUL_VAL), &(200 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+    let bc_id: u32 = token_swap.create_offer(
+        &offeror, &token_c_id, &token_b_id, &0,
+        &(100 * MUL_VAL), &(200 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+    let out = token_swap.accept_route(&acceptor, &vec![&e, ab_id, bc_id], &(10 * MUL_VAL), &(10 * MUL_VAL));
+    assert_eq!(out, 10 * MUL_VAL);
+    assert_eq!(fee_token_client.balance(&acceptor), 10000 - 500 - 1000);
+}
+
+#[test]
+fn test_split_offer() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(400 * MUL_VAL), &(80 * MUL_VAL), &(5 * MUL_VAL),
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+
+    assert_eq!(token_swap.try_split_offer(&offeror, &offer_id, &vec![&e, 400 * MUL_VAL]), 
+        Err(Ok(SwapError::AmountTooLarge)));
+    assert_eq!(token_swap.try_split_offer(&acceptor, &offer_id, &vec![&e, 100 * MUL_VAL]), 
+        Err(Ok(SwapError::InvalidOfferor)));
+
+    let child_ids = token_swap.split_offer(&offeror, &offer_id, &vec![&e, 100 * MUL_VAL, 200 * MUL_VAL]);
+    assert_eq!(child_ids.len(), 2);
+    let offer = token_swap.get_offer(&offer_id);
+    assert_eq!((offer.send_amount, offer.recv_amount, offer.fee_amount), (100 * MUL_VAL, 20 * MUL_VAL, MUL_VAL / 4));
+    let child = token_swap.get_offer(&child_ids.get(1).unwrap());
+    assert_eq!((child.send_amount, child.recv_amount, child.fee_amount), (200 * MUL_VAL, 40 * MUL_VAL, MUL_VAL / 2));
+    assert_eq!(child.min_recv_amount, 5 * MUL_VAL);
+
+    // children trade and close on their own
+    token_swap.accept_offer(&acceptor, &child_ids.get(0).unwrap(), &(20 * MUL_VAL), &0, &None);
+    assert_eq!(token_swap.get_offer(&child_ids.get(0).unwrap()).status, OfferStatus::COMPLETE);
+    assert_eq!(send_token_client.balance(&acceptor), (100 * MUL_VAL) as i128);
+    token_swap.close_offer(&offeror, &child_ids.get(1).unwrap());
+    assert_eq!(send_token_client.balance(&offeror), (1000 * MUL_VAL - 401 * MUL_VAL + 2005 * MUL_VAL / 10) as i128);
+
+    // children of a private offer stay private and keep its alternative recv tokens
+    let (alt_token_id, _, _) = create_token_contract(&e, &token_admin);
+    token_swap.allow_token(&admin, &alt_token_id);
+    token_swap.add_acceptor(&offeror, &offer_id, &acceptor);
+    token_swap.set_recv_alternative(&offeror, &offer_id, &alt_token_id, &(30 * MUL_VAL));
+    let child_id = token_swap.split_offer(&offeror, &offer_id, &vec![&e, 50 * MUL_VAL]).get(0).unwrap();
+    assert_eq!(token_swap.get_acceptors(&child_id), Some(vec![&e, acceptor.clone()]));
+    let alt = token_swap.get_recv_alternatives(&child_id).get(0).unwrap();
+    assert_eq!((alt.recv_token, alt.send_amount, alt.recv_amount), (alt_token_id, 100 * MUL_VAL, 30 * MUL_VAL));
+    let stranger = Address::generate(&e);
+    assert_eq!(token_swap.try_accept_offer(&stranger, &child_id, &(5 * MUL_VAL), &0, &None), 
+        Err(Ok(SwapError::AcceptorNotAllowed)));
+
+    // an offer taken whole by its counterparty, maybe with a dispute window, isn't split
+    let otc_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(20 * MUL_VAL), &(5 * MUL_VAL),
+        &(e.ledger().sequence() + 1000), &Some(acceptor.clone()), &OfferMode::PARTIAL);
+    token_swap.set_dispute_window(&offeror, &otc_id, &100);
+    assert_eq!(token_swap.try_split_offer(&offeror, &otc_id, &vec![&e, 50 * MUL_VAL]), 
+        Err(Ok(SwapError::AcceptorNotAllowed)));
+}
+
+#[test]
+fn test_transfer_offer() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let new_offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &0, &0, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let start_ledger = e.ledger().sequence();
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(20 * MUL_VAL), &MUL_VAL,
+        &(start_ledger + 1000), &None, &OfferMode::PARTIAL);
+    token_swap.set_vesting(&offeror, &offer_id, &100);
+    token_swap.accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &None);
+
+    assert_eq!(token_swap.try_transfer_offer(&acceptor, &offer_id, &acceptor), Err(Ok(SwapError::InvalidOfferor)));
+    token_swap.transfer_offer(&offeror, &offer_id, &new_offeror);
+    assert_eq!(token_swap.offers_of(&offeror, &0, &10).len(), 0);
+    assert_eq!(token_swap.offers_of(&new_offeror, &0, &10), vec![&e, offer_id]);
+
+    // the old key lost all rights, the new one gets the proceeds and the escrow
+    assert_eq!(token_swap.try_close_offer(&offeror, &offer_id), Err(Ok(SwapError::InvalidOfferor)));
+    e.ledger().with_mut(|li| li.sequence_number = start_ledger + 100);
+    assert_eq!(token_swap.try_withdraw_vested(&offeror, &offer_id), Err(Ok(SwapError::InvalidOfferor)));
+    token_swap.withdraw_vested(&new_offeror, &offer_id);
+    token_swap.close_offer(&new_offeror, &offer_id);
+    assert_eq!(recv_token_client.balance(&new_offeror), (10 * MUL_VAL) as i128);
+    assert_eq!(send_token_client.balance(&new_offeror), (50 * MUL_VAL) as i128);
+}
+
+#[test]
+fn test_managers() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let manager = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, _) = create_token_contract(&e, &token_admin);
+
+    token_swap.propose_fee(&admin, &0, &0, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let expire_ledger = e.ledger().sequence() + 1000;
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(20 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+    let other_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(20 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+
+    assert_eq!(token_swap.try_update_offer(&manager, &offer_id, &(30 * MUL_VAL), &MUL_VAL), 
+        Err(Ok(SwapError::InvalidOfferor)));
+    assert_eq!(token_swap.try_set_manager(&offeror, &Some(offer_id), &manager, &4), Err(Ok(SwapError::InvalidOps)));
+    assert_eq!(token_swap.try_set_manager(&manager, &Some(offer_id), &manager, &MANAGE_UPDATE), 
+        Err(Ok(SwapError::InvalidOfferor)));
+
+    // a grant for one offer only covers that offer and that operation
+    token_swap.set_manager(&offeror, &Some(offer_id), &manager, &MANAGE_UPDATE);
+    token_swap.update_offer(&manager, &offer_id, &(30 * MUL_VAL), &MUL_VAL);
+    assert_eq!(token_swap.get_offer(&offer_id).recv_amount, 30 * MUL_VAL);
+    assert_eq!(token_swap.try_update_offer(&manager, &other_id, &(30 * MUL_VAL), &MUL_VAL), 
+        Err(Ok(SwapError::InvalidOfferor)));
+    assert_eq!(token_swap.try_close_offer(&manager, &offer_id), Err(Ok(SwapError::InvalidOfferor)));
+
+    // a global grant covers every offer, the escrow still goes back to the offeror
+    token_swap.set_manager(&offeror, &None, &manager, &(MANAGE_UPDATE | MANAGE_CLOSE));
+    token_swap.close_offer(&manager, &other_id);
+    assert_eq!(send_token_client.balance(&offeror), (900 * MUL_VAL) as i128);
+    assert_eq!(send_token_client.balance(&manager), 0);
+
+    token_swap.set_manager(&offeror, &None, &manager, &0);
+    assert_eq!(token_swap.try_close_offer(&manager, &offer_id), Err(Ok(SwapError::InvalidOfferor)));
+}
+
+#[test]
+fn test_basket() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (token_a_id, token_a_client, token_a_admin_client) = create_token_contract(&e, &token_admin);
+    let (token_b_id, token_b_client, token_b_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    token_a_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    token_b_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    recv_token_admin_client.mint(&acceptor, &(1000_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &token_a_id);
+    token_swap.allow_token(&admin, &token_b_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let expire_ledger = e.ledger().sequence() + 1000;
+    let items = vec![&e, (token_a_id.clone(), 400 * MUL_VAL), (token_b_id.clone(), 200 * MUL_VAL)];
+    let basket_id = token_swap.create_basket(&offeror, &items, &recv_token_id, &(100 * MUL_VAL), &expire_ledger);
+    let closed_id = token_swap.create_basket(&offeror, &items, &recv_token_id, &(100 * MUL_VAL), &expire_ledger);
+    assert_eq!(token_a_client.balance(&offeror), (200 * MUL_VAL - 2 * MUL_VAL) as i128);
+    assert_eq!(token_swap.get_basket(&basket_id).items.get(1).unwrap().fee_amount, MUL_VAL / 2);
+
+    // the whole basket goes to the acceptor
+    token_swap.accept_basket(&acceptor, &basket_id);
+    assert_eq!(token_a_client.balance(&acceptor), (400 * MUL_VAL) as i128);
+    assert_eq!(token_b_client.balance(&acceptor), (200 * MUL_VAL) as i128);
+    assert_eq!(recv_token_client.balance(&acceptor), (900 * MUL_VAL - MUL_VAL / 2) as i128);
+    assert_eq!(recv_token_client.balance(&offeror), (100 * MUL_VAL) as i128);
+    assert_eq!(token_swap.try_accept_basket(&acceptor, &basket_id), Err(Ok(SwapError::OfferNotActive)));
+
+    assert_eq!(token_swap.try_close_basket(&acceptor, &closed_id), Err(Ok(SwapError::InvalidOfferor)));
+    token_swap.close_basket(&offeror, &closed_id);
+    assert_eq!(token_b_client.balance(&offeror), (800 * MUL_VAL - MUL_VAL / 2) as i128);
+    assert_eq!(token_swap.get_basket(&closed_id).status, OfferStatus::CANCEL);
+}
+
+#[test]
+fn test_recv_alternatives() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (alt_token_id, alt_token_client, alt_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+    alt_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &0, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(20 * MUL_VAL), &MUL_VAL,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+
+    assert_eq!(token_swap.try_set_recv_alternative(&offeror, &offer_id, &alt_token_id, &(25 * MUL_VAL)), 
+        Err(Ok(SwapError::TokenNotAllowed)));
+    token_swap.allow_token(&admin, &alt_token_id);
+    token_swap.set_recv_alternative(&offeror, &offer_id, &alt_token_id, &(25 * MUL_VAL));
+    assert_eq!(token_swap.get_recv_alternatives(&offer_id).len(), 1);
+
+    // 10 of the alternative token buy 40, which stands for 8 of recv_token
+    assert_eq!(token_swap.try_accept_offer_with(&acceptor, &offer_id, &alt_token_id, &(10 * MUL_VAL), &(41 * MUL_VAL)), 
+        Err(Ok(SwapError::SlippageExceeded)));
+    assert_eq!(token_swap.accept_offer_with(&acceptor, &offer_id, &alt_token_id, &(10 * MUL_VAL), &0), 40 * MUL_VAL);
+    assert_eq!(alt_token_client.balance(&offeror), (10 * MUL_VAL) as i128);
+    assert_eq!(alt_token_client.balance(&acceptor), (90 * MUL_VAL - MUL_VAL / 20) as i128);
+    let offer = token_swap.get_offer(&offer_id);
+    assert_eq!((offer.send_amount, offer.recv_amount), (60 * MUL_VAL, 12 * MUL_VAL));
+
+    // the rest can be bought in either token
+    token_swap.accept_offer(&acceptor, &offer_id, &(4 * MUL_VAL), &0, &None);
+    token_swap.accept_offer_with(&acceptor, &offer_id, &alt_token_id, &(10 * MUL_VAL), &0);
+    assert_eq!(token_swap.get_offer(&offer_id).status, OfferStatus::COMPLETE);
+    assert_eq!(send_token_client.balance(&acceptor), (100 * MUL_VAL) as i128);
+}
+
+#[test]
+fn test_nft_offer() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let nft_id = e.register_contract(None, MockNft);
+    let nft_client = MockNftClient::new(&e, &nft_id);
+    nft_client.mint(&offeror, &1);
+    nft_client.mint(&offeror, &2);
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(1000_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let expire_ledger = e.ledger().sequence() + 1000;
+    assert_eq!(token_swap.try_create_nft_offer(&offeror, &nft_id, &1, &recv_token_id, &(400 * MUL_VAL), &expire_ledger), 
+        Err(Ok(SwapError::TokenNotAllowed)));
+    token_swap.allow_token(&admin, &nft_id);
+    let offer_id = token_swap.create_nft_offer(&offeror, &nft_id, &1, &recv_token_id, &(400 * MUL_VAL), &expire_ledger);
+    let closed_id = token_swap.create_nft_offer(&offeror, &nft_id, &2, &recv_token_id, &(400 * MUL_VAL), &expire_ledger);
+    assert_eq!(nft_client.owner_of(&1), token_swap.address);
+    assert_eq!(token_swap.try_create_nft_offer(&acceptor, &nft_id, &1, &recv_token_id, &(400 * MUL_VAL), &expire_ledger), 
+        Err(Ok(SwapError::InsufficientBalance)));
+
+    // the maker fee comes out of the proceeds
+    token_swap.accept_nft_offer(&acceptor, &offer_id);
+    assert_eq!(nft_client.owner_of(&1), acceptor);
+    assert_eq!(recv_token_client.balance(&acceptor), (598 * MUL_VAL) as i128);
+    assert_eq!(recv_token_client.balance(&offeror), (399 * MUL_VAL) as i128);
+    assert_eq!(token_swap.try_accept_nft_offer(&acceptor, &offer_id), Err(Ok(SwapError::OfferNotActive)));
+
+    token_swap.close_nft_offer(&offeror, &closed_id);
+    assert_eq!(nft_client.owner_of(&2), offeror);
+    assert_eq!(token_swap.try_get_nft_offer(&7).err(), Some(Ok(SwapError::OfferNotFound)));
+}
+
+#[test]
+fn test_decimals() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (narrow_token_id, _, narrow_token_admin_client) = create_token_contract(&e, &token_admin);
+    narrow_token_admin_client.mint(&offeror, &(1000_i128 * 10_i128.pow(7)));
+    let wide_token_id = e.register_contract(None, MockWideToken);
+    let nft_id = e.register_contract(None, MockNft);
+
+    token_swap.propose_fee(&admin, &0, &0, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &narrow_token_id);
+    token_swap.allow_token(&admin, &wide_token_id);
+    token_swap.allow_token(&admin, &nft_id);
+    assert_eq!(token_swap.get_token_decimals(&narrow_token_id), Some(7));
+    assert_eq!(token_swap.get_token_decimals(&wide_token_id), Some(18));
+    assert_eq!(token_swap.get_token_decimals(&nft_id), None);
+
+    // 2 whole narrow tokens for 3 whole wide tokens is a price of 1.5
+    let price = token_swap.get_price(&narrow_token_id, &wide_token_id, &(2 * 10_u64.pow(7)), &(3 * 10_u64.pow(18)));
+    assert_eq!(price, 15 * 10_u128.pow(17));
+    assert_eq!(token_swap.get_recv_amount_for_price(&narrow_token_id, &wide_token_id, &(2 * 10_u64.pow(7)), &price), 
+        3 * 10_u64.pow(18));
+    assert_eq!(token_swap.get_price(&wide_token_id, &narrow_token_id, &(3 * 10_u64.pow(18)), &(2 * 10_u64.pow(7))), 
+        666_666_666_666_666_666);
+    assert_eq!(token_swap.try_get_price(&narrow_token_id, &nft_id, &1, &1), Err(Ok(SwapError::NoDecimals)));
+    assert_eq!(token_swap.try_get_recv_amount_for_price(&nft_id, &narrow_token_id, &1, &price), Err(Ok(SwapError::NoDecimals)));
+
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &narrow_token_id, &wide_token_id, &0,
+        &(4 * 10_u64.pow(7)), &(6 * 10_u64.pow(18)), &1,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+    assert_eq!(token_swap.get_offer_price(&offer_id), price);
+}
+
+#[test]
+fn test_price_band() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &10000);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    token_swap.propose_fee(&admin, &0, &0, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    // one send token is worth two recv tokens; a band of 5%
+    let oracle = MockOracleClient::new(&e, &e.register_contract(None, MockOracle));
+    let band = PriceBand { max_deviation: 500, max_age: 300, check_accept: false };
+    token_swap.set_price_band(&admin, &send_token_id, &recv_token_id, &Some(band.clone()));
+    assert_eq!(token_swap.try_create_offer(&offeror, &send_token_id, &recv_token_id, &0, &100, &200, &1,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL), Err(Ok(SwapError::OracleUnavailable)));
+    token_swap.set_oracle(&admin, &Some(oracle.address.clone()));
+    assert_eq!(token_swap.get_oracle(), Some(oracle.address.clone()));
+    assert_eq!(token_swap.try_create_offer(&offeror, &send_token_id, &recv_token_id, &0, &100, &200, &1,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL), Err(Ok(SwapError::OracleUnavailable)));
+    oracle.set_price(&send_token_id, &2_0000000);
+    oracle.set_price(&recv_token_id, &1_0000000);
+    assert_eq!(token_swap.try_set_price_band(&offeror, &send_token_id, &recv_token_id, &None), Err(Ok(SwapError::Unauthorized)));
+
+    assert_eq!(token_swap.try_create_offer(&offeror, &send_token_id, &recv_token_id, &0, &100, &180, &1,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL), Err(Ok(SwapError::PriceOutOfBand)));
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &100, &210, &1,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+    assert_eq!(token_swap.try_update_offer(&offeror, &offer_id, &220, &1), Err(Ok(SwapError::PriceOutOfBand)));
+    token_swap.update_offer(&offeror, &offer_id, &190, &1);
+
+    // fills only re-check the band when asked to
+    oracle.set_price(&send_token_id, &3_0000000);
+    token_swap.accept_offer(&acceptor, &offer_id, &95, &0, &None);
+    token_swap.set_price_band(&admin, &send_token_id, &recv_token_id, &Some(PriceBand { check_accept: true, ..band }));
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &95, &0, &None), Err(Ok(SwapError::PriceOutOfBand)));
+    oracle.set_price(&send_token_id, &2_0000000);
+    token_swap.accept_offer(&acceptor, &offer_id, &95, &0, &None);
+    assert_eq!(token_swap.get_offer(&offer_id).status, OfferStatus::COMPLETE);
+
+    // other pairs and removed bands are unrestricted
+    token_swap.set_price_band(&admin, &send_token_id, &recv_token_id, &None);
+    assert!(token_swap.get_price_band(&send_token_id, &recv_token_id).is_none());
+    token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &100, &1, &1,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+}
+
+#[test]
+fn test_oracle_peg() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &10000);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    token_swap.propose_fee(&admin, &0, &0, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &100, &100, &1,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+    let peg = OraclePeg { spread: 100, max_age: 300 };
+    assert_eq!(token_swap.try_set_oracle_peg(&offeror, &offer_id, &peg), Err(Ok(SwapError::OracleUnavailable)));
+
+    let oracle = MockOracleClient::new(&e, &e.register_contract(None, MockOracle));
+    oracle.set_price(&send_token_id, &2_0000000);
+    oracle.set_price(&recv_token_id, &1_0000000);
+    token_swap.set_oracle(&admin, &Some(oracle.address.clone()));
+    assert_eq!(token_swap.try_set_oracle_peg(&acceptor, &offer_id, &peg), Err(Ok(SwapError::InvalidOfferor)));
+    assert_eq!(token_swap.try_set_oracle_peg(&offeror, &offer_id, &OraclePeg { spread: -10000, max_age: 300 }), 
+        Err(Ok(SwapError::PriceOutOfBand)));
+    token_swap.set_oracle_peg(&offeror, &offer_id, &peg);
+    assert_eq!(token_swap.get_oracle_peg(&offer_id).unwrap().spread, 100);
+    assert_eq!(token_swap.try_set_dutch_auction(&offeror, &offer_id, &1, &(e.ledger().sequence() + 100), &1), 
+        Err(Ok(SwapError::DynamicPrice)));
+    assert_eq!(token_swap.try_split_offer(&offeror, &offer_id, &vec![&e, 50]), Err(Ok(SwapError::DynamicPrice)));
+
+    // the price follows the oracle plus the 1% spread
+    assert_eq!(token_swap.get_offer(&offer_id).recv_amount, 202);
+    oracle.set_price(&send_token_id, &3_0000000);
+    assert_eq!(token_swap.get_offer(&offer_id).recv_amount, 303);
+    token_swap.accept_offer(&acceptor, &offer_id, &150, &0, &None);
+    assert_eq!(send_token_client.balance(&acceptor), 49);
+    assert_eq!(recv_token_client.balance(&offeror), 150);
+    assert_eq!(token_swap.get_offer(&offer_id).recv_amount, 154);
+
+    // stale prices can't be traded on
+    e.ledger().with_mut(|li| li.timestamp += 301);
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &10, &0, &None), Err(Ok(SwapError::OracleUnavailable)));
+    // and are passed over for the best price of the pair
+    let other_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &50, &200, &1,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+    assert_eq!(token_swap.best_offer(&send_token_id, &recv_token_id), Some(offer_id));
+    assert_eq!(token_swap.best_price(&send_token_id, &recv_token_id).0, other_id);
+    assert_eq!(token_swap.swap_exact_out(&acceptor, &recv_token_id, &send_token_id, &10, &40), 40);
+    assert_eq!(token_swap.market_buy(&acceptor, &send_token_id, &recv_token_id, &40, &10), (40, 10));
+    assert_eq!(token_swap.take_best(&acceptor, &send_token_id, &recv_token_id, &40, &10), (40, 10));
+
+    // a manual price ends the peg
+    token_swap.update_offer(&offeror, &offer_id, &100, &1);
+    assert!(token_swap.get_oracle_peg(&offer_id).is_none());
+    token_swap.accept_offer(&acceptor, &offer_id, &100, &0, &None);
+    assert_eq!(token_swap.get_offer(&offer_id).status, OfferStatus::COMPLETE);
+}
+
+#[test]
+fn test_stats() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &100000);
+    recv_token_admin_client.mint(&acceptor, &100000);
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &10000, &20000, &1,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+    token_swap.accept_offer(&acceptor, &offer_id, &4000, &0, &None);
+    token_swap.accept_offer(&acceptor, &offer_id, &16000, &0, &None);
+
+    let stats = token_swap.get_pair_stats(&send_token_id, &recv_token_id);
+    assert_eq!((stats.send_volume, stats.recv_volume, stats.fill_count), (10000, 20000, 2));
+    assert_eq!(token_swap.get_pair_stats(&recv_token_id, &send_token_id).fill_count, 0);
+
+    // fees stay counted once claimed
+    let stats = token_swap.get_global_stats();
+    assert_eq!(stats.fill_count, 2);
+    assert_eq!(stats.fees.get(send_token_id.clone()), Some(25));
+    assert_eq!(stats.fees.get(recv_token_id.clone()), Some(100));
+    token_swap.claim_fees(&send_token_id);
+    assert_eq!(token_swap.get_global_stats().fees.get(send_token_id.clone()), Some(25));
+}
+
+#[test]
+fn test_pair_registry() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (token_a, _, token_a_admin_client) = create_token_contract(&e, &token_admin);
+    let (token_b, _, token_b_admin_client) = create_token_contract(&e, &token_admin);
+    let (token_c, _, _) = create_token_contract(&e, &token_admin);
+    token_a_admin_client.mint(&offeror, &10000);
+    token_b_admin_client.mint(&offeror, &10000);
+
+    token_swap.propose_fee(&admin, &0, &0, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_tokens(&admin, &vec![&e, token_a.clone(), token_b.clone(), token_c.clone()]);
+    assert!(token_swap.is_pair_allowed(&token_a, &token_c));
+
+    // with the registry on, only registered pairs trade, in both directions
+    assert_eq!(token_swap.try_set_pairs_only(&offeror, &true), Err(Ok(SwapError::Unauthorized)));
+    token_swap.set_pairs_only(&admin, &true);
+    assert!(token_swap.is_pairs_only());
+    let expire_ledger = e.ledger().sequence() + 1000;
+    assert_eq!(token_swap.try_create_offer(&offeror, &token_a, &token_b, &0, &100, &100, &1, &expire_ledger, &None, &OfferMode::PARTIAL),
+        Err(Ok(SwapError::TokenNotAllowed)));
+    token_swap.allow_pair(&admin, &token_a, &token_b);
+    assert!(token_swap.is_pair_allowed(&token_b, &token_a));
+    assert!(!token_swap.is_pair_allowed(&token_a, &token_c));
+    token_swap.create_offer(&offeror, &token_a, &token_b, &0, &100, &100, &1, &expire_ledger, &None, &OfferMode::PARTIAL);
+    token_swap.create_offer(&offeror, &token_b, &token_a, &0, &100, &100, &1, &expire_ledger, &None, &OfferMode::PARTIAL);
+    assert_eq!(token_swap.try_create_offer(&offeror, &token_a, &token_c, &0, &100, &100, &1, &expire_ledger, &None, &OfferMode::PARTIAL),
+        Err(Ok(SwapError::TokenNotAllowed)));
+
+    // a registered pair still needs both tokens allowed
+    token_swap.disallow_token(&admin, &token_b);
+    assert!(!token_swap.is_pair_allowed(&token_a, &token_b));
+    token_swap.allow_token(&admin, &token_b);
+    token_swap.disallow_pair(&admin, &token_b, &token_a);
+    assert!(!token_swap.is_pair_allowed(&token_a, &token_b));
+
+    token_swap.set_pairs_only(&admin, &false);
+    token_swap.create_offer(&offeror, &token_a, &token_c, &0, &100, &100, &1, &expire_ledger, &None, &OfferMode::PARTIAL);
+}
+
+#[test]
+fn test_admin_close() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let bidder = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &10000);
+    recv_token_admin_client.mint(&bidder, &10000);
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &4000, &1000, &1,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+    token_swap.start_auction(&offeror, &offer_id, &(e.ledger().sequence() + 100));
+    token_swap.place_bid(&bidder, &offer_id, &1000);
+
+    assert_eq!(token_swap.try_admin_close_offer(&offeror, &offer_id, &Symbol::new(&e, "frozen")), Err(Ok(SwapError::Unauthorized)));
+    token_swap.admin_close_offer(&admin, &offer_id, &Symbol::new(&e, "frozen"));
+    assert_eq!(token_swap.get_offer(&offer_id).status, OfferStatus::CANCELED_BY_ADMIN);
+    assert_eq!(send_token_client.balance(&offeror), 10000);
+    assert_eq!(token_swap.list_offers(&0, &10), vec![&e]);
+    assert_eq!(token_swap.try_admin_close_offer(&admin, &offer_id, &Symbol::new(&e, "frozen")), Err(Ok(SwapError::OfferNotActive)));
+
+    // the best bid of the called off auction is refundable
+    token_swap.refund_bid(&bidder, &offer_id);
+    assert_eq!(recv_token_client.balance(&bidder), 10000);
+}
+
+#[test]
+fn test_rescue() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    let user = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &10000);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &4000, &1000, &1,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+    token_swap.accept_offer(&acceptor, &offer_id, &500, &0, &None);
+
+    // tokens sent straight to the contract can be rescued, the escrow and fees can't
+    assert_eq!(token_swap.check_solvency(&send_token_id), (2010, 2010));
+    send_token_admin_client.mint(&token_swap.address, &300);
+    assert_eq!(token_swap.check_solvency(&send_token_id), (2010, 2310));
+    assert_eq!(token_swap.try_rescue(&offeror, &send_token_id, &300, &user), Err(Ok(SwapError::Unauthorized)));
+    assert_eq!(token_swap.try_rescue(&admin, &send_token_id, &301, &user), Err(Ok(SwapError::InsufficientBalance)));
+    assert_eq!(token_swap.try_rescue(&admin, &recv_token_id, &1, &user), Err(Ok(SwapError::InsufficientBalance)));
+    token_swap.rescue(&admin, &send_token_id, &300, &user);
+    assert_eq!(send_token_client.balance(&user), 300);
+
+    // the escrow is all paid out in the end
+    token_swap.close_offer(&offeror, &offer_id);
+    token_swap.claim_fees(&send_token_id);
+    token_swap.claim_fees(&recv_token_id);
+    assert_eq!(send_token_client.balance(&token_swap.address), 0);
+    assert_eq!(token_swap.try_rescue(&admin, &send_token_id, &1, &user), Err(Ok(SwapError::InsufficientBalance)));
+}
+
+#[test]
+fn test_reentrancy_guard() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &10000);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &4000, &1000, &1,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+
+    // the guard is released after each fill
+    token_swap.accept_offer(&acceptor, &offer_id, &100, &0, &None);
+    token_swap.accept_offer(&acceptor, &offer_id, &100, &0, &None);
+
+    // a fill entered while another one is running fails
+    e.as_contract(&token_swap.address, || {
+        e.storage().instance().set(&EscrowKey::Locked, &true);
+    });
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &100, &0, &None), Err(Ok(SwapError::Reentrancy)));
+    assert_eq!(token_swap.get_offer(&offer_id).recv_amount, 800);
+}
+
+#[test]
+fn test_overflow() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(u64::MAX as i128));
+    recv_token_admin_client.mint(&acceptor, &(u64::MAX as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+
+    // the maker fee on top of the whole u64 range doesn't fit
+    assert_eq!(token_swap.try_create_offer(&offeror, &send_token_id, &recv_token_id, &0, &u64::MAX, &1000, &1,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL), Err(Ok(SwapError::Overflow)));
+
+    // fills of offers with amounts near the top of the range price exactly
+    let send_amount = u64::MAX / 2;
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &send_amount, &(u64::MAX / 4), &1,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+    token_swap.accept_offer(&acceptor, &offer_id, &(u64::MAX / 8), &0, &None);
+    assert_eq!(token_swap.get_offer(&offer_id).send_amount, send_amount - (u64::MAX / 8) * 2);
+}
+
+#[test]
+fn test_rounding_dust() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &100);
+    recv_token_admin_client.mint(&acceptor, &100);
+
+    token_swap.propose_fee(&admin, &0, &0, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+
+    // 100 for 7 doesn't divide evenly, every single unit fill is paid 14 rounded down
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &100, &7, &1,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+    token_swap.accept_offer(&acceptor, &offer_id, &1, &0, &None);
+    token_swap.accept_offer(&acceptor, &offer_id, &1, &0, &None);
+    assert_eq!(token_swap.get_offer_dust(&offer_id), 0);
+    assert_eq!(token_swap.get_offer(&offer_id).send_amount, 72);
+
+    // the third fill brings the truncated fractions over a whole unit, which is set aside
+    token_swap.accept_offer(&acceptor, &offer_id, &1, &0, &None);
+    assert_eq!(send_token_client.balance(&acceptor), 42);
+    assert_eq!(token_swap.get_offer_dust(&offer_id), 1);
+    assert_eq!(token_swap.get_offer(&offer_id).send_amount, 57);
+
+    // the rest sells at no better than the original price and the dust goes to the offeror
+    token_swap.accept_offer(&acceptor, &offer_id, &4, &0, &None);
+    assert_eq!(send_token_client.balance(&acceptor), 99);
+    assert_eq!(send_token_client.balance(&offeror), 1);
+    assert_eq!(recv_token_client.balance(&offeror), 7);
+    assert_eq!(token_swap.get_offer_dust(&offer_id), 0);
+    assert_eq!(token_swap.get_offer(&offer_id).status, OfferStatus::COMPLETE);
+    assert_eq!(token_swap.check_solvency(&send_token_id), (0, 0));
+
+    assert_eq!(token_swap.get_rounding_policy(), RoundingPolicy::Offeror);
+    assert_eq!(token_swap.try_set_rounding_policy(&offeror, &RoundingPolicy::Fees), Err(Ok(SwapError::Unauthorized)));
+    send_token_admin_client.mint(&offeror, &200);
+    recv_token_admin_client.mint(&acceptor, &14);
+
+    // with the Fees policy the whole unit of dust is earned as a fee instead
+    token_swap.set_rounding_policy(&admin, &RoundingPolicy::Fees);
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &100, &7, &1,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+    for _ in 0..3 {
+        token_swap.accept_offer(&acceptor, &offer_id, &1, &0, &None);
+    }
+    assert_eq!(token_swap.get_offer_dust(&offer_id), 0);
+    assert_eq!(token_swap.get_offer(&offer_id).send_amount, 57);
+    assert_eq!(token_swap.get_accrued_fees(&send_token_id), 1);
+    token_swap.accept_offer(&acceptor, &offer_id, &4, &0, &None);
+    assert_eq!(send_token_client.balance(&acceptor), 99 + 99);
+    assert_eq!(send_token_client.balance(&offeror), 1 + 100);
+    assert_eq!(token_swap.check_solvency(&send_token_id), (1, 1));
+
+    // with the Offer policy the dust stays in the offer and the last fill gets it
+    token_swap.set_rounding_policy(&admin, &RoundingPolicy::Offer);
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &100, &7, &1,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+    for _ in 0..3 {
+        token_swap.accept_offer(&acceptor, &offer_id, &1, &0, &None);
+    }
+    assert_eq!(token_swap.get_offer_dust(&offer_id), 0);
+    assert_eq!(token_swap.get_offer(&offer_id).send_amount, 58);
+    token_swap.accept_offer(&acceptor, &offer_id, &4, &0, &None);
+    assert_eq!(send_token_client.balance(&acceptor), 99 + 99 + 100);
+    assert_eq!(token_swap.get_accrued_fees(&send_token_id), 1);
+}
+
+#[test]
+fn test_pair_minimums() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &10000);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    token_swap.propose_fee(&admin, &0, &0, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+
+    let minimums = PairMinimums { min_send_amount: 1000, min_fill_amount: 100 };
+    assert_eq!(token_swap.try_set_pair_minimums(&offeror, &send_token_id, &recv_token_id, &Some(minimums.clone())), 
+        Err(Ok(SwapError::Unauthorized)));
+    token_swap.set_pair_minimums(&admin, &send_token_id, &recv_token_id, &Some(minimums));
+    assert_eq!(token_swap.get_pair_minimums(&send_token_id, &recv_token_id).unwrap().min_fill_amount, 100);
+    assert!(token_swap.get_pair_minimums(&recv_token_id, &send_token_id).is_none());
+
+    // offers below the minimum size can't be made
+    let expire_ledger = e.ledger().sequence() + 1000;
+    assert_eq!(token_swap.try_create_offer(&offeror, &send_token_id, &recv_token_id, &0, &999, &999, &1, &expire_ledger, &None, &OfferMode::PARTIAL),
+        Err(Ok(SwapError::AmountTooSmall)));
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &1000, &1000, &1, &expire_ledger, &None, &OfferMode::PARTIAL);
+
+    // fills below the minimum fill are rejected, even though the offer's min_recv_amount allows them
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &99, &0, &None), Err(Ok(SwapError::AmountTooSmall)));
+    token_swap.accept_offer(&acceptor, &offer_id, &950, &0, &None);
+
+    // unless they take all that's left of the offer
+    token_swap.accept_offer(&acceptor, &offer_id, &50, &0, &None);
+    assert_eq!(token_swap.get_offer(&offer_id).status, OfferStatus::COMPLETE);
+
+    token_swap.set_pair_minimums(&admin, &send_token_id, &recv_token_id, &None);
+    token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &1, &1, &1, &expire_ledger, &None, &OfferMode::PARTIAL);
+}
+
+#[test]
+fn test_max_open_offers() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &10000);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    token_swap.propose_fee(&admin, &0, &0, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+
+    assert_eq!(token_swap.try_set_max_open_offers(&offeror, &Some(2)), Err(Ok(SwapError::Unauthorized)));
+    token_swap.set_max_open_offers(&admin, &Some(2));
+    assert_eq!(token_swap.get_max_open_offers(), Some(2));
+
+    let expire_ledger = e.ledger().sequence() + 1000;
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &100, &100, &1, &expire_ledger, &None, &OfferMode::PARTIAL);
+    token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &100, &100, &1, &expire_ledger, &None, &OfferMode::PARTIAL);
+    assert_eq!(token_swap.count_open_offers(&offeror), 2);
+    assert_eq!(token_swap.try_create_offer(&offeror, &send_token_id, &recv_token_id, &0, &100, &100, &1, &expire_ledger, &None, &OfferMode::PARTIAL),
+        Err(Ok(SwapError::TooManyOffers)));
+    assert_eq!(token_swap.try_split_offer(&offeror, &offer_id, &vec![&e, 50]), Err(Ok(SwapError::TooManyOffers)));
+
+    // a completed offer frees its place, and so does a closed one
+    token_swap.accept_offer(&acceptor, &offer_id, &100, &0, &None);
+    assert_eq!(token_swap.count_open_offers(&offeror), 1);
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &100, &100, &1, &expire_ledger, &None, &OfferMode::PARTIAL);
+    token_swap.close_offer(&offeror, &offer_id);
+    assert_eq!(token_swap.count_open_offers(&offeror), 1);
+
+    // offers can't be moved to an offeror at the cap either
+    let offer_ids = token_swap.offers_of(&offeror, &0, &10);
+    let receiver = Address::generate(&e);
+    token_swap.transfer_offer(&offeror, &offer_ids.get(0).unwrap(), &receiver);
+    token_swap.set_max_open_offers(&admin, &Some(1));
+    let next: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &100, &100, &1, &expire_ledger, &None, &OfferMode::PARTIAL);
+    assert_eq!(token_swap.try_transfer_offer(&offeror, &next, &receiver), Err(Ok(SwapError::TooManyOffers)));
+
+    token_swap.set_max_open_offers(&admin, &None);
+    token_swap.transfer_offer(&offeror, &next, &receiver);
+    assert_eq!(token_swap.count_open_offers(&receiver), 2);
+}
+
+#[test]
+fn test_offer_events() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &100000);
+    recv_token_admin_client.mint(&acceptor, &100000);
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &20000, &10000, &1,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+
+    // the fill is published last, versioned and with what's left of the offer
+    token_swap.accept_offer(&acceptor, &offer_id, &4000, &0, &None);
+    let (contract, topics, data) = e.events().all().last().unwrap();
+    assert_eq!(contract, token_swap.address);
+    assert_eq!(topics, (symbol_short!("OFFER"), symbol_short!("OAccept"), EVENT_VERSION).into_val(&e));
+    let accepted = OfferAccepted::try_from_val(&e, &data).unwrap();
+    assert_eq!(accepted.offer_id, offer_id);
+    assert_eq!(accepted.send_amount, 8000);
+    assert_eq!(accepted.fee_amount, 20);
+    assert_eq!((accepted.send_remaining, accepted.recv_remaining), (12000, 6000));
+
+    // filling the rest completes the offer, earning what's left of the maker fee
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &20000, &10000, &1,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+    token_swap.accept_offer(&acceptor, &offer_id, &10000, &0, &None);
+    let completed = e.events().all().iter()
+        .find(|(_, topics, _)| *topics == (symbol_short!("OFFER"), symbol_short!("OComplete"), EVENT_VERSION).into_val(&e))
+        .map(|(_, _, data)| OfferCompleted::try_from_val(&e, &data).unwrap())
+        .unwrap();
+    assert_eq!(completed.offer_id, offer_id);
+    assert_eq!(completed.fee_amount, 50);
+
+    // the refund of a closed offer includes the unearned maker fee
+    let offer_id = offer_id - 1;
+    token_swap.close_offer(&offeror, &offer_id);
+    let (_, topics, data) = e.events().all().last().unwrap();
+    assert_eq!(topics, (symbol_short!("OFFER"), symbol_short!("ORevoke"), EVENT_VERSION).into_val(&e));
+    let closed = OfferClosed::try_from_val(&e, &data).unwrap();
+    assert_eq!(closed.status, OfferStatus::CANCEL);
+    assert_eq!(closed.refund_amount, 12000 + 30);
+    assert!(closed.reason.is_empty());
+}
+
+#[test]
+fn test_config_events() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+    let (token_id, _, _) = create_token_contract(&e, &token_admin);
+
+    let fee_to = Address::generate(&e);
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &fee_to);
+    token_swap.apply_fee();
+    let (_, topics, data) = e.events().all().last().unwrap();
+    assert_eq!(topics, (symbol_short!("FEE"), symbol_short!("FeeSet")).into_val(&e));
+    assert_eq!(FeeInfo::try_from_val(&e, &data).unwrap().maker_fee_rate, DEF_MAKER_FEE_RATE);
+
+    token_swap.allow_token(&admin, &token_id);
+    let (_, topics, data) = e.events().all().last().unwrap();
+    assert_eq!(topics, (symbol_short!("ALLOW"), symbol_short!("TAllow")).into_val(&e));
+    assert_eq!(Address::try_from_val(&e, &data).unwrap(), token_id);
+
+    // allowing a token twice doesn't change anything, so nothing is published
+    let count = e.events().all().len();
+    token_swap.allow_token(&admin, &token_id);
+    assert_eq!(e.events().all().len(), count);
+
+    token_swap.disallow_token(&admin, &token_id);
+    let (_, topics, _) = e.events().all().last().unwrap();
+    assert_eq!(topics, (symbol_short!("ALLOW"), symbol_short!("TDisallow")).into_val(&e));
+}
+
+#[test]
+fn test_reopen_offer() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &10000);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    token_swap.propose_fee(&admin, &0, &0, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+
+    let expire_ledger = e.ledger().sequence() + 1000;
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &1000, &500, &1, &expire_ledger, &None, &OfferMode::PARTIAL);
+
+    // only offers which stopped trading can be reopened, and only by their offeror
+    assert_eq!(token_swap.try_reopen_offer(&offeror, &offer_id, &1000, &expire_ledger), Err(Ok(SwapError::OfferNotActive)));
+    token_swap.accept_offer(&acceptor, &offer_id, &500, &0, &None);
+    assert_eq!(token_swap.try_reopen_offer(&acceptor, &offer_id, &1000, &expire_ledger), Err(Ok(SwapError::InvalidOfferor)));
+
+    // a completed offer is funded again at the price it was filled at
+    token_swap.reopen_offer(&offeror, &offer_id, &600, &expire_ledger);
+    let offer = token_swap.get_offer(&offer_id);
+    assert_eq!(offer.status, OfferStatus::ACTIVE);
+    assert_eq!((offer.send_amount, offer.recv_amount), (600, 300));
+    assert_eq!(send_token_client.balance(&offeror), 8400);
+    assert_eq!(token_swap.offers_of(&offeror, &0, &10), vec![&e, offer_id]);
+
+    // fills of the reopened offer continue its history
+    token_swap.accept_offer(&acceptor, &offer_id, &100, &0, &None);
+    assert_eq!(token_swap.get_fills(&offer_id, &0, &10).len(), 2);
+
+    // so is a closed one, at the price it had when closed
+    token_swap.close_offer(&offeror, &offer_id);
+    assert_eq!(token_swap.try_reopen_offer(&offeror, &offer_id, &1000, &e.ledger().sequence()), Err(Ok(SwapError::InvalidExpiration)));
+    token_swap.reopen_offer(&offeror, &offer_id, &1000, &expire_ledger);
+    let offer = token_swap.get_offer(&offer_id);
+    assert_eq!((offer.send_amount, offer.recv_amount), (1000, 500));
+}
+
+#[test]
+fn test_pause_offer() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &10000);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    token_swap.propose_fee(&admin, &0, &0, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+
+    let expire_ledger = e.ledger().sequence() + 1000;
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &1000, &500, &1, &expire_ledger, &None, &OfferMode::PARTIAL);
+
+    assert_eq!(token_swap.try_pause_offer(&acceptor, &offer_id), Err(Ok(SwapError::InvalidOfferor)));
+    assert_eq!(token_swap.try_resume_offer(&offeror, &offer_id), Err(Ok(SwapError::OfferNotActive)));
+
+    // a paused offer keeps its escrow but can't be filled and leaves the book
+    token_swap.pause_offer(&offeror, &offer_id);
+    assert_eq!(token_swap.get_offer(&offer_id).status, OfferStatus::PAUSED);
+    assert_eq!(send_token_client.balance(&token_swap.address), 1000);
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &100, &0, &None), Err(Ok(SwapError::OfferNotActive)));
+    assert!(token_swap.book_levels(&send_token_id, &recv_token_id, &0, &10).is_empty());
+    assert_eq!(token_swap.count_open_offers(&offeror), 1);
+
+    token_swap.resume_offer(&offeror, &offer_id);
+    token_swap.accept_offer(&acceptor, &offer_id, &100, &0, &None);
+    assert_eq!(token_swap.book_levels(&send_token_id, &recv_token_id, &0, &10).len(), 1);
+
+    // paused offers can still be closed
+    token_swap.pause_offer(&offeror, &offer_id);
+    token_swap.close_offer(&offeror, &offer_id);
+    assert_eq!(send_token_client.balance(&offeror), 9800);
+    assert_eq!(token_swap.count_open_offers(&offeror), 0);
+}
+
+#[test]
+fn test_deal() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let party_a = Address::generate(&e);
+    let party_b = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (token_a, token_a_client, token_a_admin_client) = create_token_contract(&e, &token_admin);
+    let (token_b, token_b_client, token_b_admin_client) = create_token_contract(&e, &token_admin);
+    token_a_admin_client.mint(&party_a, &1000);
+    token_b_admin_client.mint(&party_b, &1000);
+    token_swap.allow_tokens(&admin, &vec![&e, token_a.clone(), token_b.clone()]);
+
+    let expire_ledger = e.ledger().sequence() + 100;
+    let deal_id: u32 = token_swap.open_deal(&party_a, &party_b, &token_a, &300, &token_b, &200, &expire_ledger);
+
+    // nothing settles before both legs are in and both parties confirmed
+    token_swap.deposit_deal(&party_a, &deal_id);
+    assert_eq!(token_swap.try_confirm_deal(&party_a, &deal_id), Err(Ok(SwapError::InsufficientBalance)));
+    assert_eq!(token_swap.try_deposit_deal(&admin, &deal_id), Err(Ok(SwapError::Unauthorized)));
+    token_swap.deposit_deal(&party_b, &deal_id);
+    token_swap.confirm_deal(&party_a, &deal_id);
+    assert_eq!(token_a_client.balance(&token_swap.address), 300);
+    assert_eq!(token_b_client.balance(&token_swap.address), 200);
+
+    token_swap.confirm_deal(&party_b, &deal_id);
+    assert_eq!(token_a_client.balance(&party_b), 300);
+    assert_eq!(token_b_client.balance(&party_a), 200);
+    assert_eq!(token_swap.get_deal(&deal_id).status, OfferStatus::COMPLETE);
+    assert_eq!(token_swap.try_reclaim_deal(&party_a, &deal_id), Err(Ok(SwapError::OfferNotActive)));
+
+    // a deal which isn't confirmed in time can be reclaimed by both parties
+    let deal_id: u32 = token_swap.open_deal(&party_a, &party_b, &token_a, &300, &token_b, &200, &expire_ledger);
+    token_swap.deposit_deal(&party_a, &deal_id);
+    token_swap.deposit_deal(&party_b, &deal_id);
+    token_swap.confirm_deal(&party_b, &deal_id);
+    assert_eq!(token_swap.try_reclaim_deal(&party_a, &deal_id), Err(Ok(SwapError::OfferNotExpired)));
+
+    e.ledger().with_mut(|li| li.sequence_number = expire_ledger);
+    assert_eq!(token_swap.try_confirm_deal(&party_a, &deal_id), Err(Ok(SwapError::OfferExpired)));
+    token_swap.reclaim_deal(&party_a, &deal_id);
+    token_swap.reclaim_deal(&party_b, &deal_id);
+    assert_eq!(token_swap.try_reclaim_deal(&party_b, &deal_id), Err(Ok(SwapError::InsufficientBalance)));
+    assert_eq!(token_a_client.balance(&party_a), 700);
+    assert_eq!(token_b_client.balance(&party_b), 800);
+    assert_eq!(token_swap.get_deal(&deal_id).status, OfferStatus::EXPIRED);
+}
+
+#[test]
+fn test_dispute() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    let arbiter = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+    token_swap.grant_role(&Role::Arbiter, &arbiter);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &10000);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    token_swap.propose_fee(&admin, &0, &0, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+
+    let expire_ledger = e.ledger().sequence() + 1000;
+    let open_offer: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &1000, &500, &1, &expire_ledger, &None, &OfferMode::PARTIAL);
+    assert_eq!(token_swap.try_set_dispute_window(&offeror, &open_offer, &100), Err(Ok(SwapError::AcceptorNotAllowed)));
+
+    // the fill of an offer with a dispute window stays in the contract
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &1000, &500, &1, &expire_ledger, &Some(acceptor.clone()), &OfferMode::PARTIAL);
+    token_swap.set_dispute_window(&offeror, &offer_id, &100);
+    token_swap.accept_offer(&acceptor, &offer_id, &500, &0, &None);
+    assert_eq!(recv_token_client.balance(&offeror), 0);
+    assert_eq!(send_token_client.balance(&acceptor), 0);
+    assert_eq!(token_swap.get_dispute_hold(&offer_id).unwrap().send_amount, 1000);
+
+    // released once the window has passed without a dispute
+    assert_eq!(token_swap.try_release_hold(&offer_id), Err(Ok(SwapError::OfferNotExpired)));
+    e.ledger().with_mut(|li| li.sequence_number += 100);
+    assert_eq!(token_swap.try_raise_dispute(&acceptor, &offer_id), Err(Ok(SwapError::OfferExpired)));
+    token_swap.release_hold(&offer_id);
+    assert_eq!(recv_token_client.balance(&offeror), 500);
+    assert_eq!(send_token_client.balance(&acceptor), 1000);
+    assert!(token_swap.get_dispute_hold(&offer_id).is_none());
+
+    // a disputed fill waits for the arbiter, who can unwind it
+    let expire_ledger = e.ledger().sequence() + 1000;
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &1000, &500, &1, &expire_ledger, &Some(acceptor.clone()), &OfferMode::PARTIAL);
+    token_swap.set_dispute_window(&offeror, &offer_id, &100);
+    token_swap.accept_offer(&acceptor, &offer_id, &500, &0, &None);
+    assert_eq!(token_swap.try_raise_dispute(&arbiter, &offer_id), Err(Ok(SwapError::Unauthorized)));
+    token_swap.raise_dispute(&acceptor, &offer_id);
+    e.ledger().with_mut(|li| li.sequence_number += 100);
+    assert_eq!(token_swap.try_release_hold(&offer_id), Err(Ok(SwapError::OfferLocked)));
+    assert_eq!(token_swap.try_resolve_dispute(&offeror, &offer_id, &true), Err(Ok(SwapError::Unauthorized)));
+    token_swap.resolve_dispute(&arbiter, &offer_id, &false);
+    assert_eq!(recv_token_client.balance(&acceptor), 9500);
+    assert_eq!(send_token_client.balance(&offeror), 10000 - 1000 - 1000);
+    assert_eq!(token_swap.check_solvency(&send_token_id), (1000, 1000));
+}
+
+
+#[test]
+fn test_fee_token() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+    const RATE_ONE: u128 = u128::pow(10, 18);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+    let (fee_token_id, fee_token_client, fee_token_admin_client) = create_token_contract(&e, &token_admin);
+    fee_token_admin_client.mint(&offeror, &(100_i128 * MUL_VAL as i128));
+    fee_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let fee_token = FeeToken { token: fee_token_id.clone(), max_age: 300 };
+    assert_eq!(token_swap.try_set_fee_token(&offeror, &Some(fee_token.clone())), Err(Ok(SwapError::Unauthorized)));
+    assert_eq!(token_swap.try_set_fee_token(&admin, &Some(fee_token.clone())), Err(Ok(SwapError::TokenNotAllowed)));
+    token_swap.allow_token(&admin, &fee_token_id);
+    token_swap.set_fee_token(&admin, &Some(fee_token.clone()));
+    assert_eq!(token_swap.get_fee_token().unwrap().token, fee_token_id);
+
+    // a send token unit is worth 2 fee token units, a recv token unit 3
+    token_swap.set_fee_token_rate(&admin, &send_token_id, &Some(2 * RATE_ONE));
+    token_swap.set_fee_token_rate(&admin, &recv_token_id, &Some(3 * RATE_ONE));
+    assert_eq!(token_swap.get_fee_token_rate(&send_token_id), Some(2 * RATE_ONE));
+
+    // the maker fee of 0.25% of 500 send tokens is paid in the fee token on creation
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(500 * MUL_VAL), &(50 * MUL_VAL), &MUL_VAL,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+    assert_eq!(token_swap.get_offer(&offer_id).fee_amount, 0);
+    assert_eq!(send_token_client.balance(&offeror), (500 * MUL_VAL) as i128);
+    assert_eq!(fee_token_client.balance(&offeror), (100 * MUL_VAL) as i128 - 2 * 12500);
+    assert_eq!(token_swap.get_accrued_fees(&fee_token_id), 2 * 12500);
+
+    // the taker fee of 0.5% of 10 recv tokens is paid in the fee token on the fill
+    token_swap.accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &None);
+    assert_eq!(recv_token_client.balance(&acceptor), (90 * MUL_VAL) as i128);
+    assert_eq!(fee_token_client.balance(&acceptor), (100 * MUL_VAL) as i128 - 3 * 500);
+    assert_eq!(token_swap.get_accrued_fees(&fee_token_id), 2 * 12500 + 3 * 500);
+    assert_eq!(token_swap.get_accrued_fees(&recv_token_id), 0);
+    assert_eq!(token_swap.get_accrued_fees(&send_token_id), 0);
+
+    // rates are bounded and wait for the fee delay like any other fee change
+    assert_eq!(token_swap.try_set_fee_token_rate(&admin, &send_token_id, &Some(0)), Err(Ok(SwapError::ZeroAmount)));
+    assert_eq!(token_swap.try_set_fee_token_rate(&admin, &send_token_id, &Some(RATE_ONE * u128::pow(10, 9) + 1)), 
+        Err(Ok(SwapError::FeeRateTooHigh)));
+    token_swap.set_fee_delay(&admin, &100);
+    token_swap.set_fee_token_rate(&admin, &send_token_id, &Some(RATE_ONE * u128::pow(10, 9)));
+    token_swap.set_fee_token_rate(&admin, &recv_token_id, &None);
+    assert_eq!(token_swap.get_fee_token_rate(&send_token_id), Some(2 * RATE_ONE));
+    assert_eq!(token_swap.get_fee_token_rate(&recv_token_id), Some(3 * RATE_ONE));
+    assert_eq!(token_swap.try_apply_fee_changes(), Err(Ok(SwapError::FeeTimelocked)));
+    e.ledger().with_mut(|li| li.sequence_number += 100);
+    assert_eq!(token_swap.apply_fee_changes(), 2);
+    assert_eq!(token_swap.get_fee_token_rate(&send_token_id), Some(RATE_ONE * u128::pow(10, 9)));
+    assert_eq!(token_swap.get_fee_token_rate(&recv_token_id), None);
+}
+
+
+#[test]
+fn test_fee_discounts() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+    let (gov_token_id, _, gov_token_admin_client) = create_token_contract(&e, &token_admin);
+    gov_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    gov_token_admin_client.mint(&acceptor, &(10_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    assert_eq!(token_swap.try_set_fee_discounts(&admin, &Some(gov_token_id.clone()), &vec![&e, 
+        FeeDiscount { min_balance: 10 * MUL_VAL as i128, discount: 10001 }
+    ]), Err(Ok(SwapError::FeeRateTooHigh)));
+    assert_eq!(token_swap.try_set_fee_discounts(&admin, &Some(gov_token_id.clone()), &vec![&e, 
+        FeeDiscount { min_balance: 10 * MUL_VAL as i128, discount: 1000 },
+        FeeDiscount { min_balance: 10 * MUL_VAL as i128, discount: 5000 }
+    ]), Err(Ok(SwapError::InvalidFeeTiers)));
+
+    // 10% off from 10 tokens held, 50% off from 1000
+    token_swap.set_fee_discounts(&admin, &Some(gov_token_id.clone()), &vec![&e, 
+        FeeDiscount { min_balance: 10 * MUL_VAL as i128, discount: 1000 },
+        FeeDiscount { min_balance: 1000 * MUL_VAL as i128, discount: 5000 }
+    ]);
+    assert_eq!(token_swap.get_discount_token(), Some(gov_token_id.clone()));
+    assert_eq!(token_swap.get_fee_discounts().len(), 2);
+
+    // half of the 0.25% maker fee on 500 send tokens
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(500 * MUL_VAL), &(50 * MUL_VAL), &MUL_VAL,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+    assert_eq!(token_swap.get_offer(&offer_id).fee_amount, 6250);
+    assert_eq!(send_token_client.balance(&offeror), (500 * MUL_VAL) as i128 - 6250);
+
+    // 90% of the 0.5% taker fee on 10 recv tokens
+    token_swap.accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &None);
+    assert_eq!(token_swap.get_accrued_fees(&recv_token_id), 450);
+
+    // removing the schedule charges the full fee again
+    token_swap.set_fee_discounts(&admin, &None, &vec![&e]);
+    assert_eq!(token_swap.get_discount_token(), None);
+    token_swap.accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &None);
+    assert_eq!(token_swap.get_accrued_fees(&recv_token_id), 450 + 500);
+}
+
+
+#[test]
+fn test_stake() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    let staker1 = Address::generate(&e);
+    let staker2 = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+    let (gov_token_id, gov_token_client, gov_token_admin_client) = create_token_contract(&e, &token_admin);
+    gov_token_admin_client.mint(&staker1, &(300_i128 * MUL_VAL as i128));
+    gov_token_admin_client.mint(&staker2, &(100_i128 * MUL_VAL as i128));
+    gov_token_admin_client.mint(&acceptor, &(10_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    assert_eq!(token_swap.try_stake(&staker1, &MUL_VAL), Err(Ok(SwapError::TokenNotAllowed)));
+    token_swap.set_stake_token(&admin, &Some(gov_token_id.clone()));
+    assert_eq!(token_swap.try_set_stake_share(&admin, &10001), Err(Ok(SwapError::FeeRateTooHigh)));
+    // half of every fee goes to stakers
+    token_swap.set_stake_share(&admin, &5000);
+
+    token_swap.stake(&staker1, &(300 * MUL_VAL));
+    token_swap.stake(&staker2, &(100 * MUL_VAL));
+    assert_eq!(token_swap.get_total_stake(), 400 * MUL_VAL);
+    assert_eq!(gov_token_client.balance(&staker1), 0);
+    assert_eq!(token_swap.try_set_stake_token(&admin, &None), Err(Ok(SwapError::OfferLocked)));
+
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(500 * MUL_VAL), &(50 * MUL_VAL), &MUL_VAL,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+
+    // the 500 taker fee is split between the protocol and the stakers by stake
+    token_swap.accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &None);
+    assert_eq!(token_swap.get_accrued_fees(&recv_token_id), 250);
+    assert_eq!(token_swap.get_stake_rewards(&staker1, &recv_token_id), 187);
+    assert_eq!(token_swap.get_stake_rewards(&staker2, &recv_token_id), 62);
+
+    // rewards earned before unstaking are kept
+    assert_eq!(token_swap.try_unstake(&staker2, &(101 * MUL_VAL)), Err(Ok(SwapError::InsufficientBalance)));
+    token_swap.unstake(&staker2, &(100 * MUL_VAL));
+    assert_eq!(gov_token_client.balance(&staker2), (100 * MUL_VAL) as i128);
+    token_swap.accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &None);
+    assert_eq!(token_swap.get_stake_rewards(&staker1, &recv_token_id), 187 + 250);
+    assert_eq!(token_swap.get_stake_rewards(&staker2, &recv_token_id), 62);
+
+    assert_eq!(token_swap.claim_stake_rewards(&staker2, &recv_token_id), 62);
+    assert_eq!(recv_token_client.balance(&staker2), 62);
+    assert_eq!(token_swap.get_stake_rewards(&staker2, &recv_token_id), 0);
+
+    // a stake counts toward the holder fee discounts
+    token_swap.set_fee_discounts(&admin, &Some(gov_token_id.clone()), &vec![&e, 
+        FeeDiscount { min_balance: 100 * MUL_VAL as i128, discount: 5000 }
+    ]);
+    send_token_admin_client.mint(&staker1, &(1000_i128 * MUL_VAL as i128));
+    let offer_id: u32 = token_swap.create_offer(
+        &staker1, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(10 * MUL_VAL), &MUL_VAL,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+    assert_eq!(token_swap.get_offer(&offer_id).fee_amount, 1250);
+}
+
+
+#[test]
+fn test_maker_rewards() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+    const RATE_ONE: u128 = u128::pow(10, 18);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+    let (reward_token_id, reward_token_client, reward_token_admin_client) = create_token_contract(&e, &token_admin);
+    reward_token_admin_client.mint(&admin, &(30_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    assert_eq!(token_swap.try_fund_rewards(&admin, &MUL_VAL), Err(Ok(SwapError::TokenNotAllowed)));
+    token_swap.set_reward_token(&admin, &Some(reward_token_id.clone()));
+    assert_eq!(token_swap.try_fund_rewards(&offeror, &MUL_VAL), Err(Ok(SwapError::Unauthorized)));
+    token_swap.fund_rewards(&admin, &(30 * MUL_VAL));
+    assert_eq!(token_swap.get_reward_budget(), 30 * MUL_VAL);
+    assert_eq!(token_swap.try_set_reward_token(&admin, &None), Err(Ok(SwapError::OfferLocked)));
+
+    // 2 reward tokens per recv token received
+    token_swap.set_reward_rate(&admin, &recv_token_id, &Some(2 * RATE_ONE));
+
+    let offer_id: u32 = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(500 * MUL_VAL), &(50 * MUL_VAL), &MUL_VAL,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+    token_swap.accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &None);
+    assert_eq!(token_swap.get_rewards(&offeror), 20 * MUL_VAL);
+    assert_eq!(token_swap.get_rewards(&acceptor), 0);
+
+    // filling one's own offer earns nothing
+    recv_token_admin_client.mint(&offeror, &(10_i128 * MUL_VAL as i128));
+    token_swap.accept_offer(&offeror, &offer_id, &(5 * MUL_VAL), &0, &None);
+    assert_eq!(token_swap.get_rewards(&offeror), 20 * MUL_VAL);
+    assert_eq!(token_swap.get_reward_budget(), 10 * MUL_VAL);
+
+    // the budget caps the rewards
+    token_swap.accept_offer(&acceptor, &offer_id, &(10 * MUL_VAL), &0, &None);
+    assert_eq!(token_swap.get_rewards(&offeror), 30 * MUL_VAL);
+    assert_eq!(token_swap.get_reward_budget(), 0);
+
+    assert_eq!(token_swap.claim_rewards(&offeror), 30 * MUL_VAL);
+    assert_eq!(reward_token_client.balance(&offeror), (30 * MUL_VAL) as i128);
+    assert_eq!(token_swap.get_rewards(&offeror), 0);
+    token_swap.set_reward_token(&admin, &None);
+}
+
+
+#[test]
+fn test_governance() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let voter1 = Address::generate(&e);
+    let voter2 = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, _) = create_token_contract(&e, &token_admin);
+    let (gov_token_id, gov_token_client, gov_token_admin_client) = create_token_contract(&e, &token_admin);
+    gov_token_admin_client.mint(&voter1, &(100_i128 * MUL_VAL as i128));
+    gov_token_admin_client.mint(&voter2, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+
+    assert_eq!(token_swap.try_propose(&voter1, &GovAction::Allow(send_token_id.clone())), Err(Ok(SwapError::Unauthorized)));
+    token_swap.set_gov_config(&admin, &Some(GovConfig {
+        token: gov_token_id.clone(), voting_ledgers: 100, delay_ledgers: 50, quorum: 50 * MUL_VAL
+    }));
+
+    let allow_id = token_swap.propose(&voter1, &GovAction::Allow(send_token_id.clone()));
+    let fee_id = token_swap.propose(&voter2, &GovAction::FeeRates(10, 20));
+    let disallow_id = token_swap.propose(&voter2, &GovAction::Disallow(send_token_id.clone()));
+
+    token_swap.vote(&voter1, &allow_id, &true, &(60 * MUL_VAL));
+    token_swap.vote(&voter1, &fee_id, &true, &(20 * MUL_VAL));
+    assert_eq!(token_swap.try_vote(&voter1, &allow_id, &false, &MUL_VAL), Err(Ok(SwapError::OfferLocked)));
+    token_swap.vote(&voter2, &allow_id, &false, &(40 * MUL_VAL));
+    token_swap.vote(&voter2, &fee_id, &true, &(40 * MUL_VAL));
+    token_swap.vote(&voter2, &disallow_id, &true, &(20 * MUL_VAL));
+    assert_eq!(gov_token_client.balance(&voter2), 0);
+    assert_eq!(token_swap.try_withdraw_vote(&voter2, &allow_id), Err(Ok(SwapError::OfferNotExpired)));
+
+    // voting ends, then the delay passes
+    e.ledger().with_mut(|li| li.sequence_number += 100);
+    assert_eq!(token_swap.try_vote(&voter1, &fee_id, &true, &MUL_VAL), Err(Ok(SwapError::OfferExpired)));
+    assert_eq!(token_swap.try_execute_proposal(&allow_id), Err(Ok(SwapError::FeeTimelocked)));
+    e.ledger().with_mut(|li| li.sequence_number += 50);
+
+    token_swap.execute_proposal(&allow_id);
+    assert!(token_swap.is_token_allowed(&send_token_id));
+    assert_eq!(token_swap.try_execute_proposal(&allow_id), Err(Ok(SwapError::OfferNotActive)));
+
+    token_swap.execute_proposal(&fee_id);
+    assert_eq!(token_swap.get_maker_fee_rate(), 10);
+    assert_eq!(token_swap.get_taker_fee_rate(), 20);
+
+    // 20 votes for is short of the quorum
+    assert_eq!(token_swap.try_execute_proposal(&disallow_id), Err(Ok(SwapError::Unauthorized)));
+    assert!(token_swap.is_token_allowed(&send_token_id));
+
+    assert_eq!(token_swap.withdraw_vote(&voter2, &allow_id), 40 * MUL_VAL);
+    assert_eq!(token_swap.withdraw_vote(&voter2, &fee_id), 40 * MUL_VAL);
+    assert_eq!(token_swap.withdraw_vote(&voter2, &disallow_id), 20 * MUL_VAL);
+    assert_eq!(gov_token_client.balance(&voter2), (100 * MUL_VAL) as i128);
+}
+
+
+#[test]
+fn test_finalize_config() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let fee_wallet = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, _) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, _) = create_token_contract(&e, &token_admin);
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &fee_wallet);
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+
+    assert_eq!(token_swap.try_finalize_config(&fee_wallet, &FREEZE_FEES), Err(Ok(SwapError::Unauthorized)));
+    assert_eq!(token_swap.try_finalize_config(&admin, &(FREEZE_FEES | 4)), Err(Ok(SwapError::InvalidOps)));
+    token_swap.finalize_config(&admin, &FREEZE_FEES);
+    assert_eq!(token_swap.get_frozen_config(), FREEZE_FEES);
+
+    // fee settings are locked, the allowlist isn't yet
+    assert_eq!(token_swap.try_propose_fee(&admin, &10, &10, &fee_wallet), Err(Ok(SwapError::Unauthorized)));
+    assert_eq!(token_swap.try_set_token_fee(&admin, &send_token_id, &10), Err(Ok(SwapError::Unauthorized)));
+    assert_eq!(token_swap.try_set_referral_share(&admin, &100), Err(Ok(SwapError::Unauthorized)));
+    assert_eq!(token_swap.try_set_stake_token(&admin, &Some(send_token_id.clone())), Err(Ok(SwapError::Unauthorized)));
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    // freezing adds to what's frozen
+    token_swap.finalize_config(&admin, &FREEZE_ALLOWLIST);
+    assert_eq!(token_swap.get_frozen_config(), FREEZE_FEES | FREEZE_ALLOWLIST);
+    assert_eq!(token_swap.try_disallow_token(&admin, &recv_token_id), Err(Ok(SwapError::Unauthorized)));
+    assert_eq!(token_swap.try_allow_pair(&admin, &send_token_id, &recv_token_id), Err(Ok(SwapError::Unauthorized)));
+    assert_eq!(token_swap.get_maker_fee_rate(), DEF_MAKER_FEE_RATE);
+}
+
+
+#[test]
+fn test_rate_limit() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    assert_eq!(token_swap.try_set_rate_limit(&offeror, &Some(RateLimit { window_ledgers: 100, max_creates: 2, max_accepts: 1 })), 
+        Err(Ok(SwapError::Unauthorized)));
+    token_swap.set_rate_limit(&admin, &Some(RateLimit { window_ledgers: 100, max_creates: 2, max_accepts: 1 }));
+    assert_eq!(token_swap.get_rate_limit().unwrap().max_creates, 2);
+
+    let expire_ledger = e.ledger().sequence() + 1000;
+    let create = || token_swap.try_create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(10 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+    let offer_id = create().unwrap().unwrap();
+    create().unwrap().unwrap();
+    assert_eq!(create(), Err(Ok(SwapError::TooManyOffers)));
+
+    token_swap.accept_offer(&acceptor, &offer_id, &MUL_VAL, &0, &None);
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &MUL_VAL, &0, &None), Err(Ok(SwapError::TooManyOffers)));
+
+    // reopening counts as a create
+    token_swap.close_offer(&offeror, &offer_id);
+    assert_eq!(token_swap.try_reopen_offer(&offeror, &offer_id, &(100 * MUL_VAL), &expire_ledger), Err(Ok(SwapError::TooManyOffers)));
+
+    // the limits start over in the next window
+    e.ledger().with_mut(|li| li.sequence_number += 100);
+    create().unwrap().unwrap();
+    token_swap.reopen_offer(&offeror, &offer_id, &(100 * MUL_VAL), &expire_ledger);
+    token_swap.accept_offer(&acceptor, &offer_id, &MUL_VAL, &0, &None);
+
+    token_swap.set_rate_limit(&admin, &None);
+    create().unwrap().unwrap();
+    create().unwrap().unwrap();
+}
+
+
+#[test]
+fn test_compliance() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let compliance = MockComplianceClient::new(&e, &e.register_contract(None, MockCompliance));
+    assert_eq!(token_swap.try_set_compliance(&offeror, &Some(compliance.address.clone())), Err(Ok(SwapError::Unauthorized)));
+    token_swap.set_compliance(&admin, &Some(compliance.address.clone()));
+    assert_eq!(token_swap.get_compliance(), Some(compliance.address.clone()));
+
+    let expire_ledger = e.ledger().sequence() + 1000;
+    let offer_id = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(10 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+
+    compliance.deny(&acceptor);
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &MUL_VAL, &0, &None), Err(Ok(SwapError::Unauthorized)));
+
+    let closed_id = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(10 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+    token_swap.close_offer(&offeror, &closed_id);
+
+    compliance.deny(&offeror);
+    assert_eq!(token_swap.try_create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(10 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL), Err(Ok(SwapError::Unauthorized)));
+    assert_eq!(token_swap.try_reopen_offer(&offeror, &closed_id, &(100 * MUL_VAL), &expire_ledger), Err(Ok(SwapError::Unauthorized)));
+    assert_eq!(token_swap.try_transfer_offer(&offeror, &offer_id, &acceptor), Err(Ok(SwapError::Unauthorized)));
+
+    // without the compliance contract anyone trades again
+    token_swap.set_compliance(&admin, &None);
+    token_swap.accept_offer(&acceptor, &offer_id, &MUL_VAL, &0, &None);
+}
+
+
+#[test]
+fn test_ban_address() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let expire_ledger = e.ledger().sequence() + 1000;
+    let offer_id = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(10 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+
+    assert_eq!(token_swap.try_ban_address(&offeror, &acceptor), Err(Ok(SwapError::Unauthorized)));
+    token_swap.ban_address(&admin, &acceptor);
+    token_swap.ban_address(&admin, &offeror);
+    assert!(token_swap.is_banned(&acceptor));
+
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &MUL_VAL, &0, &None), Err(Ok(SwapError::Unauthorized)));
+    assert_eq!(token_swap.try_create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(10 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL), Err(Ok(SwapError::Unauthorized)));
+    // nor can offers be handed to a banned address
+    assert_eq!(token_swap.try_transfer_offer(&offeror, &offer_id, &acceptor), Err(Ok(SwapError::Unauthorized)));
+
+    token_swap.unban_address(&admin, &acceptor);
+    assert!(!token_swap.is_banned(&acceptor));
+    token_swap.accept_offer(&acceptor, &offer_id, &MUL_VAL, &0, &None);
+
+    // a banned offeror can still get its tokens back
+    token_swap.close_offer(&offeror, &offer_id);
+    assert_eq!(token_swap.try_reopen_offer(&offeror, &offer_id, &(10 * MUL_VAL), &expire_ledger), Err(Ok(SwapError::Unauthorized)));
+    assert_eq!(send_token_client.balance(&offeror), (1000 * MUL_VAL) as i128 - 250 - 10 * MUL_VAL as i128);
+}
+
+
+#[test]
+fn test_offer_memo() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, _) = create_token_contract(&e, &token_admin);
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+
+    let spec = OfferSpec {
+        send_token: send_token_id.clone(),
+        recv_token: recv_token_id.clone(),
+        timestamp: 0,
+        send_amount: 100 * MUL_VAL,
+        recv_amount: 10 * MUL_VAL,
+        min_recv_amount: MUL_VAL,
+        expire_ledger: e.ledger().sequence() + 1000,
+        mode: OfferMode::PARTIAL,
+    };
+    assert_eq!(token_swap.try_create_offer_with_memo(&offeror, &spec, &None, &Bytes::from_array(&e, &[7; 65])), 
+        Err(Ok(SwapError::MemoTooLong)));
+
+    let memo = Bytes::from_slice(&e, b"INV-2024-0042");
+    let offer_id = token_swap.create_offer_with_memo(&offeror, &spec, &None, &memo);
+    assert_eq!(token_swap.get_offer_memo(&offer_id), memo);
+    let created = OfferCreated::try_from_val(&e, &e.events().all().last().unwrap().2).unwrap();
+    assert_eq!(created.offer_id, offer_id);
+    assert_eq!(created.memo, memo);
+
+    // offers created without a memo have an empty one
+    let offer_id = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &(100 * MUL_VAL), &(10 * MUL_VAL), &MUL_VAL,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+    assert!(token_swap.get_offer_memo(&offer_id).is_empty());
+}
+
+
+#[test]
+fn test_signed_order() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let maker = Address::generate(&e);
+    let taker = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&maker, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&taker, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+
+    let order = SignedOrder {
+        maker: maker.clone(),
+        send_token: send_token_id.clone(),
+        recv_token: recv_token_id.clone(),
+        send_amount: 100 * MUL_VAL,
+        recv_amount: 10 * MUL_VAL,
+        expire_ledger: e.ledger().sequence() + 100,
+        nonce: 1,
+    };
+    assert_eq!(token_swap.try_fill_signed_order(&maker, &order), Err(Ok(SwapError::AcceptorNotAllowed)));
+
+    token_swap.fill_signed_order(&taker, &order);
+
+    // the maker authorized just the order and pulling its send_token into the contract
+    let maker_auth = e.auths().into_iter().find(|(address, _)| *address == maker).unwrap().1;
+    assert_eq!(maker_auth, AuthorizedInvocation {
+        function: AuthorizedFunction::Contract((
+            token_swap.address.clone(),
+            Symbol::new(&e, "fill_signed_order"),
+            (order.clone(),).into_val(&e)
+        )),
+        sub_invocations: std::vec![
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    send_token_id.clone(),
+                    symbol_short!("transfer"),
+                    (maker.clone(), token_swap.address.clone(), (100 * MUL_VAL) as i128).into_val(&e)
+                )),
+                sub_invocations: std::vec![]
+            }
+        ]
+    });
+
+    // nothing was escrowed up front, the maker fee comes out of what the maker receives
+    assert_eq!(send_token_client.balance(&maker), (900 * MUL_VAL) as i128);
+    assert_eq!(send_token_client.balance(&taker), (100 * MUL_VAL) as i128);
+    assert_eq!(recv_token_client.balance(&maker), (10 * MUL_VAL) as i128 - 250);
+    assert_eq!(recv_token_client.balance(&taker), (90 * MUL_VAL) as i128 - 500);
+    assert_eq!(token_swap.get_accrued_fees(&recv_token_id), 750);
+    assert!(token_swap.is_nonce_used(&maker, &1));
+
+    // an order fills once
+    assert_eq!(token_swap.try_fill_signed_order(&taker, &order), Err(Ok(SwapError::OfferNotActive)));
+
+    // with a fee token each side pays its own fee in it, and the maker gets the whole recv_amount
+    let (fee_token_id, fee_token_client, fee_token_admin_client) = create_token_contract(&e, &token_admin);
+    fee_token_admin_client.mint(&maker, &(MUL_VAL as i128));
+    fee_token_admin_client.mint(&taker, &(MUL_VAL as i128));
+    token_swap.allow_token(&admin, &fee_token_id);
+    token_swap.set_fee_token(&admin, &Some(FeeToken { token: fee_token_id.clone(), max_age: 300 }));
+    token_swap.set_fee_token_rate(&admin, &recv_token_id, &Some(2 * u128::pow(10, 18)));
+    token_swap.fill_signed_order(&taker, &SignedOrder { nonce: 2, ..order.clone() });
+    assert_eq!(recv_token_client.balance(&maker), (20 * MUL_VAL) as i128 - 250);
+    assert_eq!(recv_token_client.balance(&taker), (80 * MUL_VAL) as i128 - 500);
+    assert_eq!(fee_token_client.balance(&maker), (MUL_VAL - 2 * 250) as i128);
+    assert_eq!(fee_token_client.balance(&taker), (MUL_VAL - 2 * 500) as i128);
+    assert_eq!(token_swap.get_accrued_fees(&fee_token_id), 2 * 750);
+    assert_eq!(token_swap.get_accrued_fees(&recv_token_id), 750);
+
+    e.ledger().with_mut(|li| li.sequence_number += 100);
+    assert_eq!(token_swap.try_fill_signed_order(&taker, &SignedOrder { nonce: 3, ..order }), Err(Ok(SwapError::OfferExpired)));
+}
+
+
+#[test]
+fn test_cancel_nonce() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let maker = Address::generate(&e);
+    let taker = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&maker, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&taker, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+
+    let order = |nonce: u64| SignedOrder {
+        maker: maker.clone(),
+        send_token: send_token_id.clone(),
+        recv_token: recv_token_id.clone(),
+        send_amount: 100 * MUL_VAL,
+        recv_amount: 10 * MUL_VAL,
+        expire_ledger: e.ledger().sequence() + 100,
+        nonce,
+    };
+
+    token_swap.cancel_nonce(&maker, &7);
+    assert!(token_swap.is_nonce_used(&maker, &7));
+    assert_eq!(token_swap.try_fill_signed_order(&taker, &order(7)), Err(Ok(SwapError::OfferNotActive)));
+
+    token_swap.cancel_all_before(&maker, &5);
+    assert_eq!(token_swap.get_min_nonce(&maker), 5);
+    assert!(token_swap.is_nonce_used(&maker, &4));
+    assert!(!token_swap.is_nonce_used(&maker, &5));
+    assert_eq!(token_swap.try_fill_signed_order(&taker, &order(0)), Err(Ok(SwapError::OfferNotActive)));
+
+    // the bound only moves up
+    token_swap.cancel_all_before(&maker, &2);
+    assert_eq!(token_swap.get_min_nonce(&maker), 5);
+
+    token_swap.fill_signed_order(&taker, &order(5));
+    assert!(token_swap.is_nonce_used(&maker, &5));
+}
+
+
+#[test]
+fn test_fill_callback() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let vault = MockVaultClient::new(&e, &e.register_contract(None, MockVault));
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&vault.address, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let expire_ledger = e.ledger().sequence() + 1000;
+    let offer_id = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(10 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+
+    // not registered, not called
+    token_swap.accept_offer(&vault.address, &offer_id, &MUL_VAL, &0, &None);
+    assert_eq!(vault.last_fill(), None);
+
+    token_swap.set_fill_callback(&vault.address, &true);
+    assert!(token_swap.has_fill_callback(&vault.address));
+    token_swap.accept_offer(&vault.address, &offer_id, &(2 * MUL_VAL), &0, &None);
+    assert_eq!(vault.last_fill(), Some((offer_id, 2 * MUL_VAL, 20 * MUL_VAL)));
+    assert_eq!(send_token_client.balance(&vault.address), 30 * MUL_VAL as i128);
+
+    token_swap.set_fill_callback(&vault.address, &false);
+    assert!(!token_swap.has_fill_callback(&vault.address));
+}
+
+
+#[test]
+fn test_swap_best() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let router = MockRouterClient::new(&e, &e.register_contract(None, MockRouter));
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    send_token_admin_client.mint(&router.address, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    // the book pays 10 send_token per recv_token
+    let expire_ledger = e.ledger().sequence() + 1000;
+    let offer_id = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(10 * MUL_VAL), &MUL_VAL,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+
+    // without a router the book is used
+    assert_eq!(token_swap.swap_best(&acceptor, &send_token_id, &recv_token_id, &MUL_VAL, &0), 10 * MUL_VAL);
+
+    assert_eq!(token_swap.try_set_router(&offeror, &Some(router.address.clone())), Err(Ok(SwapError::Unauthorized)));
+    token_swap.set_router(&admin, &Some(router.address.clone()));
+    assert_eq!(token_swap.get_router(), Some(router.address.clone()));
+
+    // the AMM pays less
+    router.set_rate(&5);
+    assert_eq!(token_swap.swap_best(&acceptor, &send_token_id, &recv_token_id, &MUL_VAL, &0), 10 * MUL_VAL);
+    assert_eq!(token_swap.get_offer(&offer_id).recv_amount, 8 * MUL_VAL);
+
+    // the AMM pays more
+    router.set_rate(&20);
+    assert_eq!(token_swap.try_swap_best(&acceptor, &send_token_id, &recv_token_id, &MUL_VAL, &(21 * MUL_VAL)), Err(Ok(SwapError::SlippageExceeded)));
+    assert_eq!(token_swap.swap_best(&acceptor, &send_token_id, &recv_token_id, &MUL_VAL, &(20 * MUL_VAL)), 20 * MUL_VAL);
+    assert_eq!(token_swap.get_offer(&offer_id).recv_amount, 8 * MUL_VAL);
+    assert_eq!(send_token_client.balance(&acceptor), 40 * MUL_VAL as i128);
+    assert_eq!(recv_token_client.balance(&router.address), MUL_VAL as i128);
+}
+
+
+#[test]
+fn test_swap_exact_in_out() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let expire_ledger = e.ledger().sequence() + 1000;
+    let cheap_id = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(10 * MUL_VAL), &1,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+    let dear_id = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(20 * MUL_VAL), &1,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+
+    // 5 recv_token buy 4.975 of the cheap offer plus its taker fee, which rounds down and
+    // leaves 2 units for the dear one
+    assert_eq!(token_swap.try_swap_exact_in(&acceptor, &recv_token_id, &send_token_id, &(5 * MUL_VAL), &(50 * MUL_VAL)), Err(Ok(SwapError::SlippageExceeded)));
+    let amount_out = token_swap.swap_exact_in(&acceptor, &recv_token_id, &send_token_id, &(5 * MUL_VAL), &0);
+    assert_eq!(amount_out, 497510);
+    assert_eq!(recv_token_client.balance(&acceptor), 95 * MUL_VAL as i128);
+    assert_eq!(token_swap.get_offer(&cheap_id).recv_amount, 50250);
+
+    // 60 send_token take the rest of the cheap offer and the remainder from the dear one
+    let balance = recv_token_client.balance(&acceptor);
+    assert_eq!(token_swap.try_swap_exact_out(&acceptor, &recv_token_id, &send_token_id, &(60 * MUL_VAL), &(7 * MUL_VAL)), Err(Ok(SwapError::SlippageExceeded)));
+    let amount_in = token_swap.swap_exact_out(&acceptor, &recv_token_id, &send_token_id, &(60 * MUL_VAL), &(8 * MUL_VAL));
+    assert_eq!(recv_token_client.balance(&acceptor), balance - amount_in as i128);
+    assert_eq!(send_token_client.balance(&acceptor), (amount_out + 60 * MUL_VAL) as i128);
+    assert_eq!(token_swap.get_offer(&dear_id).recv_amount, 20 * MUL_VAL - 19502);
+
+    // more than the book holds
+    assert_eq!(token_swap.try_swap_exact_out(&acceptor, &recv_token_id, &send_token_id, &(100 * MUL_VAL), &(100 * MUL_VAL)), Err(Ok(SwapError::SlippageExceeded)));
+}
+
+
+#[test]
+fn test_ttl_config() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, _) = create_token_contract(&e, &token_admin);
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    assert_eq!(token_swap.get_ttl_config().extend_to, 30 * DAY_IN_LEDGERS);
+    let config = TtlConfig { threshold: DAY_IN_LEDGERS, extend_to: 2 * DAY_IN_LEDGERS };
+    assert_eq!(token_swap.try_set_ttl_config(&offeror, &Some(config.clone())), Err(Ok(SwapError::Unauthorized)));
+    assert_eq!(token_swap.try_set_ttl_config(&admin, &Some(TtlConfig { threshold: 2 * DAY_IN_LEDGERS, extend_to: 2 * DAY_IN_LEDGERS })), 
+        Err(Ok(SwapError::InvalidExpiration)));
+    token_swap.set_ttl_config(&admin, &Some(config));
+    assert_eq!(token_swap.get_ttl_config().threshold, DAY_IN_LEDGERS);
+
+    let offer_id = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &(100 * MUL_VAL), &(10 * MUL_VAL), &MUL_VAL,
+        &(e.ledger().sequence() + 30 * DAY_IN_LEDGERS), &None, &OfferMode::PARTIAL);
+
+    // every read keeps the offer alive for another 2 days, restore_offer its indexes too
+    for _ in 0..4 {
+        e.ledger().with_mut(|li| li.sequence_number += 3 * DAY_IN_LEDGERS / 2);
+        assert_eq!(token_swap.get_offer(&offer_id).send_amount, 100 * MUL_VAL);
+        token_swap.restore_offer(&offer_id);
+    }
+    assert_eq!(token_swap.offers_of(&offeror, &0, &10), vec![&e, offer_id]);
+    assert_eq!(token_swap.try_restore_offer(&(offer_id + 1)), Err(Ok(SwapError::OfferNotFound)));
+}
+
+
+#[test]
+fn test_migrate_offers() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    const MUL_VAL: u64 = u64::pow(10, TOKEN_DECIMALS);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &(1000_i128 * MUL_VAL as i128));
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &(100_i128 * MUL_VAL as i128));
+
+    token_swap.propose_fee(&admin, &DEF_MAKER_FEE_RATE, &DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    token_swap.apply_fee();
+    token_swap.allow_token(&admin, &send_token_id);
+    token_swap.allow_token(&admin, &recv_token_id);
+
+    let expire_ledger = e.ledger().sequence() + 1000;
+    let mut offer_ids = Vec::new(&e);
+    for _ in 0..2 {
+        let offer_id = token_swap.create_offer(
+            &offeror, &send_token_id, &recv_token_id, &0,
+            &(100 * MUL_VAL), &(10 * MUL_VAL), &MUL_VAL,
+            &expire_ledger, &None, &OfferMode::PARTIAL);
+        assert_eq!(token_swap.get_offer_schema(&offer_id), Some(2));
+
+        // store the offer the way schema 1 did
+        let offer = token_swap.get_offer(&offer_id);
+        e.as_contract(&token_swap.address, || {
+            e.storage().persistent().set(&DataKey::RegOffers(offer_id), &offer);
+        });
+        assert_eq!(token_swap.get_offer_schema(&offer_id), Some(1));
+        offer_ids.push_back(offer_id);
+    }
+
+    // old offers are upcast on load and stored in the current schema on their next write
+    let offer_id = offer_ids.get(0).unwrap();
+    assert_eq!(token_swap.get_offer(&offer_id).send_amount, 100 * MUL_VAL);
+    token_swap.accept_offer(&acceptor, &offer_id, &MUL_VAL, &0, &None);
+    assert_eq!(token_swap.get_offer_schema(&offer_id), Some(2));
+    assert_eq!(token_swap.get_offer(&offer_id).send_amount, 90 * MUL_VAL);
+
+    // or in batches
+    offer_ids.push_back(100);
+    assert_eq!(token_swap.try_migrate_offers(&offeror, &offer_ids), Err(Ok(SwapError::Unauthorized)));
+    assert_eq!(token_swap.migrate_offers(&admin, &offer_ids), 1);
+    assert_eq!(token_swap.get_offer_schema(&offer_ids.get(1).unwrap()), Some(2));
+    assert_eq!(token_swap.migrate_offers(&admin, &offer_ids), 0);
+    assert_eq!(token_swap.get_offer_schema(&100), None);
+}
+
+
+#[test]
+fn test_testutils() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, DEF_MAKER_FEE_RATE, DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    let (_, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &1000);
+
+    let offer_id = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 10000, 1000);
+    assert_eq!(send_token_client.balance(&offeror), 0);
+    assert_eq!(token_swap.get_offer(&offer_id).fee_amount, 25);
+
+    token_swap.accept_offer(&acceptor, &offer_id, &100, &0, &None);
+    assert_eq!(send_token_client.balance(&acceptor), 1000);
+}
+
+
+#[test]
+fn test_market_buy() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let taker = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, DEF_MAKER_FEE_RATE, DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&taker, &100000);
+
+    // three price levels: 10, 5 and 4 send_token per recv_token
+    let dear_id = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 10000, 2000);
+    let cheap_id = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 10000, 1000);
+    let last_id = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 10000, 2500);
+
+    assert_eq!(token_swap.try_market_buy(&taker, &send_token_id, &recv_token_id, &4000, &23925), Err(Ok(SwapError::SlippageExceeded)));
+
+    // 4000 sweeps the two best levels for 1005 and 2010 with fees, and buys 981 of the
+    // third for the 985 left
+    let (spent, received) = token_swap.market_buy(&taker, &send_token_id, &recv_token_id, &4000, &23924);
+    assert_eq!(spent, 4000);
+    assert_eq!(recv_token_client.balance(&taker), 100000 - 4000);
+    assert_eq!(send_token_client.balance(&taker), received as i128);
+    assert_eq!(token_swap.get_offer(&cheap_id).status, OfferStatus::COMPLETE);
+    assert_eq!(token_swap.get_offer(&dear_id).status, OfferStatus::COMPLETE);
+    assert_eq!(token_swap.get_offer(&last_id).recv_amount, 2500 - 981);
+    assert_eq!(received, 10000 + 10000 + 4 * 981);
+
+    // a taker fee paid in the fee token doesn't come out of the budget
+    let (fee_token_id, fee_token_client, fee_token_admin_client) = create_token_contract(&e, &token_admin);
+    fee_token_admin_client.mint(&taker, &1000);
+    token_swap.allow_token(&admin, &fee_token_id);
+    token_swap.set_fee_token(&admin, &Some(FeeToken { token: fee_token_id.clone(), max_age: 300 }));
+    token_swap.set_fee_token_rate(&admin, &recv_token_id, &Some(10u128.pow(18)));
+    let (spent, received) = token_swap.market_buy(&taker, &send_token_id, &recv_token_id, &1000, &4000);
+    assert_eq!((spent, received), (1000, 4000));
+    assert_eq!(recv_token_client.balance(&taker), 100000 - 5000);
+    assert_eq!(fee_token_client.balance(&taker), 1000 - 5);
+}
+
+
+#[test]
+fn test_best_price() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, DEF_MAKER_FEE_RATE, DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, _) = create_token_contract(&e, &token_admin);
+
+    assert_eq!(token_swap.try_best_price(&send_token_id, &recv_token_id), Err(Ok(SwapError::OfferNotFound)));
+
+    let dear_id = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 10000, 2000);
+    send_token_admin_client.mint(&offeror, &20000);
+    let cheap_id = token_swap.create_offer(
+        &offeror, &send_token_id, &recv_token_id, &0,
+        &10000, &1000, &1,
+        &(e.ledger().sequence() + 10), &None, &OfferMode::PARTIAL);
+
+    // 0.1 recv_token per send_token
+    assert_eq!(token_swap.best_price(&send_token_id, &recv_token_id), (cheap_id, 10u128.pow(17), 10000));
+
+    // an expired offer still in the book is passed over
+    e.ledger().with_mut(|li| li.sequence_number += 10);
+    assert_eq!(token_swap.best_price(&send_token_id, &recv_token_id), (dear_id, 2 * 10u128.pow(17), 10000));
+}
+
+
+#[test]
+fn test_accept_exact_out() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, DEF_MAKER_FEE_RATE, DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    // 3 send_token per recv_token
+    let offer_id = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 30000, 10000);
+
+    // 1000 send_token cost 333.33 recv_token, rounded up to 334, plus a taker fee of 1
+    assert_eq!(token_swap.try_accept_exact_out(&acceptor, &offer_id, &1000, &334), Err(Ok(SwapError::SlippageExceeded)));
+    assert_eq!(token_swap.accept_exact_out(&acceptor, &offer_id, &1000, &335), (335, 1002));
+    assert_eq!(send_token_client.balance(&acceptor), 1002);
+    assert_eq!(recv_token_client.balance(&acceptor), 10000 - 335);
+
+    assert_eq!(token_swap.try_accept_exact_out(&acceptor, &offer_id, &30000, &20000), Err(Ok(SwapError::AmountTooLarge)));
+
+    // a taker fee paid in the fee token doesn't count against the most paid in recv_token
+    let (fee_token_id, fee_token_client, fee_token_admin_client) = create_token_contract(&e, &token_admin);
+    fee_token_admin_client.mint(&acceptor, &1000);
+    token_swap.allow_token(&admin, &fee_token_id);
+    token_swap.set_fee_token(&admin, &Some(FeeToken { token: fee_token_id.clone(), max_age: 300 }));
+    token_swap.set_fee_token_rate(&admin, &recv_token_id, &Some(10u128.pow(18)));
+    assert_eq!(token_swap.accept_exact_out(&acceptor, &offer_id, &1000, &334), (334, 1002));
+    assert_eq!(token_swap.swap_exact_out(&acceptor, &recv_token_id, &send_token_id, &3000, &1000), 1000);
+    assert_eq!(recv_token_client.balance(&acceptor), 10000 - 335 - 334 - 1000);
+    assert_eq!(fee_token_client.balance(&acceptor), 1000 - 1 - 5);
+}
+
+
+#[test]
+fn test_fee_on_transfer_token() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, DEF_MAKER_FEE_RATE, DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    let fee_token = MockFeeTokenClient::new(&e, &e.register_contract(None, MockFeeToken));
+    let (token_id, token_client, token_admin_client) = create_token_contract(&e, &token_admin);
+    token_swap.allow_token(&admin, &fee_token.address);
+    token_swap.allow_token(&admin, &token_id);
+    fee_token.mint(&offeror, &10025);
+    fee_token.mint(&acceptor, &10000);
+    token_admin_client.mint(&offeror, &10025);
+    token_admin_client.mint(&acceptor, &10000);
+
+    // 10025 sent with the maker fee of 25, 9924 arrive: the offer sells 9899 at the price asked
+    let expire_ledger = e.ledger().sequence() + 1000;
+    let offer_id = token_swap.create_offer(
+        &offeror, &fee_token.address, &token_id, &0,
+        &10000, &1000, &1000,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+    let offer = token_swap.get_offer(&offer_id);
+    assert_eq!((offer.send_amount, offer.recv_amount, offer.min_recv_amount, offer.fee_amount), (9899, 990, 990, 25));
+    assert_eq!(token_swap.check_solvency(&fee_token.address), (9924, 9924));
+
+    token_swap.accept_offer(&acceptor, &offer_id, &990, &0, &None);
+    assert_eq!(token_client.balance(&acceptor), 10000 - 990 - 4);
+    let (owed, balance) = token_swap.check_solvency(&fee_token.address);
+    assert!(owed <= balance);
+
+    // of a taker fee of 5, the 4 that arrive are earned on top of the first maker fee
+    let offer_id = token_swap.create_offer(
+        &offeror, &token_id, &fee_token.address, &0,
+        &10000, &1000, &1,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+    token_swap.accept_offer(&acceptor, &offer_id, &1000, &0, &None);
+    assert_eq!(token_swap.get_accrued_fees(&fee_token.address), 25 + 4);
+    assert_eq!(token_swap.check_solvency(&fee_token.address), (owed + 4, balance + 4));
+}
+
+
+#[test]
+fn test_fee_on_transfer_top_up() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, DEF_MAKER_FEE_RATE, DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    let fee_token = MockFeeTokenClient::new(&e, &e.register_contract(None, MockFeeToken));
+    let (token_id, _, token_admin_client) = create_token_contract(&e, &token_admin);
+    token_swap.allow_token(&admin, &fee_token.address);
+    token_swap.allow_token(&admin, &token_id);
+    fee_token.mint(&offeror, &100000);
+    token_admin_client.mint(&offeror, &10000);
+
+    let expire_ledger = e.ledger().sequence() + 1000;
+    let offer_id = token_swap.create_offer(
+        &offeror, &fee_token.address, &token_id, &0,
+        &10000, &1000, &1,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+
+    // a top up of 10000 and its fee of 25 lands as 9924 as well, so 9899 more is sold
+    token_swap.increase_offer(&offeror, &offer_id, &10000);
+    let offer = token_swap.get_offer(&offer_id);
+    assert_eq!((offer.send_amount, offer.recv_amount, offer.fee_amount), (2 * 9899, 2 * 990, 50));
+    assert_eq!(token_swap.check_solvency(&fee_token.address), (2 * 9924, 2 * 9924));
+
+    // the refund on close is covered to the last unit
+    token_swap.close_offer(&offeror, &offer_id);
+    assert_eq!(token_swap.check_solvency(&fee_token.address), (0, 0));
+
+    // so is reopening at the closed offer's price
+    token_swap.reopen_offer(&offeror, &offer_id, &10000, &expire_ledger);
+    let offer = token_swap.get_offer(&offer_id);
+    assert_eq!((offer.send_amount, offer.recv_amount, offer.fee_amount), (9899, 990, 25));
+    assert_eq!(token_swap.check_solvency(&fee_token.address), (9924, 9924));
+
+    // a maker fee of 25 paid in the fee token is earned as the 24 that arrive
+    token_swap.set_fee_token(&admin, &Some(FeeToken { token: fee_token.address.clone(), max_age: 300 }));
+    token_swap.set_fee_token_rate(&admin, &token_id, &Some(10u128.pow(18)));
+    token_swap.create_offer(
+        &offeror, &token_id, &fee_token.address, &0,
+        &10000, &1000, &1,
+        &expire_ledger, &None, &OfferMode::PARTIAL);
+    assert_eq!(token_swap.get_accrued_fees(&fee_token.address), 24);
+    assert_eq!(token_swap.check_solvency(&fee_token.address), (9924 + 24, 9924 + 24));
+}
+
+#[test]
+fn test_allow_probes_token() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    let (token_id, _, _) = create_token_contract(&e, &token_admin);
+    let compliance = e.register_contract(None, MockCompliance);
+
+    // an account and a contract that isn't a token can't be allowed
+    let account = Address::generate(&e);
+    assert_eq!(token_swap.try_allow_token(&admin, &account), Err(Ok(SwapError::TokenNotAllowed)));
+    assert_eq!(token_swap.try_allow_tokens(&admin, &vec![&e, token_id.clone(), compliance.clone()]), Err(Ok(SwapError::TokenNotAllowed)));
+    assert!(!token_swap.is_token_allowed(&token_id));
+    assert!(!token_swap.is_token_allowed(&compliance));
+
+    token_swap.allow_token(&admin, &token_id);
+    assert_eq!(token_swap.get_token_name(&token_id), Some(token::Client::new(&e, &token_id).name()));
+    assert_eq!(token_swap.get_token_decimals(&token_id), Some(7));
+    assert_eq!(token_swap.get_token_name(&compliance), None);
+}
+
+#[test]
+fn test_typed_errors() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    token_swap.initialize(&admin);
+
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &10000);
+    recv_token_admin_client.mint(&acceptor, &10000);
+    let expire_ledger = e.ledger().sequence() + 1000;
+
+    // each failure is reported with its own code instead of a trap
+    assert_eq!(token_swap.try_create_offer(&offeror, &send_token_id, &recv_token_id, &0, &1000, &500, &1, &expire_ledger, &None, &OfferMode::PARTIAL), Err(Ok(SwapError::FeeNotSet)));
+    token_swap.propose_fee(&admin, &0, &0, &Address::generate(&e));
+    token_swap.apply_fee();
+    assert_eq!(token_swap.try_create_offer(&offeror, &send_token_id, &recv_token_id, &0, &1000, &500, &1, &expire_ledger, &None, &OfferMode::PARTIAL), Err(Ok(SwapError::TokenNotAllowed)));
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+    assert_eq!(token_swap.try_create_offer(&offeror, &send_token_id, &recv_token_id, &0, &0, &500, &1, &expire_ledger, &None, &OfferMode::PARTIAL), Err(Ok(SwapError::ZeroAmount)));
+    assert_eq!(token_swap.try_create_offer(&offeror, &send_token_id, &recv_token_id, &0, &1000, &500, &501, &expire_ledger, &None, &OfferMode::PARTIAL), Err(Ok(SwapError::InvalidMinRecvAmount)));
+
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &1000, &500, &1, &expire_ledger, &None, &OfferMode::PARTIAL);
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &(offer_id + 1), &100, &0, &None), Err(Ok(SwapError::OfferNotFound)));
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &501, &0, &None), Err(Ok(SwapError::AmountTooLarge)));
+    assert_eq!(token_swap.try_close_offer(&acceptor, &offer_id), Err(Ok(SwapError::InvalidOfferor)));
+
+    // failed calls leave the offer as it was
+    let offer = token_swap.get_offer(&offer_id);
+    assert_eq!((offer.send_amount, offer.recv_amount, offer.status), (1000, 500, OfferStatus::ACTIVE));
+
+    token_swap.accept_offer(&acceptor, &offer_id, &100, &0, &None);
+    token_swap.close_offer(&offeror, &offer_id);
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &100, &0, &None), Err(Ok(SwapError::OfferNotActive)));
+    assert_eq!(token_swap.try_close_offer(&offeror, &offer_id), Err(Ok(SwapError::OfferNotActive)));
+}
+
+#[test]
+fn test_get_offer() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, DEF_MAKER_FEE_RATE, 0, &Address::generate(&e));
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    assert_eq!(token_swap.try_get_offer(&0).err(), Some(Ok(SwapError::OfferNotFound)));
+
+    let offer_id = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 10000, 5000);
+    let offer = token_swap.get_offer(&offer_id);
+    assert_eq!(offer.offeror, offeror);
+    assert_eq!((offer.send_token, offer.recv_token), (send_token_id.clone(), recv_token_id.clone()));
+    assert_eq!((offer.send_amount, offer.recv_amount, offer.min_recv_amount), (10000, 5000, 1));
+    assert_eq!(offer.fee_amount, 25);
+    assert_eq!(offer.status, OfferStatus::ACTIVE);
+
+    // the view follows fills and the end of the offer
+    token_swap.accept_offer(&acceptor, &offer_id, &1000, &0, &None);
+    let offer = token_swap.get_offer(&offer_id);
+    assert_eq!((offer.send_amount, offer.recv_amount, offer.fee_amount), (8000, 4000, 20));
+
+    token_swap.close_offer(&offeror, &offer_id);
+    assert_eq!(token_swap.get_offer(&offer_id).status, OfferStatus::CANCEL);
+    assert_eq!(token_swap.try_get_offer(&(offer_id + 1)).err(), Some(Ok(SwapError::OfferNotFound)));
+}
+
+#[test]
+fn test_list_offers() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, 0, 0, &Address::generate(&e));
+    let (_, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    assert_eq!(token_swap.list_offers(&0, &10), vec![&e]);
+
+    let first = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 1000, 500);
+    let second = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 1000, 500);
+    let third = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 1000, 500);
+
+    // pages of open offers in creation order
+    assert_eq!(token_swap.list_offers(&0, &2), vec![&e, first, second]);
+    assert_eq!(token_swap.list_offers(&2, &2), vec![&e, third]);
+    assert_eq!(token_swap.list_offers(&1, &u32::MAX), vec![&e, second, third]);
+
+    // out of range pages are empty
+    assert_eq!(token_swap.list_offers(&3, &2), vec![&e]);
+    assert_eq!(token_swap.list_offers(&u32::MAX, &u32::MAX), vec![&e]);
+    assert_eq!(token_swap.list_offers(&0, &0), vec![&e]);
+
+    // offers leave the index once they stop trading
+    token_swap.close_offer(&offeror, &second);
+    token_swap.accept_offer(&acceptor, &first, &500, &0, &None);
+    assert_eq!(token_swap.list_offers(&0, &10), vec![&e, third]);
+    assert_eq!(token_swap.count_offers(), 3);
+}
+
+#[test]
+fn test_offers_of() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let other_offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, 0, 0, &Address::generate(&e));
+    let (_, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    let first = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 1000, 500);
+    let other = create_funded_offer(&e, &token_swap, &send_token_admin_client, &other_offeror, &recv_token_id, 1000, 500);
+    let second = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 1000, 500);
+    let third = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 1000, 500);
+
+    // each offeror only sees their own offers
+    assert_eq!(token_swap.offers_of(&offeror, &0, &10), vec![&e, first, second, third]);
+    assert_eq!(token_swap.offers_of(&other_offeror, &0, &10), vec![&e, other]);
+    assert_eq!(token_swap.offers_of(&acceptor, &0, &10), vec![&e]);
+    assert_eq!(token_swap.offers_of(&offeror, &1, &1), vec![&e, second]);
+    assert_eq!(token_swap.offers_of(&offeror, &3, &10), vec![&e]);
+
+    // a rejected close keeps the offer listed
+    assert_eq!(token_swap.try_close_offer(&other_offeror, &first), Err(Ok(SwapError::InvalidOfferor)));
+    assert_eq!(token_swap.offers_of(&offeror, &0, &10), vec![&e, first, second, third]);
+
+    // closed, completed and expired offers are removed
+    token_swap.close_offer(&offeror, &first);
+    token_swap.accept_offer(&acceptor, &second, &500, &0, &None);
+    assert_eq!(token_swap.offers_of(&offeror, &0, &10), vec![&e, third]);
+    e.ledger().with_mut(|li| li.sequence_number += 1000);
+    token_swap.expire_offer(&third);
+    assert_eq!(token_swap.offers_of(&offeror, &0, &10), vec![&e]);
+    assert_eq!(token_swap.offers_of(&other_offeror, &0, &10), vec![&e, other]);
+}
+
+#[test]
+fn test_offers_for_pair() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, 0, 0, &Address::generate(&e));
+    let (token_a, _, token_a_admin_client) = create_token_contract(&e, &token_admin);
+    let (token_b, _, token_b_admin_client) = create_token_contract(&e, &token_admin);
+    let (token_c, _, _) = create_token_contract(&e, &token_admin);
+
+    let a_for_b = create_funded_offer(&e, &token_swap, &token_a_admin_client, &offeror, &token_b, 1000, 500);
+    let b_for_a = create_funded_offer(&e, &token_swap, &token_b_admin_client, &offeror, &token_a, 500, 1000);
+    let a_for_c = create_funded_offer(&e, &token_swap, &token_a_admin_client, &offeror, &token_c, 1000, 500);
+    let a_for_b_again = create_funded_offer(&e, &token_swap, &token_a_admin_client, &offeror, &token_b, 2000, 900);
+
+    // each direction of a pair is indexed on its own, oldest first
+    assert_eq!(token_swap.offers_for_pair(&token_a, &token_b, &0, &10), vec![&e, a_for_b, a_for_b_again]);
+    assert_eq!(token_swap.offers_for_pair(&token_b, &token_a, &0, &10), vec![&e, b_for_a]);
+    assert_eq!(token_swap.offers_for_pair(&token_a, &token_c, &0, &10), vec![&e, a_for_c]);
+    assert_eq!(token_swap.offers_for_pair(&token_c, &token_a, &0, &10), vec![&e]);
+    assert_eq!(token_swap.offers_for_pair(&token_a, &token_b, &1, &10), vec![&e, a_for_b_again]);
+
+    // rejected offers aren't indexed
+    token_swap.disallow_token(&admin, &token_c);
+    assert_eq!(token_swap.try_create_offer(&offeror, &token_a, &token_c, &0, &1000, &500, &1, &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL), Err(Ok(SwapError::TokenNotAllowed)));
+    assert_eq!(token_swap.offers_for_pair(&token_a, &token_c, &0, &10), vec![&e, a_for_c]);
+
+    token_swap.close_offer(&offeror, &a_for_b);
+    assert_eq!(token_swap.offers_for_pair(&token_a, &token_b, &0, &10), vec![&e, a_for_b_again]);
+}
+
+#[test]
+fn test_fill_history() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    let other_acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, 0, 0, &Address::generate(&e));
+    let (_, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &10000);
+    recv_token_admin_client.mint(&other_acceptor, &10000);
+
+    let offer_id = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 1000, 500);
+    assert!(token_swap.get_fills(&offer_id, &0, &10).is_empty());
+
+    let start_ledger = e.ledger().sequence();
+    token_swap.accept_offer(&acceptor, &offer_id, &100, &0, &None);
+    e.ledger().with_mut(|li| li.sequence_number = start_ledger + 10);
+    token_swap.accept_offer(&other_acceptor, &offer_id, &50, &0, &None);
+
+    // rejected fills aren't recorded
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &400, &0, &None), Err(Ok(SwapError::AmountTooLarge)));
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &100, &201, &None), Err(Ok(SwapError::SlippageExceeded)));
+
+    let fills = token_swap.get_fills(&offer_id, &0, &10);
+    assert_eq!(fills.len(), 2);
+    let fill = fills.get_unchecked(0);
+    assert_eq!((fill.acceptor, fill.amount, fill.send_amount, fill.ledger), (acceptor.clone(), 100, 200, start_ledger));
+    let fill = fills.get_unchecked(1);
+    assert_eq!((fill.acceptor, fill.amount, fill.send_amount, fill.ledger), (other_acceptor.clone(), 50, 100, start_ledger + 10));
+
+    // paged like the offer indexes
+    assert_eq!(token_swap.get_fills(&offer_id, &1, &1).get_unchecked(0).acceptor, other_acceptor);
+    assert_eq!(token_swap.get_fills(&offer_id, &0, &1).len(), 1);
+    assert!(token_swap.get_fills(&offer_id, &2, &10).is_empty());
+    assert!(token_swap.get_fills(&(offer_id + 1), &0, &10).is_empty());
+}
+
+#[test]
+fn test_pause() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let pauser = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, 0, 0, &Address::generate(&e));
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &10000);
+    let offer_id = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 1000, 500);
+    let other_offer_id = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 1000, 500);
+    send_token_admin_client.mint(&offeror, &1000);
+
+    // only pausers can pull the brake
+    assert_eq!(token_swap.try_pause(&acceptor), Err(Ok(SwapError::Unauthorized)));
+    token_swap.grant_role(&Role::Pauser, &pauser);
+    token_swap.pause(&pauser);
+    assert!(token_swap.is_paused());
+    assert!(token_swap.get_config().paused);
+
+    // trading stops
+    assert_eq!(token_swap.try_create_offer(&offeror, &send_token_id, &recv_token_id, &0, &1000, &500, &1, &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL), Err(Ok(SwapError::Paused)));
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &100, &0, &None), Err(Ok(SwapError::Paused)));
+    assert_eq!(token_swap.try_update_offer(&offeror, &offer_id, &400, &1), Err(Ok(SwapError::Paused)));
+
+    // but offerors can still take their funds out
+    token_swap.close_offer(&offeror, &other_offer_id);
+    assert_eq!(send_token_client.balance(&offeror), 2000);
+
+    assert_eq!(token_swap.try_unpause(&acceptor), Err(Ok(SwapError::Unauthorized)));
+    token_swap.unpause(&pauser);
+    assert!(!token_swap.is_paused());
+    token_swap.accept_offer(&acceptor, &offer_id, &100, &0, &None);
+    assert_eq!(token_swap.get_offer(&offer_id).send_amount, 800);
+}
+
+#[test]
+fn test_upgrade() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let wasm_hash = BytesN::from_array(&e, &[7; 32]);
+
+    let token_swap = create_token_swap_contract(&e);
+    assert_eq!(token_swap.try_upgrade(&wasm_hash), Err(Ok(SwapError::NotInitialized)));
+    assert_eq!(token_swap.try_migrate(), Err(Ok(SwapError::NotInitialized)));
+    token_swap.initialize(&admin);
+
+    // a deployment upgraded from schema 1 is migrated once, by the admin
+    e.as_contract(&token_swap.address, || {
+        e.storage().instance().set(&DataKey::SchemaVersion, &1u32);
+    });
+    assert_eq!(token_swap.version(), 1);
+    token_swap.migrate();
+    assert_eq!(e.auths()[0].0, admin);
+    assert_eq!(token_swap.version(), 2);
+    assert_eq!(token_swap.try_migrate(), Err(Ok(SwapError::AlreadyMigrated)));
+}
+
+#[test]
+fn test_maker_taker_fees() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &10100);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    assert_eq!(token_swap.try_get_maker_fee_rate(), Err(Ok(SwapError::FeeNotSet)));
+    assert_eq!(token_swap.try_get_taker_fee_rate(), Err(Ok(SwapError::FeeNotSet)));
+
+    // makers pay 1% and takers 3%
+    set_fees(&token_swap, &admin, 100, 300, &Address::generate(&e));
+    assert_eq!(token_swap.get_maker_fee_rate(), 100);
+    assert_eq!(token_swap.get_taker_fee_rate(), 300);
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+
+    // the maker fee is charged in send_token on top of the offer
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &10000, &5000, &1, &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL);
+    assert_eq!(send_token_client.balance(&offeror), 0);
+    assert_eq!(token_swap.get_offer(&offer_id).fee_amount, 100);
+
+    // the taker fee in recv_token on top of the fill
+    assert_eq!(token_swap.quote_accept(&offer_id, &1000, &None), (2000, 30));
+    token_swap.accept_offer(&acceptor, &offer_id, &1000, &0, &None);
+    assert_eq!(recv_token_client.balance(&acceptor), 10000 - 1030);
+    assert_eq!(recv_token_client.balance(&offeror), 1000);
+    assert_eq!(token_swap.get_accrued_fees(&recv_token_id), 30);
+    assert_eq!(token_swap.get_accrued_fees(&send_token_id), 20);
+
+    // each rate has its own cap
+    assert_eq!(token_swap.try_propose_fee(&admin, &100, &1001, &Address::generate(&e)), Err(Ok(SwapError::FeeRateTooHigh)));
+    assert_eq!(token_swap.get_taker_fee_rate(), 300);
+}
+
+#[test]
+fn test_claim_fees() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    let fee_wallet = Address::generate(&e);
+    let new_fee_wallet = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    assert_eq!(token_swap.try_claim_fees(&send_token_id), Err(Ok(SwapError::FeeNotSet)));
+    set_fees(&token_swap, &admin, 100, 100, &fee_wallet);
+
+    // fees stay in the contract until claimed, nothing is sent to the wallet on a fill
+    let offer_id = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 10000, 5000);
+    token_swap.accept_offer(&acceptor, &offer_id, &5000, &0, &None);
+    assert_eq!(send_token_client.balance(&fee_wallet), 0);
+    assert_eq!(recv_token_client.balance(&fee_wallet), 0);
+    assert_eq!(token_swap.get_accrued_fees(&send_token_id), 100);
+    assert_eq!(token_swap.get_accrued_fees(&recv_token_id), 50);
+    assert_eq!(recv_token_client.balance(&token_swap.address), 50);
+
+    // fees are claimed per token by the wallet set when claiming
+    set_fees(&token_swap, &admin, 100, 100, &new_fee_wallet);
+    assert_eq!(token_swap.claim_fees(&recv_token_id), 50);
+    assert_eq!(e.auths()[0].0, new_fee_wallet);
+    assert_eq!(recv_token_client.balance(&new_fee_wallet), 50);
+    assert_eq!(recv_token_client.balance(&fee_wallet), 0);
+    assert_eq!(token_swap.get_accrued_fees(&recv_token_id), 0);
+    assert_eq!(token_swap.get_accrued_fees(&send_token_id), 100);
+
+    // nothing left to claim
+    assert_eq!(token_swap.claim_fees(&recv_token_id), 0);
+    assert_eq!(token_swap.claim_fees(&send_token_id), 100);
+    assert_eq!(send_token_client.balance(&token_swap.address), 0);
+}
+
+#[test]
+fn test_max_fee_rate() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let fee_wallet = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, _) = create_token_contract(&e, &token_admin);
+
+    // 10% is the most any rate can be set to
+    set_fees(&token_swap, &admin, 1000, 1000, &fee_wallet);
+    assert_eq!(token_swap.try_propose_fee(&admin, &1001, &0, &fee_wallet), Err(Ok(SwapError::FeeRateTooHigh)));
+    assert_eq!(token_swap.try_propose_fee(&admin, &0, &u32::MAX, &fee_wallet), Err(Ok(SwapError::FeeRateTooHigh)));
+    assert_eq!(token_swap.try_set_token_fee(&admin, &send_token_id, &1001), Err(Ok(SwapError::FeeRateTooHigh)));
+    assert_eq!(token_swap.try_set_fee_tiers(&admin, &vec![&e, FeeTier { min_volume: 0, fee_rate: 1001 }]), Err(Ok(SwapError::FeeRateTooHigh)));
+    assert_eq!(token_swap.get_fee().maker_fee_rate, 1000);
+    assert_eq!(token_swap.get_token_fee(&send_token_id), None);
+    assert!(token_swap.get_fee_tiers().is_empty());
+
+    // so an offer never pays more than a tenth of its size in fees
+    let offer_id = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 10000, 5000);
+    assert_eq!(send_token_client.balance(&token_swap.address), 11000);
+    assert_eq!(token_swap.get_offer(&offer_id).fee_amount, 1000);
+}
+
+#[test]
+fn test_close_refunds_fee() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, 100, 0, &Address::generate(&e));
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    let offer_id = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 10000, 5000);
+    assert_eq!(send_token_client.balance(&offeror), 0);
+    token_swap.accept_offer(&acceptor, &offer_id, &500, &0, &None);
+
+    // a rejected close doesn't touch the fee
+    assert_eq!(token_swap.try_close_offer(&acceptor, &offer_id), Err(Ok(SwapError::InvalidOfferor)));
+    assert_eq!(token_swap.get_offer(&offer_id).fee_amount, 90);
+
+    // the fee on the 90% left unfilled comes back with the escrow
+    token_swap.close_offer(&offeror, &offer_id);
+    assert_eq!(send_token_client.balance(&offeror), 9000 + 90);
+    assert_eq!(token_swap.get_accrued_fees(&send_token_id), 10);
+    assert_eq!(send_token_client.balance(&token_swap.address), 10);
+    assert_eq!(token_swap.try_close_offer(&offeror, &offer_id), Err(Ok(SwapError::OfferNotActive)));
+    assert_eq!(send_token_client.balance(&offeror), 9000 + 90);
+}
+
+#[test]
+fn test_maker_fee_on_execution() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, 100, 0, &Address::generate(&e));
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    // the maker fee is escrowed at creation, none of it is earned yet
+    let filled = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 10000, 5000);
+    let expiring = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 10000, 5000);
+    assert_eq!(token_swap.get_accrued_fees(&send_token_id), 0);
+    assert_eq!(send_token_client.balance(&token_swap.address), 20200);
+
+    // and released as the offer is filled
+    token_swap.accept_offer(&acceptor, &filled, &1250, &0, &None);
+    assert_eq!(token_swap.get_accrued_fees(&send_token_id), 25);
+    assert_eq!(token_swap.get_offer(&filled).fee_amount, 75);
+    token_swap.accept_offer(&acceptor, &filled, &3750, &0, &None);
+    assert_eq!(token_swap.get_accrued_fees(&send_token_id), 100);
+    assert_eq!(token_swap.get_offer(&filled).fee_amount, 0);
+
+    token_swap.accept_offer(&acceptor, &expiring, &2500, &0, &None);
+    assert_eq!(token_swap.get_accrued_fees(&send_token_id), 150);
+
+    // fills refused after expiry earn nothing, and expiry returns the rest
+    e.ledger().with_mut(|li| li.sequence_number += 1000);
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &expiring, &100, &0, &None), Err(Ok(SwapError::OfferExpired)));
+    assert_eq!(token_swap.get_accrued_fees(&send_token_id), 150);
+    token_swap.expire_offer(&expiring);
+    assert_eq!(send_token_client.balance(&offeror), 5000 + 50);
+    assert_eq!(send_token_client.balance(&token_swap.address), 150);
+}
+
+#[test]
+fn test_transfer_only_token() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, 0, 0, &Address::generate(&e));
+    let native = MockPlainTokenClient::new(&e, &e.register_contract(None, MockPlainToken));
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    native.mint(&offeror, &10000);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    // a token without allowances can be allowed and sold
+    assert_eq!(token_swap.try_allow_token(&offeror, &native.address), Err(Ok(SwapError::Unauthorized)));
+    token_swap.allow_tokens(&admin, &vec![&e, native.address.clone(), recv_token_id.clone()]);
+    let expire_ledger = e.ledger().sequence() + 1000;
+    let offer_id: u32 = token_swap.create_offer(&offeror, &native.address, &recv_token_id, &0, &10000, &5000, &1, &expire_ledger, &None, &OfferMode::PARTIAL);
+
+    // the escrow is funded by a transfer the offeror authorized along with the offer
+    let auths = e.auths();
+    assert_eq!(auths.len(), 1);
+    assert_eq!(auths[0].1.sub_invocations.len(), 1);
+    assert_eq!(
+        auths[0].1.sub_invocations[0].function,
+        AuthorizedFunction::Contract((
+            native.address.clone(),
+            symbol_short!("transfer"),
+            (offeror.clone(), token_swap.address.clone(), 10000_i128).into_val(&e),
+        ))
+    );
+    assert_eq!(native.balance(&token_swap.address), 10000);
+
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &5001, &0, &None), Err(Ok(SwapError::AmountTooLarge)));
+    token_swap.accept_offer(&acceptor, &offer_id, &1000, &0, &None);
+    assert_eq!(native.balance(&acceptor), 2000);
+    assert_eq!(recv_token_client.balance(&offeror), 1000);
+
+    token_swap.close_offer(&offeror, &offer_id);
+    assert_eq!(native.balance(&offeror), 8000);
+    assert_eq!(native.balance(&token_swap.address), 0);
+}
+
+#[test]
+fn test_persistent_offers() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, 0, 0, &Address::generate(&e));
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    let first = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 1000, 500);
+    let instance_size = e.as_contract(&token_swap.address, || e.storage().instance().all().len());
+
+    // more offers add persistent entries, the instance stays the same size
+    let mut offer_ids = vec![&e, first];
+    for _ in 0..4 {
+        offer_ids.push_back(create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 1000, 500));
+    }
+    e.as_contract(&token_swap.address, || {
+        assert_eq!(e.storage().instance().all().len(), instance_size);
+        for offer_id in offer_ids.iter() {
+            assert!(e.storage().persistent().has(&DataKey::RegOffers(offer_id)));
+        }
+        assert!(e.storage().persistent().has(&DataKey::Allowance(send_token_id.clone())));
+        assert!(!e.storage().instance().has(&DataKey::Allowance(send_token_id.clone())));
+    });
+
+    // an offer whose entry is gone can't be traded
+    e.as_contract(&token_swap.address, || {
+        e.storage().persistent().remove(&DataKey::RegOffers(first));
+    });
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &first, &100, &0, &None), Err(Ok(SwapError::OfferNotFound)));
+    token_swap.accept_offer(&acceptor, &offer_ids.get_unchecked(1), &100, &0, &None);
+}
+
+#[test]
+fn test_extend_offer_ttl() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, 0, 0, &Address::generate(&e));
+    token_swap.set_ttl_config(&admin, &Some(TtlConfig { threshold: DAY_IN_LEDGERS, extend_to: 2 * DAY_IN_LEDGERS }));
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &1000);
+    recv_token_admin_client.mint(&acceptor, &10000);
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &1000, &500, &1, &(e.ledger().sequence() + 30 * DAY_IN_LEDGERS), &None, &OfferMode::PARTIAL);
+    assert_eq!(token_swap.try_extend_offer_ttl(&(offer_id + 1), &(10 * DAY_IN_LEDGERS)), Err(Ok(SwapError::OfferNotFound)));
+
+    // a fill keeps the offer alive past the TTL it was created with
+    e.ledger().with_mut(|li| li.sequence_number += 3 * DAY_IN_LEDGERS / 2);
+    token_swap.accept_offer(&acceptor, &offer_id, &100, &0, &None);
+    e.ledger().with_mut(|li| li.sequence_number += 3 * DAY_IN_LEDGERS / 2);
+    assert_eq!(token_swap.get_offer(&offer_id).send_amount, 800);
+
+    // and anyone can keep it alive for longer than that
+    token_swap.extend_offer_ttl(&offer_id, &(10 * DAY_IN_LEDGERS));
+    e.ledger().with_mut(|li| li.sequence_number += 9 * DAY_IN_LEDGERS);
+    assert_eq!(token_swap.get_offer(&offer_id).send_amount, 800);
+}
+
+#[test]
+fn test_decrease_offer() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, 0, 0, &Address::generate(&e));
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &1000);
+    recv_token_admin_client.mint(&acceptor, &10000);
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+
+    let expire_ledger = e.ledger().sequence() + 1000;
+    let first: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &1000, &500, &200, &expire_ledger, &None, &OfferMode::PARTIAL);
+    let second = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 1000, 500);
+
+    assert_eq!(token_swap.try_decrease_offer(&offeror, &first, &0), Err(Ok(SwapError::ZeroAmount)));
+    assert_eq!(token_swap.try_decrease_offer(&offeror, &first, &1000), Err(Ok(SwapError::AmountTooLarge)));
+
+    // the offer shrinks at its price and keeps its place in the indexes
+    token_swap.decrease_offer(&offeror, &first, &700);
+    let offer = token_swap.get_offer(&first);
+    assert_eq!((offer.send_amount, offer.recv_amount, offer.status), (300, 150, OfferStatus::ACTIVE));
+    assert_eq!(offer.min_recv_amount, 150);
+    assert_eq!(send_token_client.balance(&offeror), 700);
+    assert_eq!(token_swap.check_solvency(&send_token_id), (1300, 1300));
+    assert_eq!(token_swap.list_offers(&0, &10), vec![&e, first, second]);
+
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &first, &151, &0, &None), Err(Ok(SwapError::AmountTooLarge)));
+    token_swap.accept_offer(&acceptor, &first, &150, &0, &None);
+    assert_eq!(send_token_client.balance(&acceptor), 300);
+    assert_eq!(token_swap.get_offer(&first).status, OfferStatus::COMPLETE);
+}
+
+#[test]
+fn test_counterparty_offer() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let counterparty = Address::generate(&e);
+    let stranger = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, 0, 0, &Address::generate(&e));
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &1000);
+    recv_token_admin_client.mint(&counterparty, &10000);
+    recv_token_admin_client.mint(&stranger, &10000);
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+
+    // the private offer is the better priced one
+    let private: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &1000, &250, &1, &(e.ledger().sequence() + 1000), &Some(counterparty.clone()), &OfferMode::PARTIAL);
+    let public = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 1000, 500);
+    assert_eq!(token_swap.get_counterparty(&private), Some(counterparty.clone()));
+    assert_eq!(token_swap.get_counterparty(&public), None);
+
+    // nobody else can take it, directly or from the book
+    assert_eq!(token_swap.try_accept_offer(&stranger, &private, &250, &0, &None), Err(Ok(SwapError::AcceptorNotAllowed)));
+    assert_eq!(token_swap.take_best(&stranger, &send_token_id, &recv_token_id, &250, &0), (250, 500));
+    assert_eq!(token_swap.get_offer(&private).send_amount, 1000);
+
+    // and the counterparty only in full
+    assert_eq!(token_swap.try_accept_offer(&counterparty, &private, &249, &0, &None), Err(Ok(SwapError::AmountTooSmall)));
+    token_swap.accept_offer(&counterparty, &private, &250, &0, &None);
+    assert_eq!(token_swap.get_offer(&private).status, OfferStatus::COMPLETE);
+    assert_eq!(send_token_client.balance(&counterparty), 1000);
+    assert_eq!(recv_token_client.balance(&offeror), 500);
+}
+
+#[test]
+fn test_all_or_nothing_offer() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, 0, 0, &Address::generate(&e));
+    let (send_token_id, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    send_token_admin_client.mint(&offeror, &1000);
+    recv_token_admin_client.mint(&acceptor, &10000);
+    token_swap.allow_tokens(&admin, &vec![&e, send_token_id.clone(), recv_token_id.clone()]);
+
+    let offer_id: u32 = token_swap.create_offer(&offeror, &send_token_id, &recv_token_id, &0, &1000, &500, &1, &(e.ledger().sequence() + 1000), &None, &OfferMode::ALL_OR_NOTHING);
+    assert_eq!(token_swap.get_offer(&offer_id).mode, OfferMode::ALL_OR_NOTHING);
+
+    // any amount but the whole recv_amount is refused, min_recv_amount notwithstanding
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &499, &0, &None), Err(Ok(SwapError::AmountTooSmall)));
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &501, &0, &None), Err(Ok(SwapError::AmountTooLarge)));
+    assert_eq!(token_swap.try_quote_accept(&offer_id, &250, &None), Err(Ok(SwapError::AmountTooSmall)));
+
+    // the book passes over it for smaller amounts
+    assert_eq!(token_swap.take_best(&acceptor, &send_token_id, &recv_token_id, &250, &0), (0, 0));
+    assert!(token_swap.get_fills(&offer_id, &0, &10).is_empty());
+
+    token_swap.accept_offer(&acceptor, &offer_id, &500, &0, &None);
+    assert_eq!(token_swap.get_offer(&offer_id).status, OfferStatus::COMPLETE);
+    assert_eq!(send_token_client.balance(&acceptor), 1000);
+}
+
+#[test]
+fn test_accept_slippage() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, 0, 0, &Address::generate(&e));
+    let (_, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    let offer_id = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 1000, 500);
+    let (send_out, _) = token_swap.quote_accept(&offer_id, &100, &None);
+    assert_eq!(send_out, 200);
+
+    // the offeror halves the price before the fill lands
+    token_swap.update_offer(&offeror, &offer_id, &1000, &1);
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &100, &send_out, &None), Err(Ok(SwapError::SlippageExceeded)));
+    assert_eq!(recv_token_client.balance(&acceptor), 10000);
+    assert_eq!(send_token_client.balance(&acceptor), 0);
+    assert_eq!(token_swap.get_offer(&offer_id).send_amount, 1000);
+
+    // a minimum the new price meets goes through
+    token_swap.accept_offer(&acceptor, &offer_id, &100, &100, &None);
+    assert_eq!(send_token_client.balance(&acceptor), 100);
+    assert_eq!(send_token_client.balance(&token_swap.address), 900);
+}
+
+#[test]
+fn test_quote_accept() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, 0, 300, &Address::generate(&e));
+    let (_, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &10000);
+
+    let offer_id = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 1000, 300);
+    assert_eq!(token_swap.try_quote_accept(&(offer_id + 1), &100, &None), Err(Ok(SwapError::OfferNotFound)));
+    assert_eq!(token_swap.try_quote_accept(&offer_id, &301, &None), Err(Ok(SwapError::AmountTooLarge)));
+
+    // quoting needs no authorization and changes nothing
+    let (send_out, taker_fee) = token_swap.quote_accept(&offer_id, &100, &Some(acceptor.clone()));
+    assert_eq!((send_out, taker_fee), (333, 3));
+    assert!(e.auths().is_empty());
+    assert_eq!(token_swap.get_offer(&offer_id).send_amount, 1000);
+
+    // the fill settles exactly as quoted
+    token_swap.accept_offer(&acceptor, &offer_id, &100, &send_out, &None);
+    assert_eq!(send_token_client.balance(&acceptor), send_out as i128);
+    assert_eq!(recv_token_client.balance(&acceptor), 10000 - 100 - taker_fee as i128);
+
+    e.ledger().with_mut(|li| li.sequence_number += 1000);
+    assert_eq!(token_swap.try_quote_accept(&offer_id, &100, &None), Err(Ok(SwapError::OfferExpired)));
+}
+
+#[test]
+fn test_get_config() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let fee_wallet = Address::generate(&e);
+
+    let token_swap = create_token_swap_contract(&e);
+    assert_eq!(token_swap.try_get_config().err(), Some(Ok(SwapError::NotInitialized)));
+    token_swap.initialize(&admin);
+
+    // there's nothing to report until the fee is set
+    assert_eq!(token_swap.try_get_fee(), Err(Ok(SwapError::FeeNotSet)));
+    assert_eq!(token_swap.try_get_config().err(), Some(Ok(SwapError::FeeNotSet)));
+
+    set_fees(&token_swap, &admin, DEF_MAKER_FEE_RATE, DEF_TAKER_FEE_RATE, &fee_wallet);
+    let fee = FeeInfo { maker_fee_rate: DEF_MAKER_FEE_RATE, taker_fee_rate: DEF_TAKER_FEE_RATE, fee_wallet: fee_wallet.clone() };
+    assert_eq!(token_swap.get_fee(), fee);
+
+    token_swap.set_fee_delay(&admin, &100);
+    token_swap.pause(&admin);
+    let config = token_swap.get_config();
+    assert_eq!(config.admin, admin);
+    assert_eq!(config.fee, fee);
+    assert_eq!(config.fee_delay, 100);
+    assert!(config.paused);
+    assert_eq!(config.schema_version, 2);
+
+    // a proposed fee shows up once it's in effect
+    token_swap.propose_fee(&admin, &10, &20, &fee_wallet);
+    assert_eq!(token_swap.get_config().fee, fee);
+    e.ledger().with_mut(|li| li.sequence_number += 100);
+    token_swap.apply_fee();
+    assert_eq!(token_swap.get_config().fee.taker_fee_rate, 20);
+}
+
+#[test]
+fn test_allowlist_views() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let stranger = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    let (token_a, _, _) = create_token_contract(&e, &token_admin);
+    let (token_b, _, _) = create_token_contract(&e, &token_admin);
+    let (token_c, _, _) = create_token_contract(&e, &token_admin);
+
+    assert!(!token_swap.is_token_allowed(&token_a));
+    assert_eq!(token_swap.list_allowed_tokens(&0, &10), vec![&e]);
+
+    token_swap.allow_token(&admin, &token_a);
+    token_swap.allow_token(&admin, &token_b);
+    token_swap.allow_token(&admin, &token_c);
+    token_swap.allow_token(&admin, &token_b);
+    assert!(token_swap.is_token_allowed(&token_b));
+
+    // listed once each, in the order they were allowed
+    assert_eq!(token_swap.list_allowed_tokens(&0, &2), vec![&e, token_a.clone(), token_b.clone()]);
+    assert_eq!(token_swap.list_allowed_tokens(&2, &2), vec![&e, token_c.clone()]);
+    assert_eq!(token_swap.list_allowed_tokens(&3, &2), vec![&e]);
+
+    assert_eq!(token_swap.try_disallow_token(&stranger, &token_b), Err(Ok(SwapError::Unauthorized)));
+    assert!(token_swap.is_token_allowed(&token_b));
+
+    token_swap.disallow_token(&admin, &token_b);
+    assert!(!token_swap.is_token_allowed(&token_b));
+    assert_eq!(token_swap.list_allowed_tokens(&0, &10), vec![&e, token_a.clone(), token_c.clone()]);
+}
+
+#[test]
+fn test_batch_allowlist() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let stranger = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    let (token_a, _, _) = create_token_contract(&e, &token_admin);
+    let (token_b, _, _) = create_token_contract(&e, &token_admin);
+    let (token_c, _, _) = create_token_contract(&e, &token_admin);
+
+    // a batch is all or nothing
+    assert_eq!(token_swap.try_allow_tokens(&stranger, &vec![&e, token_a.clone()]), Err(Ok(SwapError::Unauthorized)));
+    assert_eq!(token_swap.try_allow_tokens(&admin, &vec![&e, token_a.clone(), stranger.clone(), token_b.clone()]), Err(Ok(SwapError::TokenNotAllowed)));
+    assert_eq!(token_swap.list_allowed_tokens(&0, &10), vec![&e]);
+
+    // under a single authorization of the caller
+    token_swap.allow_tokens(&admin, &vec![&e, token_a.clone(), token_b.clone(), token_c.clone()]);
+    assert_eq!(e.auths().len(), 1);
+    assert_eq!(e.auths()[0].0, admin);
+    assert_eq!(token_swap.list_allowed_tokens(&0, &10), vec![&e, token_a.clone(), token_b.clone(), token_c.clone()]);
+
+    // tokens that aren't allowed are skipped
+    token_swap.disallow_tokens(&admin, &vec![&e, token_c.clone(), token_a.clone(), stranger.clone()]);
+    assert_eq!(token_swap.list_allowed_tokens(&0, &10), vec![&e, token_b.clone()]);
+    token_swap.disallow_tokens(&admin, &vec![&e]);
+    assert_eq!(token_swap.list_allowed_tokens(&0, &10), vec![&e, token_b.clone()]);
+}
+
+#[test]
+fn test_solvency_view() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, 100, 100, &Address::generate(&e));
+    let (send_token_id, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &10000);
+    assert_eq!(token_swap.check_solvency(&send_token_id), (0, 0));
+
+    // escrow and escrowed fees are counted as offers are created, filled and closed
+    let offer_id = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 10000, 5000);
+    assert_eq!(token_swap.check_solvency(&send_token_id), (10100, 10100));
+
+    token_swap.accept_offer(&acceptor, &offer_id, &1000, &0, &None);
+    assert_eq!(token_swap.check_solvency(&send_token_id), (8100, 8100));
+    assert_eq!(token_swap.check_solvency(&recv_token_id), (10, 10));
+
+    // a failed fill changes neither side
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &4001, &0, &None), Err(Ok(SwapError::AmountTooLarge)));
+    assert_eq!(token_swap.check_solvency(&send_token_id), (8100, 8100));
+
+    token_swap.close_offer(&offeror, &offer_id);
+    assert_eq!(token_swap.check_solvency(&send_token_id), (20, 20));
+
+    // tokens sent to the contract directly aren't owed to anyone
+    send_token_admin_client.mint(&token_swap.address, &500);
+    assert_eq!(token_swap.check_solvency(&send_token_id), (20, 520));
+    token_swap.claim_fees(&send_token_id);
+    assert_eq!(token_swap.check_solvency(&send_token_id), (0, 500));
+}
+
+#[test]
+fn test_errors_not_stored() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+    let other_acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, 0, 0, &Address::generate(&e));
+    let (_, _, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, _, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &10000);
+    recv_token_admin_client.mint(&other_acceptor, &10000);
+
+    let offer_id = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 1000, 500);
+    token_swap.add_acceptor(&offeror, &offer_id, &acceptor);
+    token_swap.accept_offer(&acceptor, &offer_id, &100, &0, &None);
+    let instance_keys = e.as_contract(&token_swap.address, || e.storage().instance().all().keys());
+
+    // each caller gets the error of their own call back
+    assert_eq!(token_swap.try_accept_offer(&other_acceptor, &offer_id, &100, &0, &None), Err(Ok(SwapError::AcceptorNotAllowed)));
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &401, &0, &None), Err(Ok(SwapError::AmountTooLarge)));
+    assert_eq!(token_swap.try_accept_offer(&other_acceptor, &offer_id, &100, &0, &None), Err(Ok(SwapError::AcceptorNotAllowed)));
+
+    // and no call leaves a status behind in the contract instance
+    token_swap.accept_offer(&acceptor, &offer_id, &100, &0, &None);
+    e.as_contract(&token_swap.address, || assert_eq!(e.storage().instance().all().keys(), instance_keys));
+}
+
+#[test]
+fn test_transfers_without_allowance() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let offeror = Address::generate(&e);
+    let acceptor = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, 0, 100, &Address::generate(&e));
+    let (_, send_token_client, send_token_admin_client) = create_token_contract(&e, &token_admin);
+    let (recv_token_id, recv_token_client, recv_token_admin_client) = create_token_contract(&e, &token_admin);
+    recv_token_admin_client.mint(&acceptor, &10000);
+    let offer_id = create_funded_offer(&e, &token_swap, &send_token_admin_client, &offeror, &recv_token_id, 1000, 500);
+
+    // a refused fill moves nothing
+    assert_eq!(token_swap.try_accept_offer(&acceptor, &offer_id, &0, &0, &None), Err(Ok(SwapError::AmountTooSmall)));
+    assert_eq!(recv_token_client.balance(&acceptor), 10000);
+
+    // the acceptor authorizes the payment along with the fill, no approve is needed first
+    token_swap.accept_offer(&acceptor, &offer_id, &100, &0, &None);
+    assert_eq!(
+        e.auths(),
+        std::vec![(
+            acceptor.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    token_swap.address.clone(),
+                    Symbol::new(&e, "accept_offer"),
+                    (acceptor.clone(), offer_id, 100_u64, 0_u64, None::<Address>).into_val(&e)
+                )),
+                sub_invocations: std::vec![
+                    AuthorizedInvocation {
+                        function: AuthorizedFunction::Contract((
+                            recv_token_id.clone(),
+                            symbol_short!("transfer"),
+                            (acceptor.clone(), token_swap.address.clone(), 1_i128).into_val(&e)
+                        )),
+                        sub_invocations: std::vec![]
+                    },
+                    AuthorizedInvocation {
+                        function: AuthorizedFunction::Contract((
+                            recv_token_id.clone(),
+                            symbol_short!("transfer"),
+                            (acceptor.clone(), offeror.clone(), 100_i128).into_val(&e)
+                        )),
+                        sub_invocations: std::vec![]
+                    }
+                ]
+            }
+        )]
+    );
+    assert_eq!(recv_token_client.allowance(&acceptor, &token_swap.address), 0);
+    assert_eq!(send_token_client.allowance(&offeror, &token_swap.address), 0);
+    assert_eq!(recv_token_client.balance(&acceptor), 10000 - 101);
+    assert_eq!(send_token_client.balance(&acceptor), 200);
+}
+
+#[test]
+fn test_fee_on_transfer_escrows() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let admin = Address::generate(&e);
+    let token_admin = Address::generate(&e);
+    let maker = Address::generate(&e);
+    let taker = Address::generate(&e);
+
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, DEF_MAKER_FEE_RATE, DEF_TAKER_FEE_RATE, &Address::generate(&e));
+    let fee_token = MockFeeTokenClient::new(&e, &e.register_contract(None, MockFeeToken));
+    let (token_id, token_client, token_admin_client) = create_token_contract(&e, &token_admin);
+    token_swap.allow_tokens(&admin, &vec![&e, fee_token.address.clone(), token_id.clone()]);
+    fee_token.mint(&maker, &100000);
+    fee_token.mint(&taker, &100000);
+    token_admin_client.mint(&maker, &100000);
+
+    // an order must deliver what it sells in full, its fees are earned as they arrive
+    let order = SignedOrder {
+        maker: maker.clone(),
+        send_token: fee_token.address.clone(),
+        recv_token: token_id.clone(),
+        send_amount: 10000,
+        recv_amount: 1000,
+        expire_ledger: e.ledger().sequence() + 100,
+        nonce: 1,
+    };
+    assert_eq!(token_swap.try_fill_signed_order(&taker, &order), Err(Ok(SwapError::InsufficientBalance)));
+    token_swap.fill_signed_order(&taker, &SignedOrder {
+        send_token: token_id.clone(),
+        recv_token: fee_token.address.clone(),
+        recv_amount: 10000,
+        nonce: 2,
+        ..order
+    });
+    assert_eq!(token_client.balance(&taker), 10000);
+    assert_eq!(token_swap.get_accrued_fees(&fee_token.address), 74);
+    assert_eq!(token_swap.check_solvency(&fee_token.address), (74, 74));
+
+    // an HTLC locks what arrived
+    let preimage = Bytes::from_array(&e, &[42; 32]);
+    let htlc_id = token_swap.lock_htlc(&maker, &taker, &fee_token.address, &10000, &e.crypto().sha256(&preimage), &(e.ledger().sequence() + 100));
+    assert_eq!(token_swap.get_htlc(&htlc_id).amount, 9900);
+    assert_eq!(token_swap.check_solvency(&fee_token.address), (74 + 9900, 74 + 9900));
+    token_swap.claim_htlc(&htlc_id, &preimage);
+    assert_eq!(token_swap.check_solvency(&fee_token.address), (74, 74));
+
+    // and so does a stake
+    token_swap.set_stake_token(&admin, &Some(fee_token.address.clone()));
+    token_swap.stake(&maker, &10000);
+    assert_eq!(token_swap.get_stake(&maker), 9900);
+    assert_eq!(token_swap.get_total_stake(), 9900);
+    assert_eq!(token_swap.try_unstake(&maker, &9901), Err(Ok(SwapError::InsufficientBalance)));
+    token_swap.unstake(&maker, &9900);
+    assert_eq!(token_swap.check_solvency(&fee_token.address), (74, 74));
 }
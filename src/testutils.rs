@@ -0,0 +1,82 @@
+#![cfg(any(test, feature = "testutils"))]
+
+use soroban_sdk::{ token, Address, Env };
+
+use crate::storage_types::{ FEE_DECIMALS };
+use crate::{ OfferMode, TokenSwap, TokenSwapClient };
+
+
+/*
+Helpers for tests of contracts composing with TokenSwap, enabled with the `testutils`
+feature. A test deploys the contract with `register_token_swap`, sets the fees with
+`set_fees`, and opens offers with `create_funded_offer`, which mints the offeror what the
+offer and its maker fee need:
+
+    let e = Env::default();
+    e.mock_all_auths();
+    let token_swap = register_token_swap(&e, &admin);
+    set_fees(&token_swap, &admin, 25, 50, &fee_wallet);
+    let (send_token, _, send_token_admin) = register_token(&e, &token_admin);
+    let (recv_token, _, _) = register_token(&e, &token_admin);
+    let offer_id = create_funded_offer(&e, &token_swap, &send_token_admin, &offeror, &recv_token, 1000, 100);
+
+The helpers authorize nothing themselves, so the env has to mock the auths.
+*/
+
+// Deploys the contract and initializes it with admin.
+pub fn register_token_swap<'a>(e: &Env, admin: &Address) -> TokenSwapClient<'a> {
+    let token_swap = TokenSwapClient::new(e, &e.register_contract(None, TokenSwap {}));
+    token_swap.initialize(admin);
+    token_swap
+}
+
+// Registers a Stellar asset contract administered by admin. Returns its address and the
+// clients to use it and to mint it.
+pub fn register_token<'a>(
+    e: &Env,
+    admin: &Address,
+) -> (Address, token::Client<'a>, token::StellarAssetClient<'a>) {
+    let addr = e.register_stellar_asset_contract(admin.clone());
+    (
+        addr.clone(),
+        token::Client::new(e, &addr),
+        token::StellarAssetClient::new(e, &addr),
+    )
+}
+
+// Sets the fee rates (in 1/10^FEE_DECIMALS) and wallet right away. Works as long as no
+// fee delay is set.
+pub fn set_fees(token_swap: &TokenSwapClient, admin: &Address, maker_fee_rate: u32, taker_fee_rate: u32, fee_wallet: &Address) {
+    token_swap.propose_fee(admin, &maker_fee_rate, &taker_fee_rate, fee_wallet);
+    token_swap.apply_fee();
+}
+
+// Allows both tokens, mints offeror `send_amount` of the token of send_token_admin plus
+// the maker fee, and offers it for `recv_amount` of recv_token. The offer can be filled
+// partially, down to a single unit, until it expires in 1000 ledgers.
+pub fn create_funded_offer(
+    e: &Env,
+    token_swap: &TokenSwapClient,
+    send_token_admin: &token::StellarAssetClient,
+    offeror: &Address,
+    recv_token: &Address,
+    send_amount: u64,
+    recv_amount: u64,
+) -> u32 {
+    let send_token = send_token_admin.address.clone();
+    let admin = token_swap.get_admin();
+    for token in [&send_token, recv_token] {
+        if !token_swap.is_token_allowed(token) {
+            token_swap.allow_token(&admin, token);
+        }
+    }
+
+    let fee_rate = token_swap.get_token_fee(&send_token).unwrap_or(token_swap.get_fee().maker_fee_rate);
+    let fee_amount = (send_amount as u128) * (fee_rate as u128) / u128::pow(10, FEE_DECIMALS);
+    send_token_admin.mint(offeror, &((send_amount as u128 + fee_amount) as i128));
+
+    token_swap.create_offer(
+        offeror, &send_token, recv_token, &0,
+        &send_amount, &recv_amount, &1,
+        &(e.ledger().sequence() + 1000), &None, &OfferMode::PARTIAL)
+}
@@ -0,0 +1,69 @@
+use soroban_sdk::{ Address, Env };
+
+use crate::storage_types::{ ConfigKey, RateLimit, RateUsage, Role, ThrottleKey };
+use crate::role::{ role_check };
+use crate::error::SwapError;
+
+
+/*
+Operators can cap how many offers an account creates and how many fills it takes within
+a window of ledgers, to throttle spam and bots without pausing everyone. An account's
+usage is counted from its first call in a window, in temporary storage that lapses with
+the window.
+*/
+
+pub fn rate_limit_get(e: &Env) -> Option<RateLimit> {
+    e.storage().instance().get(&ConfigKey::RateLimit)
+}
+
+// Sets the per-account limits, None lifts them. A maximum of 0 leaves that call unlimited.
+pub fn rate_limit_set(e: &Env, caller: &Address, limit: &Option<RateLimit>) -> Result<(), SwapError> {
+    role_check(e, Role::Operator, caller)?;
+    match limit {
+        Some(limit) => {
+            if limit.window_ledgers == 0 {
+                return Err(SwapError::InvalidExpiration);
+            }
+            e.storage().instance().set(&ConfigKey::RateLimit, limit)
+        },
+        None => e.storage().instance().remove(&ConfigKey::RateLimit),
+    }
+    Ok(())
+}
+
+// Counts an offer created by account against its limit.
+pub fn throttle_create(e: &Env, account: &Address) -> Result<(), SwapError> {
+    throttle(e, account, true)
+}
+
+// Counts a fill taken by account against its limit.
+pub fn throttle_accept(e: &Env, account: &Address) -> Result<(), SwapError> {
+    throttle(e, account, false)
+}
+
+fn throttle(e: &Env, account: &Address, create: bool) -> Result<(), SwapError> {
+    let limit = match rate_limit_get(e) {
+        Some(limit) => limit,
+        None => return Ok(()),
+    };
+    let max = if create { limit.max_creates } else { limit.max_accepts };
+    if max == 0 {
+        return Ok(());
+    }
+
+    let key = ThrottleKey::Usage(account.clone());
+    let sequence = e.ledger().sequence();
+    let mut usage = match e.storage().temporary().get::<_, RateUsage>(&key) {
+        Some(usage) if sequence < usage.window_start + limit.window_ledgers => usage,
+        _ => RateUsage { window_start: sequence, creates: 0, accepts: 0 },
+    };
+    let count = if create { &mut usage.creates } else { &mut usage.accepts };
+    if *count >= max {
+        return Err(SwapError::TooManyOffers);
+    }
+    *count += 1;
+
+    e.storage().temporary().set(&key, &usage);
+    e.storage().temporary().extend_ttl(&key, limit.window_ledgers, limit.window_ledgers);
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use soroban_sdk::{ Address, Env, IntoVal, Val };
+
+use crate::storage_types::{ BALANCE_LIFETIME_THRESHOLD, BALANCE_BUMP_AMOUNT, 
+    ConfigKey, DataKey, OfferDataKey, Role, TtlConfig
+};
+use crate::offer::{ offer_load };
+use crate::role::{ role_check };
+use crate::error::SwapError;
+
+
+/*
+Offers and their index entries live in persistent storage, which the network archives
+once an entry's TTL runs out. Every read and write of them extends their TTL to
+`extend_to` ledgers once it has dropped below `threshold`, so offers in use never lapse.
+
+An offer nobody touched for longer than that is archived. Archived entries can only be
+brought back by a RestoreFootprint operation, after which `restore_offer` extends the
+offer and all its entries again, as the restore only gives them the minimum TTL.
+*/
+
+pub fn ttl_get(e: &Env) -> TtlConfig {
+    e.storage().instance().get(&ConfigKey::Ttl).unwrap_or(TtlConfig {
+        threshold: BALANCE_LIFETIME_THRESHOLD,
+        extend_to: BALANCE_BUMP_AMOUNT,
+    })
+}
+
+// None goes back to the default of extending to 30 days when less than 29 are left.
+pub fn ttl_set(e: &Env, caller: &Address, config: &Option<TtlConfig>) -> Result<(), SwapError> {
+    role_check(e, Role::Operator, caller)?;
+    match config {
+        Some(config) => {
+            if config.threshold >= config.extend_to || config.extend_to >= e.storage().max_ttl() {
+                return Err(SwapError::InvalidExpiration);
+            }
+            e.storage().instance().set(&ConfigKey::Ttl, config)
+        },
+        None => e.storage().instance().remove(&ConfigKey::Ttl),
+    }
+    Ok(())
+}
+
+// Extends the TTL of the persistent entry under key as configured. The entry must exist.
+pub fn ttl_bump<K: IntoVal<Env, Val>>(e: &Env, key: &K) {
+    let config = ttl_get(e);
+    e.storage().persistent().extend_ttl(key, config.threshold, config.extend_to);
+}
+
+// Restore offer
+// Extends the offer, its index entries and its optional settings after the offer was
+// restored from the archive. Can be called by anyone.
+pub fn offer_restore(e: &Env, offer_id: u32) -> Result<(), SwapError> {
+    let offer = offer_load(e, offer_id)?;
+
+    let keys = [
+        DataKey::OpenOffers,
+        DataKey::OfferorOffers(offer.offeror.clone()),
+        DataKey::PairOffers(offer.send_token.clone(), offer.recv_token.clone()),
+        DataKey::Book(offer.send_token.clone(), offer.recv_token.clone()),
+    ];
+    for key in keys {
+        if e.storage().persistent().has(&key) {
+            ttl_bump(e, &key);
+        }
+    }
+    let keys = [
+        OfferDataKey::Peg(offer_id),
+        OfferDataKey::Dust(offer_id),
+        OfferDataKey::Terms(offer_id),
+        OfferDataKey::DisputeWindow(offer_id),
+        OfferDataKey::Hold(offer_id),
+        OfferDataKey::Memo(offer_id),
+    ];
+    for key in keys {
+        if e.storage().persistent().has(&key) {
+            ttl_bump(e, &key);
+        }
+    }
+    Ok(())
+}
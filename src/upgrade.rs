@@ -0,0 +1,83 @@
+use soroban_sdk::{ Address, BytesN, Env, TryFromVal, Val, Vec };
+
+use crate::storage_types::{ SCHEMA_VERSION, DataKey, OfferInfo, Role, StoredOffer };
+
+use crate::admin::{ 
+    read_administrator, 
+};
+use crate::offer::{ offer_write };
+use crate::role::{ role_check };
+use crate::error::SwapError;
+
+
+pub fn read_version(e: &Env) -> u32 {
+    let key = DataKey::SchemaVersion;
+    e.storage().instance().get(&key).unwrap_or(0)
+}
+
+pub fn write_version(e: &Env, version: u32) {
+    let key = DataKey::SchemaVersion;
+    e.storage().instance().set(&key, &version);
+}
+
+pub fn upgrade_wasm(e: &Env, new_wasm_hash: &BytesN<32>) -> Result<(), SwapError> {
+    let admin = read_administrator(e)?;
+    admin.require_auth();
+
+    e.deployer().update_current_contract_wasm(new_wasm_hash.clone());
+    Ok(())
+}
+
+// Brings the stored data up to SCHEMA_VERSION after an upgrade.
+// Each schema change should add its conversion step here. Offers aren't converted here,
+// as there can be any number of them: they're upcast whenever they're loaded, stored in
+// the current schema on their next write, and can be rewritten with `offers_migrate`.
+pub fn migrate_data(e: &Env) -> Result<(), SwapError> {
+    let admin = read_administrator(e)?;
+    admin.require_auth();
+
+    let version = read_version(e);
+    if version >= SCHEMA_VERSION {
+        return Err(SwapError::AlreadyMigrated);
+    }
+
+    write_version(e, SCHEMA_VERSION);
+    Ok(())
+}
+
+// Decodes an offer entry of any schema into the current OfferInfo, along with the schema
+// it was stored in.
+pub fn offer_upcast(e: &Env, entry: &Val) -> Option<(OfferInfo, u32)> {
+    if let Ok(StoredOffer::V2(offer)) = StoredOffer::try_from_val(e, entry) {
+        return Some((offer, 2));
+    }
+    OfferInfo::try_from_val(e, entry).ok().map(|offer| (offer, 1))
+}
+
+// Schema the offer is stored in.
+pub fn offer_schema(e: &Env, offer_id: u32) -> Option<u32> {
+    let entry: Val = e.storage().persistent().get(&DataKey::RegOffers(offer_id))?;
+    offer_upcast(e, &entry).map(|(_, version)| version)
+}
+
+// Migrate offers
+// Rewrites the given offers in the current schema. Unknown and current ids are skipped.
+// Returns the number of offers migrated.
+pub fn offers_migrate(e: &Env, caller: &Address, offer_ids: &Vec<u32>) -> Result<u32, SwapError> {
+    role_check(e, Role::Admin, caller)?;
+
+    let mut count: u32 = 0;
+    for offer_id in offer_ids.iter() {
+        let entry: Val = match e.storage().persistent().get(&DataKey::RegOffers(offer_id)) {
+            Some(entry) => entry,
+            None => continue,
+        };
+        if let Some((offer, version)) = offer_upcast(e, &entry) {
+            if version < SCHEMA_VERSION {
+                offer_write(e, offer_id, &offer);
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}